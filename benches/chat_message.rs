@@ -0,0 +1,52 @@
+//! Compares parsing `channel.chat.message` websocket frames into owned and borrowed payloads.
+//!
+//! Run with `cargo bench --bench chat_message --features eventsub`.
+//! Under `cargo test`, every case runs once as a smoke test.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use twitch_api::eventsub::{channel::chat::message::borrowed, Event, EventsubWebsocketData};
+
+const FRAME: &str = r##"{"metadata":{"message_id":"befa7b53-d79d-478f-86b9-120f112b044e","message_type":"notification","message_timestamp":"2023-11-06T18:11:47.492253549Z","subscription_type":"channel.chat.message","subscription_version":"1"},"payload":{"subscription":{"id":"0b7f3361-672b-4d39-b307-dd5b576c9b27","status":"enabled","type":"channel.chat.message","version":"1","condition":{"broadcaster_user_id":"1971641","user_id":"2914196"},"transport":{"method":"websocket","session_id":"AgoQHR3s6Mb4T8GFB1l3DlPfiRIGY2VsbC1h"},"created_at":"2023-11-06T18:11:47.492253549Z","cost":0},"event":{"broadcaster_user_id":"1971641","broadcaster_user_login":"streamer","broadcaster_user_name":"streamer","chatter_user_id":"4145994","chatter_user_login":"viewer32","chatter_user_name":"viewer32","message_id":"cc106a89-1814-919d-454c-f4f2f970aae7","message":{"text":"Hi chat Kappa @streamer cheer100","fragments":[{"type":"text","text":"Hi chat ","cheermote":null,"emote":null,"mention":null},{"type":"emote","text":"Kappa","cheermote":null,"emote":{"id":"25","emote_set_id":"0","owner_id":"0","format":["static"]},"mention":null},{"type":"text","text":" ","cheermote":null,"emote":null,"mention":null},{"type":"mention","text":"@streamer","cheermote":null,"emote":null,"mention":{"user_id":"1971641","user_name":"streamer","user_login":"streamer"}},{"type":"text","text":" ","cheermote":null,"emote":null,"mention":null},{"type":"cheermote","text":"cheer100","cheermote":{"prefix":"cheer","bits":100,"tier":100},"emote":null,"mention":null}]},"color":"#00FF7F","badges":[{"set_id":"subscriber","id":"12","info":"16"},{"set_id":"bits","id":"1000","info":""}],"message_type":"text","cheer":{"bits":100},"reply":{"parent_message_id":"c8f3b22b-ee0e-4d0a-9ad2-f64ba6f3d7c5","parent_message_body":"hello everyone","parent_user_id":"1971641","parent_user_name":"streamer","parent_user_login":"streamer","thread_message_id":"c8f3b22b-ee0e-4d0a-9ad2-f64ba6f3d7c5","thread_user_id":"1971641","thread_user_name":"streamer","thread_user_login":"streamer"},"channel_points_custom_reward_id":null,"channel_points_animation_id":null,"source_broadcaster_user_id":null,"source_broadcaster_user_name":null,"source_broadcaster_user_login":null,"source_message_id":null,"source_badges":null}}}"##;
+
+fn bench(name: &str, iterations: u32, mut f: impl FnMut()) {
+    // warm up
+    for _ in 0..iterations / 10 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{name:<24} {:>10.0?}/iter ({iterations} iterations)",
+        elapsed.checked_div(iterations).unwrap_or(Duration::ZERO)
+    );
+}
+
+fn main() {
+    let iterations = if std::env::args().any(|arg| arg == "--bench") {
+        100_000
+    } else {
+        1
+    };
+
+    bench("owned", iterations, || {
+        let data = Event::parse_websocket(black_box(FRAME)).unwrap();
+        assert!(matches!(data, EventsubWebsocketData::Notification { .. }));
+        black_box(data);
+    });
+    bench("borrowed", iterations, || {
+        let notification = borrowed::parse_websocket(black_box(FRAME))
+            .unwrap()
+            .unwrap();
+        black_box(notification);
+    });
+    bench("borrowed, into_owned", iterations, || {
+        let notification = borrowed::parse_websocket(black_box(FRAME))
+            .unwrap()
+            .unwrap();
+        black_box(notification.event.into_owned());
+    });
+}
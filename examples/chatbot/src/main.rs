@@ -7,7 +7,7 @@ use eyre::WrapErr as _;
 use tokio::sync::Mutex;
 use twitch_api::{
     client::ClientDefault,
-    eventsub::{self, Event, Message, Payload},
+    eventsub::{self, channel::chat::commands, Event, Message, Payload},
     HelixClient,
 };
 use twitch_oauth2::{Scope, TwitchToken as _, UserToken};
@@ -81,12 +81,19 @@ async fn main() -> Result<(), eyre::Report> {
         );
     };
     let token = Arc::new(Mutex::new(token));
+    let commands = config
+        .command
+        .iter()
+        .fold(commands::Commands::new(), |set, command| {
+            set.command(commands::Command::new(&command.trigger))
+        });
 
     let bot = Bot {
         opts,
         client,
         token,
         config,
+        commands: Mutex::new(commands),
         broadcaster,
     };
     bot.start().await?;
@@ -98,6 +105,7 @@ pub struct Bot {
     pub client: HelixClient<'static, reqwest::Client>,
     pub token: Arc<Mutex<twitch_oauth2::UserToken>>,
     pub config: Config,
+    pub commands: Mutex<commands::Commands>,
     pub broadcaster: twitch_api::types::UserId,
 }
 
@@ -156,13 +164,17 @@ impl Bot {
                     "[{}] {}: {}",
                     timestamp, payload.chatter_user_name, payload.message.text
                 );
-                if let Some(command) = payload.message.text.strip_prefix("!") {
-                    let mut split_whitespace = command.split_whitespace();
-                    let command = split_whitespace.next().unwrap();
-                    let rest = split_whitespace.next();
-
-                    self.command(&payload, &subscription, command, rest, &token)
-                        .await?;
+                let invocation = self
+                    .commands
+                    .lock()
+                    .await
+                    .parse(&payload, std::time::Instant::now());
+                match invocation {
+                    Ok(Some(invocation)) => {
+                        self.command(&invocation, &subscription, &token).await?
+                    }
+                    Ok(None) => {}
+                    Err(e) => tracing::debug!("Ignoring command: {e}"),
                 }
             }
             Event::ChannelChatNotificationV1(Payload {
@@ -189,24 +201,26 @@ impl Bot {
 
     async fn command(
         &self,
-        payload: &eventsub::channel::ChannelChatMessageV1Payload,
+        invocation: &commands::Invocation<'_>,
         subscription: &eventsub::EventSubscriptionInformation<
             eventsub::channel::ChannelChatMessageV1,
         >,
-        command: &str,
-        _rest: Option<&str>,
         token: &UserToken,
     ) -> Result<(), eyre::Report> {
-        tracing::info!("Command: {}", command);
-        if let Some(response) = self.config.command.iter().find(|c| c.trigger == command) {
-            self.client
-                .send_chat_message_reply(
-                    &subscription.condition.broadcaster_user_id,
+        tracing::info!("Command: {}", invocation.command);
+        if let Some(response) = self
+            .config
+            .command
+            .iter()
+            .find(|c| c.trigger == invocation.command)
+        {
+            invocation
+                .reply(
+                    &self.client,
                     &subscription.condition.user_id,
-                    &payload.message_id,
                     response
                         .response
-                        .replace("{user}", payload.chatter_user_name.as_str())
+                        .replace("{user}", invocation.payload.chatter_user_name.as_str())
                         .as_str(),
                     token,
                 )
@@ -0,0 +1,798 @@
+//! Archive chat events to JSON lines files and replay them
+//!
+//! [`ChatArchive`] appends every `channel.chat.*` notification that makes up the chat log
+//! ([`message`](super::ChannelChatMessageV1), [`message_delete`](super::ChannelChatMessageDeleteV1),
+//! [`clear`](super::ChannelChatClearV1), [`clear_user_messages`](super::ChannelChatClearUserMessagesV1) and
+//! [`notification`](super::ChannelChatNotificationV1)) as a [`Record`] to a file per channel and UTC day,
+//! `<dir>/<broadcaster id>/<YYYY-MM-DD>.jsonl`. A record holds the raw notification payload as sent by Twitch together with
+//! the message metadata, so it parses with [`Event::parse`] exactly like the original.
+//! Files are rotated to `<YYYY-MM-DD>.1.jsonl`, `<YYYY-MM-DD>.2.jsonl`, … when they reach [`ArchiveConfig::max_file_size`].
+//! Redelivered notifications, with the message ID of one of the last [`ArchiveConfig::recent_message_ids`] records of the channel, are skipped.
+//!
+//! Archived records can be [replayed](replay) through a [`Router`](router::Router), so the same handlers process live and archived chat,
+//! or applied to a [`Transcript`], which keeps deleted and cleared messages as tombstones and exports the visible chat log.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use twitch_api::eventsub::{
+//!     self,
+//!     channel::chat::archive::{self, ArchiveConfig, ChatArchive, Transcript},
+//!     router::Router,
+//! };
+//!
+//! let mut archive = ChatArchive::new("chat-logs", ArchiveConfig::default());
+//! # let frame = "";
+//! // `frame` is a text message received on the EventSub websocket
+//! archive.archive_websocket(frame)?;
+//!
+//! // later
+//! let router = Router::new().on::<eventsub::channel::ChannelChatMessageV1, _>(|payload, _ctx| {
+//!     println!("{}: {}", payload.chatter_user_name, payload.message.text);
+//! });
+//! archive::replay(&router, archive.records("1234".into())?).await?;
+//!
+//! let mut transcript = Transcript::new();
+//! for record in archive.records("1234".into())? {
+//!     transcript.apply_record(&record?)?;
+//! }
+//! transcript.export(std::io::stdout())?;
+//! # Ok(()) }
+//! ```
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use crate::eventsub::{router, Event, EventType, Message, Payload};
+use crate::types;
+
+/// Errors from archiving or reading archived chat events
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+#[non_exhaustive]
+pub enum ArchiveError {
+    /// could not read or write the archive
+    Io(#[from] io::Error),
+    /// could not serialize or deserialize a record
+    Json(#[from] serde_json::Error),
+    /// could not parse an event
+    Parse(#[from] crate::eventsub::PayloadParseError),
+}
+
+/// A chat event as archived
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Record {
+    /// When the event was archived
+    pub received_at: types::Timestamp,
+    /// ID of the message the event was delivered in, if known
+    pub message_id: Option<String>,
+    /// When Twitch sent the message, if known
+    pub message_timestamp: Option<types::Timestamp>,
+    /// The subscription type
+    pub subscription_type: EventType,
+    /// The subscription version
+    pub subscription_version: String,
+    /// The channel
+    pub broadcaster_user_id: types::UserId,
+    /// The notification payload as sent by Twitch, with the `subscription` and the `event`
+    pub payload: Box<RawValue>,
+}
+
+impl Record {
+    /// Create a record from a websocket frame, if it is a notification for a chat event
+    pub fn from_websocket(
+        frame: &str,
+        received_at: types::Timestamp,
+    ) -> Result<Option<Self>, ArchiveError> {
+        #[derive(Deserialize)]
+        struct Frame<'a> {
+            #[serde(borrow)]
+            payload: &'a RawValue,
+        }
+
+        let (metadata, event) = match Event::parse_websocket(frame)? {
+            crate::eventsub::EventsubWebsocketData::Notification { metadata, payload } => {
+                (metadata, payload)
+            }
+            _ => return Ok(None),
+        };
+        let Some((subscription_type, broadcaster_user_id)) = chat_event(&event) else {
+            return Ok(None);
+        };
+        let Frame { payload } = serde_json::from_str(frame)?;
+        Ok(Some(Self {
+            received_at,
+            message_id: Some(metadata.message_id.into_owned()),
+            message_timestamp: Some(metadata.message_timestamp.into_owned()),
+            subscription_type,
+            subscription_version: metadata.subscription_version.into_owned(),
+            broadcaster_user_id,
+            payload: payload.to_owned(),
+        }))
+    }
+
+    /// Create a record from the body of a webhook request, if it is a notification for a chat event
+    pub fn from_webhook(
+        body: &str,
+        metadata: &router::Metadata,
+        received_at: types::Timestamp,
+    ) -> Result<Option<Self>, ArchiveError> {
+        #[derive(Deserialize)]
+        struct Body {
+            subscription: Subscription,
+        }
+        #[derive(Deserialize)]
+        struct Subscription {
+            version: String,
+        }
+
+        let event = Event::parse(body)?;
+        let Some((subscription_type, broadcaster_user_id)) = chat_event(&event) else {
+            return Ok(None);
+        };
+        let Body { subscription } = serde_json::from_str(body)?;
+        Ok(Some(Self {
+            received_at,
+            message_id: metadata.message_id.clone(),
+            message_timestamp: metadata.message_timestamp.clone(),
+            subscription_type,
+            subscription_version: subscription.version,
+            broadcaster_user_id,
+            payload: RawValue::from_string(body.to_owned())?,
+        }))
+    }
+
+    /// Parse the archived event
+    pub fn event(&self) -> Result<Event, ArchiveError> { Ok(Event::parse(self.payload.get())?) }
+
+    /// The metadata of the message the event was delivered in
+    pub fn metadata(&self) -> router::Metadata {
+        router::Metadata::new(self.message_id.clone(), self.message_timestamp.clone())
+    }
+
+    /// When the event happened, the message timestamp if known, otherwise when it was archived
+    pub fn timestamp(&self) -> &types::TimestampRef {
+        self.message_timestamp
+            .as_deref()
+            .unwrap_or(&*self.received_at)
+    }
+}
+
+/// The subscription type and channel of a chat event that is archived
+fn chat_event(event: &Event) -> Option<(EventType, types::UserId)> {
+    let (event_type, broadcaster_user_id) = match event {
+        Event::ChannelChatMessageV1(Payload {
+            message: Message::Notification(p),
+            ..
+        }) => (EventType::ChannelChatMessage, &p.broadcaster_user_id),
+        Event::ChannelChatMessageDeleteV1(Payload {
+            message: Message::Notification(p),
+            ..
+        }) => (EventType::ChannelChatMessageDelete, &p.broadcaster_user_id),
+        Event::ChannelChatClearV1(Payload {
+            message: Message::Notification(p),
+            ..
+        }) => (EventType::ChannelChatClear, &p.broadcaster_user_id),
+        Event::ChannelChatClearUserMessagesV1(Payload {
+            message: Message::Notification(p),
+            ..
+        }) => (
+            EventType::ChannelChatClearUserMessages,
+            &p.broadcaster_user_id,
+        ),
+        Event::ChannelChatNotificationV1(Payload {
+            message: Message::Notification(p),
+            ..
+        }) => (EventType::ChannelChatNotification, &p.broadcaster_user_id),
+        _ => return None,
+    };
+    Some((event_type, broadcaster_user_id.clone()))
+}
+
+/// Configuration for a [`ChatArchive`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ArchiveConfig {
+    /// Size in bytes after which a new file is started. Defaults to 64 MiB
+    pub max_file_size: u64,
+    /// How many message IDs per channel are remembered to skip redelivered notifications. Defaults to `1000`
+    pub recent_message_ids: usize,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            max_file_size: 64 * 1024 * 1024,
+            recent_message_ids: 1000,
+        }
+    }
+}
+
+impl ArchiveConfig {
+    /// Set the size in bytes after which a new file is started
+    pub fn max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size.max(1);
+        self
+    }
+
+    /// Set how many message IDs per channel are remembered to skip redelivered notifications, `0` archives every notification
+    pub fn recent_message_ids(mut self, recent_message_ids: usize) -> Self {
+        self.recent_message_ids = recent_message_ids;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct OpenFile {
+    date: String,
+    index: u32,
+    size: u64,
+    file: File,
+}
+
+/// The last message IDs archived for a channel
+#[derive(Debug, Default)]
+struct RecentIds {
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+}
+
+impl RecentIds {
+    /// Remember an ID, returning `false` if it was already known
+    fn insert(&mut self, id: &str, capacity: usize) -> bool {
+        if capacity == 0 {
+            return true;
+        }
+        if self.ids.contains(id) {
+            return false;
+        }
+        while self.order.len() >= capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        self.order.push_back(id.to_owned());
+        self.ids.insert(id.to_owned());
+        true
+    }
+}
+
+/// Appends chat events to JSON lines files, see the [module documentation](self)
+///
+/// Every record is written with a single write, so files are valid up to the last complete line even if the process stops.
+#[derive(Debug)]
+pub struct ChatArchive {
+    dir: PathBuf,
+    config: ArchiveConfig,
+    files: HashMap<types::UserId, OpenFile>,
+    recent: HashMap<types::UserId, RecentIds>,
+}
+
+impl ChatArchive {
+    /// Archive to files in `dir`, which is created if needed
+    pub fn new(dir: impl Into<PathBuf>, config: ArchiveConfig) -> Self {
+        Self {
+            dir: dir.into(),
+            config,
+            files: HashMap::new(),
+            recent: HashMap::new(),
+        }
+    }
+
+    /// The directory of the archive
+    pub fn dir(&self) -> &Path { &self.dir }
+
+    /// Archive a websocket frame if it is a notification for a chat event, returning whether it was archived
+    pub fn archive_websocket(&mut self, frame: &str) -> Result<bool, ArchiveError> {
+        match Record::from_websocket(frame, now())? {
+            Some(record) => self.append(&record),
+            None => Ok(false),
+        }
+    }
+
+    /// Archive the body of a webhook request if it is a notification for a chat event, returning whether it was archived
+    pub fn archive_webhook(
+        &mut self,
+        body: &str,
+        metadata: &router::Metadata,
+    ) -> Result<bool, ArchiveError> {
+        match Record::from_webhook(body, metadata, now())? {
+            Some(record) => self.append(&record),
+            None => Ok(false),
+        }
+    }
+
+    /// Append a record to the current file of its channel, rotating files by day and size
+    ///
+    /// Returns `false` without writing if the record was redelivered, see [`ArchiveConfig::recent_message_ids`].
+    /// Only the records appended by this archive are remembered, not those in existing files.
+    pub fn append(&mut self, record: &Record) -> Result<bool, ArchiveError> {
+        if let Some(message_id) = &record.message_id {
+            let recent = self
+                .recent
+                .entry(record.broadcaster_user_id.clone())
+                .or_default();
+            if !recent.insert(message_id, self.config.recent_message_ids) {
+                return Ok(false);
+            }
+        }
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let date = record.received_at.as_str().get(..10).unwrap_or("unknown");
+        let rotate = match self.files.get(&record.broadcaster_user_id) {
+            Some(open) if open.date == date => (open.size > 0
+                && open.size + line.len() as u64 > self.config.max_file_size)
+                .then_some(open.index + 1),
+            _ => Some(self.last_index(&record.broadcaster_user_id, date)?),
+        };
+        if let Some(index) = rotate {
+            let channel_dir = self.dir.join(record.broadcaster_user_id.as_str());
+            fs::create_dir_all(&channel_dir)?;
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(channel_dir.join(file_name(date, index)))?;
+            let size = file.metadata()?.len();
+            let mut open = OpenFile {
+                date: date.to_owned(),
+                index,
+                size,
+                file,
+            };
+            // continuing the last file of the day after a restart
+            if open.size > 0 && open.size + line.len() as u64 > self.config.max_file_size {
+                open.index += 1;
+                open.file = fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(channel_dir.join(file_name(date, open.index)))?;
+                open.size = 0;
+            }
+            self.files.insert(record.broadcaster_user_id.clone(), open);
+        }
+        let open = self
+            .files
+            .get_mut(&record.broadcaster_user_id)
+            .expect("file was just opened");
+        open.file.write_all(&line)?;
+        open.size += line.len() as u64;
+        Ok(true)
+    }
+
+    /// Index of the last existing file of a channel on a day, or `0`
+    fn last_index(&self, broadcaster_id: &types::UserIdRef, date: &str) -> io::Result<u32> {
+        Ok(self
+            .files_of(broadcaster_id)?
+            .into_iter()
+            .filter(|(d, ..)| d == date)
+            .map(|(_, index, _)| index)
+            .max()
+            .unwrap_or(0))
+    }
+
+    fn files_of(
+        &self,
+        broadcaster_id: &types::UserIdRef,
+    ) -> io::Result<Vec<(String, u32, PathBuf)>> {
+        let channel_dir = self.dir.join(broadcaster_id.as_str());
+        let entries = match fs::read_dir(&channel_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        let mut files = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let Some(stem) = name.strip_suffix(".jsonl") else {
+                continue;
+            };
+            let (date, index) = match stem.split_once('.') {
+                Some((date, index)) => match index.parse() {
+                    Ok(index) => (date, index),
+                    Err(_) => continue,
+                },
+                None => (stem, 0),
+            };
+            files.push((date.to_owned(), index, path));
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// The files of a channel, oldest first
+    pub fn files(&self, broadcaster_id: &types::UserIdRef) -> io::Result<Vec<PathBuf>> {
+        Ok(self
+            .files_of(broadcaster_id)?
+            .into_iter()
+            .map(|(_, _, path)| path)
+            .collect())
+    }
+
+    /// Read all records of a channel, oldest first
+    pub fn records(
+        &self,
+        broadcaster_id: &types::UserIdRef,
+    ) -> io::Result<impl Iterator<Item = Result<Record, ArchiveError>>> {
+        // files are opened one at a time, as they are read
+        Ok(self.files(broadcaster_id)?.into_iter().flat_map(|path| {
+            let (records, error) = match File::open(path) {
+                Ok(file) => (Some(read_records(io::BufReader::new(file))), None),
+                Err(e) => (None, Some(Err(e.into()))),
+            };
+            records.into_iter().flatten().chain(error)
+        }))
+    }
+}
+
+fn file_name(date: &str, index: u32) -> String {
+    if index == 0 {
+        format!("{date}.jsonl")
+    } else {
+        format!("{date}.{index}.jsonl")
+    }
+}
+
+fn now() -> types::Timestamp { crate::unix_millis_to_timestamp(crate::now_unix_millis()) }
+
+/// Read records from JSON lines, skipping empty lines
+pub fn read_records<R: BufRead>(reader: R) -> impl Iterator<Item = Result<Record, ArchiveError>> {
+    reader.lines().filter_map(|line| match line {
+        Ok(line) if line.trim().is_empty() => None,
+        Ok(line) => Some(serde_json::from_str(&line).map_err(ArchiveError::from)),
+        Err(e) => Some(Err(e.into())),
+    })
+}
+
+/// Dispatch records to a [`Router`](router::Router) in order, returning how many were handled
+///
+/// Stops at the first record that can't be read or parsed.
+pub async fn replay(
+    router: &router::Router,
+    records: impl IntoIterator<Item = Result<Record, ArchiveError>>,
+) -> Result<usize, ArchiveError> {
+    let mut handled = 0;
+    for record in records {
+        let record = record?;
+        if router
+            .dispatch_with(record.event()?, record.metadata())
+            .await
+        {
+            handled += 1;
+        }
+    }
+    Ok(handled)
+}
+
+/// What a [`TranscriptLine`] is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LineKind {
+    /// A chat message
+    Message,
+    /// A notification, like a subscription or a raid
+    Notification,
+}
+
+/// A message or notification in a [`Transcript`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TranscriptLine {
+    /// What the line is
+    pub kind: LineKind,
+    /// ID of the message
+    pub message_id: types::MsgId,
+    /// When the message was sent, if known
+    pub timestamp: Option<types::Timestamp>,
+    /// The chatter, `None` for anonymous notifications
+    pub user_id: Option<types::UserId>,
+    /// Display name of the chatter
+    pub user_name: Option<types::DisplayName>,
+    /// The text of the message, or the system message of a notification
+    pub text: String,
+    /// Whether the message was deleted, or removed by a clear
+    pub deleted: bool,
+}
+
+/// The chat log of a channel, with deleted and cleared messages kept as tombstones
+#[derive(Clone, Debug, Default)]
+pub struct Transcript {
+    lines: Vec<TranscriptLine>,
+    index: HashMap<types::MsgId, usize>,
+    /// Deletions of messages that weren't seen yet
+    pending_deletes: HashSet<types::MsgId>,
+}
+
+impl Transcript {
+    /// Create an empty transcript
+    pub fn new() -> Self { Self::default() }
+
+    /// Apply an archived record
+    pub fn apply_record(&mut self, record: &Record) -> Result<(), ArchiveError> {
+        let timestamp = record.timestamp().to_owned();
+        self.apply(&record.event()?, Some(timestamp));
+        Ok(())
+    }
+
+    /// Apply a chat event. Messages and notifications are added, deletes and clears mark earlier messages as deleted
+    pub fn apply(&mut self, event: &Event, timestamp: Option<types::Timestamp>) {
+        match event {
+            Event::ChannelChatMessageV1(Payload {
+                message: Message::Notification(p),
+                ..
+            }) => self.push(TranscriptLine {
+                kind: LineKind::Message,
+                message_id: p.message_id.clone(),
+                timestamp,
+                user_id: Some(p.chatter_user_id.clone()),
+                user_name: Some(p.chatter_user_name.clone()),
+                text: p.message.text.clone(),
+                deleted: false,
+            }),
+            Event::ChannelChatNotificationV1(Payload {
+                message: Message::Notification(p),
+                ..
+            }) => {
+                let (user_id, user_name) = match &p.chatter {
+                    super::notification::Chatter::Chatter {
+                        chatter_user_id,
+                        chatter_user_name,
+                        ..
+                    } => (
+                        Some(chatter_user_id.clone()),
+                        Some(chatter_user_name.clone()),
+                    ),
+                    _ => (None, None),
+                };
+                let text = if p.message.text.is_empty() {
+                    p.system_message.clone()
+                } else {
+                    format!("{} {}", p.system_message, p.message.text)
+                };
+                self.push(TranscriptLine {
+                    kind: LineKind::Notification,
+                    message_id: p.message_id.clone(),
+                    timestamp,
+                    user_id,
+                    user_name,
+                    text,
+                    deleted: false,
+                })
+            }
+            Event::ChannelChatMessageDeleteV1(Payload {
+                message: Message::Notification(p),
+                ..
+            }) => match self.index.get(&p.message_id) {
+                Some(&i) => self.lines[i].deleted = true,
+                None => {
+                    self.pending_deletes.insert(p.message_id.clone());
+                }
+            },
+            Event::ChannelChatClearUserMessagesV1(Payload {
+                message: Message::Notification(p),
+                ..
+            }) => {
+                for line in &mut self.lines {
+                    if line.user_id.as_ref() == Some(&p.target_user_id) {
+                        line.deleted = true;
+                    }
+                }
+            }
+            Event::ChannelChatClearV1(Payload {
+                message: Message::Notification(_),
+                ..
+            }) => {
+                for line in &mut self.lines {
+                    line.deleted = true;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn push(&mut self, mut line: TranscriptLine) {
+        // redelivered
+        if self.index.contains_key(&line.message_id) {
+            return;
+        }
+        line.deleted = self.pending_deletes.remove(&line.message_id);
+        self.index.insert(line.message_id.clone(), self.lines.len());
+        self.lines.push(line);
+    }
+
+    /// All lines, including deleted ones
+    pub fn lines(&self) -> &[TranscriptLine] { &self.lines }
+
+    /// Lines that weren't deleted
+    pub fn visible(&self) -> impl Iterator<Item = &TranscriptLine> + '_ {
+        self.lines.iter().filter(|line| !line.deleted)
+    }
+
+    /// Write the visible lines as `[timestamp] name: text`, with notifications as `[timestamp] * text`
+    pub fn export(&self, mut writer: impl Write) -> io::Result<()> {
+        for line in self.visible() {
+            let timestamp = line.timestamp.as_ref().map_or("", |t| t.as_str());
+            match (line.kind, &line.user_name) {
+                (LineKind::Message, Some(name)) => {
+                    writeln!(writer, "[{timestamp}] {name}: {}", line.text)?
+                }
+                _ => writeln!(writer, "[{timestamp}] * {}", line.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn frame(subscription_type: &str, message_id: &str, event: serde_json::Value) -> String {
+        serde_json::json!({
+            "metadata": {
+                "message_id": message_id,
+                "message_type": "notification",
+                "message_timestamp": "2023-11-06T18:11:47.492253549Z",
+                "subscription_type": subscription_type,
+                "subscription_version": "1"
+            },
+            "payload": {
+                "subscription": {
+                    "id": "0b7f3361-672b-4d39-b307-dd5b576c9b27",
+                    "status": "enabled",
+                    "type": subscription_type,
+                    "version": "1",
+                    "condition": {
+                        "broadcaster_user_id": "1971641",
+                        "user_id": "2914196"
+                    },
+                    "transport": {
+                        "method": "websocket",
+                        "session_id": "AgoQHR3s6Mb4T8GFB1l3DlPfiRIGY2VsbC1h"
+                    },
+                    "created_at": "2023-11-06T18:11:47.492253549Z",
+                    "cost": 0
+                },
+                "event": event
+            }
+        })
+        .to_string()
+    }
+
+    fn chat_message(message_id: &str, user_id: &str, text: &str) -> String {
+        frame(
+            "channel.chat.message",
+            message_id,
+            serde_json::json!({
+                "broadcaster_user_id": "1971641",
+                "broadcaster_user_login": "streamer",
+                "broadcaster_user_name": "streamer",
+                "chatter_user_id": user_id,
+                "chatter_user_login": format!("viewer{user_id}"),
+                "chatter_user_name": format!("Viewer{user_id}"),
+                "message_id": message_id,
+                "message": {
+                    "text": text,
+                    "fragments": [{"type": "text", "text": text}]
+                },
+                "color": "",
+                "badges": [],
+                "message_type": "text",
+                "cheer": null,
+                "reply": null,
+                "channel_points_custom_reward_id": null,
+                "source_broadcaster_user_id": null,
+                "source_broadcaster_user_login": null,
+                "source_broadcaster_user_name": null,
+                "source_message_id": null,
+                "source_badges": null
+            }),
+        )
+    }
+
+    fn message_delete(message_id: &str, user_id: &str) -> String {
+        frame(
+            "channel.chat.message_delete",
+            &format!("delete-{message_id}"),
+            serde_json::json!({
+                "broadcaster_user_id": "1971641",
+                "broadcaster_user_login": "streamer",
+                "broadcaster_user_name": "streamer",
+                "target_user_id": user_id,
+                "target_user_login": format!("viewer{user_id}"),
+                "target_user_name": format!("Viewer{user_id}"),
+                "message_id": message_id
+            }),
+        )
+    }
+
+    #[test]
+    fn archive_and_transcript() {
+        let dir =
+            std::env::temp_dir().join(format!("twitch_api-chat-archive-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let frames = [
+            chat_message("m1", "1", "hello"),
+            chat_message("m2", "2", "bad words"),
+            chat_message("m3", "1", "how is everyone?"),
+            message_delete("m2", "2"),
+        ];
+        let mut archive = ChatArchive::new(&dir, ArchiveConfig::default().max_file_size(2000));
+        for frame in &frames {
+            assert!(archive.archive_websocket(frame).unwrap());
+        }
+        // redelivered
+        assert!(!archive.archive_websocket(&frames[1]).unwrap());
+        // not a chat event
+        let keepalive = r#"{"metadata":{"message_id":"1","message_type":"session_keepalive","message_timestamp":"2023-07-19T10:11:12.634234626Z"},"payload":{}}"#;
+        assert!(!archive.archive_websocket(keepalive).unwrap());
+        assert!(archive.files("1971641".into()).unwrap().len() > 1);
+
+        // read back by a new archive, in order
+        let archive = ChatArchive::new(&dir, ArchiveConfig::default());
+        let records: Vec<Record> = archive
+            .records("1971641".into())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[1].subscription_type, EventType::ChannelChatMessage);
+        assert_eq!(records[1].message_id.as_deref(), Some("m2"));
+        assert!(matches!(
+            records[3].event().unwrap(),
+            Event::ChannelChatMessageDeleteV1(_)
+        ));
+
+        let mut transcript = Transcript::new();
+        for record in &records {
+            transcript.apply_record(record).unwrap();
+        }
+        // redelivered messages are only added once
+        transcript.apply_record(&records[0]).unwrap();
+        assert_eq!(transcript.lines().len(), 3);
+        assert!(transcript.lines()[1].deleted);
+        let mut export = vec![];
+        transcript.export(&mut export).unwrap();
+        assert_eq!(
+            String::from_utf8(export).unwrap(),
+            "[2023-11-06T18:11:47.492253549Z] Viewer1: hello\n\
+             [2023-11-06T18:11:47.492253549Z] Viewer1: how is everyone?\n"
+        );
+
+        // a delete can arrive before its message
+        let mut transcript = Transcript::new();
+        let timestamp = types::Timestamp::try_from("2023-11-06T18:11:47Z").ok();
+        transcript.apply(&records[3].event().unwrap(), timestamp.clone());
+        transcript.apply(&records[1].event().unwrap(), timestamp);
+        assert_eq!(transcript.visible().count(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn webhook() {
+        let frame: serde_json::Value =
+            serde_json::from_str(&chat_message("m1", "1", "hello")).unwrap();
+        let body = frame["payload"].to_string();
+        let received_at = types::Timestamp::try_from("2023-11-06T18:11:48Z").unwrap();
+        let metadata = router::Metadata::new("m1".to_owned(), None::<types::Timestamp>);
+        let record = Record::from_webhook(&body, &metadata, received_at)
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.subscription_type, EventType::ChannelChatMessage);
+        assert_eq!(record.subscription_version, "1");
+        assert_eq!(record.message_id.as_deref(), Some("m1"));
+        assert_eq!(record.timestamp().as_str(), "2023-11-06T18:11:48Z");
+        assert_eq!(record.payload.get(), body);
+    }
+}
@@ -0,0 +1,805 @@
+//! Chat commands for [`channel.chat.message`](super::ChannelChatMessageV1) notifications
+//!
+//! [`Commands`] holds a set of [`Command`]s and matches [chat messages](ChannelChatMessageV1Payload) against them:
+//!
+//! * messages have to start with one of the [prefixes](Commands::prefix), `!` by default,
+//! * commands are matched on their name or one of their [aliases](Command::alias), ignoring case,
+//! * the chatter needs the [`Permission`] of the command, which is derived from their badges,
+//! * [per-user](Command::user_cooldown) and [global](Command::global_cooldown) cooldowns are enforced,
+//! * arguments are parsed with [`Args::next`], and [mentions](super::Fragment::Mention) resolve to the mentioned user.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::time::{Duration, Instant};
+//! use twitch_api::eventsub::channel::chat::{
+//!     commands::{Command, CommandError, Commands, Permission},
+//!     ChannelChatMessageV1Payload,
+//! };
+//! use twitch_api::types;
+//!
+//! let mut commands = Commands::new()
+//!     .command(
+//!         Command::new("dice")
+//!             .alias("roll")
+//!             .user_cooldown(Duration::from_secs(10)),
+//!     )
+//!     .command(
+//!         Command::new("so")
+//!             .alias("shoutout")
+//!             .permission(Permission::Moderator),
+//!     );
+//!
+//! # let event = twitch_api::eventsub::Event::parse(r#"{
+//! #     "subscription": {
+//! #         "id": "0b7f3361-672b-4d39-b307-dd5b576c9b27", "status": "enabled", "type": "channel.chat.message", "version": "1",
+//! #         "condition": {"broadcaster_user_id": "1971641", "user_id": "2914196"},
+//! #         "transport": {"method": "websocket", "session_id": "AgoQHR3s6Mb4T8GFB1l3DlPfiRIGY2VsbC1h"},
+//! #         "created_at": "2023-11-06T18:11:47.492253549Z", "cost": 0
+//! #     },
+//! #     "event": {
+//! #         "broadcaster_user_id": "1971641", "broadcaster_user_login": "streamer", "broadcaster_user_name": "streamer",
+//! #         "chatter_user_id": "4145994", "chatter_user_login": "viewer32", "chatter_user_name": "viewer32",
+//! #         "message_id": "cc106a89-1814-919d-454c-f4f2f970aae7",
+//! #         "message": {"text": "!roll 20", "fragments": [{"type": "text", "text": "!roll 20", "cheermote": null, "emote": null, "mention": null}]},
+//! #         "color": "#00FF7F", "badges": [], "message_type": "text", "cheer": null, "reply": null,
+//! #         "channel_points_custom_reward_id": null, "source_broadcaster_user_id": null, "source_broadcaster_user_login": null,
+//! #         "source_broadcaster_user_name": null, "source_message_id": null, "source_badges": null
+//! #     }
+//! # }"#)?;
+//! # let payload: ChannelChatMessageV1Payload = match event {
+//! #     twitch_api::eventsub::Event::ChannelChatMessageV1(twitch_api::eventsub::Payload {
+//! #         message: twitch_api::eventsub::Message::Notification(payload),
+//! #         ..
+//! #     }) => payload,
+//! #     _ => unreachable!(),
+//! # };
+//! match commands.parse(&payload, Instant::now()) {
+//!     Ok(Some(mut invocation)) => match invocation.command.as_str() {
+//!         "dice" => {
+//!             let sides: Option<u32> = invocation.args.next()?;
+//!             println!("rolling a d{}", sides.unwrap_or(6));
+//!         }
+//!         "so" => {
+//!             let user: types::UserId = invocation.args.next()?;
+//!             println!("shouting out {user}");
+//!         }
+//!         _ => unreachable!(),
+//!     },
+//!     // not a command
+//!     Ok(None) => {}
+//!     Err(CommandError::Cooldown { remaining, .. }) => {
+//!         println!("try again in {}s", remaining.as_secs())
+//!     }
+//!     Err(e) => println!("{e}"),
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::{message::Badge, ChannelChatMessageV1Payload, Fragment, Mention};
+use crate::types;
+
+/// What a chatter is allowed to run, derived from their badges
+///
+/// Permissions are ordered, a moderator can run everything a VIP can.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Permission {
+    /// Anyone in chat
+    #[default]
+    Everyone,
+    /// Subscribers and founders
+    Subscriber,
+    /// VIPs
+    Vip,
+    /// Moderators and lead moderators
+    Moderator,
+    /// The broadcaster
+    Broadcaster,
+}
+
+impl Permission {
+    /// The permission of the chatter that sent `payload`
+    pub fn of(payload: &ChannelChatMessageV1Payload) -> Self {
+        if payload.chatter_user_id == payload.broadcaster_user_id {
+            return Self::Broadcaster;
+        }
+        Self::from_badges(&payload.badges)
+    }
+
+    /// The highest permission granted by a set of badges
+    pub fn from_badges(badges: &[Badge]) -> Self {
+        badges
+            .iter()
+            .map(|badge| match badge.set_id.as_str() {
+                "broadcaster" => Self::Broadcaster,
+                "moderator" | "lead_moderator" => Self::Moderator,
+                "vip" => Self::Vip,
+                "subscriber" | "founder" => Self::Subscriber,
+                _ => Self::Everyone,
+            })
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// A chat command, see [`Commands::command`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Command {
+    /// Name of the command, without prefix
+    pub name: String,
+    /// Other names of the command
+    pub aliases: Vec<String>,
+    /// Permission needed to run the command
+    pub permission: Permission,
+    /// Time a chatter has to wait before running the command again
+    pub user_cooldown: Option<Duration>,
+    /// Time anyone has to wait after the command was run
+    pub global_cooldown: Option<Duration>,
+    /// Chatters with this permission or higher are not subject to cooldowns. Defaults to [`Permission::Moderator`]
+    pub cooldown_bypass: Permission,
+}
+
+impl Command {
+    /// Create a command that anyone can run without cooldowns
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            aliases: vec![],
+            permission: Permission::Everyone,
+            user_cooldown: None,
+            global_cooldown: None,
+            cooldown_bypass: Permission::Moderator,
+        }
+    }
+
+    /// Add an alias
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    /// Set the permission needed to run the command
+    pub fn permission(mut self, permission: Permission) -> Self {
+        self.permission = permission;
+        self
+    }
+
+    /// Set the per-user cooldown
+    pub fn user_cooldown(mut self, cooldown: Duration) -> Self {
+        self.user_cooldown = Some(cooldown);
+        self
+    }
+
+    /// Set the global cooldown
+    pub fn global_cooldown(mut self, cooldown: Duration) -> Self {
+        self.global_cooldown = Some(cooldown);
+        self
+    }
+
+    /// Set the permission that bypasses cooldowns
+    pub fn cooldown_bypass(mut self, permission: Permission) -> Self {
+        self.cooldown_bypass = permission;
+        self
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+}
+
+/// A set of chat commands
+///
+/// See the [module documentation](self) for more.
+#[derive(Clone, Debug)]
+pub struct Commands {
+    prefixes: Vec<String>,
+    commands: Vec<Command>,
+    last_used: Vec<Option<Instant>>,
+    last_used_by: HashMap<(usize, types::UserId), Instant>,
+}
+
+impl Default for Commands {
+    fn default() -> Self { Self::new() }
+}
+
+impl Commands {
+    /// Create an empty set of commands with the prefix `!`
+    pub fn new() -> Self {
+        Self {
+            prefixes: vec!["!".to_owned()],
+            commands: vec![],
+            last_used: vec![],
+            last_used_by: HashMap::new(),
+        }
+    }
+
+    /// Replace the prefixes with `prefix`, call [`Commands::add_prefix`] for more
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefixes = vec![prefix.into()];
+        self
+    }
+
+    /// Add a prefix
+    pub fn add_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefixes.push(prefix.into());
+        // longest first, so that `!!` wins over `!`
+        self.prefixes.sort_by(|a, b| b.len().cmp(&a.len()));
+        self
+    }
+
+    /// Add a command
+    pub fn command(mut self, command: Command) -> Self {
+        self.commands.push(command);
+        self.last_used.push(None);
+        self
+    }
+
+    /// All commands
+    pub fn commands(&self) -> &[Command] { &self.commands }
+
+    /// Match a chat message against the commands
+    ///
+    /// Returns `Ok(None)` if the message doesn't start with a prefix. A successful match counts towards the cooldowns of the command, unless the chatter [bypasses them](Command::cooldown_bypass).
+    pub fn parse<'a>(
+        &mut self,
+        payload: &'a ChannelChatMessageV1Payload,
+        now: Instant,
+    ) -> Result<Option<Invocation<'a>>, CommandError> {
+        let text = payload.message.text.as_str();
+        let Some(prefix) = self
+            .prefixes
+            .iter()
+            .find(|prefix| !prefix.is_empty() && text.starts_with(prefix.as_str()))
+        else {
+            return Ok(None);
+        };
+        let after_prefix = &text[prefix.len()..];
+        let name = after_prefix.split_whitespace().next().unwrap_or_default();
+        if name.is_empty() || !after_prefix.starts_with(name) {
+            // `! foo` is not a command
+            return Ok(None);
+        }
+        let args_start = prefix.len() + name.len();
+
+        let Some(index) = self.commands.iter().position(|c| c.matches(name)) else {
+            return Err(CommandError::Unknown {
+                name: name.to_owned(),
+            });
+        };
+        let command = &self.commands[index];
+        let permission = Permission::of(payload);
+        if permission < command.permission {
+            return Err(CommandError::Denied {
+                command: command.name.clone(),
+                required: command.permission,
+                actual: permission,
+            });
+        }
+
+        let on_cooldown = permission < command.cooldown_bypass;
+        if on_cooldown {
+            let global = command
+                .global_cooldown
+                .zip(self.last_used[index])
+                .map(|(cooldown, at)| (at + cooldown).saturating_duration_since(now));
+            let user = command
+                .user_cooldown
+                .zip(
+                    self.last_used_by
+                        .get(&(index, payload.chatter_user_id.clone()))
+                        .copied(),
+                )
+                .map(|(cooldown, at)| (at + cooldown).saturating_duration_since(now));
+            let remaining = global.max(user).unwrap_or_default();
+            if !remaining.is_zero() {
+                return Err(CommandError::Cooldown {
+                    command: command.name.clone(),
+                    remaining,
+                });
+            }
+        }
+
+        // chatters that bypass cooldowns don't start them either
+        if on_cooldown {
+            self.last_used[index] = Some(now);
+            if command.user_cooldown.is_some() {
+                let commands = &self.commands;
+                self.last_used_by.retain(|(i, _), at| {
+                    commands[*i]
+                        .user_cooldown
+                        .map_or(false, |cooldown| *at + cooldown > now)
+                });
+                self.last_used_by
+                    .insert((index, payload.chatter_user_id.clone()), now);
+            }
+        }
+
+        Ok(Some(Invocation {
+            command: self.commands[index].name.clone(),
+            alias: name.to_owned(),
+            permission,
+            args: Args::new(payload, args_start),
+            payload,
+        }))
+    }
+}
+
+/// Errors when matching a chat message against [`Commands`]
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error, displaydoc::Display)]
+#[non_exhaustive]
+pub enum CommandError {
+    /// unknown command `{name}`
+    Unknown {
+        /// The name as written in chat
+        name: String,
+    },
+    /// command `{command}` requires {required:?}, chatter is {actual:?}
+    Denied {
+        /// The command
+        command: String,
+        /// The permission needed
+        required: Permission,
+        /// The permission of the chatter
+        actual: Permission,
+    },
+    /// command `{command}` is on cooldown for {remaining:?}
+    Cooldown {
+        /// The command
+        command: String,
+        /// Time until the command can be run again
+        remaining: Duration,
+    },
+}
+
+/// A matched command, returned by [`Commands::parse`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Invocation<'a> {
+    /// The name of the command, regardless of the alias used
+    pub command: String,
+    /// The name or alias the command was invoked with
+    pub alias: String,
+    /// The permission of the chatter
+    pub permission: Permission,
+    /// The arguments of the command
+    pub args: Args<'a>,
+    /// The chat message
+    pub payload: &'a ChannelChatMessageV1Payload,
+}
+
+impl<'a> Invocation<'a> {
+    /// Reply to the message that invoked the command
+    #[cfg(all(feature = "helix", feature = "client"))]
+    #[cfg_attr(nightly, doc(cfg(all(feature = "helix", feature = "client"))))]
+    pub async fn reply<'c, 'b, C, T>(
+        &self,
+        client: &'c crate::HelixClient<'c, C>,
+        sender_id: impl types::IntoCow<'b, types::UserIdRef> + Send + 'b,
+        message: impl Into<&'b str> + Send,
+        token: &T,
+    ) -> Result<
+        crate::helix::chat::SendChatMessageResponse,
+        crate::helix::ClientRequestError<<C as crate::HttpClient>::Error>,
+    >
+    where
+        'a: 'b,
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+    {
+        client
+            .send_chat_message_reply(
+                &*self.payload.broadcaster_user_id,
+                sender_id,
+                &*self.payload.message_id,
+                message,
+                token,
+            )
+            .await
+    }
+}
+
+/// A single argument
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Arg<'a> {
+    /// The text of the argument
+    pub text: &'a str,
+    /// The mentioned user, if the argument is a [mention](Fragment::Mention)
+    pub mention: Option<&'a Mention>,
+}
+
+/// Arguments of an [`Invocation`], split on whitespace
+///
+/// Mentions are always a single argument.
+#[derive(Clone, Debug)]
+pub struct Args<'a> {
+    message: &'a str,
+    /// Arguments with their byte offset in `message`
+    args: Vec<(usize, Arg<'a>)>,
+    position: usize,
+}
+
+impl<'a> Args<'a> {
+    fn new(payload: &'a ChannelChatMessageV1Payload, start: usize) -> Self {
+        let mut args = vec![];
+        // fragments concatenate to the message text
+        let mut begin = 0;
+        for fragment in &payload.message.fragments {
+            let text = fragment.text();
+            let end = begin + text.len();
+            match fragment {
+                Fragment::Mention { mention, .. } if begin >= start => args.push((
+                    begin,
+                    Arg {
+                        text,
+                        mention: Some(mention),
+                    },
+                )),
+                _ if end > start => {
+                    for word in text.split_whitespace() {
+                        let offset = begin + (word.as_ptr() as usize - text.as_ptr() as usize);
+                        if offset >= start {
+                            args.push((
+                                offset,
+                                Arg {
+                                    text: word,
+                                    mention: None,
+                                },
+                            ));
+                        }
+                    }
+                }
+                _ => (),
+            }
+            begin = end;
+        }
+        Self {
+            message: &payload.message.text,
+            args,
+            position: 0,
+        }
+    }
+
+    /// Parse the next argument
+    ///
+    /// Use an [`Option`] for optional arguments.
+    pub fn next<T: FromArg<'a>>(&mut self) -> Result<T, ArgError> {
+        let index = self.position;
+        let Some((_, arg)) = self.args.get(index).copied() else {
+            return T::missing().ok_or(ArgError::Missing {
+                index,
+                expected: T::EXPECTED,
+            });
+        };
+        let value = T::from_arg(arg).ok_or_else(|| ArgError::Invalid {
+            index,
+            value: arg.text.to_owned(),
+            expected: T::EXPECTED,
+        })?;
+        self.position += 1;
+        Ok(value)
+    }
+
+    /// The next argument, without consuming it
+    pub fn peek(&self) -> Option<Arg<'a>> { self.args.get(self.position).map(|(_, arg)| *arg) }
+
+    /// The remaining text of the message, starting at the next argument
+    pub fn rest(&self) -> &'a str {
+        match self.args.get(self.position) {
+            Some((offset, arg)) => self.message.get(*offset..).unwrap_or(arg.text),
+            None => "",
+        }
+    }
+
+    /// Number of arguments left
+    pub fn len(&self) -> usize { self.args.len() - self.position }
+
+    /// Whether there are no arguments left
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = Arg<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let arg = self.peek()?;
+        self.position += 1;
+        Some(arg)
+    }
+}
+
+/// Errors when parsing [`Args`]
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error, displaydoc::Display)]
+#[non_exhaustive]
+pub enum ArgError {
+    /// missing argument {index}, expected {expected}
+    Missing {
+        /// Index of the argument
+        index: usize,
+        /// What was expected
+        expected: &'static str,
+    },
+    /// invalid argument {index} `{value}`, expected {expected}
+    Invalid {
+        /// Index of the argument
+        index: usize,
+        /// The argument as written in chat
+        value: String,
+        /// What was expected
+        expected: &'static str,
+    },
+}
+
+/// A type that can be parsed from an [`Arg`], see [`Args::next`]
+pub trait FromArg<'a>: Sized {
+    /// Description of the expected argument, used in [`ArgError`]
+    const EXPECTED: &'static str;
+
+    /// Parse the argument
+    fn from_arg(arg: Arg<'a>) -> Option<Self>;
+
+    /// The value if the argument is missing
+    fn missing() -> Option<Self> { None }
+}
+
+impl<'a, T: FromArg<'a>> FromArg<'a> for Option<T> {
+    const EXPECTED: &'static str = T::EXPECTED;
+
+    fn from_arg(arg: Arg<'a>) -> Option<Self> { T::from_arg(arg).map(Some) }
+
+    fn missing() -> Option<Self> { Some(None) }
+}
+
+impl<'a> FromArg<'a> for Arg<'a> {
+    const EXPECTED: &'static str = "an argument";
+
+    fn from_arg(arg: Arg<'a>) -> Option<Self> { Some(arg) }
+}
+
+impl<'a> FromArg<'a> for &'a str {
+    const EXPECTED: &'static str = "a word";
+
+    fn from_arg(arg: Arg<'a>) -> Option<Self> { Some(arg.text) }
+}
+
+impl FromArg<'_> for String {
+    const EXPECTED: &'static str = "a word";
+
+    fn from_arg(arg: Arg<'_>) -> Option<Self> { Some(arg.text.to_owned()) }
+}
+
+impl<'a> FromArg<'a> for &'a Mention {
+    const EXPECTED: &'static str = "a mention";
+
+    fn from_arg(arg: Arg<'a>) -> Option<Self> { arg.mention }
+}
+
+/// Only mentions resolve to a user id
+impl FromArg<'_> for types::UserId {
+    const EXPECTED: &'static str = "a mention";
+
+    fn from_arg(arg: Arg<'_>) -> Option<Self> { arg.mention.map(|m| m.user_id.clone()) }
+}
+
+/// Mentions, or a login with or without `@`
+impl FromArg<'_> for types::UserName {
+    const EXPECTED: &'static str = "a user";
+
+    fn from_arg(arg: Arg<'_>) -> Option<Self> {
+        match arg.mention {
+            Some(mention) => Some(mention.user_login.clone()),
+            None => {
+                let login = arg.text.strip_prefix('@').unwrap_or(arg.text);
+                (!login.is_empty()).then(|| types::UserName::new(login.to_lowercase()))
+            }
+        }
+    }
+}
+
+macro_rules! from_str_arg {
+    ($($ty:ty => $expected:literal),* $(,)?) => {$(
+        impl FromArg<'_> for $ty {
+            const EXPECTED: &'static str = $expected;
+
+            fn from_arg(arg: Arg<'_>) -> Option<Self> { arg.text.parse().ok() }
+        }
+    )*};
+}
+
+from_str_arg! {
+    u8 => "a number", u16 => "a number", u32 => "a number", u64 => "a number", usize => "a number",
+    i8 => "a number", i16 => "a number", i32 => "a number", i64 => "a number", isize => "a number",
+    f32 => "a number", f64 => "a number", bool => "true or false",
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(
+        chatter_id: &str,
+        badges: &[&str],
+        fragments: serde_json::Value,
+    ) -> ChannelChatMessageV1Payload {
+        let text = fragments
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["text"].as_str().unwrap())
+            .collect::<String>();
+        let badges = badges
+            .iter()
+            .map(|set_id| serde_json::json!({ "set_id": set_id, "id": "1", "info": "" }))
+            .collect::<Vec<_>>();
+        serde_json::from_value(serde_json::json!({
+            "broadcaster_user_id": "1971641",
+            "broadcaster_user_login": "streamer",
+            "broadcaster_user_name": "streamer",
+            "chatter_user_id": chatter_id,
+            "chatter_user_login": "viewer32",
+            "chatter_user_name": "viewer32",
+            "message_id": "cc106a89-1814-919d-454c-f4f2f970aae7",
+            "message": { "text": text, "fragments": fragments },
+            "color": "",
+            "badges": badges,
+            "message_type": "text",
+            "cheer": null,
+            "reply": null,
+            "channel_points_custom_reward_id": null,
+            "channel_points_animation_id": null,
+            "source_badges": null,
+        }))
+        .unwrap()
+    }
+
+    fn text(text: &str) -> serde_json::Value {
+        serde_json::json!([{ "type": "text", "text": text }])
+    }
+
+    #[test]
+    fn args_and_mentions() {
+        let mut commands = Commands::new()
+            .add_prefix("?")
+            .command(Command::new("so").alias("shoutout"));
+        let message = payload(
+            "4145994",
+            &[],
+            serde_json::json!([
+                { "type": "text", "text": "?ShoutOut " },
+                {
+                    "type": "mention",
+                    "text": "@streamer",
+                    "mention": { "user_id": "1971641", "user_name": "Streamer", "user_login": "streamer" }
+                },
+                { "type": "text", "text": " 3 go  follow" },
+            ]),
+        );
+        let mut invocation = commands.parse(&message, Instant::now()).unwrap().unwrap();
+        assert_eq!(invocation.command, "so");
+        assert_eq!(invocation.alias, "ShoutOut");
+        assert_eq!(invocation.args.len(), 4);
+        assert_eq!(invocation.args.rest(), "@streamer 3 go  follow");
+        let user: types::UserId = invocation.args.next().unwrap();
+        assert_eq!(user.as_str(), "1971641");
+        assert_eq!(invocation.args.next::<u32>(), Ok(3));
+        assert_eq!(
+            invocation.args.next::<u32>(),
+            Err(ArgError::Invalid {
+                index: 2,
+                value: "go".to_owned(),
+                expected: "a number"
+            })
+        );
+        assert_eq!(invocation.args.rest(), "go  follow");
+        assert_eq!(invocation.args.next::<&str>(), Ok("go"));
+        assert_eq!(invocation.args.next::<String>().unwrap(), "follow");
+        assert_eq!(invocation.args.next::<Option<u32>>(), Ok(None));
+        assert!(invocation.args.next::<&str>().is_err());
+
+        assert_eq!(
+            commands
+                .parse(&payload("1", &[], text("hi")), Instant::now())
+                .map(|i| i.is_some()),
+            Ok(false)
+        );
+        assert_eq!(
+            commands
+                .parse(&payload("1", &[], text("! so")), Instant::now())
+                .map(|i| i.is_some()),
+            Ok(false)
+        );
+        assert_eq!(
+            commands
+                .parse(&payload("1", &[], text("!nope")), Instant::now())
+                .map(|i| i.is_some()),
+            Err(CommandError::Unknown {
+                name: "nope".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn permissions_and_cooldowns() {
+        let now = Instant::now();
+        let mut commands = Commands::new()
+            .command(Command::new("ban").permission(Permission::Moderator))
+            .command(
+                Command::new("dice")
+                    .user_cooldown(Duration::from_secs(30))
+                    .global_cooldown(Duration::from_secs(5)),
+            );
+
+        assert_eq!(
+            Permission::from_badges(&payload("1", &["subscriber", "vip"], text("")).badges),
+            Permission::Vip
+        );
+        assert_eq!(
+            Permission::of(&payload("1971641", &[], text(""))),
+            Permission::Broadcaster
+        );
+        assert_eq!(
+            commands
+                .parse(&payload("1", &["vip"], text("!ban")), now)
+                .map(|i| i.is_some()),
+            Err(CommandError::Denied {
+                command: "ban".to_owned(),
+                required: Permission::Moderator,
+                actual: Permission::Vip,
+            })
+        );
+        assert!(commands
+            .parse(&payload("1", &["moderator"], text("!ban")), now)
+            .unwrap()
+            .is_some());
+
+        let dice = |user| payload(user, &[], text("!dice"));
+        assert!(commands.parse(&dice("1"), now).unwrap().is_some());
+        assert_eq!(
+            commands
+                .parse(&dice("2"), now + Duration::from_secs(1))
+                .map(|i| i.is_some()),
+            Err(CommandError::Cooldown {
+                command: "dice".to_owned(),
+                remaining: Duration::from_secs(4),
+            })
+        );
+        assert!(commands
+            .parse(&dice("2"), now + Duration::from_secs(5))
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            commands
+                .parse(&dice("1"), now + Duration::from_secs(10))
+                .map(|i| i.is_some()),
+            Err(CommandError::Cooldown {
+                command: "dice".to_owned(),
+                remaining: Duration::from_secs(20),
+            })
+        );
+        // moderators bypass cooldowns
+        assert!(commands
+            .parse(
+                &payload("3", &["moderator"], text("!dice")),
+                now + Duration::from_secs(10)
+            )
+            .unwrap()
+            .is_some());
+        // and don't start them for others
+        assert!(commands
+            .parse(&dice("4"), now + Duration::from_secs(11))
+            .unwrap()
+            .is_some());
+        assert!(commands
+            .parse(&dice("1"), now + Duration::from_secs(30))
+            .unwrap()
+            .is_some());
+    }
+}
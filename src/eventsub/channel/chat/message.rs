@@ -3,6 +3,10 @@
 
 use super::*;
 
+#[cfg(feature = "deser_borrow")]
+#[cfg_attr(nightly, doc(cfg(feature = "deser_borrow")))]
+pub mod borrowed;
+
 /// [`channel.chat.message`](https://dev.twitch.tv/docs/eventsub/eventsub-subscription-types/#channelchatmessage): a user sends a message to a specific chat room.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "typed-builder", derive(typed_builder::TypedBuilder))]
@@ -0,0 +1,653 @@
+//! Borrowed [`channel.chat.message`](super::ChannelChatMessageV1) payloads
+//!
+//! [`Event::parse_websocket`](crate::eventsub::Event::parse_websocket) deserializes every notification into owned types.
+//! For busy chats, [`parse_websocket`] borrows the fields of a `channel.chat.message` notification from the frame instead,
+//! so a message can be handled without allocating a [`String`] per field.
+//! Strings that contain JSON escapes can not be borrowed and are owned, which [`Cow`] takes care of.
+//!
+//! Use [`ChannelChatMessageV1Payload::into_owned`] to keep a message around after the frame is dropped.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::borrow::Cow;
+//! use twitch_api::eventsub::{channel::chat::message::borrowed, Event};
+//!
+//! # let frame = r##"{"metadata":{"message_id":"befa7b53-d79d-478f-86b9-120f112b044e","message_type":"notification","message_timestamp":"2023-11-06T18:11:47.492253549Z","subscription_type":"channel.chat.message","subscription_version":"1"},"payload":{"subscription":{"id":"0b7f3361-672b-4d39-b307-dd5b576c9b27","status":"enabled","type":"channel.chat.message","version":"1","condition":{"broadcaster_user_id":"1971641","user_id":"2914196"},"transport":{"method":"websocket","session_id":"AgoQHR3s6Mb4T8GFB1l3DlPfiRIGY2VsbC1h"},"created_at":"2023-11-06T18:11:47.492253549Z","cost":0},"event":{"broadcaster_user_id":"1971641","broadcaster_user_login":"streamer","broadcaster_user_name":"streamer","chatter_user_id":"4145994","chatter_user_login":"viewer32","chatter_user_name":"viewer32","message_id":"cc106a89-1814-919d-454c-f4f2f970aae7","message":{"text":"Hi chat","fragments":[{"type":"text","text":"Hi chat","cheermote":null,"emote":null,"mention":null}]},"color":"#00FF7F","badges":[],"message_type":"text","cheer":null,"reply":null,"channel_points_custom_reward_id":null,"channel_points_animation_id":null}}}"##;
+//! match borrowed::parse_websocket(frame)? {
+//!     Some(notification) => {
+//!         let event = &notification.event;
+//!         assert!(matches!(event.message.text, Cow::Borrowed("Hi chat")));
+//!         println!("{}: {}", event.chatter_user_name, event.message.text);
+//!     }
+//!     // some other frame
+//!     None => {
+//!         let _data = Event::parse_websocket(frame)?;
+//!     }
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use std::borrow::Cow;
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::{Cheer, MessageType};
+use crate::eventsub::{
+    event::websocket::{EventsubWebsocketMetadata, NotificationMetadata},
+    EventSubscription, EventSubscriptionInformation, PayloadParseError,
+};
+use crate::types;
+
+/// A `channel.chat.message` notification borrowed from a websocket frame, see [`parse_websocket`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Notification<'a> {
+    /// Metadata of the frame
+    pub metadata: NotificationMetadata<'a>,
+    /// The subscription, parse it with [`Notification::subscription`]
+    pub subscription: &'a serde_json::value::RawValue,
+    /// The chat message
+    pub event: ChannelChatMessageV1Payload<'a>,
+}
+
+impl Notification<'_> {
+    /// Parse the subscription the notification was sent for
+    pub fn subscription(
+        &self,
+    ) -> Result<EventSubscriptionInformation<super::ChannelChatMessageV1>, crate::DeserError> {
+        crate::parse_json(self.subscription.get(), true)
+    }
+}
+
+/// Parse a websocket frame as a borrowed `channel.chat.message` notification
+///
+/// Returns [`None`] if the frame is something else, e.g. a keepalive or another event, which can be parsed with
+/// [`Event::parse_websocket`](crate::eventsub::Event::parse_websocket).
+pub fn parse_websocket(frame: &str) -> Result<Option<Notification<'_>>, PayloadParseError> {
+    #[derive(Deserialize)]
+    #[cfg_attr(feature = "deny_unknown_fields", serde(deny_unknown_fields))]
+    struct Frame<'a> {
+        #[serde(borrow)]
+        metadata: EventsubWebsocketMetadata<'a>,
+        #[serde(borrow)]
+        payload: &'a serde_json::value::RawValue,
+    }
+
+    #[derive(Deserialize)]
+    #[cfg_attr(feature = "deny_unknown_fields", serde(deny_unknown_fields))]
+    struct NotificationPayload<'a> {
+        #[serde(borrow)]
+        subscription: &'a serde_json::value::RawValue,
+        #[serde(borrow)]
+        event: ChannelChatMessageV1Payload<'a>,
+    }
+
+    let frame: Frame = crate::parse_json(frame, true)?;
+    let EventsubWebsocketMetadata::Notification(metadata) = frame.metadata else {
+        return Ok(None);
+    };
+    if metadata.subscription_type != super::ChannelChatMessageV1::EVENT_TYPE
+        || metadata.subscription_version != super::ChannelChatMessageV1::VERSION
+    {
+        return Ok(None);
+    }
+    let payload: NotificationPayload = crate::parse_json(frame.payload.get(), true)?;
+    Ok(Some(Notification {
+        metadata,
+        subscription: payload.subscription,
+        event: payload.event,
+    }))
+}
+
+/// A string borrowed from the input when it has no escapes
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct BorrowedStr<'a>(#[serde(borrow)] Cow<'a, str>);
+
+impl<'a> BorrowedStr<'a> {
+    fn into_braid<T>(self) -> Cow<'a, T>
+    where
+        T: ToOwned + ?Sized + 'a,
+        &'a T: From<&'a str>,
+        T::Owned: From<String>, {
+        match self.0 {
+            Cow::Borrowed(s) => Cow::Borrowed(s.into()),
+            Cow::Owned(s) => Cow::Owned(s.into()),
+        }
+    }
+}
+
+/// Deserialize a braid, borrowing it like a `Cow<str>`
+///
+/// `#[serde(borrow)]` only borrows `Cow<str>` and `Cow<[u8]>`, any other `Cow` is always owned.
+fn borrow_braid<'de: 'a, 'a, D, T>(deserializer: D) -> Result<Cow<'a, T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: ToOwned + ?Sized + 'a,
+    &'a T: From<&'a str>,
+    T::Owned: From<String>, {
+    let s: BorrowedStr<'a> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(s.into_braid())
+}
+
+/// Deserialize an optional braid, see [`borrow_braid`]
+fn borrow_braid_opt<'de: 'a, 'a, D, T>(deserializer: D) -> Result<Option<Cow<'a, T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: ToOwned + ?Sized + 'a,
+    &'a T: From<&'a str>,
+    T::Owned: From<String>, {
+    let s: Option<BorrowedStr<'a>> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(s.map(BorrowedStr::into_braid))
+}
+
+/// Borrowed [`channel.chat.message`](super::ChannelChatMessageV1Payload) response payload.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "deny_unknown_fields", serde(deny_unknown_fields))]
+#[non_exhaustive]
+pub struct ChannelChatMessageV1Payload<'a> {
+    /// The broadcaster user ID.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub broadcaster_user_id: Cow<'a, types::UserIdRef>,
+    /// The broadcaster display name.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub broadcaster_user_name: Cow<'a, types::DisplayNameRef>,
+    /// The broadcaster login.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub broadcaster_user_login: Cow<'a, types::UserNameRef>,
+    /// The user ID of the user that sent the message.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub chatter_user_id: Cow<'a, types::UserIdRef>,
+    /// The user name of the user that sent the message.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub chatter_user_name: Cow<'a, types::DisplayNameRef>,
+    /// The user login of the user that sent the message.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub chatter_user_login: Cow<'a, types::UserNameRef>,
+    /// A UUID that identifies the message.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub message_id: Cow<'a, types::MsgIdRef>,
+    /// The structured chat message.
+    #[serde(borrow = "'a")]
+    pub message: Message<'a>,
+    /// The type of message.
+    pub message_type: MessageType,
+    /// List of chat badges.
+    #[serde(borrow = "'a")]
+    pub badges: Vec<Badge<'a>>,
+    /// Metadata if this message is a cheer.
+    pub cheer: Option<Cheer>,
+    /// The color of the user's name in the chat room.
+    /// This is a hexadecimal RGB color code in the form, `#<RGB>`.
+    /// This may be empty if it is never set.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub color: Cow<'a, types::HexColorRef>,
+    /// Metadata if this message is a reply.
+    #[serde(borrow = "'a")]
+    pub reply: Option<Reply<'a>>,
+    /// The ID of a channel points custom reward that was redeemed.
+    #[serde(borrow = "'a", default, deserialize_with = "borrow_braid_opt")]
+    pub channel_points_custom_reward_id: Option<Cow<'a, types::RewardIdRef>>,
+    /// An ID for the type of animation selected as part of an “animate my message” redemption.
+    #[serde(borrow = "'a", default, deserialize_with = "borrow_braid_opt")]
+    pub channel_points_animation_id: Option<Cow<'a, str>>,
+    /// Only present when in a shared chat session. The broadcaster user ID of the channel the message was sent from.
+    #[serde(borrow = "'a", default, deserialize_with = "borrow_braid_opt")]
+    pub source_broadcaster_user_id: Option<Cow<'a, types::UserIdRef>>,
+    /// Only present when in a shared chat session. The user name of the broadcaster of the channel the message was sent from.
+    #[serde(borrow = "'a", default, deserialize_with = "borrow_braid_opt")]
+    pub source_broadcaster_user_name: Option<Cow<'a, types::DisplayNameRef>>,
+    /// Only present when in a shared chat session. The login of the broadcaster of the channel the message was sent from.
+    #[serde(borrow = "'a", default, deserialize_with = "borrow_braid_opt")]
+    pub source_broadcaster_user_login: Option<Cow<'a, types::UserNameRef>>,
+    /// Only present when in a shared chat session. The UUID that identifies the source message from the channel the message was sent from.
+    #[serde(borrow = "'a", default, deserialize_with = "borrow_braid_opt")]
+    pub source_message_id: Option<Cow<'a, types::MsgIdRef>>,
+    /// Only present when in a shared chat session. The list of chat badges for the chatter in the channel the message was sent from.
+    #[serde(
+        borrow = "'a",
+        default,
+        deserialize_with = "crate::deserialize_default_from_null"
+    )]
+    pub source_badges: Vec<Badge<'a>>,
+}
+
+impl ChannelChatMessageV1Payload<'_> {
+    /// Convert into the owned [`ChannelChatMessageV1Payload`](super::ChannelChatMessageV1Payload)
+    pub fn into_owned(self) -> super::ChannelChatMessageV1Payload {
+        super::ChannelChatMessageV1Payload {
+            broadcaster_user_id: self.broadcaster_user_id.into_owned(),
+            broadcaster_user_name: self.broadcaster_user_name.into_owned(),
+            broadcaster_user_login: self.broadcaster_user_login.into_owned(),
+            chatter_user_id: self.chatter_user_id.into_owned(),
+            chatter_user_name: self.chatter_user_name.into_owned(),
+            chatter_user_login: self.chatter_user_login.into_owned(),
+            message_id: self.message_id.into_owned(),
+            message: self.message.into_owned(),
+            message_type: self.message_type,
+            badges: self.badges.into_iter().map(Badge::into_owned).collect(),
+            cheer: self.cheer,
+            color: self.color.into_owned(),
+            reply: self.reply.map(Reply::into_owned),
+            channel_points_custom_reward_id: self
+                .channel_points_custom_reward_id
+                .map(Cow::into_owned),
+            channel_points_animation_id: self.channel_points_animation_id.map(Cow::into_owned),
+            source_broadcaster_user_id: self.source_broadcaster_user_id.map(Cow::into_owned),
+            source_broadcaster_user_name: self.source_broadcaster_user_name.map(Cow::into_owned),
+            source_broadcaster_user_login: self.source_broadcaster_user_login.map(Cow::into_owned),
+            source_message_id: self.source_message_id.map(Cow::into_owned),
+            source_badges: self
+                .source_badges
+                .into_iter()
+                .map(Badge::into_owned)
+                .collect(),
+        }
+    }
+}
+
+impl<'a> From<ChannelChatMessageV1Payload<'a>> for super::ChannelChatMessageV1Payload {
+    fn from(payload: ChannelChatMessageV1Payload<'a>) -> Self { payload.into_owned() }
+}
+
+/// A borrowed [message](crate::eventsub::channel::chat::Message)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Message<'a> {
+    /// The chat message in plain text.
+    #[serde(borrow = "'a")]
+    pub text: Cow<'a, str>,
+    /// Ordered list of chat message fragments.
+    #[serde(borrow = "'a")]
+    pub fragments: Vec<Fragment<'a>>,
+}
+
+impl Message<'_> {
+    /// Convert into the owned [`Message`](crate::eventsub::channel::chat::Message)
+    pub fn into_owned(self) -> crate::eventsub::channel::chat::Message {
+        crate::eventsub::channel::chat::Message {
+            text: self.text.into_owned(),
+            fragments: self
+                .fragments
+                .into_iter()
+                .map(Fragment::into_owned)
+                .collect(),
+        }
+    }
+}
+
+/// A borrowed [chat message fragment](crate::eventsub::channel::chat::Fragment)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Fragment<'a> {
+    /// A Cheermote.
+    Cheermote {
+        /// Message text in fragment
+        #[serde(borrow = "'a")]
+        text: Cow<'a, str>,
+        /// A Cheermote.
+        #[serde(borrow = "'a")]
+        cheermote: Cheermote<'a>,
+    },
+    /// A Emote.
+    Emote {
+        /// Message text in fragment
+        #[serde(borrow = "'a")]
+        text: Cow<'a, str>,
+        /// A Emote.
+        #[serde(borrow = "'a")]
+        emote: Emote<'a>,
+    },
+    /// A Mention.
+    Mention {
+        /// Message text in fragment
+        #[serde(borrow = "'a")]
+        text: Cow<'a, str>,
+        /// A Mention.
+        #[serde(borrow = "'a")]
+        mention: Mention<'a>,
+    },
+    /// A text fragment, see [`Fragment::text`].
+    Text {
+        /// Message text in fragment
+        #[serde(borrow = "'a")]
+        text: Cow<'a, str>,
+    },
+}
+
+impl Fragment<'_> {
+    /// Get the text data
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Cheermote { text, .. } => text,
+            Self::Emote { text, .. } => text,
+            Self::Mention { text, .. } => text,
+            Self::Text { text } => text,
+        }
+    }
+
+    /// Convert into the owned [`Fragment`](crate::eventsub::channel::chat::Fragment)
+    pub fn into_owned(self) -> crate::eventsub::channel::chat::Fragment {
+        use crate::eventsub::channel::chat::Fragment as Owned;
+        match self {
+            Self::Cheermote { text, cheermote } => Owned::Cheermote {
+                text: text.into_owned(),
+                cheermote: cheermote.into_owned(),
+            },
+            Self::Emote { text, emote } => Owned::Emote {
+                text: text.into_owned(),
+                emote: emote.into_owned(),
+            },
+            Self::Mention { text, mention } => Owned::Mention {
+                text: text.into_owned(),
+                mention: mention.into_owned(),
+            },
+            Self::Text { text } => Owned::Text {
+                text: text.into_owned(),
+            },
+        }
+    }
+}
+
+/// A borrowed [cheermote fragment](crate::eventsub::channel::chat::Cheermote)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "deny_unknown_fields", serde(deny_unknown_fields))]
+#[non_exhaustive]
+pub struct Cheermote<'a> {
+    /// The name portion of the Cheermote string that you use in chat to cheer Bits.
+    #[serde(borrow = "'a")]
+    pub prefix: Cow<'a, str>,
+    /// The amount of bits cheered.
+    pub bits: i32,
+    /// The tier level of the cheermote.
+    pub tier: i32,
+}
+
+impl Cheermote<'_> {
+    /// Convert into the owned [`Cheermote`](crate::eventsub::channel::chat::Cheermote)
+    pub fn into_owned(self) -> crate::eventsub::channel::chat::Cheermote {
+        crate::eventsub::channel::chat::Cheermote {
+            prefix: self.prefix.into_owned(),
+            bits: self.bits,
+            tier: self.tier,
+        }
+    }
+}
+
+/// A borrowed [emote fragment](crate::eventsub::channel::chat::Emote)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "deny_unknown_fields", serde(deny_unknown_fields))]
+#[non_exhaustive]
+pub struct Emote<'a> {
+    /// An ID that uniquely identifies this emote.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub id: Cow<'a, types::EmoteIdRef>,
+    /// An ID that identifies the emote set that the emote belongs to.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub emote_set_id: Cow<'a, types::EmoteSetIdRef>,
+    /// The ID of the broadcaster who owns the emote.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub owner_id: Cow<'a, types::UserIdRef>,
+    /// The formats that the emote is available in.
+    pub format: Vec<types::EmoteAnimationSetting>,
+}
+
+impl Emote<'_> {
+    /// Convert into the owned [`Emote`](crate::eventsub::channel::chat::Emote)
+    pub fn into_owned(self) -> crate::eventsub::channel::chat::Emote {
+        crate::eventsub::channel::chat::Emote {
+            id: self.id.into_owned(),
+            emote_set_id: self.emote_set_id.into_owned(),
+            owner_id: self.owner_id.into_owned(),
+            format: self.format,
+        }
+    }
+}
+
+/// A borrowed [user mention fragment](crate::eventsub::channel::chat::Mention)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "deny_unknown_fields", serde(deny_unknown_fields))]
+#[non_exhaustive]
+pub struct Mention<'a> {
+    /// The user ID of the mentioned user.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub user_id: Cow<'a, types::UserIdRef>,
+    /// The user name of the mentioned user.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub user_name: Cow<'a, types::DisplayNameRef>,
+    /// The user login of the mentioned user.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub user_login: Cow<'a, types::UserNameRef>,
+}
+
+impl Mention<'_> {
+    /// Convert into the owned [`Mention`](crate::eventsub::channel::chat::Mention)
+    pub fn into_owned(self) -> crate::eventsub::channel::chat::Mention {
+        crate::eventsub::channel::chat::Mention {
+            user_id: self.user_id.into_owned(),
+            user_name: self.user_name.into_owned(),
+            user_login: self.user_login.into_owned(),
+        }
+    }
+}
+
+/// A borrowed [chat badge](super::Badge)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "deny_unknown_fields", serde(deny_unknown_fields))]
+#[non_exhaustive]
+pub struct Badge<'a> {
+    /// An ID that identifies this set of chat badges. For example, Bits or Subscriber.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub set_id: Cow<'a, types::BadgeSetIdRef>,
+    /// An ID that identifies this version of the badge.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub id: Cow<'a, types::ChatBadgeIdRef>,
+    /// Contains metadata related to the chat badges in the badges tag.
+    #[serde(borrow = "'a")]
+    pub info: Cow<'a, str>,
+}
+
+impl Badge<'_> {
+    /// Convert into the owned [`Badge`](super::Badge)
+    pub fn into_owned(self) -> super::Badge {
+        super::Badge {
+            set_id: self.set_id.into_owned(),
+            id: self.id.into_owned(),
+            info: self.info.into_owned(),
+        }
+    }
+}
+
+/// Borrowed metadata for [reply messages](super::Reply)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "deny_unknown_fields", serde(deny_unknown_fields))]
+#[non_exhaustive]
+pub struct Reply<'a> {
+    /// An ID that uniquely identifies the parent message that this message is replying to.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub parent_message_id: Cow<'a, types::MsgIdRef>,
+    /// The message body of the parent message.
+    #[serde(borrow = "'a")]
+    pub parent_message_body: Cow<'a, str>,
+    /// User ID of the sender of the parent message.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub parent_user_id: Cow<'a, types::UserIdRef>,
+    /// User name of the sender of the parent message.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub parent_user_name: Cow<'a, types::DisplayNameRef>,
+    /// User login of the sender of the parent message.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub parent_user_login: Cow<'a, types::UserNameRef>,
+    /// An ID that identifies the parent message of the reply thread.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub thread_message_id: Cow<'a, types::MsgIdRef>,
+    /// User ID of the sender of the thread's parent message.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub thread_user_id: Cow<'a, types::UserIdRef>,
+    /// User name of the sender of the thread's parent message.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub thread_user_name: Cow<'a, types::DisplayNameRef>,
+    /// User login of the sender of the thread's parent message.
+    #[serde(borrow = "'a", deserialize_with = "borrow_braid")]
+    pub thread_user_login: Cow<'a, types::UserNameRef>,
+}
+
+impl Reply<'_> {
+    /// Convert into the owned [`Reply`](super::Reply)
+    pub fn into_owned(self) -> super::Reply {
+        super::Reply {
+            parent_message_id: self.parent_message_id.into_owned(),
+            parent_message_body: self.parent_message_body.into_owned(),
+            parent_user_id: self.parent_user_id.into_owned(),
+            parent_user_name: self.parent_user_name.into_owned(),
+            parent_user_login: self.parent_user_login.into_owned(),
+            thread_message_id: self.thread_message_id.into_owned(),
+            thread_user_id: self.thread_user_id.into_owned(),
+            thread_user_name: self.thread_user_name.into_owned(),
+            thread_user_login: self.thread_user_login.into_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn borrows_and_matches_owned() {
+        let frame = r##"
+        {
+            "metadata": {
+                "message_id": "befa7b53-d79d-478f-86b9-120f112b044e",
+                "message_type": "notification",
+                "message_timestamp": "2023-11-06T18:11:47.492253549Z",
+                "subscription_type": "channel.chat.message",
+                "subscription_version": "1"
+            },
+            "payload": {
+                "subscription": {
+                    "id": "0b7f3361-672b-4d39-b307-dd5b576c9b27",
+                    "status": "enabled",
+                    "type": "channel.chat.message",
+                    "version": "1",
+                    "condition": {
+                        "broadcaster_user_id": "1971641",
+                        "user_id": "2914196"
+                    },
+                    "transport": {
+                        "method": "websocket",
+                        "session_id": "AgoQHR3s6Mb4T8GFB1l3DlPfiRIGY2VsbC1h"
+                    },
+                    "created_at": "2023-11-06T18:11:47.492253549Z",
+                    "cost": 0
+                },
+                "event": {
+                    "broadcaster_user_id": "1971641",
+                    "broadcaster_user_login": "streamer",
+                    "broadcaster_user_name": "streamer",
+                    "chatter_user_id": "4145994",
+                    "chatter_user_login": "viewer32",
+                    "chatter_user_name": "viewer32",
+                    "message_id": "cc106a89-1814-919d-454c-f4f2f970aae7",
+                    "message": {
+                        "text": "Hi \"chat\" Kappa @streamer",
+                        "fragments": [
+                            {
+                                "type": "text",
+                                "text": "Hi \"chat\" ",
+                                "cheermote": null,
+                                "emote": null,
+                                "mention": null
+                            },
+                            {
+                                "type": "emote",
+                                "text": "Kappa",
+                                "cheermote": null,
+                                "emote": {
+                                    "id": "25",
+                                    "emote_set_id": "0",
+                                    "owner_id": "0",
+                                    "format": ["static"]
+                                },
+                                "mention": null
+                            },
+                            {
+                                "type": "mention",
+                                "text": "@streamer",
+                                "cheermote": null,
+                                "emote": null,
+                                "mention": {
+                                    "user_id": "1971641",
+                                    "user_name": "streamer",
+                                    "user_login": "streamer"
+                                }
+                            }
+                        ]
+                    },
+                    "color": "#00FF7F",
+                    "badges": [
+                        {
+                            "set_id": "subscriber",
+                            "id": "6",
+                            "info": "7"
+                        }
+                    ],
+                    "message_type": "text",
+                    "cheer": null,
+                    "reply": null,
+                    "channel_points_custom_reward_id": null,
+                    "channel_points_animation_id": "simmer",
+                    "source_broadcaster_user_id": null,
+                    "source_broadcaster_user_name": null,
+                    "source_broadcaster_user_login": null,
+                    "source_message_id": null,
+                    "source_badges": null
+                }
+            }
+        }
+        "##;
+
+        let notification = parse_websocket(frame).unwrap().unwrap();
+        let event = &notification.event;
+        assert!(matches!(event.chatter_user_login, Cow::Borrowed(_)));
+        assert!(matches!(event.badges[0].info, Cow::Borrowed("7")));
+        assert!(matches!(
+            event.channel_points_animation_id,
+            Some(Cow::Borrowed("simmer"))
+        ));
+        // escaped strings can't be borrowed
+        assert!(matches!(event.message.text, Cow::Owned(_)));
+        assert!(matches!(
+            &event.message.fragments[2],
+            Fragment::Mention {
+                text: Cow::Borrowed("@streamer"),
+                ..
+            }
+        ));
+        assert_eq!(
+            notification
+                .subscription()
+                .unwrap()
+                .condition
+                .user_id
+                .as_str(),
+            "2914196"
+        );
+
+        let crate::eventsub::EventsubWebsocketData::Notification {
+            payload: crate::eventsub::Event::ChannelChatMessageV1(payload),
+            ..
+        } = crate::eventsub::Event::parse_websocket(frame).unwrap()
+        else {
+            panic!("expected a chat message")
+        };
+        let crate::eventsub::Message::Notification(owned) = payload.message else {
+            panic!("expected a notification")
+        };
+        assert_eq!(notification.event.into_owned(), owned);
+
+        let keepalive = r#"{"metadata":{"message_id":"84c1e79a-2a4b-4c13-ba0b-4312293e9308","message_type":"session_keepalive","message_timestamp":"2023-07-19T10:11:12.634234626Z"},"payload":{}}"#;
+        assert!(parse_websocket(keepalive).unwrap().is_none());
+    }
+}
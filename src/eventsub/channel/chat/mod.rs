@@ -4,11 +4,16 @@ use super::{EventSubscription, EventType};
 use crate::types;
 use serde_derive::{Deserialize, Serialize};
 
+pub mod archive;
 pub mod clear;
 pub mod clear_user_messages;
+pub mod commands;
 pub mod message;
 pub mod message_delete;
 pub mod notification;
+#[cfg(feature = "helix")]
+#[cfg_attr(nightly, doc(cfg(feature = "helix")))]
+pub mod render;
 pub mod user_message_hold;
 pub mod user_message_update;
 
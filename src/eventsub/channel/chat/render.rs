@@ -0,0 +1,730 @@
+//! Render chat messages with their emotes, cheermotes, mentions and badges
+//!
+//! [`channel.chat.message`](super::ChannelChatMessageV1) events split the message into [fragments](super::Fragment),
+//! but only contain the IDs of emotes and badges, and the prefix and amount of cheermotes.
+//! A [`Renderer`] caches the chat badges from [Get Global Chat Badges](crate::helix::chat::get_global_chat_badges) and
+//! [Get Channel Chat Badges](crate::helix::chat::get_channel_chat_badges), and the cheermotes from
+//! [Get Cheermotes](crate::helix::bits::get_cheermotes) per channel, and uses them to turn a message into a
+//! [`RenderedMessage`] with image URLs for the configured [size, theme and animation](RenderOptions).
+//! A rendered message can be used as is, or turned into HTML with [`RenderedMessage::to_html`].
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! # let client: helix::HelixClient<'static, twitch_api::client::DummyHttpClient> = helix::HelixClient::default();
+//! # let token = twitch_oauth2::AccessToken::new("validtoken".to_string());
+//! # let token = twitch_oauth2::UserToken::from_existing(&client, token, None, None).await?;
+//! # let payload: twitch_api::eventsub::channel::ChannelChatMessageV1Payload = serde_json::from_str(r#"{
+//! #     "broadcaster_user_id": "1234", "broadcaster_user_login": "streamer", "broadcaster_user_name": "streamer",
+//! #     "chatter_user_id": "4145994", "chatter_user_login": "viewer32", "chatter_user_name": "viewer32",
+//! #     "message_id": "cc106a89-1814-919d-454c-f4f2f970aae7",
+//! #     "message": {"text": "Hi chat Kappa", "fragments": [
+//! #         {"type": "text", "text": "Hi chat ", "cheermote": null, "emote": null, "mention": null},
+//! #         {"type": "emote", "text": "Kappa", "cheermote": null, "mention": null,
+//! #          "emote": {"id": "25", "emote_set_id": "0", "owner_id": "0", "format": ["static"]}}
+//! #     ]},
+//! #     "color": "#00FF7F", "badges": [{"set_id": "subscriber", "id": "12", "info": "16"}],
+//! #     "message_type": "text", "cheer": null, "reply": null,
+//! #     "channel_points_custom_reward_id": null, "channel_points_animation_id": null,
+//! #     "source_broadcaster_user_id": null, "source_broadcaster_user_login": null,
+//! #     "source_broadcaster_user_name": null, "source_message_id": null, "source_badges": null
+//! # }"#)?;
+//! use twitch_api::{
+//!     eventsub::channel::chat::render::{RenderOptions, Renderer, Scale},
+//!     helix,
+//! };
+//!
+//! let mut renderer = Renderer::new(RenderOptions::default().scale(Scale::Medium));
+//! renderer.load_channel(&client, "1234", &token).await?;
+//!
+//! println!("{}", renderer.render_payload(&payload).to_html());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::time::{Duration, Instant};
+
+use super::{message, Fragment, Message};
+use crate::helix::{
+    bits::{get_cheermotes::Tiers, Cheermote},
+    chat::{BadgeSet, ChatBadge},
+};
+use crate::types;
+
+/// Size of rendered images
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Scale {
+    /// Emote size `1.0` (28 × 28) and badge size `1x` (18 × 18)
+    #[default]
+    Small,
+    /// Emote size `2.0` (56 × 56) and badge size `2x` (36 × 36)
+    Medium,
+    /// Emote size `3.0` (112 × 112) and badge size `4x` (72 × 72)
+    Large,
+}
+
+/// Background theme that rendered images are shown on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Theme {
+    /// Light background
+    Light,
+    /// Dark background
+    #[default]
+    Dark,
+}
+
+/// How to render images
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RenderOptions {
+    /// Size of images. Defaults to [`Scale::Small`]
+    pub scale: Scale,
+    /// Theme of images. Defaults to [`Theme::Dark`]
+    pub theme: Theme,
+    /// Use animated images where available. Defaults to `true`
+    pub animated: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            scale: Scale::default(),
+            theme: Theme::default(),
+            animated: true,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Set the size of images
+    pub fn scale(mut self, scale: Scale) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Set the theme of images
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Set whether to use animated images
+    pub fn animated(mut self, animated: bool) -> Self {
+        self.animated = animated;
+        self
+    }
+
+    /// URL of an emote
+    pub fn emote_url(&self, id: &types::EmoteIdRef) -> String {
+        let url = types::EmoteUrlBuilder::new(id);
+        let url = match self.scale {
+            Scale::Small => url.size_1x(),
+            Scale::Medium => url.size_2x(),
+            Scale::Large => url.size_3x(),
+        };
+        let url = match self.theme {
+            Theme::Light => url.light_mode(),
+            Theme::Dark => url.dark_mode(),
+        };
+        // the default format is animated if the emote has an animated version
+        if self.animated {
+            url.animation_default().render()
+        } else {
+            url.animation_static().render()
+        }
+    }
+
+    /// URL of a chat badge
+    pub fn badge_url<'a>(&self, badge: &'a ChatBadge) -> &'a str {
+        match self.scale {
+            Scale::Small => &badge.image_url_1x,
+            Scale::Medium => &badge.image_url_2x,
+            Scale::Large => &badge.image_url_4x,
+        }
+    }
+
+    /// URL of a cheermote tier
+    pub fn cheermote_url<'a>(&self, tier: &'a Tiers) -> &'a str {
+        let images = match self.theme {
+            Theme::Light => &tier.images.light,
+            Theme::Dark => &tier.images.dark,
+        };
+        let images = if self.animated {
+            &images.animated
+        } else {
+            &images.static_
+        };
+        match self.scale {
+            Scale::Small => &images.url_1x,
+            Scale::Medium => &images.url_2x,
+            Scale::Large => &images.url_4x,
+        }
+    }
+}
+
+/// A rendered chat badge
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RenderedBadge {
+    /// ID of the badge set
+    pub set_id: types::BadgeSetId,
+    /// ID of the badge version
+    pub id: types::ChatBadgeId,
+    /// Title of the badge
+    pub title: String,
+    /// URL of the badge image
+    pub url: String,
+}
+
+/// A part of a rendered message
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Node {
+    /// Plain text
+    Text {
+        /// The text
+        text: String,
+    },
+    /// An emote
+    Emote {
+        /// The text of the emote, i.e. its name
+        text: String,
+        /// ID of the emote
+        id: types::EmoteId,
+        /// URL of the emote image
+        url: String,
+    },
+    /// A cheermote
+    Cheermote {
+        /// The text of the cheermote, e.g. `Cheer100`
+        text: String,
+        /// The amount of bits cheered
+        bits: i64,
+        /// URL of the tier image, if the cheermote is known
+        url: Option<String>,
+        /// Color of the tier, if the cheermote is known
+        color: Option<String>,
+    },
+    /// A mention of a user
+    Mention {
+        /// The text of the mention, e.g. `@twitchdev`
+        text: String,
+        /// ID of the mentioned user
+        user_id: types::UserId,
+        /// Login of the mentioned user
+        user_login: types::UserName,
+    },
+}
+
+/// A message rendered by a [`Renderer`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RenderedMessage {
+    /// The badges of the chatter that could be resolved, in order
+    pub badges: Vec<RenderedBadge>,
+    /// The message
+    pub nodes: Vec<Node>,
+}
+
+impl RenderedMessage {
+    /// Render as HTML
+    ///
+    /// Badges are rendered as `<img class="badge">`, emotes as `<img class="emote">`,
+    /// cheermotes as `<img class="cheermote">` followed by `<span class="cheer-bits">` in the tier color,
+    /// and mentions as `<span class="mention">`. All text is escaped.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        for badge in &self.badges {
+            html.push_str(r#"<img class="badge" src=""#);
+            escape(&mut html, &badge.url);
+            html.push_str(r#"" alt=""#);
+            escape(&mut html, &badge.title);
+            html.push_str(r#"" title=""#);
+            escape(&mut html, &badge.title);
+            html.push_str(r#"">"#);
+        }
+        for node in &self.nodes {
+            match node {
+                Node::Text { text } => escape(&mut html, text),
+                Node::Emote { text, url, .. } => {
+                    html.push_str(r#"<img class="emote" src=""#);
+                    escape(&mut html, url);
+                    html.push_str(r#"" alt=""#);
+                    escape(&mut html, text);
+                    html.push_str(r#"">"#);
+                }
+                Node::Cheermote {
+                    text,
+                    bits,
+                    url: Some(url),
+                    color,
+                } => {
+                    html.push_str(r#"<img class="cheermote" src=""#);
+                    escape(&mut html, url);
+                    html.push_str(r#"" alt=""#);
+                    escape(&mut html, text);
+                    html.push_str(r#""><span class="cheer-bits""#);
+                    if let Some(color) = color {
+                        html.push_str(r#" style="color: "#);
+                        escape(&mut html, color);
+                        html.push('"');
+                    }
+                    let _ = write!(html, ">{bits}</span>");
+                }
+                Node::Cheermote { text, .. } => escape(&mut html, text),
+                Node::Mention { text, user_id, .. } => {
+                    html.push_str(r#"<span class="mention" data-user-id=""#);
+                    escape(&mut html, user_id.as_str());
+                    html.push_str(r#"">"#);
+                    escape(&mut html, text);
+                    html.push_str("</span>");
+                }
+            }
+        }
+        html
+    }
+}
+
+fn escape(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+}
+
+type BadgeMap = HashMap<(types::BadgeSetId, types::ChatBadgeId), ChatBadge>;
+
+#[derive(Debug)]
+struct Cached<T> {
+    value: T,
+    fetched_at: Instant,
+}
+
+/// Renders chat messages, caching badges and cheermotes
+///
+/// See the [module documentation](self) for more.
+#[derive(Debug)]
+pub struct Renderer {
+    options: RenderOptions,
+    max_age: Duration,
+    global_badges: Option<Cached<BadgeMap>>,
+    channel_badges: HashMap<types::UserId, Cached<BadgeMap>>,
+    cheermotes: HashMap<Option<types::UserId>, Cached<Vec<Cheermote>>>,
+}
+
+impl Renderer {
+    /// Create a renderer with no cached badges or cheermotes
+    pub fn new(options: RenderOptions) -> Self {
+        Self {
+            options,
+            max_age: Duration::from_secs(60 * 60),
+            global_badges: None,
+            channel_badges: HashMap::new(),
+            cheermotes: HashMap::new(),
+        }
+    }
+
+    /// Set how long cached badges and cheermotes are used before they are fetched again. Defaults to one hour
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// The options used for rendering
+    pub fn options(&self) -> &RenderOptions { &self.options }
+
+    /// Change the options used for rendering
+    pub fn set_options(&mut self, options: RenderOptions) { self.options = options; }
+
+    /// Cache the global chat badges, as returned by [`GetGlobalChatBadgesRequest`](crate::helix::chat::GetGlobalChatBadgesRequest)
+    pub fn insert_global_badges(&mut self, badges: Vec<BadgeSet>, now: Instant) {
+        self.global_badges = Some(Cached {
+            value: badge_map(badges),
+            fetched_at: now,
+        });
+    }
+
+    /// Cache the chat badges of a channel, as returned by [`GetChannelChatBadgesRequest`](crate::helix::chat::GetChannelChatBadgesRequest)
+    pub fn insert_channel_badges(
+        &mut self,
+        broadcaster_id: impl Into<types::UserId>,
+        badges: Vec<BadgeSet>,
+        now: Instant,
+    ) {
+        self.channel_badges.insert(
+            broadcaster_id.into(),
+            Cached {
+                value: badge_map(badges),
+                fetched_at: now,
+            },
+        );
+    }
+
+    /// Cache cheermotes, as returned by [`GetCheermotesRequest`](crate::helix::bits::GetCheermotesRequest)
+    ///
+    /// With a `broadcaster_id`, these are the cheermotes available in that channel, otherwise the global cheermotes.
+    pub fn insert_cheermotes(
+        &mut self,
+        broadcaster_id: Option<types::UserId>,
+        cheermotes: Vec<Cheermote>,
+        now: Instant,
+    ) {
+        self.cheermotes.insert(
+            broadcaster_id,
+            Cached {
+                value: cheermotes,
+                fetched_at: now,
+            },
+        );
+    }
+
+    /// Remove everything cached for a channel
+    pub fn invalidate(&mut self, broadcaster_id: &types::UserIdRef) {
+        self.channel_badges.remove(broadcaster_id);
+        self.cheermotes.remove(&Some(broadcaster_id.to_owned()));
+    }
+
+    /// Whether the global badges and the badges and cheermotes of a channel are cached and not older than [`Renderer::max_age`]
+    pub fn is_loaded(&self, broadcaster_id: &types::UserIdRef, now: Instant) -> bool {
+        let fresh = |fetched_at: Instant| now.saturating_duration_since(fetched_at) < self.max_age;
+        self.global_badges
+            .as_ref()
+            .map_or(false, |c| fresh(c.fetched_at))
+            && self
+                .channel_badges
+                .get(broadcaster_id)
+                .map_or(false, |c| fresh(c.fetched_at))
+            && self
+                .cheermotes
+                .get(&Some(broadcaster_id.to_owned()))
+                .map_or(false, |c| fresh(c.fetched_at))
+    }
+
+    /// Look up a badge, preferring the badges of the channel over the global badges
+    pub fn badge(
+        &self,
+        broadcaster_id: &types::UserIdRef,
+        set_id: &types::BadgeSetIdRef,
+        id: &types::ChatBadgeIdRef,
+    ) -> Option<&ChatBadge> {
+        let key = (set_id.to_owned(), id.to_owned());
+        self.channel_badges
+            .get(broadcaster_id)
+            .and_then(|c| c.value.get(&key))
+            .or_else(|| self.global_badges.as_ref()?.value.get(&key))
+    }
+
+    /// Look up a cheermote by its prefix, case-insensitively, in the cheermotes of the channel or the global cheermotes
+    pub fn cheermote(&self, broadcaster_id: &types::UserIdRef, prefix: &str) -> Option<&Cheermote> {
+        let find = |cached: Option<&Cached<Vec<Cheermote>>>| {
+            cached?
+                .value
+                .iter()
+                .find(|c| c.prefix.eq_ignore_ascii_case(prefix))
+        };
+        find(self.cheermotes.get(&Some(broadcaster_id.to_owned())))
+            .or_else(|| find(self.cheermotes.get(&None)))
+    }
+
+    /// Render a chat message with the badges of its chatter
+    pub fn render(
+        &self,
+        broadcaster_id: &types::UserIdRef,
+        message: &Message,
+        badges: &[message::Badge],
+    ) -> RenderedMessage {
+        let badges = badges
+            .iter()
+            .filter_map(|badge| {
+                let resolved = self.badge(broadcaster_id, &badge.set_id, &badge.id)?;
+                Some(RenderedBadge {
+                    set_id: badge.set_id.clone(),
+                    id: badge.id.clone(),
+                    title: resolved.title.clone(),
+                    url: self.options.badge_url(resolved).to_owned(),
+                })
+            })
+            .collect();
+        let nodes = message
+            .fragments
+            .iter()
+            .map(|fragment| match fragment {
+                Fragment::Text { text } => Node::Text { text: text.clone() },
+                Fragment::Emote { text, emote } => Node::Emote {
+                    text: text.clone(),
+                    id: emote.id.clone(),
+                    url: self.options.emote_url(&emote.id),
+                },
+                Fragment::Cheermote { text, cheermote } => {
+                    let bits = i64::from(cheermote.bits);
+                    let tier = self
+                        .cheermote(broadcaster_id, &cheermote.prefix)
+                        .and_then(|c| c.tier(bits));
+                    Node::Cheermote {
+                        text: text.clone(),
+                        bits,
+                        url: tier.map(|tier| self.options.cheermote_url(tier).to_owned()),
+                        color: tier.map(|tier| tier.color.clone()),
+                    }
+                }
+                Fragment::Mention { text, mention } => Node::Mention {
+                    text: text.clone(),
+                    user_id: mention.user_id.clone(),
+                    user_login: mention.user_login.clone(),
+                },
+            })
+            .collect();
+        RenderedMessage { badges, nodes }
+    }
+
+    /// Render the message of a [`channel.chat.message`](super::ChannelChatMessageV1) event
+    pub fn render_payload(
+        &self,
+        payload: &message::ChannelChatMessageV1Payload,
+    ) -> RenderedMessage {
+        self.render(
+            &payload.broadcaster_user_id,
+            &payload.message,
+            &payload.badges,
+        )
+    }
+
+    /// Fetch the global badges and cheermotes, unless they are cached
+    #[cfg(feature = "client")]
+    #[cfg_attr(nightly, doc(cfg(feature = "client")))]
+    pub async fn load_global<'c, C, T>(
+        &mut self,
+        client: &'c crate::HelixClient<'c, C>,
+        token: &T,
+    ) -> Result<(), ClientError<C>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+    {
+        let now = Instant::now();
+        self.load_global_badges(client, token, now).await?;
+        let max_age = self.max_age;
+        let fresh = |fetched_at: Instant| now.saturating_duration_since(fetched_at) < max_age;
+        if !self
+            .cheermotes
+            .get(&None)
+            .map_or(false, |c| fresh(c.fetched_at))
+        {
+            let cheermotes = client
+                .req_get(crate::helix::bits::GetCheermotesRequest::new(), token)
+                .await?
+                .data;
+            self.insert_cheermotes(None, cheermotes, now);
+        }
+        Ok(())
+    }
+
+    /// Fetch the global badges and the badges and cheermotes of a channel, unless they are cached
+    #[cfg(feature = "client")]
+    #[cfg_attr(nightly, doc(cfg(feature = "client")))]
+    pub async fn load_channel<'c, 'b, C, T>(
+        &mut self,
+        client: &'c crate::HelixClient<'c, C>,
+        broadcaster_id: impl types::IntoCow<'b, types::UserIdRef> + Send + 'b,
+        token: &T,
+    ) -> Result<(), ClientError<C>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+    {
+        let broadcaster_id = broadcaster_id.into_cow();
+        let now = Instant::now();
+        if self.is_loaded(&broadcaster_id, now) {
+            return Ok(());
+        }
+        self.load_global_badges(client, token, now).await?;
+        let badges = client
+            .req_get(
+                crate::helix::chat::GetChannelChatBadgesRequest::broadcaster_id(&*broadcaster_id),
+                token,
+            )
+            .await?
+            .data;
+        self.insert_channel_badges(broadcaster_id.clone().into_owned(), badges, now);
+        // the cheermotes of a channel include the global cheermotes
+        let cheermotes = client
+            .req_get(
+                crate::helix::bits::GetCheermotesRequest::broadcaster_id(&*broadcaster_id),
+                token,
+            )
+            .await?
+            .data;
+        self.insert_cheermotes(Some(broadcaster_id.into_owned()), cheermotes, now);
+        Ok(())
+    }
+
+    /// Fetch the global badges, unless they are cached
+    #[cfg(feature = "client")]
+    async fn load_global_badges<'c, C, T>(
+        &mut self,
+        client: &'c crate::HelixClient<'c, C>,
+        token: &T,
+        now: Instant,
+    ) -> Result<(), ClientError<C>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+    {
+        let max_age = self.max_age;
+        let fresh = |fetched_at: Instant| now.saturating_duration_since(fetched_at) < max_age;
+        if self
+            .global_badges
+            .as_ref()
+            .map_or(false, |c| fresh(c.fetched_at))
+        {
+            return Ok(());
+        }
+        let badges = client
+            .req_get(crate::helix::chat::GetGlobalChatBadgesRequest::new(), token)
+            .await?
+            .data;
+        self.insert_global_badges(badges, now);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "client")]
+type ClientError<C> = crate::helix::ClientRequestError<<C as crate::HttpClient>::Error>;
+
+fn badge_map(badges: Vec<BadgeSet>) -> BadgeMap {
+    badges
+        .into_iter()
+        .flat_map(|set| {
+            let set_id = set.set_id;
+            set.versions
+                .into_iter()
+                .map(move |badge| ((set_id.clone(), badge.id.clone()), badge))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image_array(kind: &str) -> serde_json::Value {
+        serde_json::json!({
+            "1": format!("https://cheer/{kind}/1"),
+            "1.5": format!("https://cheer/{kind}/1.5"),
+            "2": format!("https://cheer/{kind}/2"),
+            "3": format!("https://cheer/{kind}/3"),
+            "4": format!("https://cheer/{kind}/4"),
+        })
+    }
+
+    fn tier(min_bits: i64, color: &str) -> serde_json::Value {
+        serde_json::json!({
+            "min_bits": min_bits,
+            "id": min_bits.to_string(),
+            "color": color,
+            "images": {
+                "dark": {
+                    "animated": image_array(&format!("dark/animated/{min_bits}")),
+                    "static": image_array(&format!("dark/static/{min_bits}")),
+                },
+                "light": {
+                    "animated": image_array(&format!("light/animated/{min_bits}")),
+                    "static": image_array(&format!("light/static/{min_bits}")),
+                },
+            },
+            "can_cheer": true,
+            "show_in_bits_card": true,
+        })
+    }
+
+    #[test]
+    fn render() {
+        let now = Instant::now();
+        let mut renderer = Renderer::new(RenderOptions::default().animated(false));
+        let badges = |set_id: &str, title: &str| -> Vec<BadgeSet> {
+            serde_json::from_value(serde_json::json!([{
+                "set_id": set_id,
+                "versions": [{
+                    "id": "1",
+                    "image_url_1x": format!("https://badge/{title}/1"),
+                    "image_url_2x": format!("https://badge/{title}/2"),
+                    "image_url_4x": format!("https://badge/{title}/4"),
+                    "title": title,
+                    "description": title,
+                }]
+            }]))
+            .unwrap()
+        };
+        let mut global = badges("subscriber", "Subscriber");
+        global.extend(badges("vip", "VIP"));
+        renderer.insert_global_badges(global, now);
+        renderer.insert_channel_badges("1234", badges("subscriber", "Channel Sub"), now);
+        renderer.insert_cheermotes(
+            None,
+            serde_json::from_value(serde_json::json!([{
+                "prefix": "Cheer",
+                "tiers": [tier(1, "#979797"), tier(100, "#9c3ee8")],
+                "type": "global_first_party",
+                "order": 1,
+                "last_updated": "2018-05-22T00:06:04Z",
+                "is_charitable": false,
+            }]))
+            .unwrap(),
+            now,
+        );
+        assert!(!renderer.is_loaded("1234".into(), now));
+
+        let message: Message = serde_json::from_value(serde_json::json!({
+            "text": "<3 Kappa cheer250 @streamer",
+            "fragments": [
+                {"type": "text", "text": "<3 ", "cheermote": null, "emote": null, "mention": null},
+                {"type": "emote", "text": "Kappa", "cheermote": null, "emote": {"id": "25", "emote_set_id": "0", "owner_id": "0", "format": ["static"]}, "mention": null},
+                {"type": "text", "text": " ", "cheermote": null, "emote": null, "mention": null},
+                {"type": "cheermote", "text": "cheer250", "cheermote": {"prefix": "cheer", "bits": 250, "tier": 100}, "emote": null, "mention": null},
+                {"type": "text", "text": " ", "cheermote": null, "emote": null, "mention": null},
+                {"type": "mention", "text": "@streamer", "cheermote": null, "emote": null, "mention": {"user_id": "1234", "user_name": "streamer", "user_login": "streamer"}},
+            ]
+        }))
+        .unwrap();
+        let chatter_badges: Vec<message::Badge> = serde_json::from_value(serde_json::json!([
+            {"set_id": "subscriber", "id": "1", "info": "3"},
+            {"set_id": "vip", "id": "1", "info": ""},
+            {"set_id": "unknown", "id": "1", "info": ""},
+        ]))
+        .unwrap();
+
+        let rendered = renderer.render("1234".into(), &message, &chatter_badges);
+        let titles: Vec<_> = rendered.badges.iter().map(|b| b.title.as_str()).collect();
+        assert_eq!(titles, ["Channel Sub", "VIP"]);
+        assert_eq!(rendered.nodes.len(), 6);
+        assert_eq!(
+            rendered.nodes[3],
+            Node::Cheermote {
+                text: "cheer250".to_owned(),
+                bits: 250,
+                url: Some("https://cheer/dark/static/100/1".to_owned()),
+                color: Some("#9c3ee8".to_owned()),
+            }
+        );
+
+        let html = rendered.to_html();
+        assert!(html.starts_with(
+            r#"<img class="badge" src="https://badge/Channel Sub/1" alt="Channel Sub" title="Channel Sub">"#
+        ));
+        assert!(html.contains(r#"&lt;3 <img class="emote" src="https://static-cdn.jtvnw.net/emoticons/v2/25/static/dark/1.0" alt="Kappa">"#));
+        assert!(html.contains(r#"<img class="cheermote" src="https://cheer/dark/static/100/1" alt="cheer250"><span class="cheer-bits" style="color: #9c3ee8">250</span>"#));
+        assert!(html.ends_with(r#"<span class="mention" data-user-id="1234">@streamer</span>"#));
+    }
+}
@@ -7,6 +7,7 @@ use serde_derive::{Deserialize, Serialize};
 pub mod begin;
 pub mod end;
 pub mod progress;
+pub mod tracker;
 
 #[doc(inline)]
 pub use begin::{ChannelHypeTrainBeginV1, ChannelHypeTrainBeginV1Payload};
@@ -0,0 +1,1101 @@
+//! Hype train tracking across EventSub, Helix and PubSub
+//!
+//! A hype train can be observed through the `channel.hype_train.*` subscriptions,
+//! through [Get Hype Train Events](https://dev.twitch.tv/docs/api/reference#get-hype-train-events) and,
+//! with features `pubsub` and `unsupported`, through the `hype-train-events-v1` topic.
+//! [`HypeTrainTracker`] accepts updates from any of these and keeps one [`ChannelHypeTrain`] per channel with
+//!
+//! * the current level and progress towards the next one,
+//! * contributors and their contributions by type,
+//! * the bits and subscriptions conductors, i.e. the top contributor of each,
+//! * when the train expires and when the cooldown after it ends.
+//!
+//! Every update returns the [`Transition`]s it caused, which are the same whichever source the update came from.
+//! Not every source says when a hype train ends or its cooldown expires, call [`HypeTrainTracker::poll`] regularly
+//! to end hype trains past their expiry and expire cooldowns.
+//! Updates that are behind the tracked train, i.e. with a lower total or for a train that has ended, are ignored.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use twitch_api::eventsub::{
+//!     channel::hypetrain::tracker::{HypeTrainTracker, Transition},
+//!     Event,
+//! };
+//!
+//! let mut tracker = HypeTrainTracker::new();
+//! # let notification = r#"{"subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","type":"channel.hype_train.begin","version":"1","status":"enabled","cost":0,"condition":{"broadcaster_user_id":"1337"},"transport":{"method":"webhook","callback":"https://example.com/webhooks/callback"},"created_at":"2019-11-16T10:11:12.123Z"},"event":{"id":"1b0AsbInCHZW2SQFQkCzqN07Ib2","broadcaster_user_id":"1337","broadcaster_user_login":"cool_user","broadcaster_user_name":"Cool_User","total":137,"progress":137,"goal":500,"top_contributions":[{"user_id":"123","user_login":"pogchamp","user_name":"PogChamp","type":"bits","total":50},{"user_id":"456","user_login":"kappa","user_name":"Kappa","type":"subscription","total":45}],"last_contribution":{"user_id":"123","user_login":"pogchamp","user_name":"PogChamp","type":"bits","total":50},"level":1,"started_at":"2020-07-15T17:16:03.17106713Z","expires_at":"2020-07-15T17:16:11.17106713Z"}}"#;
+//! let event = Event::parse(notification)?;
+//! let update = tracker.apply_event(&event).expect("a hype train event");
+//! assert_eq!(update.transitions[0], Transition::Began { level: 1 });
+//! assert_eq!(update.train.remaining(), 363);
+//! assert_eq!(update.train.bits_conductor.as_deref(), Some("123".into()));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{collections::BTreeMap, time::SystemTime};
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    ChannelHypeTrainBeginV1Payload, ChannelHypeTrainEndV1Payload,
+    ChannelHypeTrainProgressV1Payload, Contribution, ContributionType,
+};
+use crate::{eventsub::Event, types};
+
+/// Kind of a contribution to a hype train
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ContributionKind {
+    /// Bits
+    Bits,
+    /// Subscriptions, gifted or not
+    Subscription,
+    /// Other contribution methods
+    Other,
+}
+
+impl From<&ContributionType> for ContributionKind {
+    fn from(type_: &ContributionType) -> Self {
+        match type_ {
+            ContributionType::Bits => Self::Bits,
+            ContributionType::Subscription => Self::Subscription,
+            ContributionType::Other => Self::Other,
+        }
+    }
+}
+
+/// Hype train state for a channel
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ChannelHypeTrain {
+    /// The channel
+    pub broadcaster_id: types::UserId,
+    /// The current or last hype train
+    pub train: Option<HypeTrain>,
+    /// When the cooldown after the last hype train ends, `None` if unknown or expired
+    pub cooldown_ends_at: Option<types::Timestamp>,
+    /// Cooldown length in milliseconds, from the PubSub config
+    cooldown_duration: Option<i64>,
+}
+
+impl ChannelHypeTrain {
+    /// The hype train, if one is running
+    pub fn active(&self) -> Option<&HypeTrain> { self.train.as_ref().filter(|t| !t.ended()) }
+
+    /// Whether a new hype train can't start yet at `now`
+    pub fn is_on_cooldown(&self, now: SystemTime) -> bool {
+        let Some(ends_at) = self
+            .cooldown_ends_at
+            .as_ref()
+            .and_then(|t| crate::timestamp_to_unix_millis(t.as_str()))
+        else {
+            return false;
+        };
+        now.duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(true, |now| (now.as_millis() as i64) < ends_at)
+    }
+}
+
+/// A hype train, as seen by all sources applied to the [`HypeTrainTracker`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct HypeTrain {
+    /// ID of the hype train, PubSub doesn't always send it
+    pub id: Option<types::HypeTrainId>,
+    /// The current level
+    pub level: i64,
+    /// Points contributed in all levels
+    pub total: i64,
+    /// Points contributed in the current level, not sent by Helix
+    pub progress: i64,
+    /// Points needed to complete the current level
+    pub goal: i64,
+    /// When the hype train started
+    pub started_at: Option<types::Timestamp>,
+    /// When the hype train expires, extended on every level up
+    pub expires_at: Option<types::Timestamp>,
+    /// When the hype train ended
+    pub ended_at: Option<types::Timestamp>,
+    /// The most recent contribution
+    pub last_contribution: Option<TrackedContribution>,
+    /// Contributors, by user
+    pub contributors: BTreeMap<types::UserId, Contributor>,
+    /// The top bits contributor
+    pub bits_conductor: Option<types::UserId>,
+    /// The top subscriptions contributor
+    pub subs_conductor: Option<types::UserId>,
+}
+
+impl HypeTrain {
+    /// Whether the hype train has ended
+    pub fn ended(&self) -> bool { self.ended_at.is_some() }
+
+    /// Points left to complete the current level
+    pub fn remaining(&self) -> i64 { (self.goal - self.progress).max(0) }
+
+    /// Progress towards the next level, from `0.0` to `1.0`
+    pub fn fraction(&self) -> f64 {
+        if self.goal <= 0 {
+            return 0.0;
+        }
+        (self.progress as f64 / self.goal as f64).clamp(0.0, 1.0)
+    }
+
+    /// Contributors, highest total first
+    pub fn top_contributors(&self) -> Vec<&Contributor> {
+        let mut contributors = self.contributors.values().collect::<Vec<_>>();
+        contributors.sort_by_key(|c| std::cmp::Reverse(c.total()));
+        contributors
+    }
+
+    fn new(id: Option<types::HypeTrainId>) -> Self {
+        Self {
+            id,
+            level: 1,
+            total: 0,
+            progress: 0,
+            goal: 0,
+            started_at: None,
+            expires_at: None,
+            ended_at: None,
+            last_contribution: None,
+            contributors: BTreeMap::new(),
+            bits_conductor: None,
+            subs_conductor: None,
+        }
+    }
+
+    fn contributor(&mut self, user_id: &types::UserIdRef) -> &mut Contributor {
+        self.contributors
+            .entry(user_id.to_owned())
+            .or_insert_with(|| Contributor {
+                user_id: user_id.to_owned(),
+                user_login: None,
+                user_name: None,
+                bits: 0,
+                subscriptions: 0,
+                other: 0,
+            })
+    }
+
+    /// Record a contribution total for a user, as sent in top contributions
+    fn top_contribution(&mut self, contribution: &TrackedContribution) {
+        let contributor = self.contributor(&contribution.user_id);
+        contributor.identify(contribution);
+        let total = contributor.get_mut(contribution.kind);
+        *total = (*total).max(contribution.total);
+    }
+
+    /// Record a new contribution
+    fn add_contribution(&mut self, contribution: &TrackedContribution) {
+        let contributor = self.contributor(&contribution.user_id);
+        contributor.identify(contribution);
+        *contributor.get_mut(contribution.kind) += contribution.total;
+        self.last_contribution = Some(contribution.clone());
+    }
+
+    /// Set the conductors to the top contributors, returning the changes
+    fn elect_conductors(&mut self) -> Vec<Transition> {
+        let top = |kind: ContributionKind| {
+            self.contributors
+                .values()
+                .map(|c| (c.get(kind), c))
+                .filter(|(total, _)| *total > 0)
+                .max_by_key(|(total, _)| *total)
+                .map(|(_, c)| c.user_id.clone())
+        };
+        let (bits, subs) = (
+            top(ContributionKind::Bits),
+            top(ContributionKind::Subscription),
+        );
+        let mut transitions = vec![];
+        if let Some(bits) = bits.filter(|b| self.bits_conductor.as_ref() != Some(b)) {
+            self.bits_conductor = Some(bits.clone());
+            transitions.push(Transition::ConductorChanged {
+                kind: ContributionKind::Bits,
+                user_id: bits,
+            });
+        }
+        if let Some(subs) = subs.filter(|s| self.subs_conductor.as_ref() != Some(s)) {
+            self.subs_conductor = Some(subs.clone());
+            transitions.push(Transition::ConductorChanged {
+                kind: ContributionKind::Subscription,
+                user_id: subs,
+            });
+        }
+        transitions
+    }
+}
+
+/// A single contribution
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TrackedContribution {
+    /// The contributing user
+    pub user_id: types::UserId,
+    /// Login of the user, not sent by Helix
+    pub user_login: Option<types::UserName>,
+    /// Display name of the user, not sent by Helix
+    pub user_name: Option<types::DisplayName>,
+    /// Kind of contribution
+    pub kind: ContributionKind,
+    /// Points contributed
+    pub total: i64,
+}
+
+impl From<&Contribution> for TrackedContribution {
+    fn from(contribution: &Contribution) -> Self {
+        Self {
+            user_id: contribution.user_id.clone(),
+            user_login: Some(contribution.user_login.clone()),
+            user_name: Some(contribution.user_name.clone()),
+            kind: (&contribution.type_).into(),
+            total: contribution.total,
+        }
+    }
+}
+
+/// A user that contributed to a [`HypeTrain`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Contributor {
+    /// The user
+    pub user_id: types::UserId,
+    /// Login of the user, if known
+    pub user_login: Option<types::UserName>,
+    /// Display name of the user, if known
+    pub user_name: Option<types::DisplayName>,
+    /// Points contributed with bits
+    pub bits: i64,
+    /// Points contributed with subscriptions
+    pub subscriptions: i64,
+    /// Points contributed otherwise
+    pub other: i64,
+}
+
+impl Contributor {
+    /// Points contributed in total
+    pub fn total(&self) -> i64 { self.bits + self.subscriptions + self.other }
+
+    /// Points contributed of a kind
+    pub fn get(&self, kind: ContributionKind) -> i64 {
+        match kind {
+            ContributionKind::Bits => self.bits,
+            ContributionKind::Subscription => self.subscriptions,
+            ContributionKind::Other => self.other,
+        }
+    }
+
+    fn get_mut(&mut self, kind: ContributionKind) -> &mut i64 {
+        match kind {
+            ContributionKind::Bits => &mut self.bits,
+            ContributionKind::Subscription => &mut self.subscriptions,
+            ContributionKind::Other => &mut self.other,
+        }
+    }
+
+    fn identify(&mut self, contribution: &TrackedContribution) {
+        if contribution.user_login.is_some() {
+            self.user_login.clone_from(&contribution.user_login);
+        }
+        if contribution.user_name.is_some() {
+            self.user_name.clone_from(&contribution.user_name);
+        }
+    }
+}
+
+/// A change to a [`ChannelHypeTrain`] caused by an update
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Transition {
+    /// A hype train started
+    Began {
+        /// The starting level
+        level: i64,
+    },
+    /// Points were contributed
+    Progressed {
+        /// Points added to the total
+        points: i64,
+        /// The contributor, if known
+        user_id: Option<types::UserId>,
+    },
+    /// A new level was reached
+    LeveledUp {
+        /// The new level
+        level: i64,
+    },
+    /// The bits or subscriptions conductor changed
+    ConductorChanged {
+        /// Which conductor
+        kind: ContributionKind,
+        /// The new conductor
+        user_id: types::UserId,
+    },
+    /// The hype train ended
+    Ended {
+        /// The level reached
+        level: i64,
+        /// Points contributed in all levels
+        total: i64,
+    },
+    /// The cooldown expired, a new hype train can start
+    CooldownExpired,
+}
+
+/// Result of applying an update to a [`HypeTrainTracker`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Update<'a> {
+    /// The hype train after the update
+    pub train: &'a HypeTrain,
+    /// Transitions caused by the update, empty if the update was stale or changed nothing
+    pub transitions: Vec<Transition>,
+}
+
+/// Tracks hype trains, see the [module documentation](self)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HypeTrainTracker {
+    channels: BTreeMap<types::UserId, ChannelHypeTrain>,
+}
+
+/// A hype train as described by a single update
+struct Observation {
+    broadcaster_id: types::UserId,
+    id: Option<types::HypeTrainId>,
+    level: Option<i64>,
+    total: Option<i64>,
+    progress: Option<i64>,
+    goal: Option<i64>,
+    started_at: Option<types::Timestamp>,
+    expires_at: Option<types::Timestamp>,
+    ended_at: Option<types::Timestamp>,
+    cooldown_ends_at: Option<types::Timestamp>,
+    last_contribution: Option<TrackedContribution>,
+    top_contributions: Vec<TrackedContribution>,
+}
+
+impl Observation {
+    fn new(broadcaster_id: types::UserId, id: Option<types::HypeTrainId>) -> Self {
+        Self {
+            broadcaster_id,
+            id,
+            level: None,
+            total: None,
+            progress: None,
+            goal: None,
+            started_at: None,
+            expires_at: None,
+            ended_at: None,
+            cooldown_ends_at: None,
+            last_contribution: None,
+            top_contributions: vec![],
+        }
+    }
+}
+
+impl HypeTrainTracker {
+    /// Create an empty tracker
+    pub fn new() -> Self { Self::default() }
+
+    /// Hype train state for a channel
+    pub fn channel<'a>(
+        &self,
+        broadcaster_id: impl Into<&'a types::UserIdRef>,
+    ) -> Option<&ChannelHypeTrain> {
+        self.channels.get(broadcaster_id.into())
+    }
+
+    /// All channels
+    pub fn channels(&self) -> impl Iterator<Item = &ChannelHypeTrain> + '_ {
+        self.channels.values()
+    }
+
+    /// The running hype train in a channel
+    pub fn active<'a>(
+        &self,
+        broadcaster_id: impl Into<&'a types::UserIdRef>,
+    ) -> Option<&HypeTrain> {
+        self.channel(broadcaster_id)?.active()
+    }
+
+    /// Apply an EventSub event. Returns `None` for events that aren't `channel.hype_train.*` notifications.
+    pub fn apply_event(&mut self, event: &Event) -> Option<Update<'_>> {
+        use crate::eventsub::{Message, Payload};
+
+        match event {
+            Event::ChannelHypeTrainBeginV1(Payload {
+                message: Message::Notification(payload),
+                ..
+            }) => Some(self.apply_begin(payload)),
+            Event::ChannelHypeTrainProgressV1(Payload {
+                message: Message::Notification(payload),
+                ..
+            }) => Some(self.apply_progress(payload)),
+            Event::ChannelHypeTrainEndV1(Payload {
+                message: Message::Notification(payload),
+                ..
+            }) => Some(self.apply_end(payload)),
+            _ => None,
+        }
+    }
+
+    /// Apply a [`channel.hype_train.begin`](super::ChannelHypeTrainBeginV1) notification
+    pub fn apply_begin(&mut self, payload: &ChannelHypeTrainBeginV1Payload) -> Update<'_> {
+        self.merge(Observation {
+            level: Some(payload.level),
+            total: Some(payload.total),
+            progress: Some(payload.progress),
+            goal: Some(payload.goal),
+            started_at: Some(payload.started_at.clone()),
+            expires_at: Some(payload.expires_at.clone()),
+            last_contribution: Some((&payload.last_contribution).into()),
+            top_contributions: payload.top_contributions.iter().map(Into::into).collect(),
+            ..Observation::new(
+                payload.broadcaster_user_id.clone(),
+                Some(payload.id.clone()),
+            )
+        })
+    }
+
+    /// Apply a [`channel.hype_train.progress`](super::ChannelHypeTrainProgressV1) notification
+    pub fn apply_progress(&mut self, payload: &ChannelHypeTrainProgressV1Payload) -> Update<'_> {
+        self.merge(Observation {
+            level: Some(payload.level),
+            total: Some(payload.total),
+            progress: Some(payload.progress),
+            goal: Some(payload.goal),
+            started_at: Some(payload.started_at.clone()),
+            expires_at: Some(payload.expires_at.clone()),
+            last_contribution: Some((&payload.last_contribution).into()),
+            top_contributions: payload.top_contributions.iter().map(Into::into).collect(),
+            ..Observation::new(
+                payload.broadcaster_user_id.clone(),
+                Some(payload.id.clone()),
+            )
+        })
+    }
+
+    /// Apply a [`channel.hype_train.end`](super::ChannelHypeTrainEndV1) notification
+    pub fn apply_end(&mut self, payload: &ChannelHypeTrainEndV1Payload) -> Update<'_> {
+        self.merge(Observation {
+            level: Some(payload.level),
+            total: Some(payload.total),
+            started_at: Some(payload.started_at.clone()),
+            ended_at: Some(payload.ended_at.clone()),
+            cooldown_ends_at: Some(payload.cooldown_ends_at.clone()),
+            top_contributions: payload.top_contributions.iter().map(Into::into).collect(),
+            ..Observation::new(
+                payload.broadcaster_user_id.clone(),
+                Some(payload.id.clone()),
+            )
+        })
+    }
+
+    /// Apply an event from [Get Hype Train Events](crate::helix::hypetrain::get_hypetrain_events)
+    ///
+    /// Helix doesn't tell whether the hype train has ended, it's considered running until its end is seen elsewhere
+    /// or [`HypeTrainTracker::poll`] is called after it expired.
+    #[cfg(feature = "helix")]
+    #[cfg_attr(nightly, doc(cfg(feature = "helix")))]
+    pub fn apply_helix(
+        &mut self,
+        event: &crate::helix::hypetrain::get_hypetrain_events::HypeTrainEvent,
+    ) -> Update<'_> {
+        use crate::helix::hypetrain::{Contribution, ContributionType};
+
+        let contribution = |c: &Contribution| TrackedContribution {
+            user_id: c.user.clone(),
+            user_login: None,
+            user_name: None,
+            kind: match c.type_ {
+                ContributionType::Bits => ContributionKind::Bits,
+                ContributionType::Subscription => ContributionKind::Subscription,
+                ContributionType::Other => ContributionKind::Other,
+            },
+            total: c.total,
+        };
+        let data = &event.event_data;
+        self.merge(Observation {
+            level: Some(data.level),
+            total: Some(data.total),
+            goal: Some(data.goal),
+            started_at: Some(data.started_at.clone()),
+            expires_at: Some(data.expires_at.clone()),
+            cooldown_ends_at: Some(data.cooldown_end_time.clone()),
+            last_contribution: Some(contribution(&data.last_contribution)),
+            top_contributions: data.top_contributions.iter().map(contribution).collect(),
+            ..Observation::new(data.broadcaster_id.clone(), Some(data.id.clone()))
+        })
+    }
+
+    /// Apply a PubSub topic message. Returns `None` for other topics and messages that don't change the hype train.
+    #[cfg(all(feature = "pubsub", feature = "unsupported"))]
+    #[cfg_attr(nightly, doc(cfg(all(feature = "pubsub", feature = "unsupported"))))]
+    #[allow(deprecated)]
+    pub fn apply_pubsub(&mut self, data: &crate::pubsub::TopicData) -> Option<Update<'_>> {
+        match data {
+            crate::pubsub::TopicData::HypeTrainEventsV1 { topic, reply } => {
+                self.apply_pubsub_reply(&types::UserId::new(topic.channel_id.to_string()), reply)
+            }
+            _ => None,
+        }
+    }
+
+    /// Apply a reply from `hype-train-events-v1` for a channel
+    ///
+    /// Returns `None` for messages that don't change the hype train, and for cooldown expirations when no hype train has been seen.
+    #[cfg(all(feature = "pubsub", feature = "unsupported"))]
+    #[cfg_attr(nightly, doc(cfg(all(feature = "pubsub", feature = "unsupported"))))]
+    #[allow(deprecated)]
+    pub fn apply_pubsub_reply(
+        &mut self,
+        broadcaster_id: &types::UserIdRef,
+        reply: &crate::pubsub::hypetrain::HypeTrainEventsV1Reply,
+    ) -> Option<Update<'_>> {
+        use crate::pubsub::hypetrain::{HypeTrainEventsV1Reply as Reply, SourceType};
+
+        let kind = |source: &SourceType| match source {
+            SourceType::Bits => ContributionKind::Bits,
+            SourceType::Subs => ContributionKind::Subscription,
+        };
+        // PubSub only sends the id when the train starts
+        let current_id = self
+            .active(broadcaster_id)
+            .and_then(|train| train.id.clone());
+        let observation = |id: Option<types::HypeTrainId>| {
+            Observation::new(broadcaster_id.to_owned(), id.or_else(|| current_id.clone()))
+        };
+
+        let observation = match reply {
+            Reply::HypeTrainStart(start) => {
+                let duration = start.config.cooldown_duration / 1_000_000;
+                self.channel_mut(broadcaster_id).cooldown_duration = Some(duration);
+                Observation {
+                    level: Some(start.progress.level.value),
+                    total: Some(start.progress.value),
+                    progress: Some(start.progress.total),
+                    goal: Some(start.progress.goal),
+                    started_at: start.started_at.map(crate::unix_millis_to_timestamp),
+                    expires_at: start.expires_at.map(crate::unix_millis_to_timestamp),
+                    ..observation(start.id.clone().map(types::HypeTrainId::new))
+                }
+            }
+            Reply::HypeTrainProgression(progression) => {
+                let previous = self.active(broadcaster_id).map_or(0, |train| train.total);
+                Observation {
+                    level: Some(progression.progress.level.value),
+                    total: Some(progression.progress.value),
+                    progress: Some(progression.progress.total),
+                    goal: Some(progression.progress.goal),
+                    last_contribution: Some(TrackedContribution {
+                        user_id: progression.user_id.clone(),
+                        user_login: Some(progression.user_login.clone()),
+                        user_name: Some(progression.user_display_name.clone()),
+                        kind: kind(&progression.source),
+                        total: (progression.progress.value - previous).max(0),
+                    }),
+                    ..observation(None)
+                }
+            }
+            Reply::HypeTrainLevelUp(level_up) => Observation {
+                level: Some(level_up.progress.level.value),
+                total: Some(level_up.progress.value),
+                progress: Some(level_up.progress.total),
+                goal: Some(level_up.progress.goal),
+                expires_at: Some(crate::unix_millis_to_timestamp(level_up.time_to_expire)),
+                ..observation(None)
+            },
+            Reply::HypeTrainConductorUpdate(update) => {
+                let kind = kind(&update.source);
+                let train = self.channel_mut(broadcaster_id).train.as_mut()?;
+                let conductor = match kind {
+                    ContributionKind::Bits => &mut train.bits_conductor,
+                    _ => &mut train.subs_conductor,
+                };
+                let mut transitions = vec![];
+                if conductor.as_ref() != Some(&update.user.id) {
+                    *conductor = Some(update.user.id.clone());
+                    transitions.push(Transition::ConductorChanged {
+                        kind,
+                        user_id: update.user.id.clone(),
+                    });
+                }
+                let contributor = train.contributor(&update.user.id);
+                contributor.user_login = Some(update.user.login.clone());
+                contributor.user_name = Some(update.user.display_name.clone());
+                return Some(Update { train, transitions });
+            }
+            Reply::HypeTrainEnd(end) => {
+                let channel = self.channel_mut(broadcaster_id);
+                Observation {
+                    ended_at: Some(crate::unix_millis_to_timestamp(end.ended_at)),
+                    cooldown_ends_at: channel
+                        .cooldown_duration
+                        .map(|duration| crate::unix_millis_to_timestamp(end.ended_at + duration)),
+                    ..observation(None)
+                }
+            }
+            Reply::HypeTrainCooldownExpiration(_) => {
+                let channel = self.channel_mut(broadcaster_id);
+                channel.cooldown_ends_at = None;
+                return Some(Update {
+                    train: channel.train.as_ref()?,
+                    transitions: vec![Transition::CooldownExpired],
+                });
+            }
+            _ => return None,
+        };
+        Some(self.merge(observation))
+    }
+
+    /// End hype trains that expired and cooldowns that passed at `now`
+    ///
+    /// Hype trains without an end from EventSub or PubSub end at their `expires_at`, and their cooldown is
+    /// derived from the PubSub config if known. Returns the transitions per channel.
+    pub fn poll(&mut self, now: SystemTime) -> Vec<(types::UserId, Transition)> {
+        let now = now
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |now| now.as_millis() as i64);
+        let passed = |timestamp: Option<&types::Timestamp>| {
+            timestamp
+                .and_then(|t| crate::timestamp_to_unix_millis(t.as_str()))
+                .map_or(false, |at| at <= now)
+        };
+        let mut transitions = vec![];
+        for channel in self.channels.values_mut() {
+            if let Some(train) = channel.train.as_mut().filter(|train| !train.ended()) {
+                if passed(train.expires_at.as_ref()) {
+                    train.ended_at.clone_from(&train.expires_at);
+                    if channel.cooldown_ends_at.is_none() {
+                        channel.cooldown_ends_at = train
+                            .expires_at
+                            .as_ref()
+                            .and_then(|t| crate::timestamp_to_unix_millis(t.as_str()))
+                            .zip(channel.cooldown_duration)
+                            .map(|(ended_at, duration)| {
+                                crate::unix_millis_to_timestamp(ended_at + duration)
+                            });
+                    }
+                    transitions.push((
+                        channel.broadcaster_id.clone(),
+                        Transition::Ended {
+                            level: train.level,
+                            total: train.total,
+                        },
+                    ));
+                }
+            }
+            if passed(channel.cooldown_ends_at.as_ref()) {
+                channel.cooldown_ends_at = None;
+                transitions.push((channel.broadcaster_id.clone(), Transition::CooldownExpired));
+            }
+        }
+        transitions
+    }
+
+    fn channel_mut(&mut self, broadcaster_id: &types::UserIdRef) -> &mut ChannelHypeTrain {
+        self.channels
+            .entry(broadcaster_id.to_owned())
+            .or_insert_with(|| ChannelHypeTrain {
+                broadcaster_id: broadcaster_id.to_owned(),
+                train: None,
+                cooldown_ends_at: None,
+                cooldown_duration: None,
+            })
+    }
+
+    fn merge(&mut self, observation: Observation) -> Update<'_> {
+        let channel = self.channel_mut(&observation.broadcaster_id);
+        let mut transitions = vec![];
+
+        let same_train = channel.train.as_ref().map_or(false, |train| {
+            match (&train.id, &observation.id) {
+                (Some(a), Some(b)) => a == b,
+                // PubSub messages without an id belong to the running train
+                _ => !train.ended(),
+            }
+        });
+        if !same_train {
+            channel.train = Some(HypeTrain::new(observation.id.clone()));
+            transitions.push(Transition::Began {
+                level: observation.level.unwrap_or(1),
+            });
+        }
+        let train = channel.train.as_mut().expect("train was just set");
+
+        let stale = train.ended() || observation.total.map_or(false, |total| total < train.total);
+        if let Some(cooldown_ends_at) = observation.cooldown_ends_at.filter(|_| !train.ended()) {
+            channel.cooldown_ends_at = Some(cooldown_ends_at);
+        }
+        if stale {
+            return Update { train, transitions };
+        }
+
+        if train.id.is_none() {
+            train.id = observation.id;
+        }
+        train.started_at = observation.started_at.or(train.started_at.take());
+        train.expires_at = observation.expires_at.or(train.expires_at.take());
+        train.goal = observation.goal.unwrap_or(train.goal);
+        train.progress = observation.progress.unwrap_or(train.progress);
+
+        if let Some(total) = observation.total {
+            let points = total - train.total;
+            train.total = total;
+            if points > 0 {
+                let user_id = observation
+                    .last_contribution
+                    .as_ref()
+                    .map(|c| c.user_id.clone());
+                if let Some(contribution) = &observation.last_contribution {
+                    train.add_contribution(contribution);
+                }
+                if same_train {
+                    transitions.push(Transition::Progressed { points, user_id });
+                }
+            }
+        }
+        // top contributions are totals, so they don't add to the last contribution
+        for contribution in &observation.top_contributions {
+            train.top_contribution(contribution);
+        }
+        transitions.extend(train.elect_conductors());
+
+        if let Some(level) = observation.level.filter(|&level| level > train.level) {
+            train.level = level;
+            if same_train {
+                transitions.push(Transition::LeveledUp { level });
+            }
+        }
+
+        if let Some(ended_at) = observation.ended_at {
+            train.ended_at = Some(ended_at);
+            transitions.push(Transition::Ended {
+                level: train.level,
+                total: train.total,
+            });
+        }
+
+        Update { train, transitions }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contribution(user: &str, type_: &str, total: i64) -> serde_json::Value {
+        serde_json::json!({"user_id": user, "user_login": user, "user_name": user, "type": type_, "total": total})
+    }
+
+    fn payload<T: serde::de::DeserializeOwned>(extra: serde_json::Value) -> T {
+        let mut payload = serde_json::json!({
+            "id": "1b0AsbInCHZW2SQFQkCzqN07Ib2",
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cool_user",
+            "broadcaster_user_name": "Cool_User",
+            "started_at": "2020-07-15T17:16:03.17106713Z",
+        });
+        payload
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(payload).unwrap()
+    }
+
+    #[test]
+    fn eventsub_lifecycle() {
+        let mut tracker = HypeTrainTracker::new();
+        let update = tracker.apply_begin(&payload(serde_json::json!({
+            "total": 100,
+            "progress": 100,
+            "goal": 500,
+            "level": 1,
+            "top_contributions": [contribution("a", "bits", 100)],
+            "last_contribution": contribution("a", "bits", 100),
+            "expires_at": "2020-07-15T17:21:03.17106713Z"
+        })));
+        assert_eq!(
+            update.transitions,
+            [
+                Transition::Began { level: 1 },
+                Transition::ConductorChanged {
+                    kind: ContributionKind::Bits,
+                    user_id: "a".into()
+                }
+            ]
+        );
+
+        let progress: ChannelHypeTrainProgressV1Payload = payload(serde_json::json!({
+            "total": 600,
+            "progress": 100,
+            "goal": 800,
+            "level": 2,
+            "top_contributions": [contribution("b", "subscription", 500), contribution("a", "bits", 100)],
+            "last_contribution": contribution("b", "subscription", 500),
+            "expires_at": "2020-07-15T17:26:03.17106713Z"
+        }));
+        let update = tracker.apply_progress(&progress);
+        assert_eq!(
+            update.transitions,
+            [
+                Transition::Progressed {
+                    points: 500,
+                    user_id: Some("b".into())
+                },
+                Transition::ConductorChanged {
+                    kind: ContributionKind::Subscription,
+                    user_id: "b".into()
+                },
+                Transition::LeveledUp { level: 2 }
+            ]
+        );
+        assert_eq!(update.train.fraction(), 0.125);
+        // contributions aren't counted twice from top and last contributions
+        assert_eq!(
+            update.train.contributors[types::UserIdRef::from_static("b")].total(),
+            500
+        );
+
+        // redelivered progress is ignored
+        assert!(tracker.apply_progress(&progress).transitions.is_empty());
+
+        let update = tracker.apply_end(&payload(serde_json::json!({
+            "total": 600,
+            "level": 2,
+            "top_contributions": [],
+            "ended_at": "2020-07-15T17:27:03.17106713Z",
+            "cooldown_ends_at": "2020-07-15T18:27:03.17106713Z"
+        })));
+        assert_eq!(
+            update.transitions,
+            [Transition::Ended {
+                level: 2,
+                total: 600
+            }]
+        );
+
+        let channel = tracker.channel("1337").unwrap();
+        assert!(channel.active().is_none());
+        let at = |millis: u64| SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(millis);
+        assert!(channel.is_on_cooldown(at(1_594_833_000_000)));
+        assert!(!channel.is_on_cooldown(at(1_594_837_624_000)));
+    }
+
+    #[cfg(all(feature = "pubsub", feature = "unsupported"))]
+    #[test]
+    fn pubsub() {
+        use crate::pubsub::hypetrain::HypeTrainEventsV1Reply;
+
+        let progress = |level: i64, total: i64, value: i64| {
+            serde_json::json!({
+                "goal": 1000,
+                "level": {"value": level, "goal": 1000, "rewards": []},
+                "remaining_seconds": 200,
+                "total": total,
+                "value": value
+            })
+        };
+        let reply = |reply: serde_json::Value| -> HypeTrainEventsV1Reply {
+            serde_json::from_value(reply).unwrap()
+        };
+        let id = types::UserIdRef::from_static("1234");
+        let mut tracker = HypeTrainTracker::new();
+
+        let update = tracker
+            .apply_pubsub_reply(
+                id,
+                &reply(serde_json::json!({
+                    "type": "hype-train-progression",
+                    "data": {
+                        "sequence_id": 1,
+                        "source": "BITS",
+                        "action": "CHEER",
+                        "quantity": 300,
+                        "progress": progress(1, 300, 300),
+                        "user_id": "5678",
+                        "user_login": "viewer",
+                        "user_display_name": "Viewer",
+                        "user_profile_image_url": null
+                    }
+                })),
+            )
+            .unwrap();
+        assert_eq!(update.transitions[0], Transition::Began { level: 1 });
+        assert_eq!(
+            update.train.contributors[types::UserIdRef::from_static("5678")].bits,
+            300
+        );
+
+        let update = tracker
+            .apply_pubsub_reply(
+                id,
+                &reply(serde_json::json!({
+                    "type": "hype-train-level-up",
+                    "data": {"time_to_expire": 1603128256000_i64, "progress": progress(2, 100, 1100)}
+                })),
+            )
+            .unwrap();
+        assert_eq!(
+            update.transitions,
+            [
+                Transition::Progressed {
+                    points: 800,
+                    user_id: None
+                },
+                Transition::LeveledUp { level: 2 }
+            ]
+        );
+        assert_eq!(
+            update.train.expires_at.as_ref().unwrap().as_str(),
+            "2020-10-19T17:24:16.000Z"
+        );
+
+        let update = tracker
+            .apply_pubsub_reply(
+                id,
+                &reply(serde_json::json!({
+                    "type": "hype-train-end",
+                    "data": {"ended_at": 1603128366000_i64, "ending_reason": "EXPIRED"}
+                })),
+            )
+            .unwrap();
+        assert_eq!(
+            update.transitions,
+            [Transition::Ended {
+                level: 2,
+                total: 1100
+            }]
+        );
+
+        let update = tracker
+            .apply_pubsub_reply(
+                id,
+                &reply(serde_json::json!({"type": "hype-train-cooldown-expiration"})),
+            )
+            .unwrap();
+        assert_eq!(update.transitions, [Transition::CooldownExpired]);
+    }
+
+    #[cfg(feature = "helix")]
+    #[test]
+    fn helix() {
+        use crate::helix::hypetrain::get_hypetrain_events::HypeTrainEvent;
+
+        let event = |total: i64, level: i64, last: serde_json::Value, top: serde_json::Value| {
+            serde_json::from_value::<HypeTrainEvent>(serde_json::json!({
+                "id": "1b0AsbInCHZW2SQFQkCzqN07Ib2",
+                "event_type": "hypetrain.progression",
+                "event_timestamp": "2020-04-24T20:07:24Z",
+                "version": "1.0",
+                "event_data": {
+                    "broadcaster_id": "270954519",
+                    "cooldown_end_time": "2020-04-24T20:13:21.003802269Z",
+                    "expires_at": "2020-04-24T20:12:21.003802269Z",
+                    "goal": 1800,
+                    "id": "70f0c7d8-ff60-4c50-b138-f3a352833b50",
+                    "last_contribution": last,
+                    "level": level,
+                    "started_at": "2020-04-24T20:05:47.30473127Z",
+                    "top_contributions": top,
+                    "total": total
+                }
+            }))
+            .unwrap()
+        };
+        let contribution = |user: &str, type_: &str, total: i64| serde_json::json!({"user": user, "type": type_, "total": total});
+
+        let mut tracker = HypeTrainTracker::new();
+        let first = event(
+            600,
+            2,
+            contribution("134247454", "BITS", 200),
+            serde_json::json!([contribution("134247450", "BITS", 600)]),
+        );
+        let update = tracker.apply_helix(&first);
+        assert_eq!(
+            update.transitions,
+            [
+                Transition::Began { level: 2 },
+                Transition::ConductorChanged {
+                    kind: ContributionKind::Bits,
+                    user_id: "134247450".into()
+                }
+            ]
+        );
+        assert_eq!(update.train.level, 2);
+        assert_eq!(update.train.goal, 1800);
+
+        let update = tracker.apply_helix(&event(
+            900,
+            3,
+            contribution("134247454", "SUBSCRIPTION", 300),
+            serde_json::json!([
+                contribution("134247450", "BITS", 600),
+                contribution("134247454", "SUBSCRIPTION", 300)
+            ]),
+        ));
+        assert_eq!(
+            update.transitions,
+            [
+                Transition::Progressed {
+                    points: 300,
+                    user_id: Some("134247454".into())
+                },
+                Transition::ConductorChanged {
+                    kind: ContributionKind::Subscription,
+                    user_id: "134247454".into()
+                },
+                Transition::LeveledUp { level: 3 }
+            ]
+        );
+
+        // an older event is ignored
+        assert!(tracker.apply_helix(&first).transitions.is_empty());
+
+        // Helix doesn't tell when the hype train ends
+        let channel = tracker.channel("270954519").unwrap();
+        assert_eq!(channel.active().unwrap().total, 900);
+        assert_eq!(
+            channel.cooldown_ends_at.as_ref().unwrap().as_str(),
+            "2020-04-24T20:13:21.003802269Z"
+        );
+
+        // until it expires
+        let at = |timestamp: &str| {
+            let millis = crate::timestamp_to_unix_millis(timestamp).unwrap();
+            SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(millis as u64)
+        };
+        assert!(tracker.poll(at("2020-04-24T20:12:21Z")).is_empty());
+        assert_eq!(
+            tracker.poll(at("2020-04-24T20:12:22Z")),
+            [(
+                types::UserId::new("270954519".to_owned()),
+                Transition::Ended {
+                    level: 3,
+                    total: 900
+                }
+            )]
+        );
+        assert!(tracker.active("270954519").is_none());
+        assert!(tracker.apply_helix(&first).transitions.is_empty());
+        assert!(tracker.poll(at("2020-04-24T20:13:00Z")).is_empty());
+        assert_eq!(
+            tracker.poll(at("2020-04-24T20:13:22Z")),
+            [(
+                types::UserId::new("270954519".to_owned()),
+                Transition::CooldownExpired
+            )]
+        );
+        assert!(tracker
+            .channel("270954519")
+            .unwrap()
+            .cooldown_ends_at
+            .is_none());
+    }
+}
@@ -8,6 +8,7 @@ pub mod begin;
 pub mod end;
 pub mod lock;
 pub mod progress;
+pub mod tracker;
 
 #[doc(inline)]
 pub use begin::{ChannelPredictionBeginV1, ChannelPredictionBeginV1Payload};
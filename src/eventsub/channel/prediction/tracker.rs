@@ -0,0 +1,855 @@
+//! Prediction lifecycle tracking across EventSub, Helix and PubSub
+//!
+//! A prediction can be observed through the `channel.prediction.*` subscriptions,
+//! through [Get Predictions](https://dev.twitch.tv/docs/api/reference#get-predictions) and,
+//! with feature `pubsub`, through the `predictions-channel-v1` and `predictions-user-v1` topics.
+//! [`PredictionTracker`] accepts updates from any of these and keeps one canonical [`TrackedPrediction`] per prediction,
+//! returning the [`Transition`]s every update caused.
+//!
+//! A prediction moves from [active](PredictionState::Active) to [locked](PredictionState::Locked)
+//! and ends either [resolved](PredictionState::Resolved) or [canceled](PredictionState::Canceled), it never moves back.
+//! Updates that are behind the tracked state, e.g. a `channel.prediction.progress` delivered after the lock,
+//! are considered stale and ignored, so sources can be mixed freely.
+//!
+//! The user's own bet from `predictions-user-v1` is attached to the prediction it was placed on.
+//! Bets on predictions that haven't been seen yet are kept until the prediction is first seen.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use twitch_api::eventsub::{
+//!     channel::prediction::tracker::{PredictionState, PredictionTracker, Transition},
+//!     Event,
+//! };
+//!
+//! let mut tracker = PredictionTracker::new();
+//! # let notification = r#"{"subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","type":"channel.prediction.lock","version":"1","status":"enabled","cost":0,"condition":{"broadcaster_user_id":"1337"},"transport":{"method":"webhook","callback":"https://example.com/webhooks/callback"},"created_at":"2019-11-16T10:11:12.123Z"},"event":{"id":"1243456","broadcaster_user_id":"1337","broadcaster_user_login":"cool_user","broadcaster_user_name":"Cool_User","title":"Aren’t shoes just really hard socks?","outcomes":[{"id":"1243456","title":"Yeah!","color":"blue","users":10,"channel_points":15000,"top_predictors":null},{"id":"2243456","title":"No!","color":"pink","users":3,"channel_points":4000,"top_predictors":null}],"started_at":"2020-07-15T17:16:03.17106713Z","locked_at":"2020-07-15T17:21:03.17106713Z"}}"#;
+//! let event = Event::parse(notification)?;
+//! let update = tracker.apply_event(&event).expect("a prediction event");
+//! assert_eq!(update.transitions, [Transition::Began, Transition::Locked]);
+//! assert_eq!(update.prediction.state, PredictionState::Locked);
+//! assert_eq!(update.prediction.total_points(), 19000);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    ChannelPredictionBeginV1Payload, ChannelPredictionEndV1Payload, ChannelPredictionLockV1Payload,
+    ChannelPredictionProgressV1Payload,
+};
+use crate::{eventsub::Event, types};
+
+/// Status of a [`TrackedPrediction`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[non_exhaustive]
+pub enum PredictionState {
+    /// Viewers can make predictions
+    Active,
+    /// Predictions are locked, the prediction hasn't ended yet
+    Locked,
+    /// The prediction ended with a winning outcome
+    Resolved,
+    /// The prediction was canceled and points refunded
+    Canceled,
+}
+
+impl PredictionState {
+    /// Whether the prediction has ended
+    pub const fn is_ended(self) -> bool { matches!(self, Self::Resolved | Self::Canceled) }
+
+    const fn rank(self) -> u8 {
+        match self {
+            Self::Active => 0,
+            Self::Locked => 1,
+            Self::Resolved | Self::Canceled => 2,
+        }
+    }
+
+    #[cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm")))]
+    fn from_pubsub(status: &str) -> Self {
+        match status {
+            "RESOLVED" => Self::Resolved,
+            "CANCELED" => Self::Canceled,
+            // pending states are locked until the broadcaster's choice is processed
+            "LOCKED" | "RESOLVE_PENDING" | "CANCEL_PENDING" => Self::Locked,
+            _ => Self::Active,
+        }
+    }
+}
+
+impl From<&types::PredictionStatus> for PredictionState {
+    fn from(status: &types::PredictionStatus) -> Self {
+        if *status == types::PredictionStatus::Resolved {
+            Self::Resolved
+        } else if *status == types::PredictionStatus::Canceled {
+            Self::Canceled
+        } else if *status == types::PredictionStatus::Locked {
+            Self::Locked
+        } else {
+            Self::Active
+        }
+    }
+}
+
+/// A prediction, as seen by all sources applied to the [`PredictionTracker`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TrackedPrediction {
+    /// ID of the prediction
+    pub id: types::PredictionId,
+    /// The broadcaster running the prediction
+    pub broadcaster_id: types::UserId,
+    /// Title of the prediction
+    pub title: String,
+    /// Current state
+    pub state: PredictionState,
+    /// Outcomes with their pool totals and top predictors
+    pub outcomes: Vec<TrackedOutcome>,
+    /// The winning outcome, set when [resolved](PredictionState::Resolved)
+    pub winning_outcome_id: Option<types::PredictionOutcomeId>,
+    /// Duration of the prediction window in seconds, not sent by EventSub
+    pub prediction_window: Option<i64>,
+    /// When the prediction started
+    pub started_at: Option<types::Timestamp>,
+    /// When the prediction will lock automatically
+    pub locks_at: Option<types::Timestamp>,
+    /// When the prediction was locked
+    pub locked_at: Option<types::Timestamp>,
+    /// When the prediction ended
+    pub ended_at: Option<types::Timestamp>,
+    /// The user's own bet, from `predictions-user-v1`
+    pub user_bet: Option<UserBet>,
+}
+
+impl TrackedPrediction {
+    /// Total channel points in the pool
+    pub fn total_points(&self) -> i64 { self.outcomes.iter().map(|o| o.channel_points).sum() }
+
+    /// Total users that made a prediction
+    pub fn total_users(&self) -> i64 { self.outcomes.iter().map(|o| o.users).sum() }
+
+    /// Get an outcome
+    pub fn outcome<'a>(
+        &self,
+        outcome_id: impl Into<&'a types::PredictionOutcomeIdRef>,
+    ) -> Option<&TrackedOutcome> {
+        let outcome_id = outcome_id.into();
+        self.outcomes
+            .iter()
+            .find(|o| o.id.as_str() == outcome_id.as_str())
+    }
+
+    /// The winning outcome, if resolved
+    pub fn winning_outcome(&self) -> Option<&TrackedOutcome> {
+        self.outcome(self.winning_outcome_id.as_deref()?)
+    }
+}
+
+/// An outcome of a [`TrackedPrediction`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TrackedOutcome {
+    /// ID of the outcome
+    pub id: types::PredictionOutcomeId,
+    /// Title of the outcome
+    pub title: String,
+    /// Color of the outcome, `BLUE` or `PINK`
+    pub color: String,
+    /// Users that chose this outcome
+    pub users: i64,
+    /// Channel points used on this outcome
+    pub channel_points: i64,
+    /// The top predictors on this outcome
+    pub top_predictors: Vec<TopPredictor>,
+}
+
+/// A top predictor on a [`TrackedOutcome`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TopPredictor {
+    /// ID of the user
+    pub user_id: types::UserId,
+    /// Display name of the user
+    pub user_name: types::DisplayName,
+    /// Login of the user, not sent by PubSub
+    pub user_login: Option<types::UserName>,
+    /// Channel points used
+    pub channel_points_used: i64,
+    /// Channel points won, set when the prediction is resolved
+    pub channel_points_won: Option<i64>,
+}
+
+/// The user's own bet on a [`TrackedPrediction`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct UserBet {
+    /// The chosen outcome
+    pub outcome_id: types::PredictionOutcomeId,
+    /// Channel points wagered
+    pub points: i64,
+    /// When the bet was first placed
+    pub predicted_at: types::Timestamp,
+    /// When the bet was last changed
+    pub updated_at: types::Timestamp,
+    /// The result, once the prediction has ended
+    pub result: Option<BetResult>,
+}
+
+/// Result of a [`UserBet`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct BetResult {
+    /// `WIN`, `LOSE` or `REFUND`
+    pub kind: String,
+    /// Channel points won
+    pub points_won: Option<i64>,
+}
+
+/// A change to a [`TrackedPrediction`] caused by an update
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Transition {
+    /// The prediction was seen for the first time
+    Began,
+    /// Outcomes, pool totals or top predictors changed
+    Updated,
+    /// The prediction was locked
+    Locked,
+    /// The prediction was resolved
+    Resolved {
+        /// The winning outcome
+        winning_outcome_id: Option<types::PredictionOutcomeId>,
+    },
+    /// The prediction was canceled
+    Canceled,
+    /// The user placed or added to their bet
+    BetPlaced {
+        /// The chosen outcome
+        outcome_id: types::PredictionOutcomeId,
+        /// Total channel points wagered
+        points: i64,
+    },
+    /// The user's bet got its result
+    BetResolved(BetResult),
+}
+
+/// Result of applying an update to a [`PredictionTracker`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Update<'a> {
+    /// The prediction after the update
+    pub prediction: &'a TrackedPrediction,
+    /// Transitions caused by the update, empty if the update was stale or changed nothing
+    pub transitions: Vec<Transition>,
+}
+
+/// Tracks predictions, see the [module documentation](self)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PredictionTracker {
+    predictions: BTreeMap<types::PredictionId, TrackedPrediction>,
+    pending_bets: BTreeMap<types::PredictionId, UserBet>,
+}
+
+/// A prediction as described by a single update
+struct Observation {
+    id: types::PredictionId,
+    broadcaster_id: types::UserId,
+    title: String,
+    state: PredictionState,
+    outcomes: Vec<TrackedOutcome>,
+    winning_outcome_id: Option<types::PredictionOutcomeId>,
+    prediction_window: Option<i64>,
+    started_at: Option<types::Timestamp>,
+    locks_at: Option<types::Timestamp>,
+    locked_at: Option<types::Timestamp>,
+    ended_at: Option<types::Timestamp>,
+}
+
+impl PredictionTracker {
+    /// Create an empty tracker
+    pub fn new() -> Self { Self::default() }
+
+    /// Get a prediction
+    pub fn prediction<'a>(
+        &self,
+        id: impl Into<&'a types::PredictionIdRef>,
+    ) -> Option<&TrackedPrediction> {
+        self.predictions.get(id.into())
+    }
+
+    /// All tracked predictions
+    pub fn predictions(&self) -> impl Iterator<Item = &TrackedPrediction> + '_ {
+        self.predictions.values()
+    }
+
+    /// The predictions in a channel that haven't ended
+    pub fn active<'a>(
+        &self,
+        broadcaster_id: impl Into<&'a types::UserIdRef>,
+    ) -> impl Iterator<Item = &TrackedPrediction> + '_ {
+        let broadcaster_id = broadcaster_id.into().to_owned();
+        self.predictions
+            .values()
+            .filter(move |p| p.broadcaster_id == broadcaster_id && !p.state.is_ended())
+    }
+
+    /// Stop tracking a prediction
+    pub fn remove<'a>(
+        &mut self,
+        id: impl Into<&'a types::PredictionIdRef>,
+    ) -> Option<TrackedPrediction> {
+        let id = id.into();
+        self.pending_bets.remove(id);
+        self.predictions.remove(id)
+    }
+
+    /// Apply an EventSub event. Returns `None` for events that aren't `channel.prediction.*` notifications.
+    pub fn apply_event(&mut self, event: &Event) -> Option<Update<'_>> {
+        use crate::eventsub::{Message, Payload};
+
+        match event {
+            Event::ChannelPredictionBeginV1(Payload {
+                message: Message::Notification(payload),
+                ..
+            }) => Some(self.apply_begin(payload)),
+            Event::ChannelPredictionProgressV1(Payload {
+                message: Message::Notification(payload),
+                ..
+            }) => Some(self.apply_progress(payload)),
+            Event::ChannelPredictionLockV1(Payload {
+                message: Message::Notification(payload),
+                ..
+            }) => Some(self.apply_lock(payload)),
+            Event::ChannelPredictionEndV1(Payload {
+                message: Message::Notification(payload),
+                ..
+            }) => Some(self.apply_end(payload)),
+            _ => None,
+        }
+    }
+
+    /// Apply a [`channel.prediction.begin`](super::ChannelPredictionBeginV1) notification
+    pub fn apply_begin(&mut self, payload: &ChannelPredictionBeginV1Payload) -> Update<'_> {
+        self.merge(Observation {
+            id: payload.id.clone(),
+            broadcaster_id: payload.broadcaster_user_id.clone(),
+            title: payload.title.clone(),
+            state: PredictionState::Active,
+            outcomes: payload.outcomes.iter().map(TrackedOutcome::from).collect(),
+            winning_outcome_id: None,
+            prediction_window: None,
+            started_at: Some(payload.started_at.clone()),
+            locks_at: Some(payload.locks_at.clone()),
+            locked_at: None,
+            ended_at: None,
+        })
+    }
+
+    /// Apply a [`channel.prediction.progress`](super::ChannelPredictionProgressV1) notification
+    pub fn apply_progress(&mut self, payload: &ChannelPredictionProgressV1Payload) -> Update<'_> {
+        self.merge(Observation {
+            id: payload.id.clone(),
+            broadcaster_id: payload.broadcaster_user_id.clone(),
+            title: payload.title.clone(),
+            state: PredictionState::Active,
+            outcomes: payload.outcomes.iter().map(TrackedOutcome::from).collect(),
+            winning_outcome_id: None,
+            prediction_window: None,
+            started_at: Some(payload.started_at.clone()),
+            locks_at: Some(payload.locks_at.clone()),
+            locked_at: None,
+            ended_at: None,
+        })
+    }
+
+    /// Apply a [`channel.prediction.lock`](super::ChannelPredictionLockV1) notification
+    pub fn apply_lock(&mut self, payload: &ChannelPredictionLockV1Payload) -> Update<'_> {
+        self.merge(Observation {
+            id: payload.id.clone(),
+            broadcaster_id: payload.broadcaster_user_id.clone(),
+            title: payload.title.clone(),
+            state: PredictionState::Locked,
+            outcomes: payload.outcomes.iter().map(TrackedOutcome::from).collect(),
+            winning_outcome_id: None,
+            prediction_window: None,
+            started_at: Some(payload.started_at.clone()),
+            locks_at: None,
+            locked_at: Some(payload.locked_at.clone()),
+            ended_at: None,
+        })
+    }
+
+    /// Apply a [`channel.prediction.end`](super::ChannelPredictionEndV1) notification
+    pub fn apply_end(&mut self, payload: &ChannelPredictionEndV1Payload) -> Update<'_> {
+        let state = PredictionState::from(&payload.status);
+        self.merge(Observation {
+            id: payload.id.clone(),
+            broadcaster_id: payload.broadcaster_user_id.clone(),
+            title: payload.title.clone(),
+            state,
+            outcomes: payload.outcomes.iter().map(TrackedOutcome::from).collect(),
+            winning_outcome_id: (state == PredictionState::Resolved)
+                .then(|| payload.winning_outcome_id.clone()),
+            prediction_window: None,
+            started_at: Some(payload.started_at.clone()),
+            locks_at: None,
+            locked_at: None,
+            ended_at: Some(payload.ended_at.clone()),
+        })
+    }
+
+    /// Apply a prediction from [Get Predictions](crate::helix::predictions::get_predictions)
+    #[cfg(feature = "helix")]
+    #[cfg_attr(nightly, doc(cfg(feature = "helix")))]
+    pub fn apply_helix(
+        &mut self,
+        prediction: &crate::helix::predictions::Prediction,
+    ) -> Update<'_> {
+        self.merge(Observation {
+            id: prediction.id.clone(),
+            broadcaster_id: prediction.broadcaster_id.clone(),
+            title: prediction.title.clone(),
+            state: PredictionState::from(&prediction.status),
+            outcomes: prediction
+                .outcomes
+                .iter()
+                .map(TrackedOutcome::from)
+                .collect(),
+            winning_outcome_id: prediction.winning_outcome_id.clone(),
+            prediction_window: Some(prediction.prediction_window),
+            started_at: Some(prediction.created_at.clone()),
+            locks_at: None,
+            locked_at: prediction.locked_at.clone(),
+            ended_at: prediction.ended_at.clone(),
+        })
+    }
+
+    /// Apply a PubSub topic message. Returns `None` for other topics and for bets on predictions that haven't been seen yet.
+    #[cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm")))]
+    #[cfg_attr(
+        nightly,
+        doc(cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm"))))
+    )]
+    #[allow(deprecated)]
+    pub fn apply_pubsub(&mut self, data: &crate::pubsub::TopicData) -> Option<Update<'_>> {
+        use crate::pubsub::TopicData;
+
+        match data {
+            TopicData::PredictionsChannelV1 { reply, .. } => {
+                Some(self.apply_pubsub_event(&reply.data.event))
+            }
+            TopicData::PredictionsUserV1 { reply, .. } => self.apply_pubsub_bet(reply),
+            _ => None,
+        }
+    }
+
+    /// Apply a prediction from `predictions-channel-v1`
+    #[cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm")))]
+    #[cfg_attr(
+        nightly,
+        doc(cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm"))))
+    )]
+    #[allow(deprecated)]
+    pub fn apply_pubsub_event(&mut self, event: &crate::pubsub::predictions::Event) -> Update<'_> {
+        let state = PredictionState::from_pubsub(&event.status);
+        self.merge(Observation {
+            id: types::PredictionId::new(event.id.clone()),
+            broadcaster_id: types::UserId::new(event.channel_id.clone()),
+            title: event.title.clone(),
+            state,
+            outcomes: event.outcomes.iter().map(TrackedOutcome::from).collect(),
+            winning_outcome_id: event
+                .winning_outcome_id
+                .clone()
+                .filter(|_| state == PredictionState::Resolved)
+                .map(types::PredictionOutcomeId::new),
+            prediction_window: Some(event.prediction_window_seconds),
+            started_at: Some(event.created_at.clone()),
+            locks_at: None,
+            locked_at: event.locked_at.clone(),
+            ended_at: event.ended_at.clone(),
+        })
+    }
+
+    /// Apply the user's bet from `predictions-user-v1`
+    ///
+    /// Returns `None` if the prediction hasn't been seen yet, the bet is attached once it is.
+    #[cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm")))]
+    #[cfg_attr(
+        nightly,
+        doc(cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm"))))
+    )]
+    #[allow(deprecated)]
+    pub fn apply_pubsub_bet(
+        &mut self,
+        reply: &crate::pubsub::predictions::PredictionsUserV1Reply,
+    ) -> Option<Update<'_>> {
+        let bet = &reply.data.prediction;
+        let id = types::PredictionId::new(bet.event_id.clone());
+        let outcome_id = types::PredictionOutcomeId::new(bet.outcome_id.clone());
+        let result = bet.result.as_ref().map(|result| BetResult {
+            kind: result.type_field.clone(),
+            points_won: result.points_won,
+        });
+
+        let Some(prediction) = self.predictions.get_mut(&id) else {
+            let pending = self.pending_bets.entry(id).or_insert_with(|| UserBet {
+                outcome_id: outcome_id.clone(),
+                points: bet.points,
+                predicted_at: bet.predicted_at.clone(),
+                updated_at: bet.updated_at.clone(),
+                result: None,
+            });
+            pending.outcome_id = outcome_id;
+            pending.points = bet.points;
+            pending.updated_at = bet.updated_at.clone();
+            pending.result = pending.result.take().or(result);
+            return None;
+        };
+
+        let mut transitions = vec![];
+        match &mut prediction.user_bet {
+            Some(existing) => {
+                if existing.points != bet.points || existing.outcome_id != outcome_id {
+                    existing.outcome_id = outcome_id.clone();
+                    existing.points = bet.points;
+                    transitions.push(Transition::BetPlaced {
+                        outcome_id,
+                        points: bet.points,
+                    });
+                }
+                existing.updated_at = bet.updated_at.clone();
+            }
+            None => {
+                prediction.user_bet = Some(UserBet {
+                    outcome_id: outcome_id.clone(),
+                    points: bet.points,
+                    predicted_at: bet.predicted_at.clone(),
+                    updated_at: bet.updated_at.clone(),
+                    result: None,
+                });
+                transitions.push(Transition::BetPlaced {
+                    outcome_id,
+                    points: bet.points,
+                });
+            }
+        }
+        let existing = prediction.user_bet.as_mut().expect("bet was just set");
+        if let Some(result) = result.filter(|_| existing.result.is_none()) {
+            existing.result = Some(result.clone());
+            transitions.push(Transition::BetResolved(result));
+        }
+        Some(Update {
+            prediction,
+            transitions,
+        })
+    }
+
+    fn merge(&mut self, observation: Observation) -> Update<'_> {
+        let mut transitions = vec![];
+        let pending_bets = &mut self.pending_bets;
+        let prediction = self
+            .predictions
+            .entry(observation.id.clone())
+            .or_insert_with(|| {
+                transitions.push(Transition::Began);
+                TrackedPrediction {
+                    id: observation.id.clone(),
+                    broadcaster_id: observation.broadcaster_id.clone(),
+                    title: observation.title.clone(),
+                    state: PredictionState::Active,
+                    outcomes: vec![],
+                    winning_outcome_id: None,
+                    prediction_window: None,
+                    started_at: None,
+                    locks_at: None,
+                    locked_at: None,
+                    ended_at: None,
+                    user_bet: pending_bets.remove(&observation.id),
+                }
+            });
+
+        if observation.state.rank() < prediction.state.rank() {
+            // stale update, e.g. progress delivered after the lock
+            return Update {
+                prediction,
+                transitions,
+            };
+        }
+
+        prediction.title = observation.title;
+        prediction.prediction_window = observation
+            .prediction_window
+            .or(prediction.prediction_window);
+        prediction.started_at = observation.started_at.or(prediction.started_at.take());
+        prediction.locks_at = observation.locks_at.or(prediction.locks_at.take());
+        prediction.locked_at = observation.locked_at.or(prediction.locked_at.take());
+        prediction.ended_at = observation.ended_at.or(prediction.ended_at.take());
+
+        if !observation.outcomes.is_empty() && observation.outcomes != prediction.outcomes {
+            prediction.outcomes = observation.outcomes;
+            if transitions.is_empty() {
+                transitions.push(Transition::Updated);
+            }
+        }
+
+        if observation.state.rank() > prediction.state.rank() {
+            prediction.state = observation.state;
+            transitions.push(match observation.state {
+                PredictionState::Active => unreachable!("active is the initial state"),
+                PredictionState::Locked => Transition::Locked,
+                PredictionState::Resolved => {
+                    prediction.winning_outcome_id = observation.winning_outcome_id.clone();
+                    Transition::Resolved {
+                        winning_outcome_id: observation.winning_outcome_id,
+                    }
+                }
+                PredictionState::Canceled => Transition::Canceled,
+            });
+        }
+
+        Update {
+            prediction,
+            transitions,
+        }
+    }
+}
+
+impl From<&types::PredictionOutcome> for TrackedOutcome {
+    fn from(outcome: &types::PredictionOutcome) -> Self {
+        Self {
+            id: outcome.id.clone(),
+            title: outcome.title.clone(),
+            color: outcome.color.to_ascii_uppercase(),
+            users: outcome.users.unwrap_or_default(),
+            channel_points: outcome.channel_points.unwrap_or_default(),
+            top_predictors: outcome
+                .top_predictors
+                .iter()
+                .flatten()
+                .map(|predictor| TopPredictor {
+                    user_id: predictor.user_id.clone(),
+                    user_name: predictor.user_name.clone(),
+                    user_login: Some(predictor.user_login.clone()),
+                    channel_points_used: predictor.channel_points_used,
+                    channel_points_won: predictor.channel_points_won,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm")))]
+#[allow(deprecated)]
+impl From<&crate::pubsub::predictions::Outcome> for TrackedOutcome {
+    fn from(outcome: &crate::pubsub::predictions::Outcome) -> Self {
+        Self {
+            id: types::PredictionOutcomeId::new(outcome.id.clone()),
+            title: outcome.title.clone(),
+            color: outcome.color.to_ascii_uppercase(),
+            users: outcome.total_users,
+            channel_points: outcome.total_points,
+            top_predictors: outcome
+                .top_predictors
+                .iter()
+                .map(|predictor| TopPredictor {
+                    user_id: types::UserId::new(predictor.user_id.clone()),
+                    user_name: types::DisplayName::new(predictor.user_display_name.clone()),
+                    user_login: None,
+                    channel_points_used: predictor.points,
+                    channel_points_won: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcomes(yes: (i64, i64), no: (i64, i64)) -> serde_json::Value {
+        serde_json::json!([
+            {"id": "o1", "title": "Yeah!", "color": "blue", "users": yes.0, "channel_points": yes.1, "top_predictors": null},
+            {"id": "o2", "title": "No!", "color": "pink", "users": no.0, "channel_points": no.1, "top_predictors": null}
+        ])
+    }
+
+    fn payload<T: serde::de::DeserializeOwned>(extra: serde_json::Value) -> T {
+        let mut payload = serde_json::json!({
+            "id": "p1",
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cool_user",
+            "broadcaster_user_name": "Cool_User",
+            "title": "Aren’t shoes just really hard socks?",
+            "started_at": "2020-07-15T17:16:03.17106713Z",
+        });
+        payload
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(payload).unwrap()
+    }
+
+    #[test]
+    fn eventsub_lifecycle() {
+        let mut tracker = PredictionTracker::new();
+        let locks_at = "2020-07-15T17:21:03.17106713Z";
+
+        let update = tracker.apply_begin(&payload(serde_json::json!({
+            "locks_at": locks_at,
+            "outcomes": [
+                {"id": "o1", "title": "Yeah!", "color": "blue"},
+                {"id": "o2", "title": "No!", "color": "pink"}
+            ]
+        })));
+        assert_eq!(update.transitions, [Transition::Began]);
+        assert_eq!(update.prediction.total_points(), 0);
+
+        let progress: ChannelPredictionProgressV1Payload = payload(serde_json::json!({
+            "locks_at": locks_at,
+            "outcomes": outcomes((1, 500), (0, 0))
+        }));
+        let update = tracker.apply_progress(&progress);
+        assert_eq!(update.transitions, [Transition::Updated]);
+        assert_eq!(update.prediction.outcome("o1").unwrap().color, "BLUE");
+
+        let update = tracker.apply_lock(&payload(serde_json::json!({
+            "locked_at": locks_at,
+            "outcomes": outcomes((2, 700), (1, 300))
+        })));
+        assert_eq!(
+            update.transitions,
+            [Transition::Updated, Transition::Locked]
+        );
+
+        // progress delivered late doesn't roll the prediction back
+        let update = tracker.apply_progress(&progress);
+        assert!(update.transitions.is_empty());
+        assert_eq!(update.prediction.state, PredictionState::Locked);
+        assert_eq!(update.prediction.total_points(), 1000);
+
+        let update = tracker.apply_end(&payload(serde_json::json!({
+            "ended_at": "2020-07-15T17:26:03.17106713Z",
+            "status": "resolved",
+            "winning_outcome_id": "o2",
+            "outcomes": outcomes((2, 700), (1, 300))
+        })));
+        assert_eq!(
+            update.transitions,
+            [Transition::Resolved {
+                winning_outcome_id: Some(types::PredictionOutcomeId::from_static("o2"))
+            }]
+        );
+        assert_eq!(update.prediction.winning_outcome().unwrap().title, "No!");
+        assert_eq!(tracker.active("1337").count(), 0);
+    }
+
+    #[cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm")))]
+    #[test]
+    fn pubsub_bets() {
+        use crate::pubsub::predictions::{Event, PredictionsUserV1Reply};
+
+        let bet = |points: i64, result: serde_json::Value| -> PredictionsUserV1Reply {
+            serde_json::from_value(serde_json::json!({
+                "type": if result.is_null() { "prediction-made" } else { "prediction-result" },
+                "data": {
+                    "timestamp": "2021-01-01T00:03:00Z",
+                    "prediction": {
+                        "id": "b1",
+                        "event_id": "p1",
+                        "outcome_id": "o1",
+                        "channel_id": "1337",
+                        "points": points,
+                        "predicted_at": "2021-01-01T00:03:00Z",
+                        "updated_at": "2021-01-01T00:03:00Z",
+                        "user_id": "5678",
+                        "result": result,
+                        "user_display_name": null
+                    }
+                }
+            }))
+            .unwrap()
+        };
+        let event = |status: &str| -> Event {
+            serde_json::from_value(serde_json::json!({
+                "id": "p1",
+                "channel_id": "1337",
+                "created_at": "2021-01-01T00:00:00Z",
+                "ended_at": null,
+                "locked_at": null,
+                "outcomes": [{
+                    "id": "o1",
+                    "color": "BLUE",
+                    "title": "Yeah!",
+                    "total_points": 100,
+                    "total_users": 1,
+                    "top_predictors": [{
+                        "id": "b1",
+                        "event_id": "p1",
+                        "outcome_id": "o1",
+                        "channel_id": "1337",
+                        "points": 100,
+                        "predicted_at": "2021-01-01T00:03:00Z",
+                        "updated_at": "2021-01-01T00:03:00Z",
+                        "user_id": "5678",
+                        "user_display_name": "Viewer"
+                    }]
+                }],
+                "prediction_window_seconds": 300,
+                "status": status,
+                "title": "Aren’t shoes just really hard socks?",
+                "winning_outcome_id": if status == "RESOLVED" { Some("o1") } else { None }
+            }))
+            .unwrap()
+        };
+
+        let mut tracker = PredictionTracker::new();
+        // the bet arrives before the prediction
+        assert!(tracker
+            .apply_pubsub_bet(&bet(100, serde_json::Value::Null))
+            .is_none());
+        let update = tracker.apply_pubsub_event(&event("ACTIVE"));
+        assert_eq!(update.transitions, [Transition::Began]);
+        assert_eq!(update.prediction.user_bet.as_ref().unwrap().points, 100);
+        assert_eq!(update.prediction.prediction_window, Some(300));
+
+        let update = tracker
+            .apply_pubsub_bet(&bet(150, serde_json::Value::Null))
+            .unwrap();
+        assert_eq!(
+            update.transitions,
+            [Transition::BetPlaced {
+                outcome_id: types::PredictionOutcomeId::from_static("o1"),
+                points: 150
+            }]
+        );
+
+        let update = tracker.apply_pubsub_event(&event("RESOLVE_PENDING"));
+        assert_eq!(update.transitions, [Transition::Locked]);
+        let update = tracker.apply_pubsub_event(&event("RESOLVED"));
+        assert_eq!(
+            update.transitions,
+            [Transition::Resolved {
+                winning_outcome_id: Some(types::PredictionOutcomeId::from_static("o1"))
+            }]
+        );
+
+        let result =
+            serde_json::json!({"type": "WIN", "points_won": 300, "is_acknowledged": false});
+        let update = tracker.apply_pubsub_bet(&bet(150, result)).unwrap();
+        assert_eq!(
+            update.transitions,
+            [Transition::BetResolved(BetResult {
+                kind: "WIN".to_owned(),
+                points_won: Some(300)
+            })]
+        );
+    }
+}
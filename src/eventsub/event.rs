@@ -649,6 +649,229 @@ impl Event {
     }
 }
 
+impl Event {
+    /// Get the event type, version, message type and transport of this event
+    fn parts(&self) -> (EventType, &'static str, &'static str, &TransportResponse) {
+        macro_rules! match_event {
+            ($($(#[$meta:meta])* $module:ident::$event:ident);* $(;)?) => {{
+                match &self {
+                    $(
+                        $(#[$meta])*
+                        Event::$event(payload) => (
+                            payload.get_event_type(),
+                            payload.get_event_version(),
+                            payload.message_type(),
+                            &payload.subscription.transport,
+                        ),
+                    )*
+                }
+            }}
+        }
+
+        fill_events!(match_event())
+    }
+
+    /// The message type of this event, as sent in the `Twitch-Eventsub-Message-Type` header
+    pub fn message_type(&self) -> &'static str { self.parts().2 }
+
+    /// Serialize this event as the JSON body Twitch would send. See [`Payload::to_body`]
+    pub fn to_body(&self) -> Result<String, serde_json::Error> {
+        macro_rules! match_event {
+            ($($(#[$meta:meta])* $module:ident::$event:ident);* $(;)?) => {{
+                match &self {
+                    $(
+                        $(#[$meta])*
+                        Event::$event(payload) => payload.to_body(),
+                    )*
+                }
+            }}
+        }
+
+        fill_events!(match_event())
+    }
+
+    /// Render this event as the webhook request Twitch would send, the inverse of [`Event::parse_http`].
+    ///
+    /// The request is sent to the callback of the subscription transport, or `/` if the transport is not a webhook.
+    /// No `Twitch-Eventsub-Message-Signature` header is set, use [`Event::to_http_signed`] for that.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::convert::TryFrom;
+    /// use twitch_api::{eventsub::Event, types::Timestamp};
+    /// # let body = r#"{"subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","type":"channel.follow","version":"2","status":"enabled","cost":0,"condition":{"broadcaster_user_id":"1337","moderator_user_id":"1337"},"transport":{"method":"webhook","callback":"https://example.com/webhooks/callback"},"created_at":"2019-11-16T10:11:12.634234626Z"},"event":{"user_id":"1234","user_login":"cool_user","user_name":"Cool_User","broadcaster_user_id":"1337","broadcaster_user_login":"cooler_user","broadcaster_user_name":"Cooler_User","followed_at":"2020-07-15T18:16:11.17106713Z"}}"#;
+    /// let event = Event::parse(body)?;
+    /// let timestamp = Timestamp::try_from("2019-11-16T10:11:12.634234626Z")?;
+    /// let request =
+    ///     event.to_http("befa7b53-d79d-478f-86b9-120f112b044e", &timestamp)?;
+    /// assert_eq!(Event::parse_http(&request)?, event);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_http(
+        &self,
+        message_id: &str,
+        message_timestamp: &types::TimestampRef,
+    ) -> Result<http::Request<Vec<u8>>, PayloadSerializeError> {
+        let (event_type, version, message_type, transport) = self.parts();
+        let uri = match transport {
+            TransportResponse::Webhook(webhook) => webhook.callback.as_str(),
+            _ => "/",
+        };
+        Ok(http::Request::builder()
+            .method(http::Method::POST)
+            .uri(uri)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header("Twitch-Eventsub-Message-Id", message_id)
+            .header("Twitch-Eventsub-Message-Retry", "0")
+            .header("Twitch-Eventsub-Message-Type", message_type)
+            .header(
+                "Twitch-Eventsub-Message-Timestamp",
+                message_timestamp.as_str(),
+            )
+            .header("Twitch-Eventsub-Subscription-Type", event_type.to_str())
+            .header("Twitch-Eventsub-Subscription-Version", version)
+            .body(self.to_body()?.into_bytes())?)
+    }
+
+    /// Render this event as the webhook request Twitch would send, signed with `secret`.
+    ///
+    /// The returned request passes [`Event::verify_payload`] with the same secret.
+    #[cfg(feature = "hmac")]
+    #[cfg_attr(nightly, doc(cfg(feature = "hmac")))]
+    pub fn to_http_signed(
+        &self,
+        message_id: &str,
+        message_timestamp: &types::TimestampRef,
+        secret: &[u8],
+    ) -> Result<http::Request<Vec<u8>>, PayloadSerializeError> {
+        use crypto_hmac::{Hmac, Mac};
+        use std::fmt::Write;
+
+        let mut request = self.to_http(message_id, message_timestamp)?;
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret).expect("");
+        mac.update(message_id.as_bytes());
+        mac.update(message_timestamp.as_str().as_bytes());
+        mac.update(request.body());
+        let mut signature = String::from("sha256=");
+        for byte in mac.finalize().into_bytes() {
+            write!(signature, "{byte:02x}").expect("writing to a string can't fail");
+        }
+        request.headers_mut().insert(
+            "Twitch-Eventsub-Message-Signature",
+            http::HeaderValue::from_str(&signature).expect("signature is valid ascii"),
+        );
+        Ok(request)
+    }
+
+    /// Render this event as the websocket frame Twitch would send, the inverse of [`Event::parse_websocket`].
+    ///
+    /// Returns an error for webhook callback verification requests, as they are not sent over websockets.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::convert::TryFrom;
+    /// use twitch_api::{
+    ///     eventsub::{Event, EventsubWebsocketData},
+    ///     types::Timestamp,
+    /// };
+    /// # let body = r#"{"subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","type":"channel.follow","version":"2","status":"enabled","cost":0,"condition":{"broadcaster_user_id":"1337","moderator_user_id":"1337"},"transport":{"method":"websocket","session_id":"AQoQexAWVYKSTIu4ec_2VAxyuhAB"},"created_at":"2019-11-16T10:11:12.634234626Z"},"event":{"user_id":"1234","user_login":"cool_user","user_name":"Cool_User","broadcaster_user_id":"1337","broadcaster_user_login":"cooler_user","broadcaster_user_name":"Cooler_User","followed_at":"2020-07-15T18:16:11.17106713Z"}}"#;
+    /// let event = Event::parse(body)?;
+    /// let timestamp = Timestamp::try_from("2019-11-16T10:11:12.634234626Z")?;
+    /// let frame =
+    ///     event.to_websocket("befa7b53-d79d-478f-86b9-120f112b044e", &timestamp)?;
+    /// let EventsubWebsocketData::Notification { payload, .. } =
+    ///     Event::parse_websocket(&frame)?
+    /// else {
+    ///     unreachable!()
+    /// };
+    /// assert_eq!(payload, event);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_websocket(
+        &self,
+        message_id: &str,
+        message_timestamp: &types::TimestampRef,
+    ) -> Result<String, PayloadSerializeError> {
+        let (subscription_type, version, message_type, _) = self.parts();
+        let message_id = Cow::Borrowed(message_id);
+        let message_timestamp = Cow::Borrowed(message_timestamp);
+        let subscription_version = Cow::Borrowed(version);
+        let metadata = match message_type {
+            "notification" => EventsubWebsocketMetadata::Notification(NotificationMetadata {
+                message_id,
+                message_timestamp,
+                subscription_type,
+                subscription_version,
+            }),
+            "revocation" => EventsubWebsocketMetadata::Revocation(RevocationMetadata {
+                message_id,
+                message_timestamp,
+                subscription_type,
+                subscription_version,
+            }),
+            _ => return Err(PayloadSerializeError::VerificationRequestOverWebsocket),
+        };
+        let payload = serde_json::value::RawValue::from_string(self.to_body()?)?;
+        Ok(websocket::to_frame(&metadata, &payload)?)
+    }
+}
+
+/// An [`EventSubscription`] that has a corresponding variant in [`Event`]
+///
+/// This allows going between [`Event`] and the typed [`Payload`] generically.
+///
+/// # Examples
+///
+/// ```rust
+/// use twitch_api::eventsub::{self, Event, EventVariant};
+///
+/// fn is_follow(event: &Event) -> bool {
+///     eventsub::channel::ChannelFollowV2::from_event_ref(event).is_some()
+/// }
+/// ```
+pub trait EventVariant: EventSubscription + Sized {
+    /// Wrap the payload in its [`Event`] variant
+    fn into_event(payload: Payload<Self>) -> Event;
+    /// Get the payload out of the event, returning the event back if it's another variant
+    fn try_from_event(event: Event) -> Result<Payload<Self>, Event>;
+    /// Get a reference to the payload if the event is this variant
+    fn from_event_ref(event: &Event) -> Option<&Payload<Self>>;
+}
+
+macro_rules! impl_event_variant {
+    ($($(#[$meta:meta])* $module:ident::$event:ident);* $(;)?) => {
+        $(
+            $(#[$meta])*
+            #[allow(deprecated)]
+            impl EventVariant for $module::$event {
+                fn into_event(payload: Payload<Self>) -> Event { Event::$event(payload) }
+
+                fn try_from_event(event: Event) -> Result<Payload<Self>, Event> {
+                    match event {
+                        Event::$event(payload) => Ok(payload),
+                        event => Err(event),
+                    }
+                }
+
+                fn from_event_ref(event: &Event) -> Option<&Payload<Self>> {
+                    match event {
+                        Event::$event(payload) => Some(payload),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+fill_events!(impl_event_variant());
+
+impl<E: EventVariant> From<Payload<E>> for Event {
+    fn from(payload: Payload<E>) -> Self { E::into_event(payload) }
+}
+
 /// Helper function to get version and type of event from text.
 #[allow(clippy::type_complexity)]
 fn get_version_event_type_and_message_type_from_text(
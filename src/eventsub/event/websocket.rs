@@ -244,6 +244,35 @@ impl<'d> EventsubWebsocketData<'d> {
     }
 }
 
+impl EventsubWebsocketData<'_> {
+    /// Render this message as the websocket frame Twitch would send, the inverse of [`Event::parse_websocket`]
+    pub fn to_frame(&self) -> Result<String, super::PayloadSerializeError> {
+        let payload = match self {
+            EventsubWebsocketData::Welcome { payload, .. } => serde_json::to_string(payload)?,
+            EventsubWebsocketData::Keepalive { .. } => "{}".to_owned(),
+            EventsubWebsocketData::Notification { payload, .. }
+            | EventsubWebsocketData::Revocation { payload, .. } => payload.to_body()?,
+            EventsubWebsocketData::Reconnect { payload, .. } => serde_json::to_string(payload)?,
+        };
+        let payload = serde_json::value::RawValue::from_string(payload)?;
+        Ok(to_frame(&self.metadata(), &payload)?)
+    }
+}
+
+/// Serialize a websocket frame from its metadata and payload
+pub(crate) fn to_frame(
+    metadata: &EventsubWebsocketMetadata<'_>,
+    payload: &serde_json::value::RawValue,
+) -> Result<String, serde_json::Error> {
+    #[derive(Serialize)]
+    struct Frame<'a> {
+        metadata: &'a EventsubWebsocketMetadata<'a>,
+        payload: &'a serde_json::value::RawValue,
+    }
+
+    serde_json::to_string(&Frame { metadata, payload })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -424,4 +453,52 @@ mod tests {
             }
         ))
     }
+
+    #[test]
+    fn frame_roundtrip() {
+        let frames = [
+            r#"{"metadata":{"message_id":"96a3f3b5-5dec-4eed-908e-e11ee657416c","message_type":"session_welcome","message_timestamp":"2022-10-19T14:56:51.634234626Z"},"payload":{"session":{"id":"AQoQILE98gtqShGmLD7AM6yJThAB","status":"connected","connected_at":"2022-10-19T14:56:51.616329898Z","keepalive_timeout_seconds":10,"reconnect_url":null}}}"#,
+            r#"{"metadata":{"message_id":"84c1e79a-2a4b-4c13-ba0b-4312293e9308","message_type":"session_keepalive","message_timestamp":"2019-11-16T10:11:12.634234626Z"},"payload":{}}"#,
+            r#"{"metadata":{"message_id":"befa7b53-d79d-478f-86b9-120f112b044e","message_type":"notification","message_timestamp":"2019-11-16T10:11:12.123Z","subscription_type":"channel.follow","subscription_version":"1"},"payload":{"subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","status":"enabled","type":"channel.follow","version":"1","cost":1,"condition":{"broadcaster_user_id":"12826"},"transport":{"method":"websocket","session_id":"AQoQexAWVYKSTIu4ec_2VAxyuhAB"},"created_at":"2019-11-16T10:11:12.123Z"},"event":{"user_id":"1337","user_login":"awesome_user","user_name":"Awesome_User","broadcaster_user_id":"12826","broadcaster_user_login":"twitch","broadcaster_user_name":"Twitch","followed_at":"2020-07-15T18:16:11.17106713Z"}}}"#,
+            r#"{"metadata":{"message_id":"84c1e79a-2a4b-4c13-ba0b-4312293e9308","message_type":"session_reconnect","message_timestamp":"2019-11-18T09:10:11.634234626Z"},"payload":{"session":{"id":"AQoQexAWVYKSTIu4ec_2VAxyuhAB","status":"reconnecting","keepalive_timeout_seconds":null,"reconnect_url":"wss://eventsub.wss.twitch.tv?...","connected_at":"2019-11-16T10:11:12.634234626Z"}}}"#,
+            r#"{"metadata":{"message_id":"84c1e79a-2a4b-4c13-ba0b-4312293e9308","message_type":"revocation","message_timestamp":"2019-11-16T10:11:12.464757833Z","subscription_type":"channel.follow","subscription_version":"1"},"payload":{"subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","status":"authorization_revoked","type":"channel.follow","version":"1","cost":1,"condition":{"broadcaster_user_id":"12826"},"transport":{"method":"websocket","session_id":"AQoQexAWVYKSTIu4ec_2VAxyuhAB"},"created_at":"2019-11-16T10:11:12.464757833Z"}}}"#,
+        ];
+        for frame in frames {
+            let data = Event::parse_websocket(frame).unwrap();
+            let rendered = data.to_frame().unwrap();
+            let reparsed = Event::parse_websocket(&rendered).unwrap();
+            assert_eq!(data.metadata(), reparsed.metadata());
+            assert_eq!(rendered, reparsed.to_frame().unwrap());
+            match (&data, &reparsed) {
+                (
+                    EventsubWebsocketData::Notification { payload: a, .. },
+                    EventsubWebsocketData::Notification { payload: b, .. },
+                )
+                | (
+                    EventsubWebsocketData::Revocation { payload: a, .. },
+                    EventsubWebsocketData::Revocation { payload: b, .. },
+                ) => {
+                    assert_eq!(a, b);
+                    assert_eq!(
+                        a.to_websocket(&data.message_id(), &data.message_timestamp())
+                            .unwrap(),
+                        rendered
+                    );
+                }
+                (
+                    EventsubWebsocketData::Welcome { payload: a, .. },
+                    EventsubWebsocketData::Welcome { payload: b, .. },
+                ) => assert_eq!(a, b),
+                (
+                    EventsubWebsocketData::Reconnect { payload: a, .. },
+                    EventsubWebsocketData::Reconnect { payload: b, .. },
+                ) => assert_eq!(a, b),
+                (
+                    EventsubWebsocketData::Keepalive { .. },
+                    EventsubWebsocketData::Keepalive { .. },
+                ) => {}
+                _ => panic!("message type changed"),
+            }
+        }
+    }
 }
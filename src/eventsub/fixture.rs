@@ -0,0 +1,610 @@
+//! Generate EventSub notification payloads for tests
+//!
+//! [`FixtureGenerator`] produces notifications for every EventSub subscription type supported by this crate,
+//! similar to `twitch event trigger` in the Twitch CLI. Each fixture starts from a known-good payload
+//! and gets new values from a seeded random number generator, so the same seed always gives the same fixtures.
+//!
+//! Values are replaced consistently within a fixture:
+//!
+//! * ids, logins and display names are replaced, a user id that appears in both the condition and the event
+//!   gets the same new id and a display name follows its login,
+//! * all timestamps are shifted by the same amount,
+//! * amounts in the event, like bits, totals, votes, costs and charity amounts, are multiplied by the same factor,
+//!   so sums and progress towards a goal stay valid,
+//! * subscription tiers, suspicious user statuses, ban evasion evaluations and flags like `is_enabled` or `is_paused` are picked at random.
+//!
+//! Other fields are kept as in the template: messages, levels and months, enum values that other fields depend on,
+//! like the type of a chat notification, and which optional fields are set.
+//! Use [`Fixture::set`] or [`Fixture::modify`] to override specific fields.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use twitch_api::eventsub::{
+//!     channel::ChannelFollowV2, fixture::FixtureGenerator, Event, Message,
+//! };
+//!
+//! let mut generator = FixtureGenerator::new(42);
+//! let payload = generator
+//!     .fixture::<ChannelFollowV2>()
+//!     .set("/event/user_login", "justinfan")
+//!     .modify::<ChannelFollowV2>(|event| event.user_name = "JustinFan".into())?
+//!     .payload::<ChannelFollowV2>()?;
+//!
+//! let Message::Notification(event) = &payload.message else {
+//!     unreachable!()
+//! };
+//! assert_eq!(event.user_login.as_str(), "justinfan");
+//! assert_eq!(event.user_name.as_str(), "JustinFan");
+//!
+//! // The same seed gives the same fixture
+//! assert_eq!(
+//!     FixtureGenerator::new(7).fixture::<ChannelFollowV2>(),
+//!     FixtureGenerator::new(7).fixture::<ChannelFollowV2>(),
+//! );
+//!
+//! // Fixtures for all supported subscription types
+//! for fixture in FixtureGenerator::new(42).all() {
+//!     let event: Event = fixture.event()?;
+//!     # let _ = event;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use serde_json::{Map, Value};
+
+use super::{
+    automod, channel, conduit, stream, user, Event, EventSubscription, EventType, Payload,
+    PayloadParseError, Status, TransportResponse,
+};
+
+/// Templates for all supported subscription types, keyed by `type/version`
+static TEMPLATES: Lazy<Map<String, Value>> = Lazy::new(|| {
+    serde_json::from_str(include_str!("fixture/templates.json"))
+        .expect("fixture templates are valid json")
+});
+
+/// Errors from [`FixtureGenerator`] and [`Fixture`]
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+#[non_exhaustive]
+pub enum FixtureError {
+    /// no fixture for `{0}` version `{1}`
+    UnknownEvent(EventType, String),
+    /// fixture is for `{found}` version `{found_version}`, not `{expected}` version `{expected_version}`
+    Mismatch {
+        /// The subscription type that was asked for
+        expected: EventType,
+        /// The subscription version that was asked for
+        expected_version: &'static str,
+        /// The subscription type of the fixture
+        found: EventType,
+        /// The subscription version of the fixture
+        found_version: String,
+    },
+    /// could not (de)serialize fixture
+    SerializeError(#[from] serde_json::Error),
+    /// could not parse fixture
+    ParseError(#[from] PayloadParseError),
+}
+
+/// Seeded generator of EventSub notification [fixtures](Fixture)
+///
+/// See the [module documentation](self) for more.
+#[derive(Clone, Debug)]
+pub struct FixtureGenerator {
+    rng: crate::SplitMix64,
+}
+
+impl FixtureGenerator {
+    /// Create a new generator. The same seed always generates the same fixtures, in the same order.
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            rng: crate::SplitMix64::new(seed),
+        }
+    }
+
+    /// Generate a notification for the subscription type `E`
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no template for `E`, this can only happen for subscription types that are not in [`Event`].
+    pub fn fixture<E: EventSubscription>(&mut self) -> Fixture {
+        self.fixture_for(E::EVENT_TYPE, E::VERSION)
+            .expect("all subscription types have a fixture")
+    }
+
+    /// Generate a notification for the subscription type and version
+    pub fn fixture_for(
+        &mut self,
+        event_type: EventType,
+        version: &str,
+    ) -> Result<Fixture, FixtureError> {
+        let template = TEMPLATES
+            .get(&format!("{}/{}", event_type, version))
+            .ok_or_else(|| FixtureError::UnknownEvent(event_type, version.to_owned()))?;
+        let mut json = template.clone();
+        let mut randomizer = Randomizer::new(self);
+        randomizer.randomize(None, &mut json);
+        if let Some(event) = json.get_mut("event") {
+            randomizer.randomize_values(None, event);
+        }
+        Ok(Fixture {
+            event_type,
+            version: version.to_owned(),
+            json,
+        })
+    }
+
+    /// Generate a notification for every subscription type in [`Event`]
+    #[allow(deprecated)]
+    pub fn all(&mut self) -> Vec<Fixture> {
+        macro_rules! all_fixtures {
+            ($($(#[$meta:meta])* $module:ident::$event:ident);* $(;)?) => {{
+                let mut fixtures = vec![];
+                $(
+                    $(#[$meta])*
+                    fixtures.push(self.fixture::<$module::$event>());
+                )*
+                fixtures
+            }};
+        }
+        fill_events!(all_fixtures())
+    }
+
+    fn below(&mut self, n: u64) -> u64 { self.rng.next_u64() % n }
+}
+
+/// A generated EventSub notification
+///
+/// The fixture is kept as JSON, in the same shape as a webhook notification body: `{"subscription": {..}, "event": {..}}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fixture {
+    event_type: EventType,
+    version: String,
+    json: Value,
+}
+
+impl Fixture {
+    /// Subscription type of this fixture
+    pub const fn event_type(&self) -> EventType { self.event_type }
+
+    /// Subscription version of this fixture
+    pub fn version(&self) -> &str { &self.version }
+
+    /// The fixture as JSON
+    pub const fn json(&self) -> &Value { &self.json }
+
+    /// Set the value at a [JSON pointer](https://datatracker.ietf.org/doc/html/rfc6901), e.g. `/event/user_login`.
+    ///
+    /// Missing object keys are inserted, `null`s along the way are replaced with objects.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pointer goes through something that is not an object, or an array index that doesn't exist.
+    pub fn set(mut self, pointer: &str, value: impl Into<Value>) -> Self {
+        let mut target = &mut self.json;
+        for token in pointer.split('/').skip(1) {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            if target.is_null() {
+                *target = Value::Object(Map::new());
+            }
+            target = match target {
+                Value::Object(map) => map.entry(token).or_insert(Value::Null),
+                Value::Array(array) => token
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| array.get_mut(index))
+                    .unwrap_or_else(|| panic!("no index `{}` in `{}`", token, pointer)),
+                _ => panic!("`{}` is not an object or array in `{}`", token, pointer),
+            };
+        }
+        *target = value.into();
+        self
+    }
+
+    /// Set a field on the event
+    pub fn event_field(self, field: &str, value: impl Into<Value>) -> Self {
+        let pointer = format!("/event/{}", field.replace('~', "~0").replace('/', "~1"));
+        self.set(&pointer, value)
+    }
+
+    /// Set the condition of the subscription
+    pub fn condition<E: EventSubscription>(self, condition: &E) -> Result<Self, FixtureError> {
+        self.check::<E>()?;
+        let condition = condition.condition()?;
+        Ok(self.set("/subscription/condition", condition))
+    }
+
+    /// Set the status of the subscription
+    pub fn status(self, status: Status) -> Result<Self, FixtureError> {
+        let status = serde_json::to_value(status)?;
+        Ok(self.set("/subscription/status", status))
+    }
+
+    /// Set the transport of the subscription
+    pub fn transport(self, transport: TransportResponse) -> Result<Self, FixtureError> {
+        let transport = serde_json::to_value(transport)?;
+        Ok(self.set("/subscription/transport", transport))
+    }
+
+    /// Change the event through its typed payload
+    pub fn modify<E: EventSubscription>(
+        mut self,
+        f: impl FnOnce(&mut E::Payload),
+    ) -> Result<Self, FixtureError> {
+        self.check::<E>()?;
+        let event = self.json["event"].take();
+        let mut event: E::Payload = serde_json::from_value(event)?;
+        f(&mut event);
+        self.json["event"] = serde_json::to_value(event)?;
+        Ok(self)
+    }
+
+    /// The fixture as a typed notification [`Payload`]
+    pub fn payload<E: EventSubscription>(&self) -> Result<Payload<E>, FixtureError> {
+        self.check::<E>()?;
+        Ok(Payload::parse_notification(&self.to_body())?)
+    }
+
+    /// The fixture as an [`Event`]
+    pub fn event(&self) -> Result<Event, FixtureError> { Ok(Event::parse(&self.to_body())?) }
+
+    /// The fixture as a webhook notification body
+    pub fn to_body(&self) -> String { self.json.to_string() }
+
+    fn check<E: EventSubscription>(&self) -> Result<(), FixtureError> {
+        if E::EVENT_TYPE == self.event_type && E::VERSION == self.version {
+            Ok(())
+        } else {
+            Err(FixtureError::Mismatch {
+                expected: E::EVENT_TYPE,
+                expected_version: E::VERSION,
+                found: self.event_type,
+                found_version: self.version.clone(),
+            })
+        }
+    }
+}
+
+/// Fields in events that are amounts, scaled together so that sums and goals stay valid
+const AMOUNTS: &[&str] = &[
+    "bits",
+    "bits_votes",
+    "channel_points",
+    "channel_points_used",
+    "channel_points_votes",
+    "channel_points_won",
+    "cost",
+    "cumulative_total",
+    "current_amount",
+    "goal",
+    "progress",
+    "redemptions_redeemed_current_stream",
+    "target_amount",
+    "total",
+    "users",
+    "value",
+    "viewer_count",
+    "viewers",
+    "votes",
+];
+/// Fields in events that can be either value
+const FLAGS: &[&str] = &[
+    "is_enabled",
+    "is_in_stock",
+    "is_mature",
+    "is_paused",
+    "should_redemptions_skip_request_queue",
+];
+/// Fields in events with a known set of values, only replaced if the template has one of them.
+/// All fields in a group get the same value.
+const CHOICES: &[(&[&str], &[&str])] = &[
+    (&["tier", "sub_tier"], &["1000", "2000", "3000"]),
+    (&["low_trust_status"], &["active_monitoring", "restricted"]),
+    (
+        &["ban_evasion_evaluation"],
+        &["unknown", "possible", "likely"],
+    ),
+];
+
+const ADJECTIVES: &[&str] = &[
+    "quiet", "brave", "lucky", "sleepy", "rapid", "gentle", "cosmic", "fuzzy", "silent", "golden",
+    "mighty", "sneaky", "happy", "clever", "rusty", "shiny",
+];
+const NOUNS: &[&str] = &[
+    "otter", "falcon", "panda", "comet", "badger", "pixel", "walrus", "lynx", "gecko", "raven",
+    "koala", "marmot", "turtle", "beaver", "cobra", "heron",
+];
+
+/// Replaces ids, logins, timestamps and values in a template
+struct Randomizer<'a> {
+    rng: &'a mut FixtureGenerator,
+    ids: HashMap<String, String>,
+    logins: HashMap<String, String>,
+    /// Seconds to shift all timestamps with
+    offset: i64,
+    /// Factor to multiply all amounts with
+    scale: u64,
+    /// Choices already made, by index in [`CHOICES`]
+    choices: HashMap<usize, &'static str>,
+}
+
+impl<'a> Randomizer<'a> {
+    fn new(rng: &'a mut FixtureGenerator) -> Self {
+        // up to a year back or forward
+        let offset = rng.below(2 * 365 * 86_400) as i64 - 365 * 86_400;
+        let scale = 1 + rng.below(10);
+        Self {
+            rng,
+            ids: HashMap::new(),
+            logins: HashMap::new(),
+            offset,
+            scale,
+            choices: HashMap::new(),
+        }
+    }
+
+    /// Randomize amounts, flags and choices in an event
+    fn randomize_values(&mut self, key: Option<&str>, value: &mut Value) {
+        let key = key.unwrap_or_default();
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    self.randomize_values(Some(key.as_str()), value)
+                }
+            }
+            Value::Array(array) => {
+                for value in array {
+                    self.randomize_values(Some(key), value)
+                }
+            }
+            Value::Number(n) if AMOUNTS.contains(&key) => {
+                if let Some(amount) = n.as_u64().and_then(|n| n.checked_mul(self.scale)) {
+                    *n = amount.into();
+                }
+            }
+            Value::Bool(b) if FLAGS.contains(&key) => *b = self.rng.below(2) == 1,
+            Value::String(s) => {
+                if let Some(choice) = self.choice(key, s.as_str()) {
+                    *s = choice.to_owned();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn choice(&mut self, key: &str, s: &str) -> Option<&'static str> {
+        let (group, (_, choices)) = CHOICES
+            .iter()
+            .enumerate()
+            .find(|(_, (fields, choices))| fields.contains(&key) && choices.contains(&s))?;
+        if let Some(&choice) = self.choices.get(&group) {
+            return Some(choice);
+        }
+        let choice = choices[self.rng.below(choices.len() as u64) as usize];
+        self.choices.insert(group, choice);
+        Some(choice)
+    }
+
+    fn randomize(&mut self, key: Option<&str>, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    self.randomize(Some(key.as_str()), value)
+                }
+            }
+            Value::Array(array) => {
+                for value in array {
+                    self.randomize(key, value)
+                }
+            }
+            Value::String(s) => {
+                if let Some(new) = self.replace(key.unwrap_or_default(), s) {
+                    *s = new;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn replace(&mut self, key: &str, s: &str) -> Option<String> {
+        if is_uuid(s) {
+            Some(self.id(s, |rng| rng.rng.uuid()))
+        } else if (key.ends_with("id") || key.ends_with("ids"))
+            && !s.is_empty()
+            && s.bytes().all(|b| b.is_ascii_digit())
+        {
+            let len = s.len();
+            Some(self.id(s, |rng| {
+                let mut id = (1 + rng.below(9)).to_string();
+                for _ in 1..len {
+                    id.push(char::from(b'0' + rng.below(10) as u8));
+                }
+                id
+            }))
+        } else if key == "login" || key.ends_with("_login") {
+            Some(self.login(s))
+        } else if (key == "display_name" || key.ends_with("user_name"))
+            && !s.is_empty()
+            && s.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+        {
+            let login = self.login(s);
+            if s.starts_with(|c: char| c.is_ascii_uppercase()) {
+                let (first, rest) = login.split_at(1);
+                Some(first.to_ascii_uppercase() + rest)
+            } else {
+                Some(login)
+            }
+        } else {
+            shift_timestamp(s, self.offset)
+        }
+    }
+
+    fn id(&mut self, s: &str, generate: impl FnOnce(&mut FixtureGenerator) -> String) -> String {
+        if let Some(id) = self.ids.get(s) {
+            return id.clone();
+        }
+        let id = generate(&mut *self.rng);
+        self.ids.insert(s.to_owned(), id.clone());
+        id
+    }
+
+    fn login(&mut self, s: &str) -> String {
+        let key = s.to_ascii_lowercase();
+        if let Some(login) = self.logins.get(&key) {
+            return login.clone();
+        }
+        let login = format!(
+            "{}{}{}",
+            ADJECTIVES[self.rng.below(ADJECTIVES.len() as u64) as usize],
+            NOUNS[self.rng.below(NOUNS.len() as u64) as usize],
+            self.rng.below(1000),
+        );
+        self.logins.insert(key, login.clone());
+        login
+    }
+}
+
+fn is_uuid(s: &str) -> bool {
+    s.len() == 36
+        && s.bytes().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
+}
+
+/// Shift a `YYYY-MM-DDTHH:MM:SS[.fraction]Z` timestamp by `offset` seconds
+fn shift_timestamp(s: &str, offset: i64) -> Option<String> {
+    let millis = crate::timestamp_to_unix_millis(s)?;
+    Some(
+        crate::unix_millis_to_timestamp(millis + offset * 1_000)
+            .as_str()
+            .to_owned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventsub::Message;
+    use std::collections::HashSet;
+
+    #[test]
+    #[allow(deprecated)]
+    fn all_fixtures_parse() {
+        macro_rules! check {
+            ($($(#[$meta:meta])* $module:ident::$event:ident);* $(;)?) => {$(
+                $(#[$meta])*
+                {
+                    let mut generator = FixtureGenerator::new(0);
+                    for _ in 0..8 {
+                        let fixture = generator.fixture::<$module::$event>();
+                        if let Err(e) = fixture.payload::<$module::$event>() {
+                            panic!("{}: {}\n{:#}", stringify!($event), e, fixture.json());
+                        }
+                        assert!(matches!(fixture.event(), Ok(Event::$event(_))));
+                    }
+                }
+            )*};
+        }
+        fill_events!(check());
+
+        for fixture in FixtureGenerator::new(1).all() {
+            fixture.event().unwrap();
+        }
+    }
+
+    #[test]
+    fn seeded_and_consistent() {
+        let a = FixtureGenerator::new(3).all();
+        assert_eq!(a, FixtureGenerator::new(3).all());
+        assert_ne!(a, FixtureGenerator::new(4).all());
+
+        let mut generator = FixtureGenerator::new(5);
+        let first = generator.fixture::<channel::ChannelRaidV1>();
+        assert_ne!(first, generator.fixture::<channel::ChannelRaidV1>());
+
+        let payload = first.payload::<channel::ChannelRaidV1>().unwrap();
+        let Message::Notification(event) = &payload.message else {
+            panic!("not a notification")
+        };
+        // the condition and event refer to the same broadcaster
+        assert_eq!(
+            payload.subscription.condition.to_broadcaster_user_id,
+            Some(event.to_broadcaster_user_id.clone())
+        );
+        assert_eq!(
+            event.to_broadcaster_user_login.as_str(),
+            event.to_broadcaster_user_name.as_str().to_ascii_lowercase()
+        );
+    }
+
+    #[test]
+    fn values() {
+        let mut generator = FixtureGenerator::new(11);
+        let (mut goals, mut tiers) = (HashSet::new(), HashSet::new());
+        for _ in 0..16 {
+            let fixture = generator.fixture::<channel::ChannelCharityCampaignProgressV1>();
+            let amount = |field: &str| fixture.json()["event"][field]["value"].as_u64().unwrap();
+            assert_eq!(
+                amount("target_amount") * 260_000,
+                amount("current_amount") * 1_500_000
+            );
+
+            let fixture = generator.fixture::<channel::ChannelHypeTrainProgressV1>();
+            let event = &fixture.json()["event"];
+            assert!(event["progress"].as_u64() <= event["goal"].as_u64());
+            goals.insert(event["goal"].as_u64().unwrap());
+
+            let fixture = generator.fixture::<channel::ChannelSubscriptionGiftV1>();
+            tiers.insert(fixture.json()["event"]["tier"].as_str().unwrap().to_owned());
+        }
+        assert!(goals.len() > 1);
+        assert!(tiers.len() > 1);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn overrides() {
+        let mut generator = FixtureGenerator::new(9);
+        let fixture = generator
+            .fixture::<channel::ChannelFollowV2>()
+            .event_field("user_id", "1234")
+            .status(Status::AuthorizationRevoked)
+            .unwrap()
+            .modify::<channel::ChannelFollowV2>(|event| event.user_name = "Foo".into())
+            .unwrap();
+        let payload = fixture.payload::<channel::ChannelFollowV2>().unwrap();
+        assert_eq!(payload.subscription.status, Status::AuthorizationRevoked);
+        let Message::Notification(event) = &payload.message else {
+            panic!("not a notification")
+        };
+        assert_eq!(event.user_id.as_str(), "1234");
+        assert_eq!(event.user_name.as_str(), "Foo");
+
+        assert!(matches!(
+            fixture.payload::<channel::ChannelFollowV1>(),
+            Err(FixtureError::Mismatch { .. })
+        ));
+        assert!(generator
+            .fixture_for(EventType::ChannelFollow, "99")
+            .is_err());
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(
+            shift_timestamp("2024-02-28T23:59:59.123Z", 2).as_deref(),
+            Some("2024-02-29T00:00:01.123Z")
+        );
+        assert_eq!(
+            shift_timestamp("2024-01-01T00:00:00Z", -1).as_deref(),
+            Some("2023-12-31T23:59:59.000Z")
+        );
+        assert_eq!(shift_timestamp("not a timestamp at all", 1), None);
+    }
+}
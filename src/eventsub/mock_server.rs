@@ -0,0 +1,1134 @@
+//! In-process mock EventSub WebSocket server
+//!
+//! Start a [`MockServer`] in your tests and point your websocket client at [`MockServer::url`],
+//! either directly or through the `TWITCH_EVENTSUB_WEBSOCKET_URL` environment variable with the `mock_api` feature enabled.
+//!
+//! [`TWITCH_EVENTSUB_WEBSOCKET_URL`](crate::TWITCH_EVENTSUB_WEBSOCKET_URL) reads the variable only once, the first time it's used.
+//! To go through it, pick the server address up front with [`MockServerConfig::addr`]
+//! and set the variable before anything connects, see the second example below.
+//!
+//! The server behaves like the Twitch EventSub WebSocket server:
+//!
+//! * every connection receives a `session_welcome` message with a new session id,
+//! * `session_keepalive` messages are sent when no other message was sent for a while,
+//! * subscriptions are created through [`MockServer::helix`], a [`HttpClient`](crate::HttpClient) that implements
+//!   the [Create](crate::helix::eventsub::create_eventsub_subscription), [Get](crate::helix::eventsub::get_eventsub_subscriptions)
+//!   and [Delete](crate::helix::eventsub::delete_eventsub_subscription) EventSub Subscription endpoints,
+//! * notifications are injected with [`MockServer::notify`],
+//! * [`MockServer::reconnect`] sends a `session_reconnect` message, and the session (with its subscriptions) is moved to the new connection,
+//! * [`MockServer::close`] closes a connection with a Twitch [close code](CloseCode),
+//! * clients that send messages are disconnected with [`CloseCode::ClientSentInboundTraffic`].
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! # let token = twitch_oauth2::UserToken::from_existing(&twitch_api::client::DummyHttpClient, twitch_oauth2::AccessToken::new("token".to_string()), None, None).await?;
+//! use twitch_api::{
+//!     eventsub::{self, mock_server::MockServer},
+//!     helix::HelixClient,
+//! };
+//!
+//! let server = MockServer::start().await?;
+//! let client = HelixClient::with_client(server.helix());
+//!
+//! // connect your websocket client to `server.url()`, then wait for it
+//! let session_id = server.next_session().await.expect("server is running");
+//!
+//! let condition = eventsub::stream::StreamOnlineV1::broadcaster_user_id("1337");
+//! client
+//!     .create_eventsub_subscription(
+//!         condition.clone(),
+//!         eventsub::Transport::websocket(&session_id),
+//!         &token,
+//!     )
+//!     .await?;
+//!
+//! let payload: eventsub::stream::StreamOnlineV1Payload = serde_json::from_str(
+//!     r#"{
+//!         "id": "9001",
+//!         "broadcaster_user_id": "1337",
+//!         "broadcaster_user_login": "cool_user",
+//!         "broadcaster_user_name": "Cool_User",
+//!         "type": "live",
+//!         "started_at": "2020-10-11T10:11:12.123Z"
+//!     }"#,
+//! )?;
+//! server.notify(&condition, payload)?;
+//! # Ok(()) }
+//! ```
+//!
+//! Connecting through `TWITCH_EVENTSUB_WEBSOCKET_URL`, with the `mock_api` feature enabled
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use twitch_api::eventsub::mock_server::MockServer;
+//!
+//! // must happen before `twitch_api::TWITCH_EVENTSUB_WEBSOCKET_URL` is first used
+//! std::env::set_var("TWITCH_EVENTSUB_WEBSOCKET_URL", "ws://127.0.0.1:18422/ws");
+//! let server = MockServer::config()
+//!     .addr(([127, 0, 0, 1], 18422))
+//!     .start()
+//!     .await?;
+//! assert_eq!(server.url(), &*twitch_api::TWITCH_EVENTSUB_WEBSOCKET_URL);
+//!
+//! let (_socket, _) =
+//!     tokio_tungstenite::connect_async(twitch_api::TWITCH_EVENTSUB_WEBSOCKET_URL.as_str()).await?;
+//! let _session_id = server.next_session().await.expect("server is running");
+//! # Ok(()) }
+//! ```
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request as WsRequest, Response as WsResponse},
+    protocol::{frame::coding::CloseCode as WsCloseCode, CloseFrame},
+    Message as WsMessage,
+};
+
+use super::{
+    EventSubSubscription, EventSubscription, EventSubscriptionInformation, EventType, EventVariant,
+    EventsubWebsocketData, KeepaliveMetadata, Message, Payload, PayloadSerializeError,
+    ReconnectMetadata, ReconnectPayload, SessionData, Status, TransportResponse,
+    WebsocketTransportResponse, WelcomeMetadata, WelcomePayload,
+};
+use crate::{
+    client::{BoxedFuture, Bytes, Request, Response},
+    types,
+};
+
+/// [Close codes](https://dev.twitch.tv/docs/eventsub/handling-websocket-events/#close-message) sent by the EventSub WebSocket server
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CloseCode {
+    /// Indicates a problem with the server
+    InternalServerError,
+    /// Sending outgoing messages to the server is prohibited with the exception of pong messages.
+    ClientSentInboundTraffic,
+    /// You must respond to ping messages with a pong message.
+    ClientFailedPingPong,
+    /// When you connect to the server, you must create a subscription within 10 seconds or the connection is closed.
+    ConnectionUnused,
+    /// When you receive a `session_reconnect` message, you have 30 seconds to reconnect to the server and close the old connection.
+    ReconnectGraceTimeExpired,
+    /// Transient network timeout.
+    NetworkTimeout,
+    /// Transient network error.
+    NetworkError,
+    /// The reconnect URL is invalid.
+    InvalidReconnect,
+}
+
+impl CloseCode {
+    /// The numeric close code
+    pub const fn code(self) -> u16 {
+        match self {
+            CloseCode::InternalServerError => 4000,
+            CloseCode::ClientSentInboundTraffic => 4001,
+            CloseCode::ClientFailedPingPong => 4002,
+            CloseCode::ConnectionUnused => 4003,
+            CloseCode::ReconnectGraceTimeExpired => 4004,
+            CloseCode::NetworkTimeout => 4005,
+            CloseCode::NetworkError => 4006,
+            CloseCode::InvalidReconnect => 4007,
+        }
+    }
+
+    /// The close reason Twitch sends
+    pub const fn reason(self) -> &'static str {
+        match self {
+            CloseCode::InternalServerError => "internal server error",
+            CloseCode::ClientSentInboundTraffic => {
+                "sending outgoing messages to the server is prohibited"
+            }
+            CloseCode::ClientFailedPingPong => "failed ping pong",
+            CloseCode::ConnectionUnused => "connection unused",
+            CloseCode::ReconnectGraceTimeExpired => "client failed to reconnect",
+            CloseCode::NetworkTimeout => "network timeout",
+            CloseCode::NetworkError => "network error",
+            CloseCode::InvalidReconnect => "invalid reconnect attempt",
+        }
+    }
+
+    /// The status subscriptions on the closed session get
+    const fn status(self) -> Status {
+        match self {
+            CloseCode::InternalServerError => Status::WebsocketInternalError,
+            CloseCode::ClientSentInboundTraffic => Status::WebsocketReceivedInboundTraffic,
+            CloseCode::ClientFailedPingPong => Status::WebsocketFailedPingPong,
+            CloseCode::ConnectionUnused => Status::WebsocketConnectionUnused,
+            CloseCode::ReconnectGraceTimeExpired | CloseCode::InvalidReconnect => {
+                Status::WebsocketFailedToReconnect
+            }
+            CloseCode::NetworkTimeout => Status::WebsocketNetworkTimeout,
+            CloseCode::NetworkError => Status::WebsocketNetworkError,
+        }
+    }
+}
+
+/// Errors from the [`MockServer`]
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+#[non_exhaustive]
+pub enum MockServerError {
+    /// session `{0}` is not connected
+    NoSession(String),
+    /// subscription `{0}` does not exist
+    NoSubscription(String),
+    /// subscription `{0}` is not on a connected session
+    NotConnected(String),
+    /// no enabled subscription matches the condition
+    NoMatchingSubscription,
+    /// could not serialize message
+    SerializeError(#[from] PayloadSerializeError),
+    /// could not serialize condition
+    ConditionError(#[from] serde_json::Error),
+}
+
+/// Configuration for a [`MockServer`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct MockServerConfig {
+    /// The address to listen on. Defaults to `127.0.0.1:0`, a random local port
+    pub addr: SocketAddr,
+    /// The `keepalive_timeout_seconds` sent in the welcome message. Defaults to `10`
+    pub keepalive_timeout_seconds: i64,
+    /// Send keepalive messages after this long without any other message, `None` disables keepalives. Defaults to 7 seconds
+    pub keepalive_interval: Option<Duration>,
+    /// How long a client has to reconnect after a `session_reconnect` message. Defaults to 30 seconds
+    pub reconnect_grace: Duration,
+    /// The `max_total_cost` returned by the mock helix endpoint. Defaults to `10`
+    pub max_total_cost: usize,
+}
+
+impl Default for MockServerConfig {
+    fn default() -> Self {
+        Self {
+            addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+            keepalive_timeout_seconds: 10,
+            keepalive_interval: Some(Duration::from_secs(7)),
+            reconnect_grace: Duration::from_secs(30),
+            max_total_cost: 10,
+        }
+    }
+}
+
+impl MockServerConfig {
+    /// Set the address to listen on, use port `0` for a random port
+    pub fn addr(mut self, addr: impl Into<SocketAddr>) -> Self {
+        self.addr = addr.into();
+        self
+    }
+
+    /// Set the `keepalive_timeout_seconds` sent in the welcome message
+    pub fn keepalive_timeout_seconds(mut self, seconds: i64) -> Self {
+        self.keepalive_timeout_seconds = seconds;
+        self
+    }
+
+    /// Set how long to wait before sending a keepalive message, `None` disables keepalives
+    pub fn keepalive_interval(mut self, interval: impl Into<Option<Duration>>) -> Self {
+        self.keepalive_interval = interval.into();
+        self
+    }
+
+    /// Set how long a client has to reconnect after a `session_reconnect` message
+    pub fn reconnect_grace(mut self, grace: Duration) -> Self {
+        self.reconnect_grace = grace;
+        self
+    }
+
+    /// Set the `max_total_cost` returned by the mock helix endpoint
+    pub fn max_total_cost(mut self, max_total_cost: usize) -> Self {
+        self.max_total_cost = max_total_cost;
+        self
+    }
+
+    /// Start a server with this configuration
+    pub async fn start(self) -> std::io::Result<MockServer> { MockServer::start_with(self).await }
+}
+
+enum Command {
+    Frame(String),
+    /// Close the connection, `None` when the session moved to another connection
+    Close(Option<CloseCode>),
+}
+
+struct Session {
+    tx: mpsc::UnboundedSender<Command>,
+    connected_at: types::Timestamp,
+}
+
+struct State {
+    sessions: HashMap<String, Session>,
+    subscriptions: Vec<EventSubSubscription>,
+}
+
+struct Shared {
+    config: MockServerConfig,
+    url: url::Url,
+    rng: Mutex<crate::SplitMix64>,
+    state: Mutex<State>,
+    new_sessions: mpsc::UnboundedSender<String>,
+}
+
+/// An in-process mock of the EventSub WebSocket server.
+///
+/// The server is stopped when dropped. See the [module documentation](self) for an example.
+pub struct MockServer {
+    shared: Arc<Shared>,
+    new_sessions: tokio::sync::Mutex<mpsc::UnboundedReceiver<String>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl std::fmt::Debug for MockServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockServer")
+            .field("url", &self.shared.url)
+            .field("config", &self.shared.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        let state = self.shared.state.lock().expect("poisoned");
+        for session in state.sessions.values() {
+            let _ = session.tx.send(Command::Close(None));
+        }
+    }
+}
+
+impl MockServer {
+    /// Start a server with the default [configuration](MockServerConfig) on a random local port
+    pub async fn start() -> std::io::Result<Self> { Self::start_with(Default::default()).await }
+
+    /// Create a configuration to start a server with
+    pub fn config() -> MockServerConfig { MockServerConfig::default() }
+
+    /// Start a server with the given configuration, listening on [`MockServerConfig::addr`]
+    pub async fn start_with(config: MockServerConfig) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(config.addr).await?;
+        let addr = listener.local_addr()?;
+        let url = url::Url::parse(&format!("ws://{addr}/ws")).expect("url should be valid");
+        let (new_sessions_tx, new_sessions) = mpsc::unbounded_channel();
+        let seed = crate::now_unix_millis() as u64;
+        let shared = Arc::new(Shared {
+            config,
+            url,
+            rng: Mutex::new(crate::SplitMix64::new(seed ^ u64::from(addr.port()))),
+            state: Mutex::new(State {
+                sessions: HashMap::new(),
+                subscriptions: vec![],
+            }),
+            new_sessions: new_sessions_tx,
+        });
+
+        let task = tokio::spawn({
+            let shared = shared.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(handle_connection(shared.clone(), stream));
+                }
+            }
+        });
+
+        Ok(Self {
+            shared,
+            new_sessions: tokio::sync::Mutex::new(new_sessions),
+            task,
+        })
+    }
+
+    /// The websocket url to connect to
+    pub fn url(&self) -> &url::Url { &self.shared.url }
+
+    /// A [`HttpClient`](crate::HttpClient) that serves the EventSub subscription endpoints of this server.
+    ///
+    /// Use it with [`HelixClient::with_client`](crate::HelixClient::with_client).
+    pub fn helix(&self) -> MockHelix {
+        MockHelix {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Wait for the next welcomed session and return its id.
+    ///
+    /// Reconnected sessions keep their id and are returned again.
+    pub async fn next_session(&self) -> Option<String> {
+        self.new_sessions.lock().await.recv().await
+    }
+
+    /// Ids of all connected sessions
+    pub fn sessions(&self) -> Vec<String> {
+        self.shared
+            .state
+            .lock()
+            .expect("poisoned")
+            .sessions
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// All subscriptions, including disabled ones
+    pub fn subscriptions(&self) -> Vec<EventSubSubscription> {
+        self.shared
+            .state
+            .lock()
+            .expect("poisoned")
+            .subscriptions
+            .clone()
+    }
+
+    /// Send a notification to every connected session with an enabled subscription on this condition.
+    ///
+    /// Returns the number of sessions the notification was sent to.
+    pub fn notify<E>(&self, condition: &E, event: E::Payload) -> Result<usize, MockServerError>
+    where E: EventVariant {
+        let condition_value = super::reconcile::normalize_condition(&condition.condition()?);
+        let state = self.shared.state.lock().expect("poisoned");
+        let mut sent = 0;
+        for subscription in state.subscriptions.iter().filter(|s| {
+            s.status == Status::Enabled
+                && s.type_ == E::EVENT_TYPE
+                && s.version == E::VERSION
+                && super::reconcile::normalize_condition(&s.condition) == condition_value
+        }) {
+            let Some(session) = session_of(&state, subscription) else {
+                continue;
+            };
+            let payload = Payload::<E> {
+                subscription: EventSubscriptionInformation {
+                    id: subscription.id.clone(),
+                    status: subscription.status.clone(),
+                    cost: subscription.cost,
+                    condition: condition.clone(),
+                    created_at: subscription.created_at.clone(),
+                    transport: notification_transport(&subscription.transport),
+                    type_: E::EVENT_TYPE,
+                    version: E::VERSION.to_owned(),
+                },
+                message: Message::Notification(event.clone()),
+            };
+            let frame = E::into_event(payload).to_websocket(&self.shared.message_id(), &now())?;
+            if session.tx.send(Command::Frame(frame)).is_ok() {
+                sent += 1;
+            }
+        }
+        if sent == 0 {
+            return Err(MockServerError::NoMatchingSubscription);
+        }
+        Ok(sent)
+    }
+
+    /// Send a raw text frame to a session
+    pub fn send_raw(
+        &self,
+        session_id: &str,
+        frame: impl Into<String>,
+    ) -> Result<(), MockServerError> {
+        self.send(session_id, Command::Frame(frame.into()))
+    }
+
+    /// Revoke a subscription, sending a `revocation` message to its session.
+    ///
+    /// The subscription is only revoked if its session is connected.
+    pub fn revoke(&self, subscription_id: &str, status: Status) -> Result<(), MockServerError> {
+        let mut state = self.shared.state.lock().expect("poisoned");
+        let index = state
+            .subscriptions
+            .iter()
+            .position(|s| s.id.as_str() == subscription_id)
+            .ok_or_else(|| MockServerError::NoSubscription(subscription_id.to_owned()))?;
+        let tx = session_of(&state, &state.subscriptions[index])
+            .ok_or_else(|| MockServerError::NotConnected(subscription_id.to_owned()))?
+            .tx
+            .clone();
+        let mut revoked = state.subscriptions[index].clone();
+        revoked.status = status.clone();
+        revoked.transport = notification_transport(&revoked.transport);
+        let body = serde_json::json!({ "subscription": revoked }).to_string();
+        let event = super::Event::parse(&body)
+            .map_err(|_| MockServerError::NoSubscription(subscription_id.to_owned()))?;
+        let frame = event.to_websocket(&self.shared.message_id(), &now())?;
+        state.subscriptions[index].status = status;
+        let _ = tx.send(Command::Frame(frame));
+        Ok(())
+    }
+
+    /// Send a `session_reconnect` message to a session.
+    ///
+    /// The client should connect to the `reconnect_url` in the message, the session and its subscriptions then move to the new connection.
+    /// If the client doesn't reconnect within the [grace period](MockServerConfig::reconnect_grace),
+    /// the connection is closed with [`CloseCode::ReconnectGraceTimeExpired`].
+    pub fn reconnect(&self, session_id: &str) -> Result<(), MockServerError> {
+        let mut reconnect_url = self.shared.url.clone();
+        reconnect_url
+            .query_pairs_mut()
+            .append_pair("reconnect", session_id);
+        let tx = {
+            let state = self.shared.state.lock().expect("poisoned");
+            let session = state
+                .sessions
+                .get(session_id)
+                .ok_or_else(|| MockServerError::NoSession(session_id.to_owned()))?;
+            let message_id = self.shared.message_id();
+            let message_timestamp = now();
+            let frame = EventsubWebsocketData::Reconnect {
+                metadata: ReconnectMetadata {
+                    message_id: message_id.into(),
+                    message_timestamp: Cow::Owned(message_timestamp),
+                },
+                payload: ReconnectPayload {
+                    session: SessionData {
+                        id: session_id.into(),
+                        status: "reconnecting".into(),
+                        keepalive_timeout_seconds: None,
+                        reconnect_url: Some(reconnect_url.as_str().into()),
+                        connected_at: Cow::Owned(session.connected_at.clone()),
+                        recovery_url: None,
+                    },
+                },
+            }
+            .to_frame()?;
+            let _ = session.tx.send(Command::Frame(frame));
+            session.tx.clone()
+        };
+
+        let shared = self.shared.clone();
+        let session_id = session_id.to_owned();
+        tokio::spawn(async move {
+            tokio::time::sleep(shared.config.reconnect_grace).await;
+            let still_connected = shared
+                .state
+                .lock()
+                .expect("poisoned")
+                .sessions
+                .get(&session_id)
+                .map_or(false, |s| s.tx.same_channel(&tx));
+            if still_connected {
+                let _ = tx.send(Command::Close(Some(CloseCode::ReconnectGraceTimeExpired)));
+            }
+        });
+        Ok(())
+    }
+
+    /// Close a session with a close code
+    pub fn close(&self, session_id: &str, code: CloseCode) -> Result<(), MockServerError> {
+        self.send(session_id, Command::Close(Some(code)))
+    }
+
+    fn send(&self, session_id: &str, command: Command) -> Result<(), MockServerError> {
+        let state = self.shared.state.lock().expect("poisoned");
+        state
+            .sessions
+            .get(session_id)
+            .and_then(|s| s.tx.send(command).ok())
+            .ok_or_else(|| MockServerError::NoSession(session_id.to_owned()))
+    }
+}
+
+impl Shared {
+    /// A random UUID (v4)
+    fn message_id(&self) -> String { self.rng.lock().expect("poisoned").uuid() }
+
+    fn session_id(&self) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+        let mut rng = self.rng.lock().expect("poisoned");
+        let mut id = String::from("AQoQ");
+        for _ in 0..24 {
+            id.push(ALPHABET[(rng.next_u64() % 64) as usize] as char);
+        }
+        id
+    }
+
+    /// Register a connection, returning the session id and the connection it replaces
+    #[allow(clippy::type_complexity)]
+    fn register(
+        &self,
+        reconnect: Option<String>,
+        tx: mpsc::UnboundedSender<Command>,
+    ) -> Result<
+        (
+            String,
+            types::Timestamp,
+            Option<mpsc::UnboundedSender<Command>>,
+        ),
+        CloseCode,
+    > {
+        let mut state = self.state.lock().expect("poisoned");
+        let connected_at = now();
+        match reconnect {
+            Some(id) => {
+                let session = state
+                    .sessions
+                    .get_mut(&id)
+                    .ok_or(CloseCode::InvalidReconnect)?;
+                let previous = std::mem::replace(&mut session.tx, tx);
+                session.connected_at = connected_at.clone();
+                Ok((id, connected_at, Some(previous)))
+            }
+            None => {
+                let id = self.session_id();
+                state.sessions.insert(
+                    id.clone(),
+                    Session {
+                        tx,
+                        connected_at: connected_at.clone(),
+                    },
+                );
+                Ok((id, connected_at, None))
+            }
+        }
+    }
+
+    /// Remove a connection if it still owns the session
+    fn disconnect(&self, session_id: &str, tx: &mpsc::UnboundedSender<Command>, status: Status) {
+        let mut state = self.state.lock().expect("poisoned");
+        if !state
+            .sessions
+            .get(session_id)
+            .map_or(false, |s| s.tx.same_channel(tx))
+        {
+            return;
+        }
+        state.sessions.remove(session_id);
+        let disconnected_at = now();
+        for subscription in &mut state.subscriptions {
+            if let TransportResponse::Websocket(transport) = &mut subscription.transport {
+                if transport.session_id == session_id && subscription.status == Status::Enabled {
+                    subscription.status = status.clone();
+                    transport.disconnected_at = Some(disconnected_at.clone());
+                }
+            }
+        }
+    }
+}
+
+async fn handle_connection(shared: Arc<Shared>, stream: TcpStream) {
+    let mut query = None;
+    let ws = match tokio_tungstenite::accept_hdr_async(
+        stream,
+        |request: &WsRequest, response: WsResponse| -> Result<WsResponse, ErrorResponse> {
+            query = request.uri().query().map(str::to_owned);
+            Ok(response)
+        },
+    )
+    .await
+    {
+        Ok(ws) => ws,
+        Err(_) => return,
+    };
+    let (mut sink, mut stream) = ws.split();
+
+    let reconnect = query.and_then(|q| {
+        url::form_urlencoded::parse(q.as_bytes())
+            .find(|(k, _)| k == "reconnect")
+            .map(|(_, v)| v.into_owned())
+    });
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (session_id, connected_at, previous) = match shared.register(reconnect, tx.clone()) {
+        Ok(registered) => registered,
+        Err(code) => {
+            let _ = sink.send(close_message(code)).await;
+            return;
+        }
+    };
+
+    let welcome = EventsubWebsocketData::Welcome {
+        metadata: WelcomeMetadata {
+            message_id: shared.message_id().into(),
+            message_timestamp: Cow::Owned(now()),
+        },
+        payload: WelcomePayload {
+            session: SessionData {
+                id: session_id.as_str().into(),
+                status: "connected".into(),
+                keepalive_timeout_seconds: Some(shared.config.keepalive_timeout_seconds),
+                reconnect_url: None,
+                connected_at: Cow::Owned(connected_at),
+                recovery_url: None,
+            },
+        },
+    }
+    .to_frame()
+    .expect("welcome message should serialize");
+    if sink.send(WsMessage::Text(welcome.into())).await.is_err() {
+        shared.disconnect(&session_id, &tx, Status::WebsocketNetworkError);
+        return;
+    }
+    if let Some(previous) = previous {
+        let _ = previous.send(Command::Close(None));
+    }
+    let _ = shared.new_sessions.send(session_id.clone());
+
+    let keepalive_interval = shared.config.keepalive_interval;
+    let keepalive = tokio::time::sleep(keepalive_interval.unwrap_or(Duration::MAX));
+    tokio::pin!(keepalive);
+
+    let status = loop {
+        tokio::select! {
+            command = rx.recv() => match command {
+                Some(Command::Frame(frame)) => {
+                    if sink.send(WsMessage::Text(frame.into())).await.is_err() {
+                        break Status::WebsocketNetworkError;
+                    }
+                    if let Some(interval) = keepalive_interval {
+                        keepalive.as_mut().reset(tokio::time::Instant::now() + interval);
+                    }
+                }
+                Some(Command::Close(Some(code))) => {
+                    let _ = sink.send(close_message(code)).await;
+                    break code.status();
+                }
+                Some(Command::Close(None)) | None => {
+                    let _ = sink.send(WsMessage::Close(None)).await;
+                    break Status::WebsocketDisconnected;
+                }
+            },
+            _ = &mut keepalive, if keepalive_interval.is_some() => {
+                let frame = EventsubWebsocketData::Keepalive {
+                    metadata: KeepaliveMetadata {
+                        message_id: shared.message_id().into(),
+                        message_timestamp: Cow::Owned(now()),
+                    },
+                    payload: (),
+                }
+                .to_frame()
+                .expect("keepalive message should serialize");
+                if sink.send(WsMessage::Text(frame.into())).await.is_err() {
+                    break Status::WebsocketNetworkError;
+                }
+                if let Some(interval) = keepalive_interval {
+                    keepalive.as_mut().reset(tokio::time::Instant::now() + interval);
+                }
+            },
+            message = stream.next() => match message {
+                Some(Ok(WsMessage::Ping(_) | WsMessage::Pong(_) | WsMessage::Frame(_))) => {
+                    // tungstenite queues the pong, make sure it's sent
+                    let _ = sink.flush().await;
+                }
+                Some(Ok(WsMessage::Close(_))) | None => break Status::WebsocketDisconnected,
+                Some(Err(_)) => break Status::WebsocketNetworkError,
+                Some(Ok(_)) => {
+                    let code = CloseCode::ClientSentInboundTraffic;
+                    let _ = sink.send(close_message(code)).await;
+                    break code.status();
+                }
+            },
+        }
+    };
+    shared.disconnect(&session_id, &tx, status);
+}
+
+fn close_message(code: CloseCode) -> WsMessage {
+    WsMessage::Close(Some(CloseFrame {
+        code: WsCloseCode::from(code.code()),
+        reason: code.reason().into(),
+    }))
+}
+
+fn session_of<'a>(state: &'a State, subscription: &EventSubSubscription) -> Option<&'a Session> {
+    match &subscription.transport {
+        TransportResponse::Websocket(transport) => state.sessions.get(&transport.session_id),
+        _ => None,
+    }
+}
+
+/// Notifications only contain the session id in the transport
+fn notification_transport(transport: &TransportResponse) -> TransportResponse {
+    match transport {
+        TransportResponse::Websocket(transport) => {
+            TransportResponse::Websocket(WebsocketTransportResponse {
+                session_id: transport.session_id.clone(),
+                connected_at: None,
+                disconnected_at: None,
+            })
+        }
+        other => other.clone(),
+    }
+}
+
+/// The current time as a [`Timestamp`](types::Timestamp)
+fn now() -> types::Timestamp { crate::unix_millis_to_timestamp(crate::now_unix_millis()) }
+
+/// A [`HttpClient`](crate::HttpClient) serving the EventSub subscription endpoints of a [`MockServer`]
+///
+/// Only websocket transports are accepted. Requests to other endpoints get a `404 Not Found` response.
+#[derive(Clone)]
+pub struct MockHelix {
+    shared: Arc<Shared>,
+}
+
+impl std::fmt::Debug for MockHelix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockHelix")
+            .field("url", &self.shared.url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl crate::HttpClient for MockHelix {
+    type Error = std::convert::Infallible;
+
+    fn req(&self, request: Request) -> BoxedFuture<'_, Result<Response, Self::Error>> {
+        let response = self.handle(request);
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+impl MockHelix {
+    fn handle(&self, request: Request) -> Response {
+        if !request.uri().path().ends_with("eventsub/subscriptions") {
+            return error_response(http::StatusCode::NOT_FOUND, "not found");
+        }
+        let query = request
+            .uri()
+            .query()
+            .map(|q| {
+                url::form_urlencoded::parse(q.as_bytes())
+                    .into_owned()
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default();
+        match *request.method() {
+            http::Method::POST => self.create(request.body()),
+            http::Method::GET => self.get(&query),
+            http::Method::DELETE => self.delete(&query),
+            _ => error_response(http::StatusCode::METHOD_NOT_ALLOWED, "method not allowed"),
+        }
+    }
+
+    fn create(&self, body: &[u8]) -> Response {
+        #[derive(serde_derive::Deserialize)]
+        struct Body {
+            #[serde(rename = "type")]
+            type_: EventType,
+            version: String,
+            condition: serde_json::Value,
+            transport: serde_json::Value,
+        }
+
+        let body: Body = match serde_json::from_slice(body) {
+            Ok(body) => body,
+            Err(e) => return error_response(http::StatusCode::BAD_REQUEST, &e.to_string()),
+        };
+        if body.transport.get("method").and_then(|m| m.as_str()) != Some("websocket") {
+            return error_response(
+                http::StatusCode::BAD_REQUEST,
+                "the mock server only supports the websocket transport",
+            );
+        }
+        let Some(session_id) = body.transport.get("session_id").and_then(|s| s.as_str()) else {
+            return error_response(
+                http::StatusCode::BAD_REQUEST,
+                "missing transport.session_id",
+            );
+        };
+
+        let mut state = self.shared.state.lock().expect("poisoned");
+        let Some(connected_at) = state
+            .sessions
+            .get(session_id)
+            .map(|s| s.connected_at.clone())
+        else {
+            return error_response(
+                http::StatusCode::BAD_REQUEST,
+                "websocket transport session does not exist or has already disconnected",
+            );
+        };
+        let condition = super::reconcile::normalize_condition(&body.condition);
+        let exists = state.subscriptions.iter().any(|s| {
+            s.status == Status::Enabled
+                && s.type_ == body.type_
+                && s.version == body.version
+                && super::reconcile::normalize_condition(&s.condition) == condition
+                && matches!(&s.transport, TransportResponse::Websocket(t) if t.session_id == session_id)
+        });
+        if exists {
+            return error_response(http::StatusCode::CONFLICT, "subscription already exists");
+        }
+
+        let subscription = EventSubSubscription {
+            cost: 0,
+            condition: body.condition,
+            created_at: now(),
+            id: types::EventSubId::new(self.shared.message_id()),
+            status: Status::Enabled,
+            transport: TransportResponse::Websocket(WebsocketTransportResponse {
+                session_id: session_id.to_owned(),
+                connected_at: Some(connected_at),
+                disconnected_at: None,
+            }),
+            type_: body.type_,
+            version: body.version,
+        };
+        state.subscriptions.push(subscription.clone());
+        json_response(
+            http::StatusCode::ACCEPTED,
+            serde_json::json!({
+                "data": [subscription],
+                "total": state.subscriptions.len(),
+                "total_cost": total_cost(&state),
+                "max_total_cost": self.shared.config.max_total_cost,
+            }),
+        )
+    }
+
+    fn get(&self, query: &HashMap<String, String>) -> Response {
+        let state = self.shared.state.lock().expect("poisoned");
+        let status = query.get("status").map(|s| serde_json::json!(s));
+        let type_ = query.get("type").map(|s| serde_json::json!(s));
+        let data = state
+            .subscriptions
+            .iter()
+            .filter(|s| {
+                let s = serde_json::to_value(s).unwrap_or_default();
+                status
+                    .as_ref()
+                    .map_or(true, |status| &s["status"] == status)
+                    && type_.as_ref().map_or(true, |type_| &s["type"] == type_)
+            })
+            .collect::<Vec<_>>();
+        json_response(
+            http::StatusCode::OK,
+            serde_json::json!({
+                "data": data,
+                "total": state.subscriptions.len(),
+                "total_cost": total_cost(&state),
+                "max_total_cost": self.shared.config.max_total_cost,
+                "pagination": {},
+            }),
+        )
+    }
+
+    fn delete(&self, query: &HashMap<String, String>) -> Response {
+        let Some(id) = query.get("id") else {
+            return error_response(http::StatusCode::BAD_REQUEST, "missing id");
+        };
+        let mut state = self.shared.state.lock().expect("poisoned");
+        let len = state.subscriptions.len();
+        state.subscriptions.retain(|s| s.id.as_str() != id);
+        if state.subscriptions.len() == len {
+            return error_response(http::StatusCode::NOT_FOUND, "subscription not found");
+        }
+        http::Response::builder()
+            .status(http::StatusCode::NO_CONTENT)
+            .body(Bytes::new())
+            .expect("response should be valid")
+    }
+}
+
+fn total_cost(state: &State) -> usize {
+    state
+        .subscriptions
+        .iter()
+        .filter(|s| s.status == Status::Enabled)
+        .map(|s| s.cost)
+        .sum()
+}
+
+fn json_response(status: http::StatusCode, body: serde_json::Value) -> Response {
+    http::Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(Bytes::from(body.to_string()))
+        .expect("response should be valid")
+}
+
+fn error_response(status: http::StatusCode, message: &str) -> Response {
+    json_response(
+        status,
+        serde_json::json!({
+            "error": status.canonical_reason().unwrap_or_default(),
+            "status": status.as_u16(),
+            "message": message,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventsub::{stream, Event};
+
+    type Client = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    async fn next_frame(client: &mut Client) -> String {
+        loop {
+            match client.next().await.unwrap().unwrap() {
+                WsMessage::Text(text) => return text.to_string(),
+                WsMessage::Ping(_) | WsMessage::Pong(_) => continue,
+                other => panic!("unexpected message: {other:?}"),
+            }
+        }
+    }
+
+    async fn subscribe(server: &MockServer, session_id: &str) {
+        let request = http::Request::builder()
+            .method(http::Method::POST)
+            .uri("https://api.twitch.tv/helix/eventsub/subscriptions")
+            .body(Bytes::from(
+                serde_json::json!({
+                    "type": "stream.online",
+                    "version": "1",
+                    "condition": {"broadcaster_user_id": "1337"},
+                    "transport": {"method": "websocket", "session_id": session_id},
+                })
+                .to_string(),
+            ))
+            .unwrap();
+        let response = crate::HttpClient::req(&server.helix(), request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), http::StatusCode::ACCEPTED);
+    }
+
+    fn online() -> stream::StreamOnlineV1Payload {
+        serde_json::from_str(
+            r#"{
+                "id": "9001",
+                "broadcaster_user_id": "1337",
+                "broadcaster_user_login": "cool_user",
+                "broadcaster_user_name": "Cool_User",
+                "type": "live",
+                "started_at": "2020-10-11T10:11:12.123Z"
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn welcome_subscribe_notify() {
+        let server = MockServer::config()
+            .keepalive_interval(Duration::from_millis(50))
+            .start()
+            .await
+            .unwrap();
+        let (mut client, _) = tokio_tungstenite::connect_async(server.url().as_str())
+            .await
+            .unwrap();
+
+        let welcome = next_frame(&mut client).await;
+        let EventsubWebsocketData::Welcome { payload, .. } =
+            Event::parse_websocket(&welcome).unwrap()
+        else {
+            panic!("expected welcome")
+        };
+        let session_id = payload.session.id.into_owned();
+        assert_eq!(
+            server.next_session().await.as_deref(),
+            Some(session_id.as_str())
+        );
+
+        let keepalive = next_frame(&mut client).await;
+        assert!(matches!(
+            Event::parse_websocket(&keepalive).unwrap(),
+            EventsubWebsocketData::Keepalive { .. }
+        ));
+
+        let condition = stream::StreamOnlineV1::broadcaster_user_id("1337");
+        assert!(matches!(
+            server.notify(&condition, online()),
+            Err(MockServerError::NoMatchingSubscription)
+        ));
+        subscribe(&server, &session_id).await;
+        assert_eq!(server.subscriptions().len(), 1);
+        assert_eq!(server.notify(&condition, online()).unwrap(), 1);
+
+        let notification = loop {
+            let frame = next_frame(&mut client).await;
+            match Event::parse_websocket(&frame).unwrap() {
+                EventsubWebsocketData::Keepalive { .. } => continue,
+                EventsubWebsocketData::Notification { payload, .. } => break payload,
+                _ => panic!("expected notification"),
+            }
+        };
+        let Event::StreamOnlineV1(payload) = notification else {
+            panic!("expected stream.online")
+        };
+        assert_eq!(payload.message, Message::Notification(online()));
+    }
+
+    #[tokio::test]
+    async fn reconnect_and_close() {
+        let server = MockServer::config()
+            .keepalive_interval(None)
+            .start()
+            .await
+            .unwrap();
+        let (mut client, _) = tokio_tungstenite::connect_async(server.url().as_str())
+            .await
+            .unwrap();
+        let _welcome = next_frame(&mut client).await;
+        let session_id = server.next_session().await.unwrap();
+        subscribe(&server, &session_id).await;
+
+        server.reconnect(&session_id).unwrap();
+        let EventsubWebsocketData::Reconnect { payload, .. } =
+            Event::parse_websocket(&next_frame(&mut client).await).unwrap()
+        else {
+            panic!("expected reconnect")
+        };
+        let reconnect_url = payload.session.reconnect_url.unwrap().into_owned();
+
+        let (mut new_client, _) = tokio_tungstenite::connect_async(reconnect_url.as_str())
+            .await
+            .unwrap();
+        let _welcome = next_frame(&mut new_client).await;
+        assert_eq!(server.next_session().await.unwrap(), session_id);
+        // the old connection is closed
+        assert!(matches!(
+            client.next().await,
+            Some(Ok(WsMessage::Close(_))) | None
+        ));
+
+        // subscriptions moved with the session
+        let condition = stream::StreamOnlineV1::broadcaster_user_id("1337");
+        assert_eq!(server.notify(&condition, online()).unwrap(), 1);
+        let _notification = next_frame(&mut new_client).await;
+
+        server
+            .close(&session_id, CloseCode::NetworkTimeout)
+            .unwrap();
+        let Some(Ok(WsMessage::Close(Some(frame)))) = new_client.next().await else {
+            panic!("expected close frame")
+        };
+        assert_eq!(u16::from(frame.code), 4005);
+        // wait for the server to process the disconnect
+        while !server.sessions().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            server.subscriptions()[0].status,
+            Status::WebsocketNetworkTimeout
+        );
+
+        // a subscription without a connected session can't be revoked
+        let subscription_id = server.subscriptions()[0].id.clone();
+        assert!(matches!(
+            server.revoke(subscription_id.as_str(), Status::AuthorizationRevoked),
+            Err(MockServerError::NotConnected(_))
+        ));
+        assert_eq!(
+            server.subscriptions()[0].status,
+            Status::WebsocketNetworkTimeout
+        );
+    }
+}
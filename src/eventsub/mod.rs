@@ -223,12 +223,29 @@ use crate::parse_json;
 pub mod automod;
 pub mod channel;
 pub mod conduit;
+#[macro_use]
 pub mod event;
+#[cfg(any(feature = "mock", test))]
+#[cfg_attr(nightly, doc(cfg(feature = "mock")))]
+pub mod fixture;
+#[cfg(feature = "mock_server")]
+#[cfg_attr(nightly, doc(cfg(feature = "mock_server")))]
+pub mod mock_server;
+#[cfg(all(feature = "helix", feature = "client"))]
+#[cfg_attr(nightly, doc(cfg(all(feature = "helix", feature = "client"))))]
+pub mod raid_coordinator;
+#[cfg(all(feature = "helix", feature = "client"))]
+#[cfg_attr(nightly, doc(cfg(all(feature = "helix", feature = "client"))))]
+pub mod raid_protection;
+#[cfg(all(feature = "helix", feature = "client"))]
+#[cfg_attr(nightly, doc(cfg(all(feature = "helix", feature = "client"))))]
+pub mod reconcile;
+pub mod router;
 pub mod stream;
 pub mod user;
 
 #[doc(inline)]
-pub use event::{Event, EventType};
+pub use event::{Event, EventType, EventVariant};
 
 pub use event::websocket::*;
 
@@ -518,6 +535,58 @@ impl<E: EventSubscription> Payload<E> {
     }
 }
 
+impl<E: EventSubscription> Payload<E> {
+    /// The message type of this payload, as sent in the `Twitch-Eventsub-Message-Type` header
+    pub const fn message_type(&self) -> &'static str {
+        match self.message {
+            Message::VerificationRequest(..) => "webhook_callback_verification",
+            Message::Revocation() => "revocation",
+            Message::Notification(..) => "notification",
+        }
+    }
+
+    /// Serialize this payload as the JSON body Twitch would send.
+    ///
+    /// This is the inverse of [`Payload::parse_request_str`] with [`Payload::message_type`]
+    pub fn to_body(&self) -> Result<String, serde_json::Error> {
+        #[derive(Serialize)]
+        struct Body<'a, E: EventSubscription> {
+            #[serde(bound = "E: EventSubscription")]
+            subscription: &'a EventSubscriptionInformation<E>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            challenge: Option<&'a str>,
+            #[serde(bound = "E: EventSubscription")]
+            #[serde(skip_serializing_if = "Option::is_none")]
+            event: Option<&'a <E as EventSubscription>::Payload>,
+        }
+
+        let (challenge, event) = match &self.message {
+            Message::VerificationRequest(VerificationRequest { challenge }) => {
+                (Some(challenge.as_str()), None)
+            }
+            Message::Revocation() => (None, None),
+            Message::Notification(event) => (None, Some(event)),
+        };
+        serde_json::to_string(&Body {
+            subscription: &self.subscription,
+            challenge,
+            event,
+        })
+    }
+}
+
+/// Errors that can happen when serializing a payload
+#[derive(thiserror::Error, displaydoc::Display, Debug)]
+#[non_exhaustive]
+pub enum PayloadSerializeError {
+    /// could not serialize payload
+    SerializeError(#[from] serde_json::Error),
+    /// could not construct http request
+    HttpError(#[from] http::Error),
+    /// webhook callback verification requests are not sent over websockets
+    VerificationRequestOverWebsocket,
+}
+
 /// Errors that can happen when parsing payload
 #[derive(thiserror::Error, displaydoc::Display, Debug)]
 #[non_exhaustive]
@@ -1373,4 +1442,56 @@ mod test {
         let _payload = dbg!(crate::eventsub::Event::parse_http(&request).unwrap());
         assert!(crate::eventsub::Event::verify_payload(&request, secret));
     }
+
+    #[test]
+    fn http_roundtrip() {
+        use crate::eventsub::Event;
+        use std::convert::TryFrom;
+
+        let bodies = [
+            r#"{"challenge":"pogchamp-kappa-360noscope-vohiyo","subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","status":"webhook_callback_verification_pending","type":"channel.follow","version":"1","cost":1,"condition":{"broadcaster_user_id":"12826"},"transport":{"method":"webhook","callback":"https://example.com/webhooks/callback"},"created_at":"2019-11-16T10:11:12.123Z"}}"#,
+            r#"{"subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","status":"authorization_revoked","type":"channel.follow","cost":1,"version":"1","condition":{"broadcaster_user_id":"12826"},"transport":{"method":"webhook","callback":"https://example.com/webhooks/callback"},"created_at":"2019-11-16T10:11:12.123Z"}}"#,
+            r#"{"subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","type":"channel.follow","version":"2","status":"enabled","cost":0,"condition":{"broadcaster_user_id":"1337","moderator_user_id":"1337"},"transport":{"method":"webhook","callback":"https://example.com/webhooks/callback"},"created_at":"2019-11-16T10:11:12.634234626Z"},"event":{"user_id":"1234","user_login":"cool_user","user_name":"Cool_User","broadcaster_user_id":"1337","broadcaster_user_login":"cooler_user","broadcaster_user_name":"Cooler_User","followed_at":"2020-07-15T18:16:11.17106713Z"}}"#,
+        ];
+        let timestamp = types::Timestamp::try_from("2019-11-16T10:11:12.123Z").unwrap();
+        for body in bodies {
+            let event = Event::parse(body).unwrap();
+            let request = event
+                .to_http("e76c6bd4-55c9-4987-8304-da1588d8988b", &timestamp)
+                .unwrap();
+            assert_eq!(request.uri(), "https://example.com/webhooks/callback");
+            assert_eq!(
+                request.headers()["Twitch-Eventsub-Message-Type"],
+                event.message_type()
+            );
+            assert_eq!(Event::parse_http(&request).unwrap(), event);
+        }
+
+        let verification = Event::parse(bodies[0]).unwrap();
+        assert!(matches!(
+            verification.to_websocket("e76c6bd4-55c9-4987-8304-da1588d8988b", &timestamp),
+            Err(crate::eventsub::PayloadSerializeError::VerificationRequestOverWebsocket)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "hmac")]
+    fn sign_request() {
+        use crate::eventsub::Event;
+        use std::convert::TryFrom;
+
+        let body = r#"{"challenge":"11535768-497e-14ec-8197-ba2cb5341a01","subscription":{"id":"8d8fa82b-9792-79da-4e11-a6fa58a7a582","status":"webhook_callback_verification_pending","type":"channel.subscribe","version":"1","condition":{"broadcaster_user_id":"88525095"},"transport":{"method":"webhook","callback":"http://localhost:80/twitch/eventsub"},"created_at":"2022-02-06T04:03:24.2706497Z","cost":0}}"#;
+        let event = Event::parse(body).unwrap();
+        let timestamp = types::Timestamp::try_from("2022-02-06T04:03:24.2726598Z").unwrap();
+        let request = event
+            .to_http_signed(
+                "8d8fa82b-9792-79da-4e11-a6fa58a7a582",
+                &timestamp,
+                b"HELLOabc2321",
+            )
+            .unwrap();
+        assert!(Event::verify_payload(&request, b"HELLOabc2321"));
+        assert!(!Event::verify_payload(&request, b"wrongsecret"));
+        assert_eq!(Event::parse_http(&request).unwrap(), event);
+    }
 }
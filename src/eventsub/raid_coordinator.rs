@@ -0,0 +1,546 @@
+//! Start a raid and follow it until it arrives or is canceled
+//!
+//! A raid goes through a countdown before the viewers are moved to the target channel.
+//! [`RaidCoordinator`] starts the raid with [Start a raid](crate::helix::raids::start_a_raid), then follows it through
+//!
+//! * the `channel.moderate` subscription on the source channel, which reports the raid and its cancellation,
+//! * with features `pubsub` and `unsupported` or `tpm`, the `raid` topic on the source channel, which reports the countdown, viewer count, go and cancellation,
+//! * the [`channel.raid`](crate::eventsub::channel::ChannelRaidV1) subscriptions from the source channel and to the target channel.
+//!   A notification for the subscription on the target confirms that the raid arrived.
+//!
+//! The coordinator does not listen to anything itself, the caller passes in a stream of [`RaidSignal`]s.
+//! A [`RaidSignal::Cancel`] in that stream cancels the raid with [Cancel a raid](crate::helix::raids::cancel_a_raid).
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! # let client: helix::HelixClient<'static, twitch_api::client::DummyHttpClient> = helix::HelixClient::default();
+//! # let token = twitch_oauth2::AccessToken::new("validtoken".to_string());
+//! # let token = twitch_oauth2::UserToken::from_existing(&client, token, None, None).await?;
+//! # let events: Vec<twitch_api::eventsub::Event> = vec![];
+//! use twitch_api::{
+//!     eventsub::raid_coordinator::{RaidCoordinator, RaidOutcome, RaidSignal},
+//!     helix,
+//! };
+//!
+//! let coordinator = RaidCoordinator::new("1234", "5678");
+//! // subscribe to these before starting the raid
+//! let [_from, _to] = coordinator.subscriptions();
+//! // e.g. events received on a websocket
+//! let signals = futures::stream::iter(events.into_iter().map(RaidSignal::from));
+//! let outcome = coordinator
+//!     .run(&client, &token, signals, |raid, transition| {
+//!         println!("{transition:?}, {:?} viewers", raid.viewer_count)
+//!     })
+//!     .await?;
+//! match outcome {
+//!     RaidOutcome::Arrived { viewers } => println!("raided with {viewers} viewers"),
+//!     outcome => println!("raid did not arrive: {outcome:?}"),
+//! }
+//! # Ok(()) }
+//! ```
+
+use crate::{
+    eventsub::{
+        channel::{self, moderate::ActionV2},
+        Event, Message, Payload,
+    },
+    helix::{ClientRequestError, HelixClient},
+    types,
+};
+use twitch_oauth2::TwitchToken;
+
+type ClientError<C> = ClientRequestError<<C as crate::HttpClient>::Error>;
+
+/// Phase of a [`Raid`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum RaidPhase {
+    /// The raid has been requested, but the countdown has not been seen yet
+    Requested,
+    /// The countdown is running
+    Countdown,
+    /// The countdown finished and the viewers are being moved to the target
+    Went,
+    /// The target received the raid
+    Arrived,
+    /// The raid was canceled
+    Canceled,
+}
+
+impl RaidPhase {
+    /// Returns `true` if the raid can not change anymore
+    pub fn is_final(self) -> bool { matches!(self, Self::Arrived | Self::Canceled) }
+}
+
+/// A raid followed by a [`RaidCoordinator`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Raid {
+    /// The broadcaster that is raiding
+    pub from_broadcaster_id: types::UserId,
+    /// The broadcaster being raided
+    pub to_broadcaster_id: types::UserId,
+    /// The current phase
+    pub phase: RaidPhase,
+    /// Login of the broadcaster being raided, once known
+    pub to_broadcaster_login: Option<types::UserName>,
+    /// Display name of the broadcaster being raided, once known
+    pub to_broadcaster_name: Option<types::DisplayName>,
+    /// The last reported amount of viewers joining the raid
+    pub viewer_count: Option<i64>,
+    /// Seconds from the start of the countdown until the raid goes, as reported by PubSub
+    pub force_raid_now_seconds: Option<i64>,
+    /// When the raid was requested
+    pub created_at: Option<types::Timestamp>,
+    /// Whether the channel being raided contains mature content
+    pub is_mature: Option<bool>,
+}
+
+/// A change to a [`Raid`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Transition {
+    /// The raid was requested with [Start a raid](crate::helix::raids::start_a_raid)
+    Requested,
+    /// The countdown started or its viewer count changed
+    Countdown {
+        /// Amount of viewers joining the raid
+        viewer_count: i64,
+    },
+    /// The raid went
+    Went {
+        /// Amount of viewers joining the raid, if known
+        viewer_count: Option<i64>,
+    },
+    /// The target received the raid
+    Arrived {
+        /// Amount of viewers that arrived
+        viewers: i64,
+    },
+    /// The raid was canceled
+    Canceled,
+}
+
+/// Final outcome of [`RaidCoordinator::run`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RaidOutcome {
+    /// The target received the raid
+    Arrived {
+        /// Amount of viewers that arrived
+        viewers: i64,
+    },
+    /// The raid went, but the signals ended before the target confirmed it
+    Unconfirmed {
+        /// Amount of viewers joining the raid, if known
+        viewer_count: Option<i64>,
+    },
+    /// The raid was canceled
+    Canceled {
+        /// `true` if the raid was canceled by a [`RaidSignal::Cancel`], `false` if it was canceled elsewhere
+        requested: bool,
+    },
+    /// The signals ended before the raid went
+    Abandoned,
+}
+
+/// Input for a [`RaidCoordinator`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+#[allow(deprecated)]
+pub enum RaidSignal {
+    /// An EventSub event. Only `channel.raid` and `channel.moderate` notifications for this raid are used.
+    EventSub(Box<Event>),
+    /// A reply on the `raid` PubSub topic of the source channel
+    #[cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm")))]
+    #[cfg_attr(
+        nightly,
+        doc(cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm"))))
+    )]
+    PubSub(crate::pubsub::raid::RaidReply),
+    /// Cancel the raid
+    Cancel,
+}
+
+impl From<Event> for RaidSignal {
+    fn from(event: Event) -> Self { Self::EventSub(Box::new(event)) }
+}
+
+#[cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm")))]
+#[allow(deprecated)]
+impl From<crate::pubsub::raid::RaidReply> for RaidSignal {
+    fn from(reply: crate::pubsub::raid::RaidReply) -> Self { Self::PubSub(reply) }
+}
+
+/// Errors from [`RaidCoordinator::run`]
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+#[non_exhaustive]
+pub enum RaidError<C: crate::HttpClient> {
+    /// could not start the raid
+    Start(#[source] ClientError<C>),
+    /// could not cancel the raid
+    Cancel(#[source] ClientError<C>),
+}
+
+/// Starts a raid and follows it to a [`RaidOutcome`]
+#[derive(Clone, Debug)]
+pub struct RaidCoordinator {
+    raid: Raid,
+}
+
+impl RaidCoordinator {
+    /// Coordinate a raid from one broadcaster to another
+    pub fn new(
+        from_broadcaster_id: impl Into<types::UserId>,
+        to_broadcaster_id: impl Into<types::UserId>,
+    ) -> Self {
+        Self {
+            raid: Raid {
+                from_broadcaster_id: from_broadcaster_id.into(),
+                to_broadcaster_id: to_broadcaster_id.into(),
+                phase: RaidPhase::Requested,
+                to_broadcaster_login: None,
+                to_broadcaster_name: None,
+                viewer_count: None,
+                force_raid_now_seconds: None,
+                created_at: None,
+                is_mature: None,
+            },
+        }
+    }
+
+    /// The raid as currently known
+    pub fn raid(&self) -> &Raid { &self.raid }
+
+    /// The `channel.raid` subscriptions to follow the raid, from the source and to the target.
+    ///
+    /// `channel.moderate` on the source channel additionally needs a moderator,
+    /// see [`ChannelModerateV2`](channel::ChannelModerateV2).
+    pub fn subscriptions(&self) -> [channel::ChannelRaidV1; 2] {
+        [
+            channel::ChannelRaidV1::from_broadcaster_user_id(self.raid.from_broadcaster_id.clone()),
+            channel::ChannelRaidV1::to_broadcaster_user_id(self.raid.to_broadcaster_id.clone()),
+        ]
+    }
+
+    /// Apply a signal to the raid, returning the transition it caused.
+    ///
+    /// Signals for other raids, signals that would move the raid back to an earlier phase and [`RaidSignal::Cancel`] are ignored.
+    pub fn apply(&mut self, signal: &RaidSignal) -> Option<Transition> {
+        match signal {
+            RaidSignal::EventSub(event) => self.apply_event(event),
+            #[cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm")))]
+            RaidSignal::PubSub(reply) => self.apply_pubsub(reply),
+            RaidSignal::Cancel => None,
+        }
+    }
+
+    /// Apply an EventSub event to the raid, returning the transition it caused.
+    pub fn apply_event(&mut self, event: &Event) -> Option<Transition> {
+        if self.raid.phase.is_final() {
+            return None;
+        }
+        match event {
+            Event::ChannelRaidV1(Payload {
+                subscription,
+                message: Message::Notification(p),
+            }) => {
+                if p.from_broadcaster_user_id != self.raid.from_broadcaster_id
+                    || p.to_broadcaster_user_id != self.raid.to_broadcaster_id
+                {
+                    return None;
+                }
+                self.raid.to_broadcaster_login = Some(p.to_broadcaster_user_login.clone());
+                self.raid.to_broadcaster_name = Some(p.to_broadcaster_user_name.clone());
+                self.raid.viewer_count = Some(p.viewers);
+                // unset condition fields are sent as empty strings
+                if subscription.condition.to_broadcaster_user_id.as_ref()
+                    == Some(&self.raid.to_broadcaster_id)
+                {
+                    self.raid.phase = RaidPhase::Arrived;
+                    Some(Transition::Arrived { viewers: p.viewers })
+                } else {
+                    self.went()
+                }
+            }
+            Event::ChannelModerateV2(Payload {
+                message: Message::Notification(p),
+                ..
+            }) if p.broadcaster_user_id == self.raid.from_broadcaster_id => match &p.action {
+                ActionV2::Raid(a) if a.user_id == self.raid.to_broadcaster_id => {
+                    self.raid.to_broadcaster_login = Some(a.user_login.clone());
+                    self.raid.to_broadcaster_name = Some(a.user_name.clone());
+                    self.countdown(a.viewer_count as i64)
+                }
+                ActionV2::Unraid(a) if a.user_id == self.raid.to_broadcaster_id => self.canceled(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Apply a reply from the `raid` PubSub topic to the raid, returning the transition it caused.
+    #[cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm")))]
+    #[cfg_attr(
+        nightly,
+        doc(cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm"))))
+    )]
+    #[allow(deprecated)]
+    pub fn apply_pubsub(&mut self, reply: &crate::pubsub::raid::RaidReply) -> Option<Transition> {
+        use crate::pubsub::raid::RaidReply;
+
+        let (source_id, target_id, target_login, target_name, viewer_count, force_raid_now_seconds) =
+            match reply {
+                RaidReply::RaidUpdateV2(r) => (
+                    &r.source_id,
+                    &r.target_id,
+                    &r.target_login,
+                    &r.target_display_name,
+                    r.viewer_count,
+                    r.force_raid_now_seconds,
+                ),
+                RaidReply::RaidGoV2(r) => (
+                    &r.source_id,
+                    &r.target_id,
+                    &r.target_login,
+                    &r.target_display_name,
+                    r.viewer_count,
+                    r.force_raid_now_seconds,
+                ),
+                RaidReply::RaidCancelV2(r) => (
+                    &r.source_id,
+                    &r.target_id,
+                    &r.target_login,
+                    &r.target_display_name,
+                    r.viewer_count,
+                    r.force_raid_now_seconds,
+                ),
+            };
+        if self.raid.phase.is_final()
+            || *source_id != self.raid.from_broadcaster_id
+            || *target_id != self.raid.to_broadcaster_id
+        {
+            return None;
+        }
+        self.raid.to_broadcaster_login = Some(target_login.clone());
+        self.raid.to_broadcaster_name = Some(target_name.clone());
+        self.raid.force_raid_now_seconds = Some(force_raid_now_seconds);
+        match reply {
+            RaidReply::RaidUpdateV2(_) => self.countdown(viewer_count),
+            RaidReply::RaidGoV2(_) => {
+                self.raid.viewer_count = Some(viewer_count);
+                self.went()
+            }
+            RaidReply::RaidCancelV2(_) => self.canceled(),
+        }
+    }
+
+    /// Start the raid and follow it until it reaches an outcome or `signals` ends.
+    ///
+    /// `on_transition` is called for every transition of the raid, including [`Transition::Requested`] once the raid was started.
+    pub async fn run<'c, C, T, S>(
+        mut self,
+        client: &'c HelixClient<'c, C>,
+        token: &'c T,
+        signals: S,
+        mut on_transition: impl FnMut(&Raid, &Transition),
+    ) -> Result<RaidOutcome, RaidError<C>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: TwitchToken + Send + Sync + ?Sized,
+        S: futures::Stream<Item = RaidSignal>,
+    {
+        use futures::StreamExt;
+
+        let started = client
+            .start_a_raid(
+                &*self.raid.from_broadcaster_id,
+                &*self.raid.to_broadcaster_id,
+                token,
+            )
+            .await
+            .map_err(RaidError::Start)?;
+        self.raid.created_at = Some(started.created_at);
+        self.raid.is_mature = Some(started.is_mature);
+        on_transition(&self.raid, &Transition::Requested);
+
+        futures::pin_mut!(signals);
+        while let Some(signal) = signals.next().await {
+            if let RaidSignal::Cancel = signal {
+                if self.raid.phase >= RaidPhase::Went {
+                    continue;
+                }
+                client
+                    .cancel_a_raid(&*self.raid.from_broadcaster_id, token)
+                    .await
+                    .map_err(RaidError::Cancel)?;
+                self.raid.phase = RaidPhase::Canceled;
+                on_transition(&self.raid, &Transition::Canceled);
+                return Ok(RaidOutcome::Canceled { requested: true });
+            }
+            let Some(transition) = self.apply(&signal) else {
+                continue;
+            };
+            on_transition(&self.raid, &transition);
+            match transition {
+                Transition::Arrived { viewers } => return Ok(RaidOutcome::Arrived { viewers }),
+                Transition::Canceled => return Ok(RaidOutcome::Canceled { requested: false }),
+                _ => (),
+            }
+        }
+        Ok(if self.raid.phase == RaidPhase::Went {
+            RaidOutcome::Unconfirmed {
+                viewer_count: self.raid.viewer_count,
+            }
+        } else {
+            RaidOutcome::Abandoned
+        })
+    }
+
+    fn countdown(&mut self, viewer_count: i64) -> Option<Transition> {
+        if self.raid.phase > RaidPhase::Countdown
+            || (self.raid.phase == RaidPhase::Countdown
+                && self.raid.viewer_count == Some(viewer_count))
+        {
+            return None;
+        }
+        self.raid.phase = RaidPhase::Countdown;
+        self.raid.viewer_count = Some(viewer_count);
+        Some(Transition::Countdown { viewer_count })
+    }
+
+    fn went(&mut self) -> Option<Transition> {
+        if self.raid.phase >= RaidPhase::Went {
+            return None;
+        }
+        self.raid.phase = RaidPhase::Went;
+        Some(Transition::Went {
+            viewer_count: self.raid.viewer_count,
+        })
+    }
+
+    fn canceled(&mut self) -> Option<Transition> {
+        if self.raid.phase >= RaidPhase::Went {
+            return None;
+        }
+        self.raid.phase = RaidPhase::Canceled;
+        Some(Transition::Canceled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raid_event(condition: &str) -> Event {
+        Event::parse(&format!(
+            r#"{{"subscription":{{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","type":"channel.raid","version":"1","status":"enabled","cost":0,"condition":{condition},"transport":{{"method":"webhook","callback":"https://example.com/webhooks/callback"}},"created_at":"2019-11-16T10:11:12.123Z"}},"event":{{"from_broadcaster_user_id":"1234","from_broadcaster_user_login":"cool_user","from_broadcaster_user_name":"Cool_User","to_broadcaster_user_id":"1337","to_broadcaster_user_login":"cooler_user","to_broadcaster_user_name":"Cooler_User","viewers":9001}}}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn eventsub() {
+        let mut coordinator = RaidCoordinator::new("1234", "1337");
+        let from = raid_event(r#"{"from_broadcaster_user_id":"1234"}"#);
+        let to = raid_event(r#"{"to_broadcaster_user_id":"1337"}"#);
+
+        assert_eq!(
+            coordinator.apply(&RaidSignal::from(from.clone())),
+            Some(Transition::Went {
+                viewer_count: Some(9001)
+            })
+        );
+        assert_eq!(coordinator.apply(&RaidSignal::from(from)), None);
+        assert_eq!(
+            coordinator.apply(&RaidSignal::from(to.clone())),
+            Some(Transition::Arrived { viewers: 9001 })
+        );
+        assert_eq!(coordinator.raid().phase, RaidPhase::Arrived);
+        assert_eq!(
+            coordinator.raid().to_broadcaster_login.as_deref(),
+            Some("cooler_user".into())
+        );
+        assert_eq!(coordinator.apply(&RaidSignal::from(to.clone())), None);
+
+        // unset condition fields are empty
+        let mut coordinator = RaidCoordinator::new("1234", "1337");
+        let from = raid_event(r#"{"from_broadcaster_user_id":"1234","to_broadcaster_user_id":""}"#);
+        let to = raid_event(r#"{"from_broadcaster_user_id":"","to_broadcaster_user_id":"1337"}"#);
+        assert_eq!(
+            coordinator.apply(&RaidSignal::from(from)),
+            Some(Transition::Went {
+                viewer_count: Some(9001)
+            })
+        );
+        assert_eq!(
+            coordinator.apply(&RaidSignal::from(to.clone())),
+            Some(Transition::Arrived { viewers: 9001 })
+        );
+
+        // a raid to another channel is ignored
+        let mut other = RaidCoordinator::new("1234", "42");
+        assert_eq!(other.apply(&RaidSignal::from(to)), None);
+        assert_eq!(other.raid().phase, RaidPhase::Requested);
+    }
+
+    #[test]
+    #[cfg(all(feature = "pubsub", any(feature = "unsupported", feature = "tpm")))]
+    #[allow(deprecated)]
+    fn pubsub() {
+        use crate::pubsub::raid::RaidReply;
+
+        let reply = |kind: &str, viewer_count: i64| -> RaidReply {
+            serde_json::from_value(serde_json::json!({
+                "type": kind,
+                "raid": {
+                    "id": "7fbea6d0-1337-4c61-8c92-b7510e639010",
+                    "creator_id": "1234",
+                    "source_id": "1234",
+                    "target_id": "1337",
+                    "target_login": "cooler_user",
+                    "target_display_name": "Cooler_User",
+                    "target_profile_image": "https://static-cdn.jtvnw.net/jtv_user_pictures/deadbeef-profile_image-70x70.png",
+                    "transition_jitter_seconds": 0,
+                    "force_raid_now_seconds": 90,
+                    "viewer_count": viewer_count
+                }
+            }))
+            .unwrap()
+        };
+
+        let mut coordinator = RaidCoordinator::new("1234", "1337");
+        assert_eq!(
+            coordinator.apply(&reply("raid_update_v2", 10).into()),
+            Some(Transition::Countdown { viewer_count: 10 })
+        );
+        assert_eq!(coordinator.apply(&reply("raid_update_v2", 10).into()), None);
+        assert_eq!(
+            coordinator.apply(&reply("raid_update_v2", 12).into()),
+            Some(Transition::Countdown { viewer_count: 12 })
+        );
+        assert_eq!(coordinator.raid().force_raid_now_seconds, Some(90));
+        assert_eq!(
+            coordinator.apply(&reply("raid_cancel_v2", 12).into()),
+            Some(Transition::Canceled)
+        );
+        assert_eq!(
+            coordinator.apply(&reply("raid_go_v2", 12).into()),
+            None,
+            "canceled raids stay canceled"
+        );
+
+        let mut coordinator = RaidCoordinator::new("1234", "1337");
+        assert_eq!(
+            coordinator.apply(&reply("raid_go_v2", 15).into()),
+            Some(Transition::Went {
+                viewer_count: Some(15)
+            })
+        );
+        assert_eq!(coordinator.apply(&reply("raid_cancel_v2", 15).into()), None);
+        assert_eq!(coordinator.raid().phase, RaidPhase::Went);
+    }
+}
@@ -0,0 +1,1254 @@
+//! Detect follow-bot floods and hate raids and lock down chat in response
+//!
+//! [`RaidShield`] watches the EventSub events of a channel for
+//!
+//! * floods of follows, not counting follows from accounts known to be older than [`ProtectionConfig::new_account_age`],
+//! * many chatters sending the same or [similar](ProtectionConfig::similarity) messages,
+//! * a high rate of chat messages,
+//! * messages from [suspicious users](channel::ChannelSuspiciousUserMessageV1) that are likely ban evaders.
+//!
+//! Thresholds are lowered for a while after the channel is [raided](channel::ChannelRaidV1), since hate raids often start with one.
+//!
+//! When it detects a [`Threat`], the shield locks down the channel with [`Step`]s:
+//! it turns on [Shield Mode](crate::helix::moderation::update_shield_mode_status),
+//! tightens the [chat settings](crate::helix::chat::update_chat_settings) to followers-only and slow mode,
+//! and bans the accounts involved that are younger than [`ProtectionConfig::new_account_age`].
+//! Account ages are looked up with [Get Users](crate::helix::users::get_users), see [`RaidShield::pending_lookups`].
+//! Once no threat was seen for [`ProtectionConfig::cooldown`], Shield Mode and the chat settings are restored.
+//!
+//! Every step is recorded in the [log](RaidShield::log) together with the step that reverts it,
+//! and [`RaidShield::undo`] reverts everything the shield did, including bans.
+//!
+//! The shield does no IO itself, pass the steps to [`RaidShield::execute`] or make the requests yourself.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! # let client: helix::HelixClient<'static, twitch_api::client::DummyHttpClient> = helix::HelixClient::default();
+//! # let token = twitch_oauth2::AccessToken::new("validtoken".to_string());
+//! # let token = twitch_oauth2::UserToken::from_existing(&client, token, None, None).await?;
+//! # let events: Vec<twitch_api::eventsub::Event> = vec![];
+//! use std::time::Instant;
+//! use twitch_api::{
+//!     eventsub::raid_protection::{ProtectionConfig, RaidShield},
+//!     helix::{
+//!         self,
+//!         moderation::bulk::{BulkConfig, BulkModerator},
+//!     },
+//! };
+//!
+//! let mut shield = RaidShield::new("1234", ProtectionConfig::default());
+//! // shared by all calls to `execute`, so that bans across events are rate limited together
+//! let moderator = BulkModerator::new("1234", "5678", BulkConfig::default());
+//! shield.sync(&client, &token, "5678").await?;
+//! // e.g. events received on a websocket
+//! for event in events {
+//!     let mut steps = shield.handle_event(&event, Instant::now());
+//!     steps.extend(shield.lookup_accounts(&client, &token).await?);
+//!     steps.extend(shield.poll(Instant::now()));
+//!     shield
+//!         .execute(&client, &token, &moderator, &steps, tokio::time::sleep)
+//!         .await?;
+//! }
+//! for entry in shield.log() {
+//!     println!("{:?}: {:?} because of {:?}", entry.at, entry.step, entry.reason);
+//! }
+//! # Ok(()) }
+//! ```
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::{
+    eventsub::{
+        channel::{
+            self,
+            suspicious_user::{BanEvasionEvaluation, SuspiciousUserType},
+        },
+        Event, Message, Payload,
+    },
+    helix::{chat::queue::ChatRestrictions, ClientRequestError, HelixClient},
+    types,
+};
+use twitch_oauth2::TwitchToken;
+
+type ClientError<C> = ClientRequestError<<C as crate::HttpClient>::Error>;
+
+/// Most recent chat messages kept to compare new messages with
+const MAX_MESSAGES: usize = 300;
+
+/// Configuration for a [`RaidShield`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ProtectionConfig {
+    /// Time in which follows are counted. Defaults to 1 minute
+    pub follow_window: Duration,
+    /// Number of follows in [`follow_window`](Self::follow_window) that is a flood. Defaults to `20`
+    pub follow_threshold: usize,
+    /// Accounts younger than this are new. Follows from older accounts don't count towards a flood,
+    /// and only new accounts are banned. Defaults to 7 days
+    pub new_account_age: Duration,
+    /// Time in which chat messages are compared and counted. Defaults to 30 seconds
+    pub message_window: Duration,
+    /// Number of chatters sending similar messages in [`message_window`](Self::message_window) that is spam. Defaults to `5`
+    pub similar_chatters: usize,
+    /// How similar two messages have to be, from `0.0` to `1.0`. Defaults to `0.8`
+    pub similarity: f64,
+    /// Number of messages in [`message_window`](Self::message_window) that is a flood. Defaults to `150`
+    pub message_threshold: usize,
+    /// Time after a raid in which thresholds are lowered. Defaults to 5 minutes
+    pub raid_window: Duration,
+    /// Factor for thresholds after a raid. Defaults to `0.5`
+    pub raid_sensitivity: f64,
+    /// Time without threats after which the lockdown ends. Defaults to 10 minutes
+    pub cooldown: Duration,
+    /// Turn on Shield Mode in a lockdown. Defaults to `true`
+    pub shield_mode: bool,
+    /// Followers-only duration in a lockdown, `None` to leave followers-only mode alone. Defaults to 10 minutes
+    pub followers_only: Option<Duration>,
+    /// Slow mode wait time in a lockdown, `None` to leave slow mode alone. Defaults to 10 seconds
+    pub slow_mode: Option<Duration>,
+    /// Ban new accounts involved in a threat. Defaults to `true`
+    pub ban_new_accounts: bool,
+    /// Ban chatters that Twitch considers likely ban evaders. Defaults to `false`
+    pub ban_likely_evaders: bool,
+    /// Reason given for bans. Defaults to `"raid protection"`
+    pub ban_reason: String,
+}
+
+impl Default for ProtectionConfig {
+    fn default() -> Self {
+        Self {
+            follow_window: Duration::from_secs(60),
+            follow_threshold: 20,
+            new_account_age: Duration::from_secs(7 * 24 * 60 * 60),
+            message_window: Duration::from_secs(30),
+            similar_chatters: 5,
+            similarity: 0.8,
+            message_threshold: 150,
+            raid_window: Duration::from_secs(5 * 60),
+            raid_sensitivity: 0.5,
+            cooldown: Duration::from_secs(10 * 60),
+            shield_mode: true,
+            followers_only: Some(Duration::from_secs(10 * 60)),
+            slow_mode: Some(Duration::from_secs(10)),
+            ban_new_accounts: true,
+            ban_likely_evaders: false,
+            ban_reason: "raid protection".to_owned(),
+        }
+    }
+}
+
+impl ProtectionConfig {
+    /// Set the number of follows in a window that is a flood
+    pub fn follows(mut self, threshold: usize, window: Duration) -> Self {
+        self.follow_threshold = threshold.max(1);
+        self.follow_window = window;
+        self
+    }
+
+    /// Set the age under which accounts are new
+    pub fn new_account_age(mut self, age: Duration) -> Self {
+        self.new_account_age = age;
+        self
+    }
+
+    /// Set the number of chatters sending similar messages that is spam, and how similar they have to be
+    pub fn similar_messages(mut self, chatters: usize, similarity: f64) -> Self {
+        self.similar_chatters = chatters.max(2);
+        self.similarity = similarity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the number of messages in [`message_window`](Self::message_window) that is a flood
+    pub fn message_threshold(mut self, threshold: usize) -> Self {
+        self.message_threshold = threshold.max(1);
+        self
+    }
+
+    /// Set the time without threats after which the lockdown ends
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Set whether to turn on Shield Mode in a lockdown
+    pub fn shield_mode(mut self, shield_mode: bool) -> Self {
+        self.shield_mode = shield_mode;
+        self
+    }
+
+    /// Set the followers-only duration in a lockdown
+    pub fn followers_only(mut self, duration: Option<Duration>) -> Self {
+        self.followers_only = duration;
+        self
+    }
+
+    /// Set the slow mode wait time in a lockdown
+    pub fn slow_mode(mut self, wait_time: Option<Duration>) -> Self {
+        self.slow_mode = wait_time;
+        self
+    }
+
+    /// Set whether to ban new accounts involved in a threat
+    pub fn ban_new_accounts(mut self, ban: bool) -> Self {
+        self.ban_new_accounts = ban;
+        self
+    }
+
+    /// Set whether to ban likely ban evaders
+    pub fn ban_likely_evaders(mut self, ban: bool) -> Self {
+        self.ban_likely_evaders = ban;
+        self
+    }
+}
+
+/// Something the [`RaidShield`] detected
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Threat {
+    /// Many follows from accounts that aren't known to be old
+    FollowFlood {
+        /// Number of follows in the window
+        follows: usize,
+    },
+    /// Many chatters sending similar messages
+    ChatSpam {
+        /// Number of chatters
+        chatters: usize,
+        /// The latest of the messages
+        text: String,
+    },
+    /// Many chat messages
+    MessageFlood {
+        /// Number of messages in the window
+        messages: usize,
+    },
+    /// A message from a likely ban evader
+    BanEvader {
+        /// The chatter
+        user_id: types::UserId,
+    },
+}
+
+/// A change to the channel
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Step {
+    /// Turn Shield Mode on or off
+    ShieldMode(bool),
+    /// Set followers-only mode, `None` turns it off
+    FollowersOnly(Option<Duration>),
+    /// Set slow mode, `None` turns it off
+    SlowMode(Option<Duration>),
+    /// Ban a user
+    Ban {
+        /// The user
+        user_id: types::UserId,
+        /// The reason
+        reason: String,
+    },
+    /// Remove the ban of a user
+    Unban {
+        /// The user
+        user_id: types::UserId,
+    },
+}
+
+/// Why a [`Step`] was taken
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Reason {
+    /// A threat was detected
+    Threat(Threat),
+    /// The lockdown ended after the [cooldown](ProtectionConfig::cooldown)
+    Relaxed,
+    /// Everything was [undone](RaidShield::undo)
+    Undo,
+}
+
+/// A step taken by the [`RaidShield`]
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct LogEntry {
+    /// When the step was taken
+    pub at: Instant,
+    /// The step
+    pub step: Step,
+    /// Why it was taken
+    pub reason: Reason,
+    /// The step that reverts it, if it changed anything that can be reverted
+    pub undo: Option<Step>,
+    /// Whether the step was reverted
+    pub undone: bool,
+}
+
+#[derive(Clone, Debug)]
+struct RecentMessage {
+    at: Instant,
+    user_id: types::UserId,
+    text: String,
+}
+
+/// Watches the events of a channel for raids and floods, see the [module documentation](self)
+#[derive(Clone, Debug)]
+pub struct RaidShield {
+    broadcaster_id: types::UserId,
+    config: ProtectionConfig,
+    shield_mode: bool,
+    restrictions: ChatRestrictions,
+    raided_at: Option<Instant>,
+    follows: VecDeque<(Instant, types::UserId)>,
+    messages: VecDeque<RecentMessage>,
+    /// Times of all chat messages in the window, [`messages`](Self::messages) is capped at [`MAX_MESSAGES`]
+    message_times: VecDeque<Instant>,
+    /// Creation time of accounts, in milliseconds since the Unix epoch
+    accounts: HashMap<types::UserId, i64>,
+    /// Users involved in a threat whose account age isn't known yet
+    flagged: HashSet<types::UserId>,
+    banned: HashSet<types::UserId>,
+    /// Time of the last threat, if locked down
+    lockdown: Option<Instant>,
+    log: Vec<LogEntry>,
+    /// Current time in milliseconds since the Unix epoch, for account ages
+    clock: fn() -> i64,
+}
+
+impl RaidShield {
+    /// Protect the chat of a broadcaster
+    pub fn new(broadcaster_id: impl Into<types::UserId>, config: ProtectionConfig) -> Self {
+        Self {
+            broadcaster_id: broadcaster_id.into(),
+            config,
+            shield_mode: false,
+            restrictions: ChatRestrictions::default(),
+            raided_at: None,
+            follows: VecDeque::new(),
+            messages: VecDeque::new(),
+            message_times: VecDeque::new(),
+            accounts: HashMap::new(),
+            flagged: HashSet::new(),
+            banned: HashSet::new(),
+            lockdown: None,
+            log: vec![],
+            clock: crate::now_unix_millis,
+        }
+    }
+
+    /// Set the function that returns the current time in milliseconds since the Unix epoch,
+    /// which account ages are computed with. Defaults to the system clock
+    pub fn with_clock(mut self, clock: fn() -> i64) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The configuration
+    pub fn config(&self) -> &ProtectionConfig { &self.config }
+
+    /// Set the current chat settings, which are restored after a lockdown
+    pub fn set_restrictions(&mut self, restrictions: impl Into<ChatRestrictions>) {
+        self.restrictions = restrictions.into();
+    }
+
+    /// Set whether Shield Mode is currently on
+    pub fn set_shield_mode(&mut self, active: bool) { self.shield_mode = active; }
+
+    /// Whether the channel is locked down
+    pub fn is_locked_down(&self) -> bool { self.lockdown.is_some() }
+
+    /// Every step taken, oldest first
+    pub fn log(&self) -> &[LogEntry] { &self.log }
+
+    /// The EventSub subscriptions the shield uses. Subscriptions that need a moderator use `moderator_id`
+    pub fn subscriptions(
+        &self,
+        moderator_id: impl Into<types::UserId>,
+    ) -> (
+        channel::ChannelRaidV1,
+        channel::ChannelFollowV2,
+        channel::ChannelChatMessageV1,
+        channel::ChannelSuspiciousUserMessageV1,
+    ) {
+        let moderator_id = moderator_id.into();
+        (
+            channel::ChannelRaidV1::to_broadcaster_user_id(self.broadcaster_id.clone()),
+            channel::ChannelFollowV2::new(self.broadcaster_id.clone(), moderator_id.clone()),
+            channel::ChannelChatMessageV1::new(self.broadcaster_id.clone(), moderator_id.clone()),
+            channel::ChannelSuspiciousUserMessageV1::new(self.broadcaster_id.clone(), moderator_id),
+        )
+    }
+
+    /// Handle an event, returning the steps to take.
+    ///
+    /// Besides the events that are checked for threats, `channel.shield_mode.begin`, `channel.shield_mode.end`
+    /// and `channel.chat_settings.update` keep the current state of the channel up to date.
+    /// Events for other channels are ignored.
+    #[allow(deprecated)]
+    pub fn handle_event(&mut self, event: &Event, now: Instant) -> Vec<Step> {
+        self.prune(now);
+        match event {
+            Event::ChannelRaidV1(Payload {
+                message: Message::Notification(p),
+                ..
+            }) if p.to_broadcaster_user_id == self.broadcaster_id => {
+                self.raided_at = Some(now);
+                vec![]
+            }
+            Event::ChannelFollowV1(Payload {
+                message: Message::Notification(p),
+                ..
+            }) if p.broadcaster_user_id == self.broadcaster_id => self.follow(&p.user_id, now),
+            Event::ChannelFollowV2(Payload {
+                message: Message::Notification(p),
+                ..
+            }) if p.broadcaster_user_id == self.broadcaster_id => self.follow(&p.user_id, now),
+            Event::ChannelChatMessageV1(Payload {
+                message: Message::Notification(p),
+                ..
+            }) if p.broadcaster_user_id == self.broadcaster_id => {
+                let privileged = p
+                    .badges
+                    .iter()
+                    .any(|b| matches!(b.set_id.as_str(), "broadcaster" | "moderator"));
+                if privileged {
+                    return vec![];
+                }
+                self.message(&p.chatter_user_id, &p.message.text, now)
+            }
+            Event::ChannelSuspiciousUserMessageV1(Payload {
+                message: Message::Notification(p),
+                ..
+            }) if p.broadcaster_user_id == self.broadcaster_id => {
+                let mut steps = self.message(&p.user_id, &p.message.text, now);
+                let evader = p.ban_evasion_evaluation == BanEvasionEvaluation::Likely
+                    || p.types.contains(&SuspiciousUserType::BanEvader);
+                if evader && self.config.ban_likely_evaders && !self.banned.contains(&p.user_id) {
+                    let threat = Threat::BanEvader {
+                        user_id: p.user_id.clone(),
+                    };
+                    steps.push(self.ban(p.user_id.clone(), &threat, now));
+                }
+                steps
+            }
+            Event::ChannelShieldModeBeginV1(Payload {
+                message: Message::Notification(p),
+                ..
+            }) if p.broadcaster_user_id == self.broadcaster_id => {
+                self.shield_mode = true;
+                vec![]
+            }
+            Event::ChannelShieldModeEndV1(Payload {
+                message: Message::Notification(p),
+                ..
+            }) if p.broadcaster_user_id == self.broadcaster_id => {
+                self.shield_mode = false;
+                vec![]
+            }
+            Event::ChannelChatSettingsUpdateV1(Payload {
+                message: Message::Notification(p),
+                ..
+            }) if p.broadcaster_user_id == self.broadcaster_id => {
+                self.restrictions = p.into();
+                vec![]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Users involved in a threat whose account age has to be looked up with [`insert_account`](Self::insert_account)
+    pub fn pending_lookups(&self) -> Vec<types::UserId> { self.flagged.iter().cloned().collect() }
+
+    /// Set when the account of a user was created, returning a ban if the user was involved in a threat and is new
+    pub fn insert_account(
+        &mut self,
+        user_id: impl Into<types::UserId>,
+        created_at: &types::TimestampRef,
+        now: Instant,
+    ) -> Option<Step> {
+        let user_id = user_id.into();
+        let created_at = crate::timestamp_to_unix_millis(created_at.as_str())?;
+        self.accounts.insert(user_id.clone(), created_at);
+        if !self.flagged.remove(&user_id) || self.is_new(&user_id) != Some(true) {
+            return None;
+        }
+        let threat = self
+            .log
+            .iter()
+            .rev()
+            .find_map(|entry| match &entry.reason {
+                Reason::Threat(threat) => Some(threat.clone()),
+                _ => None,
+            })?;
+        Some(self.ban(user_id, &threat, now))
+    }
+
+    /// When the lockdown ends if no other threat is detected
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.lockdown.map(|last| last + self.config.cooldown)
+    }
+
+    /// End the lockdown if the cooldown has passed, returning the steps that restore Shield Mode and the chat settings.
+    ///
+    /// Bans are kept, use [`undo`](Self::undo) to revert them as well.
+    pub fn poll(&mut self, now: Instant) -> Vec<Step> {
+        match self.poll_timeout() {
+            Some(at) if at <= now => {
+                self.lockdown = None;
+                self.flagged.clear();
+                self.revert(now, Reason::Relaxed, |step| {
+                    !matches!(step, Step::Ban { .. } | Step::Unban { .. })
+                })
+            }
+            _ => vec![],
+        }
+    }
+
+    /// End any lockdown and revert every step that wasn't reverted yet, including bans, newest first
+    pub fn undo(&mut self, now: Instant) -> Vec<Step> {
+        self.lockdown = None;
+        self.flagged.clear();
+        self.revert(now, Reason::Undo, |_| true)
+    }
+
+    fn revert(
+        &mut self,
+        now: Instant,
+        reason: Reason,
+        filter: impl Fn(&Step) -> bool,
+    ) -> Vec<Step> {
+        let mut undos = vec![];
+        for entry in self.log.iter_mut().rev() {
+            if entry.undone || !filter(&entry.step) {
+                continue;
+            }
+            if let Some(undo) = entry.undo.clone() {
+                entry.undone = true;
+                undos.push(undo);
+            }
+        }
+        for undo in &undos {
+            self.record(undo.clone(), None, reason.clone(), now);
+        }
+        undos
+    }
+
+    /// Drop follows and messages outside their windows
+    fn prune(&mut self, now: Instant) {
+        while let Some((at, _)) = self.follows.front() {
+            if now.saturating_duration_since(*at) <= self.config.follow_window {
+                break;
+            }
+            self.follows.pop_front();
+        }
+        while let Some(message) = self.messages.front() {
+            if now.saturating_duration_since(message.at) <= self.config.message_window
+                && self.messages.len() <= MAX_MESSAGES
+            {
+                break;
+            }
+            self.messages.pop_front();
+        }
+        while let Some(at) = self.message_times.front() {
+            if now.saturating_duration_since(*at) <= self.config.message_window {
+                break;
+            }
+            self.message_times.pop_front();
+        }
+    }
+
+    /// A threshold, lowered after a raid
+    fn threshold(&self, base: usize, now: Instant) -> usize {
+        match self.raided_at {
+            Some(at) if now.saturating_duration_since(at) <= self.config.raid_window => {
+                ((base as f64 * self.config.raid_sensitivity).ceil() as usize).max(2)
+            }
+            _ => base,
+        }
+    }
+
+    /// Whether an account is new, if its age is known
+    fn is_new(&self, user_id: &types::UserIdRef) -> Option<bool> {
+        let created_at = self.accounts.get(user_id)?;
+        Some((self.clock)() - created_at < self.config.new_account_age.as_millis() as i64)
+    }
+
+    fn follow(&mut self, user_id: &types::UserIdRef, now: Instant) -> Vec<Step> {
+        if self.is_new(user_id) == Some(false) {
+            return vec![];
+        }
+        self.follows.push_back((now, user_id.to_owned()));
+        let follows = self.follows.len();
+        if follows < self.threshold(self.config.follow_threshold, now) {
+            return vec![];
+        }
+        let users: Vec<_> = self.follows.iter().map(|(_, id)| id.clone()).collect();
+        self.respond(Threat::FollowFlood { follows }, users, now)
+    }
+
+    fn message(&mut self, user_id: &types::UserIdRef, text: &str, now: Instant) -> Vec<Step> {
+        let text = normalize(text);
+        let mut chatters: Vec<types::UserId> = vec![user_id.to_owned()];
+        for message in &self.messages {
+            if !chatters.contains(&message.user_id)
+                && similarity(&message.text, &text) >= self.config.similarity
+            {
+                chatters.push(message.user_id.clone());
+            }
+        }
+        // every message counts towards floods, including spam
+        self.message_times.push_back(now);
+        self.messages.push_back(RecentMessage {
+            at: now,
+            user_id: user_id.to_owned(),
+            text: text.clone(),
+        });
+        if chatters.len() >= self.threshold(self.config.similar_chatters, now) {
+            let threat = Threat::ChatSpam {
+                chatters: chatters.len(),
+                text,
+            };
+            return self.respond(threat, chatters, now);
+        }
+        let messages = self.message_times.len();
+        if messages >= self.threshold(self.config.message_threshold, now) {
+            return self.respond(Threat::MessageFlood { messages }, vec![], now);
+        }
+        vec![]
+    }
+
+    /// Lock down the channel and ban the new accounts among `users`
+    fn respond(&mut self, threat: Threat, users: Vec<types::UserId>, now: Instant) -> Vec<Step> {
+        let mut steps = vec![];
+        let reason = Reason::Threat(threat.clone());
+        if self.lockdown.is_none() {
+            if self.config.shield_mode && !self.shield_mode {
+                steps.push(self.record(
+                    Step::ShieldMode(true),
+                    Some(Step::ShieldMode(false)),
+                    reason.clone(),
+                    now,
+                ));
+            }
+            if let Some(duration) = self.config.followers_only {
+                let previous = self.restrictions.followers_only;
+                if previous.map_or(true, |previous| previous < duration) {
+                    steps.push(self.record(
+                        Step::FollowersOnly(Some(duration)),
+                        Some(Step::FollowersOnly(previous)),
+                        reason.clone(),
+                        now,
+                    ));
+                }
+            }
+            if let Some(wait_time) = self.config.slow_mode {
+                let previous = self.restrictions.slow_mode;
+                if previous.map_or(true, |previous| previous < wait_time) {
+                    steps.push(self.record(
+                        Step::SlowMode(Some(wait_time)),
+                        Some(Step::SlowMode(previous)),
+                        reason,
+                        now,
+                    ));
+                }
+            }
+        }
+        self.lockdown = Some(now);
+        if self.config.ban_new_accounts {
+            for user_id in users {
+                if self.banned.contains(&user_id) {
+                    continue;
+                }
+                match self.is_new(&user_id) {
+                    Some(true) => steps.push(self.ban(user_id, &threat, now)),
+                    Some(false) => (),
+                    None => {
+                        self.flagged.insert(user_id);
+                    }
+                }
+            }
+        }
+        steps
+    }
+
+    fn ban(&mut self, user_id: types::UserId, threat: &Threat, now: Instant) -> Step {
+        let step = Step::Ban {
+            user_id: user_id.clone(),
+            reason: self.config.ban_reason.clone(),
+        };
+        self.record(
+            step,
+            Some(Step::Unban { user_id }),
+            Reason::Threat(threat.clone()),
+            now,
+        )
+    }
+
+    /// Log a step and apply it to the known state of the channel
+    fn record(&mut self, step: Step, undo: Option<Step>, reason: Reason, now: Instant) -> Step {
+        match &step {
+            Step::ShieldMode(active) => self.shield_mode = *active,
+            Step::FollowersOnly(duration) => self.restrictions.followers_only = *duration,
+            Step::SlowMode(wait_time) => self.restrictions.slow_mode = *wait_time,
+            Step::Ban { user_id, .. } => {
+                self.flagged.remove(user_id);
+                self.banned.insert(user_id.clone());
+            }
+            Step::Unban { user_id } => {
+                self.banned.remove(user_id);
+            }
+        }
+        self.log.push(LogEntry {
+            at: now,
+            step: step.clone(),
+            reason,
+            undo,
+            undone: false,
+        });
+        step
+    }
+
+    /// Get the current Shield Mode status and chat settings of the channel
+    pub async fn sync<'c, C, T>(
+        &mut self,
+        client: &'c HelixClient<'c, C>,
+        token: &T,
+        moderator_id: impl types::IntoCow<'c, types::UserIdRef> + Send + 'c,
+    ) -> Result<(), ClientError<C>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: TwitchToken + Send + Sync + ?Sized,
+    {
+        let moderator_id = moderator_id.into_cow();
+        let status = client
+            .req_get(
+                crate::helix::moderation::GetShieldModeStatusRequest::new(
+                    &*self.broadcaster_id,
+                    &*moderator_id,
+                ),
+                token,
+            )
+            .await?
+            .data;
+        self.shield_mode = status.is_active;
+        let settings = client
+            .get_chat_settings(&*self.broadcaster_id, Some(&*moderator_id), token)
+            .await?;
+        self.restrictions = (&settings).into();
+        Ok(())
+    }
+
+    /// Look up the ages of the [pending accounts](Self::pending_lookups), returning bans for new accounts
+    ///
+    /// Accounts that Get Users doesn't return, e.g. because they were deleted or suspended, are no longer pending.
+    pub async fn lookup_accounts<'c, C, T>(
+        &mut self,
+        client: &'c HelixClient<'c, C>,
+        token: &T,
+    ) -> Result<Vec<Step>, ClientError<C>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: TwitchToken + Send + Sync + ?Sized,
+    {
+        let pending = self.pending_lookups();
+        let mut steps = vec![];
+        for chunk in pending.chunks(100) {
+            let ids: Vec<&types::UserIdRef> = chunk.iter().map(|id| &**id).collect();
+            let users = client
+                .req_get(crate::helix::users::GetUsersRequest::ids(&ids[..]), token)
+                .await?
+                .data;
+            for user in users {
+                steps.extend(self.insert_account(user.id, &user.created_at, Instant::now()));
+            }
+            // users that weren't returned don't exist anymore
+            for user_id in chunk {
+                self.flagged.remove(user_id);
+            }
+        }
+        Ok(steps)
+    }
+
+    /// Make the requests for steps as the moderator of a [`BulkModerator`](crate::helix::moderation::bulk::BulkModerator)
+    ///
+    /// Bans and unbans are sent with `moderator`, which uses `sleep` to wait for its rate limit, and their reports are returned.
+    /// Reuse the same moderator for the broadcaster of the shield across calls, so that its rate limit applies to all of them.
+    /// If a request fails, the known state of the channel may be wrong, use [`sync`](Self::sync) to get it again.
+    pub async fn execute<'c, C, T, S, F>(
+        &self,
+        client: &'c HelixClient<'c, C>,
+        token: &T,
+        moderator: &crate::helix::moderation::bulk::BulkModerator,
+        steps: &[Step],
+        sleep: S,
+    ) -> Result<Vec<crate::helix::moderation::bulk::BatchReport>, ClientError<C>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: TwitchToken + Send + Sync + ?Sized,
+        S: Fn(Duration) -> F,
+        F: std::future::Future<Output = ()>,
+    {
+        use crate::helix::{
+            chat::{UpdateChatSettingsBody, UpdateChatSettingsRequest},
+            moderation::{
+                bulk::{Action, Target},
+                UpdateShieldModeStatusBody, UpdateShieldModeStatusRequest,
+            },
+        };
+
+        let moderator_id = moderator.moderator_id();
+        let mut bans: HashMap<&str, Vec<Target>> = HashMap::new();
+        let mut unbans = vec![];
+        for step in steps {
+            let body = match step {
+                Step::ShieldMode(active) => {
+                    client
+                        .req_put(
+                            UpdateShieldModeStatusRequest::new(&*self.broadcaster_id, moderator_id),
+                            UpdateShieldModeStatusBody::is_active(*active),
+                            token,
+                        )
+                        .await?;
+                    continue;
+                }
+                Step::FollowersOnly(duration) => UpdateChatSettingsBody {
+                    follower_mode: Some(duration.is_some()),
+                    follower_mode_duration: duration.map(|d| d.as_secs() / 60),
+                    ..Default::default()
+                },
+                Step::SlowMode(wait_time) => UpdateChatSettingsBody {
+                    slow_mode: Some(wait_time.is_some()),
+                    slow_mode_wait_time: wait_time.map(|d| d.as_secs()),
+                    ..Default::default()
+                },
+                Step::Ban { user_id, reason } => {
+                    bans.entry(reason.as_str())
+                        .or_default()
+                        .push(Target::Id(user_id.clone()));
+                    continue;
+                }
+                Step::Unban { user_id } => {
+                    unbans.push(Target::Id(user_id.clone()));
+                    continue;
+                }
+            };
+            client
+                .req_patch(
+                    UpdateChatSettingsRequest::new(&*self.broadcaster_id, moderator_id),
+                    body,
+                    token,
+                )
+                .await?;
+        }
+
+        let mut reports = vec![];
+        for (reason, targets) in bans {
+            reports.push(
+                moderator
+                    .run(client, token, &Action::ban(reason), targets, &sleep)
+                    .await?,
+            );
+        }
+        if !unbans.is_empty() {
+            reports.push(
+                moderator
+                    .run(client, token, &Action::Unban, unbans, &sleep)
+                    .await?,
+            );
+        }
+        Ok(reports)
+    }
+}
+
+/// Lowercase letters and digits, with single spaces between words
+fn normalize(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Similarity of two texts from `0.0` to `1.0`, the Sørensen–Dice coefficient of their character pairs
+fn similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let pairs = |s: &str| {
+        let chars: Vec<char> = s.chars().collect();
+        chars.windows(2).map(|w| (w[0], w[1])).collect::<Vec<_>>()
+    };
+    let (mut a, b) = (pairs(a), pairs(b));
+    let total = a.len() + b.len();
+    if total == 0 {
+        return 0.0;
+    }
+    let mut matches = 0;
+    for pair in &b {
+        if let Some(i) = a.iter().position(|p| p == pair) {
+            a.swap_remove(i);
+            matches += 1;
+        }
+    }
+    2.0 * matches as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    /// The time the tests run at in milliseconds since the Unix epoch, 2023-11-14T22:13:20Z
+    const NOW: i64 = 1_700_000_000_000;
+
+    fn clock() -> i64 { NOW }
+
+    /// A new message id for every event
+    fn message_id() -> String {
+        static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let n = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        format!("cc106a89-1814-919d-454c-{n:012x}")
+    }
+
+    fn notification(
+        kind: &str,
+        version: &str,
+        condition: serde_json::Value,
+        event: serde_json::Value,
+    ) -> Event {
+        let payload = serde_json::json!({
+            "subscription": {
+                "id": "0b7f3361-672b-4d39-b307-dd5b576c9b27",
+                "status": "enabled",
+                "type": kind,
+                "version": version,
+                "condition": condition,
+                "transport": {
+                    "method": "websocket",
+                    "session_id": "AgoQHR3s6Mb4T8GFB1l3DlPfiRIGY2VsbC1h"
+                },
+                "created_at": "2023-11-06T18:11:47.492253549Z",
+                "cost": 0
+            },
+            "event": event
+        });
+        Event::parse(&payload.to_string()).unwrap()
+    }
+
+    fn chat_message(user_id: &str, text: &str) -> Event {
+        notification(
+            "channel.chat.message",
+            "1",
+            serde_json::json!({
+                "broadcaster_user_id": "1971641",
+                "user_id": "2914196"
+            }),
+            serde_json::json!({
+                "broadcaster_user_id": "1971641",
+                "broadcaster_user_login": "streamer",
+                "broadcaster_user_name": "streamer",
+                "chatter_user_id": user_id,
+                "chatter_user_login": format!("viewer{user_id}"),
+                "chatter_user_name": format!("viewer{user_id}"),
+                "message_id": message_id(),
+                "message": {
+                    "text": text,
+                    "fragments": [{"type": "text", "text": text}]
+                },
+                "color": "",
+                "badges": [],
+                "message_type": "text",
+                "cheer": null,
+                "reply": null,
+                "channel_points_custom_reward_id": null,
+                "source_broadcaster_user_id": null,
+                "source_broadcaster_user_login": null,
+                "source_broadcaster_user_name": null,
+                "source_message_id": null,
+                "source_badges": null
+            }),
+        )
+    }
+
+    fn follow(broadcaster_id: &str, user_id: &str) -> Event {
+        notification(
+            "channel.follow",
+            "2",
+            serde_json::json!({
+                "broadcaster_user_id": broadcaster_id,
+                "moderator_user_id": broadcaster_id
+            }),
+            serde_json::json!({
+                "broadcaster_user_id": broadcaster_id,
+                "broadcaster_user_login": "streamer",
+                "broadcaster_user_name": "streamer",
+                "user_id": user_id,
+                "user_login": format!("viewer{user_id}"),
+                "user_name": format!("viewer{user_id}"),
+                "followed_at": "2023-11-06T18:12:00.123Z"
+            }),
+        )
+    }
+
+    fn raid(to_broadcaster_id: &str) -> Event {
+        notification(
+            "channel.raid",
+            "1",
+            serde_json::json!({
+                "from_broadcaster_user_id": "",
+                "to_broadcaster_user_id": to_broadcaster_id
+            }),
+            serde_json::json!({
+                "from_broadcaster_user_id": "1234",
+                "from_broadcaster_user_login": "raider",
+                "from_broadcaster_user_name": "Raider",
+                "to_broadcaster_user_id": to_broadcaster_id,
+                "to_broadcaster_user_login": "streamer",
+                "to_broadcaster_user_name": "streamer",
+                "viewers": 500
+            }),
+        )
+    }
+
+    fn suspicious_message(broadcaster_id: &str, user_id: &str, text: &str) -> Event {
+        notification(
+            "channel.suspicious_user.message",
+            "1",
+            serde_json::json!({
+                "broadcaster_user_id": broadcaster_id,
+                "moderator_user_id": broadcaster_id
+            }),
+            serde_json::json!({
+                "broadcaster_user_id": broadcaster_id,
+                "broadcaster_user_login": "streamer",
+                "broadcaster_user_name": "streamer",
+                "user_id": user_id,
+                "user_login": format!("viewer{user_id}"),
+                "user_name": format!("viewer{user_id}"),
+                "low_trust_status": "active_monitoring",
+                "shared_ban_channel_ids": [],
+                "types": ["ban_evader"],
+                "ban_evasion_evaluation": "likely",
+                "message": {
+                    "message_id": message_id(),
+                    "text": text,
+                    "fragments": [
+                        {"type": "text", "text": text, "cheermote": null, "emote": null}
+                    ]
+                }
+            }),
+        )
+    }
+
+    #[test]
+    fn similarity_of_messages() {
+        assert_eq!(normalize("  Hello,  WORLD!! "), "hello world");
+        assert_eq!(similarity("follow my channel", "follow my channel"), 1.0);
+        assert!(similarity("follow my channel", "follow my channel pls") > 0.8);
+        assert!(similarity("follow my channel", "nice stream today") < 0.3);
+        assert_eq!(similarity("", "a"), 0.0);
+    }
+
+    #[test]
+    fn spam_lockdown() {
+        let config = ProtectionConfig::default()
+            .similar_messages(3, 0.8)
+            .cooldown(Duration::from_secs(60));
+        let mut shield = RaidShield::new("1971641", config).with_clock(clock);
+        let now = Instant::now();
+
+        assert!(shield
+            .handle_event(&chat_message("1", "hello everyone"), now)
+            .is_empty());
+        assert!(shield
+            .handle_event(&chat_message("2", "buy followers at example dot com"), now)
+            .is_empty());
+        assert!(shield
+            .handle_event(&chat_message("3", "Buy followers at example.com!"), now)
+            .is_empty());
+        let steps = shield.handle_event(&chat_message("4", "buy followers at example com"), now);
+        assert_eq!(
+            steps,
+            [
+                Step::ShieldMode(true),
+                Step::FollowersOnly(Some(Duration::from_secs(600))),
+                Step::SlowMode(Some(Duration::from_secs(10))),
+            ]
+        );
+        assert!(shield.is_locked_down());
+        // spam counts towards message floods too
+        assert_eq!(shield.message_times.len(), 4);
+        let pending: HashSet<_> = shield.pending_lookups().into_iter().collect();
+        assert_eq!(pending, ["2", "3", "4"].map(types::UserId::from).into());
+
+        // an old account is left alone, a new one is banned
+        let recent = crate::unix_millis_to_timestamp(NOW - 60_000);
+        let old = types::Timestamp::try_from("2015-01-01T00:00:00Z").unwrap();
+        assert_eq!(shield.insert_account("2", &old, now), None);
+        assert_eq!(
+            shield.insert_account("3", &recent, now),
+            Some(Step::Ban {
+                user_id: "3".into(),
+                reason: "raid protection".to_owned()
+            })
+        );
+
+        // the lockdown ends after the cooldown, the ban stays
+        let later = now + Duration::from_secs(61);
+        assert_eq!(shield.poll_timeout(), Some(now + Duration::from_secs(60)));
+        assert_eq!(
+            shield.poll(later),
+            [
+                Step::SlowMode(None),
+                Step::FollowersOnly(None),
+                Step::ShieldMode(false),
+            ]
+        );
+        assert!(!shield.is_locked_down());
+        assert_eq!(
+            shield.undo(later),
+            [Step::Unban {
+                user_id: "3".into()
+            }]
+        );
+        assert!(shield.undo(later).is_empty());
+        assert!(shield.log().iter().all(|e| e.undo.is_none() || e.undone));
+    }
+
+    #[test]
+    fn follow_flood() {
+        let config = ProtectionConfig::default().follows(3, Duration::from_secs(60));
+        let mut shield = RaidShield::new("1971641", config).with_clock(clock);
+        let now = Instant::now();
+
+        // follows from old accounts don't count
+        let old = types::Timestamp::try_from("2015-01-01T00:00:00Z").unwrap();
+        assert_eq!(shield.insert_account("10", &old, now), None);
+        assert!(shield
+            .handle_event(&follow("1971641", "10"), now)
+            .is_empty());
+        assert!(shield.handle_event(&follow("1971641", "1"), now).is_empty());
+        // the first follow is out of the window by now
+        let later = now + Duration::from_secs(61);
+        assert!(shield
+            .handle_event(&follow("1971641", "2"), later)
+            .is_empty());
+        assert!(shield
+            .handle_event(&follow("1971641", "3"), later)
+            .is_empty());
+        assert_eq!(
+            shield.handle_event(&follow("1971641", "4"), later),
+            [
+                Step::ShieldMode(true),
+                Step::FollowersOnly(Some(Duration::from_secs(600))),
+                Step::SlowMode(Some(Duration::from_secs(10))),
+            ]
+        );
+        assert_eq!(
+            shield.log()[0].reason,
+            Reason::Threat(Threat::FollowFlood { follows: 3 })
+        );
+        let pending: HashSet<_> = shield.pending_lookups().into_iter().collect();
+        assert_eq!(pending, ["2", "3", "4"].map(types::UserId::from).into());
+    }
+
+    #[test]
+    fn message_flood() {
+        // more messages than are kept for similarity
+        let config = ProtectionConfig::default().message_threshold(400);
+        let mut shield = RaidShield::new("1971641", config).with_clock(clock);
+        let now = Instant::now();
+
+        let message = chat_message("1", "hello");
+        for _ in 1..400 {
+            assert!(shield.handle_event(&message, now).is_empty());
+        }
+        assert!(shield
+            .handle_event(&message, now)
+            .contains(&Step::ShieldMode(true)));
+        assert_eq!(
+            shield.log()[0].reason,
+            Reason::Threat(Threat::MessageFlood { messages: 400 })
+        );
+    }
+
+    #[test]
+    fn raid_lowers_thresholds() {
+        let config = ProtectionConfig::default().follows(4, Duration::from_secs(600));
+        let mut shield = RaidShield::new("1971641", config.clone()).with_clock(clock);
+        let now = Instant::now();
+
+        assert!(shield.handle_event(&raid("1971641"), now).is_empty());
+        assert!(shield.handle_event(&follow("1971641", "1"), now).is_empty());
+        let steps = shield.handle_event(&follow("1971641", "2"), now);
+        assert!(steps.contains(&Step::ShieldMode(true)));
+        assert_eq!(
+            shield.log()[0].reason,
+            Reason::Threat(Threat::FollowFlood { follows: 2 })
+        );
+
+        // the threshold is back to normal after the raid window
+        let mut shield = RaidShield::new("1971641", config).with_clock(clock);
+        let later = now + Duration::from_secs(301);
+        assert!(shield.handle_event(&raid("1971641"), now).is_empty());
+        for user_id in ["1", "2", "3"] {
+            assert!(shield
+                .handle_event(&follow("1971641", user_id), later)
+                .is_empty());
+        }
+        assert!(!shield.is_locked_down());
+    }
+
+    #[test]
+    fn likely_evader() {
+        let now = Instant::now();
+        let mut shield = RaidShield::new("1971641", ProtectionConfig::default()).with_clock(clock);
+        assert!(shield
+            .handle_event(&suspicious_message("1971641", "5", "hi"), now)
+            .is_empty());
+
+        let config = ProtectionConfig::default().ban_likely_evaders(true);
+        let mut shield = RaidShield::new("1971641", config).with_clock(clock);
+        assert_eq!(
+            shield.handle_event(&suspicious_message("1971641", "5", "hi"), now),
+            [Step::Ban {
+                user_id: "5".into(),
+                reason: "raid protection".to_owned()
+            }]
+        );
+        assert_eq!(
+            shield.log()[0].reason,
+            Reason::Threat(Threat::BanEvader {
+                user_id: "5".into()
+            })
+        );
+        // already banned
+        assert!(shield
+            .handle_event(&suspicious_message("1971641", "5", "hello?"), now)
+            .is_empty());
+        assert!(!shield.is_locked_down());
+    }
+
+    #[test]
+    fn other_broadcasters() {
+        let config = ProtectionConfig::default()
+            .follows(3, Duration::from_secs(60))
+            .ban_likely_evaders(true);
+        let mut shield = RaidShield::new("1971641", config).with_clock(clock);
+        let now = Instant::now();
+
+        assert!(shield.handle_event(&raid("1234"), now).is_empty());
+        assert!(shield.handle_event(&follow("1234", "1"), now).is_empty());
+        assert!(shield.handle_event(&follow("1234", "2"), now).is_empty());
+        assert!(shield
+            .handle_event(&suspicious_message("1234", "3", "hi"), now)
+            .is_empty());
+        assert!(shield.log().is_empty());
+
+        // the raid on the other channel didn't lower the threshold either
+        assert!(shield.handle_event(&follow("1971641", "1"), now).is_empty());
+        assert!(shield.handle_event(&follow("1971641", "2"), now).is_empty());
+        assert!(!shield.handle_event(&follow("1971641", "3"), now).is_empty());
+    }
+}
@@ -0,0 +1,739 @@
+//! Reconcile EventSub subscriptions against a desired set
+//!
+//! Webhook and conduit subscriptions outlive the process that created them, so after a restart
+//! there can be duplicate subscriptions, subscriptions that failed verification, or subscriptions that are missing entirely.
+//!
+//! A [`Reconciler`] takes the set of subscriptions you want to exist, compares it to what
+//! [Get EventSub Subscriptions](crate::helix::eventsub::get_eventsub_subscriptions) returns (all pages, all statuses)
+//! and produces a [`Plan`] with the subscriptions that need to be created and deleted.
+//! The plan can be printed for a dry-run, or executed with [`Plan::apply`].
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! # let client: helix::HelixClient<'static, twitch_api::client::DummyHttpClient> = helix::HelixClient::default();
+//! # let client_id = twitch_oauth2::types::ClientId::from_static("your_client_id");
+//! # let client_secret = twitch_oauth2::types::ClientSecret::from_static("your_client_id");
+//! # let token = twitch_oauth2::AppAccessToken::get_app_access_token(&client, client_id, client_secret, vec![]).await?;
+//! use twitch_api::{
+//!     eventsub::{
+//!         self,
+//!         reconcile::{DesiredSubscription, Reconciler},
+//!     },
+//!     helix,
+//! };
+//!
+//! let transport =
+//!     eventsub::Transport::conduit("bb7a1803-eb03-41ef-a1ab-e9242e72053e");
+//! let reconciler = Reconciler::new([
+//!     DesiredSubscription::new(
+//!         eventsub::stream::StreamOnlineV1::broadcaster_user_id("1234"),
+//!         transport.clone(),
+//!     )?,
+//!     DesiredSubscription::new(
+//!         eventsub::stream::StreamOfflineV1::broadcaster_user_id("1234"),
+//!         transport,
+//!     )?,
+//! ]);
+//!
+//! let plan = reconciler.plan(&client, &token).await?;
+//! // Print the plan, this is the dry-run output
+//! println!("{plan}");
+//! let report = plan.apply(&client, &token).await;
+//! println!(
+//!     "created {}, deleted {}, {} failures",
+//!     report.created.len(),
+//!     report.deleted.len(),
+//!     report.failures.len()
+//! );
+//! # Ok(()) }
+//! ```
+
+use crate::{
+    eventsub::{
+        automod, channel, conduit, stream, user, EventSubSubscription, EventSubscription,
+        EventType, Status, Transport, TransportResponse,
+    },
+    helix::{self, ClientRequestError, HelixClient},
+};
+use futures::TryStreamExt;
+use twitch_oauth2::TwitchToken;
+
+type ClientError<C> = ClientRequestError<<C as crate::HttpClient>::Error>;
+
+/// A subscription that should exist
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DesiredSubscription {
+    /// The subscription type
+    pub event_type: EventType,
+    /// The subscription type version
+    pub version: String,
+    /// The [condition](https://dev.twitch.tv/docs/eventsub/eventsub-reference#conditions) for the subscription
+    pub condition: serde_json::Value,
+    /// The transport the subscription should be delivered with
+    pub transport: Transport,
+}
+
+impl DesiredSubscription {
+    /// Create a desired subscription from a [subscription](EventSubscription)
+    pub fn new<E: EventSubscription>(
+        subscription: E,
+        transport: Transport,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            event_type: E::EVENT_TYPE,
+            version: E::VERSION.to_owned(),
+            condition: subscription.condition()?,
+            transport,
+        })
+    }
+
+    /// Create a desired subscription from its parts
+    pub fn from_parts(
+        event_type: EventType,
+        version: impl Into<String>,
+        condition: serde_json::Value,
+        transport: Transport,
+    ) -> Self {
+        Self {
+            event_type,
+            version: version.into(),
+            condition,
+            transport,
+        }
+    }
+
+    /// Returns `true` if the existing subscription has the same type, version, condition and transport as this one.
+    ///
+    /// # Notes
+    ///
+    /// The secret of a webhook is never returned by Twitch, so only the callback is compared.
+    ///
+    /// Optional condition fields that are `null` or empty are ignored, Twitch returns unset fields as empty strings.
+    pub fn matches(&self, existing: &EventSubSubscription) -> bool {
+        self.event_type == existing.type_
+            && self.version == existing.version
+            && normalize_condition(&self.condition) == normalize_condition(&existing.condition)
+            && transport_matches(&self.transport, &existing.transport)
+    }
+}
+
+impl std::fmt::Display for DesiredSubscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} v{} {} via {}",
+            self.event_type,
+            self.version,
+            normalize_condition(&self.condition),
+            TransportDisplay(&self.transport)
+        )
+    }
+}
+
+/// Why a subscription is going to be deleted
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeleteReason {
+    /// The subscription is in a state where Twitch will not deliver notifications anymore, e.g. [`Status::WebhookCallbackVerificationFailed`]
+    Stale(Status),
+    /// Another subscription with the same type, version, condition and transport is kept instead
+    Duplicate,
+    /// The subscription is not part of the desired set
+    Unwanted,
+}
+
+impl std::fmt::Display for DeleteReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeleteReason::Stale(status) => write!(f, "stale: {}", StatusDisplay(status)),
+            DeleteReason::Duplicate => f.write_str("duplicate"),
+            DeleteReason::Unwanted => f.write_str("unwanted"),
+        }
+    }
+}
+
+/// An existing subscription that is going to be deleted
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Deletion {
+    /// The subscription to delete
+    pub subscription: EventSubSubscription,
+    /// The reason for deleting it
+    pub reason: DeleteReason,
+}
+
+/// Reconciles EventSub subscriptions against a desired set.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone, Debug, Default)]
+pub struct Reconciler {
+    desired: Vec<DesiredSubscription>,
+    prune: bool,
+}
+
+impl Reconciler {
+    /// Create a reconciler for the given desired subscriptions.
+    ///
+    /// Subscriptions that are not in the desired set are kept unless [pruning](Reconciler::prune) is enabled
+    pub fn new(desired: impl IntoIterator<Item = DesiredSubscription>) -> Self {
+        let mut reconciler = Self {
+            desired: vec![],
+            prune: false,
+        };
+        for subscription in desired {
+            reconciler = reconciler.with(subscription);
+        }
+        reconciler
+    }
+
+    /// Add a desired subscription. Adding the same subscription twice has no effect.
+    pub fn with(mut self, subscription: DesiredSubscription) -> Self {
+        if !self.desired.contains(&subscription) {
+            self.desired.push(subscription);
+        }
+        self
+    }
+
+    /// Set whether subscriptions that are not part of the desired set should be deleted. Disabled by default.
+    ///
+    /// Only enable this if the desired set contains every subscription of the client id,
+    /// otherwise subscriptions managed by other services are deleted as well.
+    /// Stale subscriptions and duplicates of desired subscriptions are deleted regardless.
+    pub const fn prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    /// The desired subscriptions
+    pub fn desired(&self) -> &[DesiredSubscription] { &self.desired }
+
+    /// Fetch all existing subscriptions and compute the [`Plan`] to converge to the desired set.
+    pub async fn plan<'c, C, T>(
+        &self,
+        client: &'c HelixClient<'c, C>,
+        token: &'c T,
+    ) -> Result<Plan, ClientError<C>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: TwitchToken + Send + Sync + ?Sized,
+    {
+        let pages: Vec<helix::eventsub::EventSubSubscriptions> = client
+            .get_eventsub_subscriptions(None, None, None, token)
+            .try_collect()
+            .await?;
+        Ok(self.plan_from_pages(&pages))
+    }
+
+    /// Compute the [`Plan`] from already fetched pages of [Get EventSub Subscriptions](crate::helix::eventsub::get_eventsub_subscriptions)
+    pub fn plan_from_pages(&self, pages: &[helix::eventsub::EventSubSubscriptions]) -> Plan {
+        // The costs are the same on every page, the last page is the freshest.
+        let (total_cost, max_total_cost) = pages
+            .last()
+            .map(|p| (p.total_cost, p.max_total_cost))
+            .unwrap_or_default();
+        let existing = pages
+            .iter()
+            .flat_map(|p| p.subscriptions.iter().cloned())
+            .collect::<Vec<_>>();
+        self.plan_from(existing, total_cost, max_total_cost)
+    }
+
+    /// Compute the [`Plan`] from a list of existing subscriptions and the current costs.
+    pub fn plan_from(
+        &self,
+        existing: impl IntoIterator<Item = EventSubSubscription>,
+        total_cost: usize,
+        max_total_cost: usize,
+    ) -> Plan {
+        let mut plan = Plan {
+            create: vec![],
+            delete: vec![],
+            keep: vec![],
+            total_cost,
+            max_total_cost,
+        };
+        // For every desired subscription, the existing ones that match it.
+        let mut matched: Vec<Vec<EventSubSubscription>> = vec![vec![]; self.desired.len()];
+
+        for subscription in existing {
+            if !is_live(&subscription.status) {
+                let reason = DeleteReason::Stale(subscription.status.clone());
+                plan.delete.push(Deletion {
+                    subscription,
+                    reason,
+                });
+            } else if let Some(i) = self.desired.iter().position(|d| d.matches(&subscription)) {
+                matched[i].push(subscription);
+            } else if self.prune {
+                plan.delete.push(Deletion {
+                    subscription,
+                    reason: DeleteReason::Unwanted,
+                });
+            } else {
+                plan.keep.push(subscription);
+            }
+        }
+
+        for (desired, mut matches) in self.desired.iter().zip(matched) {
+            if matches.is_empty() {
+                plan.create.push(desired.clone());
+                continue;
+            }
+            // Prefer enabled subscriptions, then the oldest one.
+            matches.sort_by(|a, b| {
+                (a.status != Status::Enabled)
+                    .cmp(&(b.status != Status::Enabled))
+                    .then_with(|| a.created_at.as_str().cmp(b.created_at.as_str()))
+            });
+            let mut matches = matches.into_iter();
+            plan.keep.extend(matches.next());
+            plan.delete.extend(matches.map(|subscription| Deletion {
+                subscription,
+                reason: DeleteReason::Duplicate,
+            }));
+        }
+        plan
+    }
+}
+
+/// Changes needed to converge the existing subscriptions to the desired set.
+///
+/// The [`Display`](std::fmt::Display) implementation gives a human readable dry-run output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Plan {
+    /// Subscriptions that will be created
+    pub create: Vec<DesiredSubscription>,
+    /// Subscriptions that will be deleted
+    pub delete: Vec<Deletion>,
+    /// Subscriptions that are left untouched
+    pub keep: Vec<EventSubSubscription>,
+    /// Total cost of all existing subscriptions, as reported by [`EventSubSubscriptions::total_cost`](helix::eventsub::EventSubSubscriptions::total_cost)
+    pub total_cost: usize,
+    /// The maximum total cost allowed, as reported by [`EventSubSubscriptions::max_total_cost`](helix::eventsub::EventSubSubscriptions::max_total_cost)
+    pub max_total_cost: usize,
+}
+
+impl Plan {
+    /// Returns `true` if nothing needs to be created or deleted.
+    pub fn is_empty(&self) -> bool { self.create.is_empty() && self.delete.is_empty() }
+
+    /// The cost that will be freed by the deletions
+    pub fn freed_cost(&self) -> usize { self.delete.iter().map(|d| d.subscription.cost).sum() }
+
+    /// The total cost after the deletions, but before any creations.
+    ///
+    /// The cost of a new subscription is only known once it's created,
+    /// it's `0` if the user in the condition has authorized the client and `1` otherwise.
+    pub fn cost_after_deletions(&self) -> usize {
+        self.total_cost.saturating_sub(self.freed_cost())
+    }
+
+    /// The worst case total cost after applying this plan, assuming every new subscription costs `1`.
+    pub fn max_cost_after_apply(&self) -> usize { self.cost_after_deletions() + self.create.len() }
+
+    /// Returns `true` if applying this plan could exceed [`max_total_cost`](Plan::max_total_cost)
+    pub fn may_exceed_max_cost(&self) -> bool { self.max_cost_after_apply() > self.max_total_cost }
+
+    /// Apply the plan, deleting subscriptions first to free up cost, then creating the missing ones.
+    ///
+    /// Failures do not abort the operation, they're collected in [`Report::failures`]
+    pub async fn apply<'c, C, T>(self, client: &'c HelixClient<'c, C>, token: &'c T) -> Report<C>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: TwitchToken + Send + Sync + ?Sized, {
+        let mut report = Report {
+            created: vec![],
+            deleted: vec![],
+            failures: vec![],
+            total_cost: self.total_cost,
+            max_total_cost: self.max_total_cost,
+        };
+
+        for deletion in self.delete {
+            match client
+                .delete_eventsub_subscription(&deletion.subscription.id, token)
+                .await
+            {
+                Ok(_) => {
+                    report.total_cost =
+                        report.total_cost.saturating_sub(deletion.subscription.cost);
+                    report.deleted.push(deletion);
+                }
+                Err(error) => report.failures.push(Failure::Delete { deletion, error }),
+            }
+        }
+
+        for desired in self.create {
+            match create(client, &desired, token).await {
+                Ok(created) => {
+                    report.total_cost = created.total_cost;
+                    report.max_total_cost = created.max_total_cost;
+                    report.created.push(created.subscription);
+                }
+                Err(error) => report.failures.push(Failure::Create { desired, error }),
+            }
+        }
+        report
+    }
+}
+
+impl std::fmt::Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for desired in &self.create {
+            writeln!(f, "+ {desired}")?;
+        }
+        for Deletion {
+            subscription,
+            reason,
+        } in &self.delete
+        {
+            writeln!(
+                f,
+                "- {} {} v{} {} via {} ({reason}, cost {})",
+                subscription.id,
+                subscription.type_,
+                subscription.version,
+                normalize_condition(&subscription.condition),
+                TransportResponseDisplay(&subscription.transport),
+                subscription.cost,
+            )?;
+        }
+        writeln!(
+            f,
+            "{} to create, {} to delete, {} unchanged",
+            self.create.len(),
+            self.delete.len(),
+            self.keep.len()
+        )?;
+        write!(
+            f,
+            "cost: {} of {} now, {} after deletions, at most {} after creations",
+            self.total_cost,
+            self.max_total_cost,
+            self.cost_after_deletions(),
+            self.max_cost_after_apply()
+        )
+    }
+}
+
+/// Result of [`Plan::apply`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Report<C: crate::HttpClient> {
+    /// Subscriptions that were created
+    pub created: Vec<EventSubSubscription>,
+    /// Subscriptions that were deleted
+    pub deleted: Vec<Deletion>,
+    /// Operations that failed
+    pub failures: Vec<Failure<C>>,
+    /// Total cost after applying, as last reported by Twitch or computed from the deletions
+    pub total_cost: usize,
+    /// The maximum total cost allowed
+    pub max_total_cost: usize,
+}
+
+impl<C: crate::HttpClient> Report<C> {
+    /// Returns `true` if every operation succeeded
+    pub fn is_success(&self) -> bool { self.failures.is_empty() }
+}
+
+/// A failed operation in [`Plan::apply`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Failure<C: crate::HttpClient> {
+    /// Creating a subscription failed
+    Create {
+        /// The subscription that could not be created
+        desired: DesiredSubscription,
+        /// The error
+        error: CreateError<C>,
+    },
+    /// Deleting a subscription failed
+    Delete {
+        /// The subscription that could not be deleted
+        deletion: Deletion,
+        /// The error
+        error: ClientError<C>,
+    },
+}
+
+/// Errors that can happen when creating a [`DesiredSubscription`]
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+#[non_exhaustive]
+pub enum CreateError<C: crate::HttpClient> {
+    /// request failed
+    Request(#[from] ClientError<C>),
+    /// condition could not be converted to the subscription type
+    Condition(#[from] serde_json::Error),
+    /// could not find an implementation for version `{version}` on event type `{event_type}` in this library
+    Unimplemented {
+        /// Version
+        version: String,
+        /// Event type
+        event_type: EventType,
+    },
+}
+
+struct Created {
+    subscription: EventSubSubscription,
+    total_cost: usize,
+    max_total_cost: usize,
+}
+
+#[allow(deprecated)]
+async fn create<'c, C, T>(
+    client: &'c HelixClient<'c, C>,
+    desired: &DesiredSubscription,
+    token: &'c T,
+) -> Result<Created, CreateError<C>>
+where
+    C: crate::HttpClient + Send + Sync + 'c,
+    T: TwitchToken + Send + Sync + ?Sized,
+{
+    macro_rules! match_event {
+        ($($(#[$meta:meta])* $module:ident::$event:ident);* $(;)?) => {{
+            #[deny(unreachable_patterns)]
+            match (desired.version.as_str(), &desired.event_type) {
+                $(  $(#[$meta])* (<$module::$event as EventSubscription>::VERSION, &<$module::$event as EventSubscription>::EVENT_TYPE) => {
+                    let subscription: $module::$event = serde_json::from_value(desired.condition.clone())?;
+                    let created = client
+                        .create_eventsub_subscription(subscription, desired.transport.clone(), token)
+                        .await?;
+                    Created {
+                        subscription: EventSubSubscription {
+                            cost: created.cost,
+                            condition: created.condition.condition()?,
+                            created_at: created.created_at,
+                            id: created.id,
+                            status: created.status,
+                            transport: created.transport,
+                            type_: created.type_,
+                            version: created.version,
+                        },
+                        total_cost: created.total_cost,
+                        max_total_cost: created.max_total_cost,
+                    }
+                }  )*
+                (v, e) => return Err(CreateError::Unimplemented { version: v.to_owned(), event_type: *e }),
+            }
+        }}
+    }
+
+    Ok(fill_events!(match_event()))
+}
+
+/// Subscriptions in these states will still deliver notifications
+fn is_live(status: &Status) -> bool {
+    matches!(
+        status,
+        Status::Enabled | Status::WebhookCallbackVerificationPending
+    )
+}
+
+/// Remove `null` and empty string values, Twitch returns unset optional condition fields as `""`.
+pub(crate) fn normalize_condition(condition: &serde_json::Value) -> serde_json::Value {
+    match condition {
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.iter()
+                .filter(|(_, v)| !v.is_null() && v.as_str() != Some(""))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn transport_matches(desired: &Transport, existing: &TransportResponse) -> bool {
+    match (desired, existing) {
+        (Transport::Webhook(d), TransportResponse::Webhook(e)) => d.callback == e.callback,
+        (Transport::Websocket(d), TransportResponse::Websocket(e)) => d.session_id == e.session_id,
+        (Transport::Conduit(d), TransportResponse::Conduit(e)) => d.conduit_id == e.conduit_id,
+        _ => false,
+    }
+}
+
+struct StatusDisplay<'a>(&'a Status);
+
+impl std::fmt::Display for StatusDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match serde_json::to_value(self.0) {
+            Ok(serde_json::Value::String(s)) => f.write_str(&s),
+            _ => write!(f, "{:?}", self.0),
+        }
+    }
+}
+
+struct TransportDisplay<'a>(&'a Transport);
+
+impl std::fmt::Display for TransportDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Transport::Webhook(t) => write!(f, "webhook {}", t.callback),
+            Transport::Websocket(t) => write!(f, "websocket {}", t.session_id),
+            Transport::Conduit(t) => write!(f, "conduit {}", t.conduit_id),
+        }
+    }
+}
+
+struct TransportResponseDisplay<'a>(&'a TransportResponse);
+
+impl std::fmt::Display for TransportResponseDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            TransportResponse::Webhook(t) => write!(f, "webhook {}", t.callback),
+            TransportResponse::Websocket(t) => write!(f, "websocket {}", t.session_id),
+            TransportResponse::Conduit(t) => write!(f, "conduit {}", t.conduit_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn existing(
+        id: &str,
+        status: &str,
+        condition: serde_json::Value,
+        created_at: &str,
+    ) -> EventSubSubscription {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "status": status,
+            "type": "stream.online",
+            "version": "1",
+            "condition": condition,
+            "created_at": created_at,
+            "transport": {
+                "method": "webhook",
+                "callback": "https://example.com/webhooks/callback"
+            },
+            "cost": 1
+        }))
+        .unwrap()
+    }
+
+    fn desired(broadcaster: &str) -> DesiredSubscription {
+        DesiredSubscription::new(
+            stream::StreamOnlineV1::broadcaster_user_id(broadcaster),
+            Transport::webhook(
+                "https://example.com/webhooks/callback",
+                "secretabcd".to_owned(),
+            ),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn converge() {
+        let reconciler = Reconciler::new([desired("1"), desired("2"), desired("3")]);
+        let existing = vec![
+            existing(
+                "a",
+                "enabled",
+                serde_json::json!({"broadcaster_user_id": "1"}),
+                "2020-11-10T20:08:33.12345678Z",
+            ),
+            // duplicate of "a", newer
+            existing(
+                "b",
+                "enabled",
+                serde_json::json!({"broadcaster_user_id": "1"}),
+                "2021-11-10T20:08:33.12345678Z",
+            ),
+            existing(
+                "c",
+                "webhook_callback_verification_failed",
+                serde_json::json!({"broadcaster_user_id": "2"}),
+                "2020-11-10T20:08:33.12345678Z",
+            ),
+            existing(
+                "d",
+                "enabled",
+                serde_json::json!({"broadcaster_user_id": "4"}),
+                "2020-11-10T20:08:33.12345678Z",
+            ),
+        ];
+        let plan = reconciler
+            .clone()
+            .prune(true)
+            .plan_from(existing.clone(), 4, 10);
+
+        assert_eq!(plan.create, vec![desired("2"), desired("3")]);
+        assert_eq!(plan.keep.len(), 1);
+        assert_eq!(plan.keep[0].id.as_str(), "a");
+        let deleted = plan
+            .delete
+            .iter()
+            .map(|d| (d.subscription.id.as_str(), d.reason.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            deleted,
+            vec![
+                (
+                    "c",
+                    DeleteReason::Stale(Status::WebhookCallbackVerificationFailed)
+                ),
+                ("d", DeleteReason::Unwanted),
+                ("b", DeleteReason::Duplicate),
+            ]
+        );
+        assert_eq!(plan.freed_cost(), 3);
+        assert_eq!(plan.cost_after_deletions(), 1);
+        assert_eq!(plan.max_cost_after_apply(), 3);
+        assert!(!plan.may_exceed_max_cost());
+        assert_eq!(
+            plan.to_string(),
+            "+ stream.online v1 {\"broadcaster_user_id\":\"2\"} via webhook https://example.com/webhooks/callback\n\
+             + stream.online v1 {\"broadcaster_user_id\":\"3\"} via webhook https://example.com/webhooks/callback\n\
+             - c stream.online v1 {\"broadcaster_user_id\":\"2\"} via webhook https://example.com/webhooks/callback (stale: webhook_callback_verification_failed, cost 1)\n\
+             - d stream.online v1 {\"broadcaster_user_id\":\"4\"} via webhook https://example.com/webhooks/callback (unwanted, cost 1)\n\
+             - b stream.online v1 {\"broadcaster_user_id\":\"1\"} via webhook https://example.com/webhooks/callback (duplicate, cost 1)\n\
+             2 to create, 3 to delete, 1 unchanged\n\
+             cost: 4 of 10 now, 1 after deletions, at most 3 after creations"
+        );
+
+        // subscriptions that are not desired are kept by default
+        let plan = reconciler.plan_from(existing, 4, 10);
+        assert!(plan.keep.iter().any(|s| s.id.as_str() == "d"));
+        assert!(plan
+            .delete
+            .iter()
+            .all(|d| d.reason != DeleteReason::Unwanted));
+    }
+
+    #[test]
+    fn empty_condition_fields() {
+        let desired = DesiredSubscription::new(
+            channel::ChannelRaidV1::to_broadcaster_user_id("1337"),
+            Transport::websocket("AQoQexAWVYKSTIu4ec_2VAxyuhAB"),
+        )
+        .unwrap();
+        let existing: EventSubSubscription = serde_json::from_value(serde_json::json!({
+            "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+            "status": "enabled",
+            "type": "channel.raid",
+            "version": "1",
+            "condition": {
+                "from_broadcaster_user_id": "",
+                "to_broadcaster_user_id": "1337"
+            },
+            "created_at": "2020-11-10T20:08:33.12345678Z",
+            "transport": {
+                "method": "websocket",
+                "session_id": "AQoQexAWVYKSTIu4ec_2VAxyuhAB",
+                "connected_at": "2020-11-10T20:08:33.12345678Z"
+            },
+            "cost": 0
+        }))
+        .unwrap();
+        assert!(desired.matches(&existing));
+        let plan = Reconciler::new([desired]).plan_from([existing], 0, 10);
+        assert!(plan.is_empty());
+    }
+}
@@ -0,0 +1,482 @@
+//! Route [`Event`]s to typed handlers
+//!
+//! Instead of matching on every variant of [`Event`], register a handler per [`EventSubscription`] type.
+//! Handlers are called with the typed notification payload and a [`Context`] containing the subscription
+//! and message metadata.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use twitch_api::eventsub::{self, router::Router, Event};
+//!
+//! let router = Router::new()
+//!     .on::<eventsub::channel::ChannelChatMessageV1, _>(|payload, _ctx| {
+//!         println!("{}: {}", payload.chatter_user_name, payload.message.text);
+//!     })
+//!     .on_async::<eventsub::stream::StreamOnlineV1, _, _>(|payload, ctx| async move {
+//!         println!(
+//!             "{} went live, message id: {:?}",
+//!             payload.broadcaster_user_name, ctx.metadata.message_id
+//!         );
+//!     })
+//!     .fallback(|event, _metadata| {
+//!         println!("unhandled event: {:?}", event);
+//!     });
+//!
+//! # let frame = "";
+//! # if !frame.is_empty() {
+//! // `frame` is a text message received on the EventSub websocket
+//! router.dispatch_websocket(Event::parse_websocket(frame)?).await;
+//! # }
+//! # Ok(()) }
+//! ```
+//!
+//! Routers can be built in separate modules and combined with [`Router::merge`].
+
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use super::{
+    Event, EventSubSubscription, EventSubscription, EventSubscriptionInformation, EventType,
+    EventVariant, EventsubWebsocketData, Message,
+};
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+type ErasedHandler = Arc<dyn Fn(&Event, &Metadata) -> Option<BoxFuture> + Send + Sync>;
+type FallbackHandler = Arc<dyn Fn(Event, Metadata) -> BoxFuture + Send + Sync>;
+type RevocationHandler = Arc<dyn Fn(EventSubSubscription, Metadata) -> BoxFuture + Send + Sync>;
+
+/// Metadata about the message an event was delivered in
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Metadata {
+    /// An ID that uniquely identifies the message, use this to deduplicate messages.
+    pub message_id: Option<String>,
+    /// The UTC date and time that the message was sent
+    pub message_timestamp: Option<crate::types::Timestamp>,
+}
+
+impl Metadata {
+    /// Create metadata from a message id and timestamp
+    pub fn new(
+        message_id: impl Into<Option<String>>,
+        message_timestamp: impl Into<Option<crate::types::Timestamp>>,
+    ) -> Self {
+        Self {
+            message_id: message_id.into(),
+            message_timestamp: message_timestamp.into(),
+        }
+    }
+
+    /// Get the metadata from the `Twitch-Eventsub-Message-Id` and `Twitch-Eventsub-Message-Timestamp` headers of a webhook request
+    pub fn from_http<B>(request: &http::Request<B>) -> Self {
+        use std::convert::TryFrom;
+
+        let header = |name: &str| request.headers().get(name).and_then(|v| v.to_str().ok());
+        Self {
+            message_id: header("Twitch-Eventsub-Message-Id").map(str::to_owned),
+            message_timestamp: header("Twitch-Eventsub-Message-Timestamp")
+                .and_then(|t| crate::types::Timestamp::try_from(t).ok()),
+        }
+    }
+}
+
+/// Context passed to a typed handler
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Context<E: EventSubscription> {
+    /// The subscription that delivered the event
+    pub subscription: EventSubscriptionInformation<E>,
+    /// Metadata about the message
+    pub metadata: Metadata,
+}
+
+struct Route {
+    event_type: EventType,
+    version: &'static str,
+    handler: ErasedHandler,
+}
+
+/// Routes [`Event`]s to handlers registered per [`EventSubscription`] type.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone, Default)]
+pub struct Router {
+    routes: Vec<Arc<Route>>,
+    fallback: Option<FallbackHandler>,
+    revocation: Option<RevocationHandler>,
+    broadcasters: Option<Vec<String>>,
+}
+
+impl std::fmt::Debug for Router {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Router")
+            .field(
+                "routes",
+                &self
+                    .routes
+                    .iter()
+                    .map(|r| (r.event_type, r.version))
+                    .collect::<Vec<_>>(),
+            )
+            .field("fallback", &self.fallback.is_some())
+            .field("revocation", &self.revocation.is_some())
+            .field("broadcasters", &self.broadcasters)
+            .finish()
+    }
+}
+
+impl Router {
+    /// Create an empty router
+    pub fn new() -> Self { Self::default() }
+
+    /// Register a handler for notifications of `E`.
+    ///
+    /// Multiple handlers can be registered for the same type, they are called in the order they were registered.
+    pub fn on<E, F>(self, handler: F) -> Self
+    where
+        E: EventVariant + Send + 'static,
+        E::Payload: Send,
+        F: Fn(E::Payload, Context<E>) + Send + Sync + 'static, {
+        self.on_async::<E, _, _>(move |payload, ctx| {
+            handler(payload, ctx);
+            std::future::ready(())
+        })
+    }
+
+    /// Register an async handler for notifications of `E`.
+    ///
+    /// Multiple handlers can be registered for the same type, they are called in the order they were registered.
+    pub fn on_async<E, F, Fut>(mut self, handler: F) -> Self
+    where
+        E: EventVariant + Send + 'static,
+        E::Payload: Send,
+        F: Fn(E::Payload, Context<E>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static, {
+        let handler: ErasedHandler = Arc::new(move |event, metadata| {
+            let payload = E::from_event_ref(event)?;
+            let Message::Notification(notification) = &payload.message else {
+                return None;
+            };
+            let fut = handler(
+                notification.clone(),
+                Context {
+                    subscription: payload.subscription.clone(),
+                    metadata: metadata.clone(),
+                },
+            );
+            Some(Box::pin(fut) as BoxFuture)
+        });
+        self.routes.push(Arc::new(Route {
+            event_type: E::EVENT_TYPE,
+            version: E::VERSION,
+            handler,
+        }));
+        self
+    }
+
+    /// Set the handler for notifications that no typed handler is registered for,
+    /// and for webhook verification requests.
+    pub fn fallback<F>(self, handler: F) -> Self
+    where F: Fn(Event, Metadata) + Send + Sync + 'static {
+        self.fallback_async(move |event, metadata| {
+            handler(event, metadata);
+            std::future::ready(())
+        })
+    }
+
+    /// Set the async handler for notifications that no typed handler is registered for,
+    /// and for webhook verification requests.
+    pub fn fallback_async<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(Event, Metadata) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static, {
+        self.fallback = Some(Arc::new(move |event, metadata| {
+            Box::pin(handler(event, metadata))
+        }));
+        self
+    }
+
+    /// Set the handler for revocation messages.
+    ///
+    /// If no revocation handler is set, revocations are passed to the [fallback](Router::fallback).
+    pub fn on_revocation<F>(self, handler: F) -> Self
+    where F: Fn(EventSubSubscription, Metadata) + Send + Sync + 'static {
+        self.on_revocation_async(move |subscription, metadata| {
+            handler(subscription, metadata);
+            std::future::ready(())
+        })
+    }
+
+    /// Set the async handler for revocation messages.
+    ///
+    /// If no revocation handler is set, revocations are passed to the [fallback](Router::fallback).
+    pub fn on_revocation_async<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(EventSubSubscription, Metadata) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static, {
+        self.revocation = Some(Arc::new(move |subscription, metadata| {
+            Box::pin(handler(subscription, metadata))
+        }));
+        self
+    }
+
+    /// Only dispatch events for these broadcasters.
+    ///
+    /// The broadcaster is taken from the subscription condition,
+    /// i.e. `broadcaster_user_id`, `to_broadcaster_user_id` or `from_broadcaster_user_id`.
+    /// Events with no broadcaster in their condition, like [`user.update`](super::user::UserUpdateV1), are always dispatched.
+    pub fn broadcasters<I, S>(mut self, broadcasters: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>, {
+        self.broadcasters = Some(broadcasters.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Add all handlers of `other` to this router.
+    ///
+    /// The fallback and revocation handlers of `other` are only used if this router doesn't have one.
+    /// The broadcaster filter of `other` is ignored.
+    pub fn merge(mut self, other: Router) -> Self {
+        self.routes.extend(other.routes);
+        self.fallback = self.fallback.or(other.fallback);
+        self.revocation = self.revocation.or(other.revocation);
+        self
+    }
+
+    /// Returns `true` if a typed handler is registered for this type and version
+    pub fn handles(&self, event_type: EventType, version: &str) -> bool {
+        self.routes
+            .iter()
+            .any(|r| r.event_type == event_type && r.version == version)
+    }
+
+    /// Dispatch an event without any message metadata.
+    ///
+    /// Returns `true` if a handler was called.
+    pub async fn dispatch(&self, event: Event) -> bool {
+        self.dispatch_with(event, Metadata::default()).await
+    }
+
+    /// Dispatch a parsed webhook request, taking the metadata from its headers.
+    ///
+    /// Returns `true` if a handler was called.
+    pub async fn dispatch_http<B>(&self, request: &http::Request<B>, event: Event) -> bool {
+        self.dispatch_with(event, Metadata::from_http(request))
+            .await
+    }
+
+    /// Dispatch a websocket message. Only notifications and revocations are dispatched.
+    ///
+    /// Returns `true` if a handler was called.
+    pub async fn dispatch_websocket(&self, data: EventsubWebsocketData<'_>) -> bool {
+        let (event, metadata) = match data {
+            EventsubWebsocketData::Notification { metadata, payload } => (
+                payload,
+                Metadata::new(
+                    metadata.message_id.into_owned(),
+                    metadata.message_timestamp.into_owned(),
+                ),
+            ),
+            EventsubWebsocketData::Revocation { metadata, payload } => (
+                payload,
+                Metadata::new(
+                    metadata.message_id.into_owned(),
+                    metadata.message_timestamp.into_owned(),
+                ),
+            ),
+            _ => return false,
+        };
+        self.dispatch_with(event, metadata).await
+    }
+
+    /// Dispatch an event with the given metadata.
+    ///
+    /// Returns `true` if a handler was called.
+    pub async fn dispatch_with(&self, event: Event, metadata: Metadata) -> bool {
+        if !self.is_allowed(&event) {
+            return false;
+        }
+
+        if event.is_revocation() {
+            if let Some(revocation) = &self.revocation {
+                if let Ok(subscription) = event.subscription() {
+                    revocation(subscription, metadata).await;
+                    return true;
+                }
+            }
+        } else {
+            let futures = self
+                .routes
+                .iter()
+                .filter_map(|r| (r.handler)(&event, &metadata))
+                .collect::<Vec<_>>();
+            if !futures.is_empty() {
+                for fut in futures {
+                    fut.await;
+                }
+                return true;
+            }
+        }
+
+        match &self.fallback {
+            Some(fallback) => {
+                fallback(event, metadata).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn is_allowed(&self, event: &Event) -> bool {
+        let Some(broadcasters) = &self.broadcasters else {
+            return true;
+        };
+        let Ok(subscription) = event.subscription() else {
+            return true;
+        };
+        let ids = [
+            "broadcaster_user_id",
+            "to_broadcaster_user_id",
+            "from_broadcaster_user_id",
+        ]
+        .iter()
+        .filter_map(|k| subscription.condition.get(*k)?.as_str())
+        .filter(|id| !id.is_empty())
+        .collect::<Vec<_>>();
+        ids.is_empty() || ids.iter().any(|id| broadcasters.iter().any(|b| b == id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::eventsub::{channel, stream};
+
+    const FOLLOW: &str = r#"
+    {
+        "subscription": {
+            "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+            "type": "channel.follow",
+            "version": "2",
+            "status": "enabled",
+            "cost": 0,
+            "condition": {
+               "broadcaster_user_id": "1337",
+               "moderator_user_id": "1337"
+            },
+             "transport": {
+                "method": "webhook",
+                "callback": "https://example.com/webhooks/callback"
+            },
+            "created_at": "2019-11-16T10:11:12.634234626Z"
+        },
+        "event": {
+            "user_id": "1234",
+            "user_login": "cool_user",
+            "user_name": "Cool_User",
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cooler_user",
+            "broadcaster_user_name": "Cooler_User",
+            "followed_at": "2020-07-15T18:16:11.17106713Z"
+        }
+    }
+    "#;
+
+    fn block_on<F: Future>(fut: F) -> F::Output { futures::executor::block_on(fut) }
+
+    #[test]
+    fn route_typed() {
+        let follows = Arc::new(AtomicUsize::new(0));
+        let unhandled = Arc::new(AtomicUsize::new(0));
+        let router = Router::new()
+            .on::<channel::ChannelFollowV2, _>({
+                let follows = follows.clone();
+                move |payload, ctx| {
+                    assert_eq!(payload.user_login.as_str(), "cool_user");
+                    assert_eq!(
+                        ctx.subscription.condition.broadcaster_user_id.as_str(),
+                        "1337"
+                    );
+                    assert_eq!(ctx.metadata.message_id.as_deref(), Some("abc"));
+                    follows.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .on_async::<stream::StreamOnlineV1, _, _>(|_, _| async {
+                panic!("wrong handler");
+            })
+            .fallback({
+                let unhandled = unhandled.clone();
+                move |_, _| {
+                    unhandled.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+
+        let event = Event::parse(FOLLOW).unwrap();
+        assert!(block_on(router.dispatch_with(
+            event.clone(),
+            Metadata::new("abc".to_owned(), None)
+        )));
+        assert_eq!(follows.load(Ordering::SeqCst), 1);
+        assert_eq!(unhandled.load(Ordering::SeqCst), 0);
+
+        // filtered out
+        let filtered = router.clone().broadcasters(["1"]);
+        assert!(!block_on(filtered.dispatch(event.clone())));
+        assert_eq!(follows.load(Ordering::SeqCst), 1);
+
+        // no typed handler, goes to fallback
+        let router = Router::new().fallback({
+            let unhandled = unhandled.clone();
+            move |_, _| {
+                unhandled.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        assert!(block_on(router.dispatch(event)));
+        assert_eq!(unhandled.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn route_revocation() {
+        let revocation = r#"
+        {
+            "subscription": {
+                "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+                "status": "authorization_revoked",
+                "type": "channel.follow",
+                "cost": 1,
+                "version": "2",
+                "condition": {
+                    "broadcaster_user_id": "1337",
+                    "moderator_user_id": "1337"
+                },
+                "transport": {
+                    "method": "webhook",
+                    "callback": "https://example.com/webhooks/callback"
+                },
+                "created_at": "2019-11-16T10:11:12.634234626Z"
+            }
+        }
+        "#;
+        let revoked = Arc::new(AtomicUsize::new(0));
+        let router = Router::new()
+            .on::<channel::ChannelFollowV2, _>(|_, _| panic!("not a notification"))
+            .on_revocation({
+                let revoked = revoked.clone();
+                move |subscription, _| {
+                    assert_eq!(subscription.type_, EventType::ChannelFollow);
+                    revoked.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+        let event = Event::parse(revocation).unwrap();
+        assert!(block_on(router.dispatch(event)));
+        assert_eq!(revoked.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,254 @@
+//! Find cheermotes in chat messages
+//!
+//! EventSub `channel.chat.message` events already split cheermotes
+//! into fragments, but messages from other sources, like IRC or chat logs, are plain text.
+//! [`parse`] splits such a message into [text](Token::Text) and [cheers](Token::Cheer), using the cheermotes
+//! available in the channel, as returned by [Get Cheermotes](super::get_cheermotes).
+//!
+//! A cheer is a word made of a cheermote prefix, in any case, followed by the amount of bits, for example `Cheer100` or `kappa500`.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # use twitch_api::helix::{HelixClient, bits::GetCheermotesRequest};
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! # let client: HelixClient<'static, twitch_api::DummyHttpClient> = HelixClient::default();
+//! # let token = twitch_oauth2::AccessToken::new("validtoken".to_string());
+//! # let token = twitch_oauth2::UserToken::from_existing(&client, token, None, None).await?;
+//! use twitch_api::helix::bits::cheer::{self, Token};
+//!
+//! let cheermotes = client
+//!     .req_get(GetCheermotesRequest::broadcaster_id("1234"), &token)
+//!     .await?
+//!     .data;
+//! let message = cheer::parse("Cheer100 great stream! kappa50", &cheermotes);
+//! assert_eq!(message.total_bits(), 150);
+//! for token in message.tokens() {
+//!     match token {
+//!         Token::Text(text) => print!("{text}"),
+//!         Token::Cheer(cheer) => print!("[{} bits in {}]", cheer.bits, cheer.color()),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use super::get_cheermotes::{Cheermote, CheermoteImages, Tiers};
+
+/// A cheer in a message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Cheer<'t, 'c> {
+    /// The text of the cheer, e.g. `Cheer100`
+    pub text: &'t str,
+    /// The prefix as written in the message, e.g. `Cheer`
+    pub prefix: &'t str,
+    /// The amount of bits cheered
+    pub bits: i64,
+    /// The cheermote
+    pub cheermote: &'c Cheermote,
+    /// The tier of the cheermote for this amount of bits
+    pub tier: &'c Tiers,
+}
+
+impl<'c> Cheer<'_, 'c> {
+    /// Hex code of the color of the tier
+    pub fn color(&self) -> &'c str { &self.tier.color }
+
+    /// Images of the tier
+    pub fn images(&self) -> &'c CheermoteImages { &self.tier.images }
+}
+
+/// A part of a message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Token<'t, 'c> {
+    /// Text without cheers, including whitespace
+    Text(&'t str),
+    /// A cheer
+    Cheer(Cheer<'t, 'c>),
+}
+
+/// A message split into text and cheers, returned by [`parse`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheerMessage<'t, 'c> {
+    tokens: Vec<Token<'t, 'c>>,
+}
+
+impl<'t, 'c> CheerMessage<'t, 'c> {
+    /// The parts of the message, in order
+    pub fn tokens(&self) -> &[Token<'t, 'c>] { &self.tokens }
+
+    /// The cheers in the message
+    pub fn cheers(&self) -> impl Iterator<Item = &Cheer<'t, 'c>> + '_ {
+        self.tokens.iter().filter_map(|token| match token {
+            Token::Cheer(cheer) => Some(cheer),
+            Token::Text(_) => None,
+        })
+    }
+
+    /// Total amount of bits cheered in the message
+    pub fn total_bits(&self) -> i64 { self.cheers().map(|cheer| cheer.bits).sum() }
+
+    /// Whether the message contains any cheers
+    pub fn has_cheers(&self) -> bool { self.cheers().next().is_some() }
+
+    /// Consume the message, returning its parts
+    pub fn into_tokens(self) -> Vec<Token<'t, 'c>> { self.tokens }
+}
+
+/// Split a message into text and cheers
+///
+/// Words that look like a cheer but have an unknown prefix, or an amount below the lowest tier, are left as text.
+pub fn parse<'t, 'c>(message: &'t str, cheermotes: &'c [Cheermote]) -> CheerMessage<'t, 'c> {
+    let mut tokens = vec![];
+    // start of the text not yet pushed as a token
+    let mut text_start = 0;
+    for (start, word) in words(message) {
+        let Some(cheer) = parse_cheer(word, cheermotes) else {
+            continue;
+        };
+        if text_start < start {
+            tokens.push(Token::Text(&message[text_start..start]));
+        }
+        tokens.push(Token::Cheer(cheer));
+        text_start = start + word.len();
+    }
+    if text_start < message.len() {
+        tokens.push(Token::Text(&message[text_start..]));
+    }
+    CheerMessage { tokens }
+}
+
+/// Parse a single word as a cheer
+pub fn parse_cheer<'t, 'c>(word: &'t str, cheermotes: &'c [Cheermote]) -> Option<Cheer<'t, 'c>> {
+    let digits = word.len() - word.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let (prefix, amount) = word.split_at(word.len() - digits);
+    if prefix.is_empty() || amount.is_empty() || amount.starts_with('0') {
+        return None;
+    }
+    let bits = amount.parse().ok()?;
+    let cheermote = cheermotes
+        .iter()
+        .find(|cheermote| cheermote.prefix.eq_ignore_ascii_case(prefix))?;
+    let tier = cheermote.tier(bits)?;
+    Some(Cheer {
+        text: word,
+        prefix,
+        bits,
+        cheermote,
+        tier,
+    })
+}
+
+/// Whitespace separated words with their byte offset
+fn words(message: &str) -> impl Iterator<Item = (usize, &str)> {
+    message
+        .split(char::is_whitespace)
+        .scan(0, move |offset, word| {
+            let start = *offset;
+            // the separator is a single character
+            *offset += word.len()
+                + message[start + word.len()..]
+                    .chars()
+                    .next()
+                    .map_or(0, char::len_utf8);
+            Some((start, word))
+        })
+        .filter(|(_, word)| !word.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cheermote with tiers for the given minimum bits
+    fn cheermote(prefix: &str, tiers: &[i64]) -> Cheermote {
+        let images = |min_bits: i64| {
+            let array = serde_json::json!({
+                "1": format!("https://cheer/{prefix}/{min_bits}/1"),
+                "1.5": format!("https://cheer/{prefix}/{min_bits}/1.5"),
+                "2": format!("https://cheer/{prefix}/{min_bits}/2"),
+                "3": format!("https://cheer/{prefix}/{min_bits}/3"),
+                "4": format!("https://cheer/{prefix}/{min_bits}/4"),
+            });
+            let image = serde_json::json!({"animated": array, "static": array});
+            serde_json::json!({"dark": image, "light": image})
+        };
+        let tiers: Vec<_> = tiers
+            .iter()
+            .map(|min_bits| {
+                serde_json::json!({
+                    "min_bits": min_bits,
+                    "id": min_bits.to_string(),
+                    "color": format!("#{min_bits:06}"),
+                    "images": images(*min_bits),
+                    "can_cheer": true,
+                    "show_in_bits_card": true,
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "prefix": prefix,
+            "tiers": tiers,
+            "type": "global_first_party",
+            "order": 1,
+            "last_updated": "2018-05-22T00:06:04Z",
+            "is_charitable": false,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_message() {
+        let cheermotes = [
+            cheermote("Cheer", &[1, 100, 1000]),
+            cheermote("Kappa", &[1, 100]),
+            cheermote("BigCheer", &[500]),
+        ];
+        let message = parse(
+            "cheer1  Hello Cheer250 world!\tKAPPA100 cheer cheer0 Nope100 BigCheer100 bigcheer500",
+            &cheermotes,
+        );
+        let text = |t| Token::Text(t);
+        let tokens = message.tokens();
+        assert!(matches!(
+            tokens[0],
+            Token::Cheer(Cheer {
+                text: "cheer1",
+                bits: 1,
+                ..
+            })
+        ));
+        assert_eq!(tokens[1], text("  Hello "));
+        let Token::Cheer(cheer) = tokens[2] else {
+            panic!("expected a cheer")
+        };
+        assert_eq!(cheer.prefix, "Cheer");
+        assert_eq!(cheer.bits, 250);
+        assert_eq!(cheer.tier.id, "100");
+        assert_eq!(cheer.color(), "#000100");
+        assert_eq!(
+            cheer.images().dark.animated.url_2x,
+            "https://cheer/Cheer/100/2"
+        );
+        assert_eq!(tokens[3], text(" world!\t"));
+        assert!(matches!(
+            tokens[4],
+            Token::Cheer(Cheer {
+                prefix: "KAPPA",
+                bits: 100,
+                ..
+            })
+        ));
+        assert_eq!(tokens[5], text(" cheer cheer0 Nope100 BigCheer100 "));
+        assert!(matches!(tokens[6], Token::Cheer(Cheer { bits: 500, .. })));
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(message.total_bits(), 1 + 250 + 100 + 500);
+
+        let message = parse("no cheers here 🎉", &cheermotes);
+        assert!(!message.has_cheers());
+        assert_eq!(message.tokens(), [text("no cheers here 🎉")]);
+    }
+}
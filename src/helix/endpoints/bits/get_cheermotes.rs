@@ -93,6 +93,18 @@ pub struct Cheermote {
     pub type_: CheermoteType,
 }
 
+impl Cheermote {
+    /// The tier used when cheering `bits` bits with this cheermote
+    ///
+    /// This is the tier with the highest [`min_bits`](Tiers::min_bits) that is not above `bits`.
+    pub fn tier(&self, bits: i64) -> Option<&Tiers> {
+        self.tiers
+            .iter()
+            .filter(|tier| tier.min_bits <= bits)
+            .max_by_key(|tier| tier.min_bits)
+    }
+}
+
 /// Types of cheermotes
 #[derive(PartialEq, Eq, Deserialize, Serialize, Debug, Clone)]
 #[cfg_attr(feature = "deny_unknown_fields", serde(deny_unknown_fields))]
@@ -40,6 +40,7 @@ use crate::{
 use serde_derive::{Deserialize, Serialize};
 use std::borrow::Cow;
 
+pub mod cheer;
 pub mod get_bits_leaderboard;
 pub mod get_cheermotes;
 
@@ -0,0 +1,795 @@
+//! Emote registry for [Get Global Emotes](super::get_global_emotes), [Get Channel Emotes](super::get_channel_emotes),
+//! [Get Emote Sets](super::get_emote_sets) and [Get User Emotes](super::get_user_emotes)
+//!
+//! [`EmoteRegistry`] caches the responses of these endpoints and indexes the emotes by ID, name, emote set and channel,
+//! so questions like "what is emote `X`" or "which emotes can this user use in this channel" are answered without new requests.
+//! Emotes are kept as the [`KnownEmote`] they were received as, with the owner of channel emotes attached.
+//!
+//! With a [`HelixClient`](crate::helix::HelixClient), the `load_*` methods fetch what isn't cached yet, otherwise the responses can be
+//! inserted with the `insert_*` methods.
+//!
+//! Which emotes a user can use comes in two flavours:
+//!
+//! * [Get User Emotes](super::get_user_emotes) is authoritative, but needs a token of that user.
+//!   Use [`EmoteRegistry::user_emotes`] and [`EmoteRegistry::find_usable`].
+//! * The [`Requirement`] of a channel emote can be checked against what is known about a user, like whether they follow or subscribe,
+//!   with an [`Eligibility`]. Use [`EmoteRegistry::usable_in_channel`] and [`EmoteRegistry::find_in_channel`].
+//!
+//! [`find_emotes`] finds the emotes in a message, for example to render a message before sending it.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use twitch_api::helix::{
+//!     self,
+//!     chat::emotes::{find_emotes, Eligibility, EmoteRegistry},
+//! };
+//! # let client: helix::HelixClient<'static, twitch_api::client::DummyHttpClient> = helix::HelixClient::default();
+//! # let token = twitch_oauth2::AccessToken::new("validtoken".to_string());
+//! # let token = twitch_oauth2::UserToken::from_existing(&client, token, None, None).await?;
+//!
+//! let mut registry = EmoteRegistry::new();
+//! registry.load_global_emotes(&client, &token).await?;
+//! registry.load_channel_emotes(&client, &token, "1234".into()).await?;
+//!
+//! let eligibility = Eligibility::default().following(true);
+//! let message = "Kappa that was close";
+//! for found in find_emotes(message, |word| {
+//!     registry.find_in_channel(word, "1234".into(), &eligibility)
+//! }) {
+//!     println!("{} is emote {}", &message[found.range.clone()], found.emote.id());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use super::{get_emote_sets, ChannelEmote, GlobalEmote, UserEmote};
+use crate::types;
+
+/// An emote in an [`EmoteRegistry`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KnownEmote {
+    /// A global emote, from [Get Global Emotes](super::get_global_emotes)
+    Global(GlobalEmote),
+    /// An emote of a channel, from [Get Channel Emotes](super::get_channel_emotes)
+    Channel {
+        /// The broadcaster that owns the emote
+        owner_id: types::UserId,
+        /// The emote
+        emote: ChannelEmote,
+    },
+    /// An emote from [Get Emote Sets](super::get_emote_sets)
+    Set(get_emote_sets::Emote),
+    /// An emote only known from [Get User Emotes](super::get_user_emotes)
+    User(UserEmote),
+}
+
+impl KnownEmote {
+    /// ID of the emote
+    pub fn id(&self) -> &types::EmoteIdRef {
+        match self {
+            Self::Global(emote) => &emote.id,
+            Self::Channel { emote, .. } => &emote.id,
+            Self::Set(emote) => &emote.id,
+            Self::User(emote) => &emote.id,
+        }
+    }
+
+    /// Name of the emote, as typed in chat
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Global(emote) => &emote.name,
+            Self::Channel { emote, .. } => &emote.name,
+            Self::Set(emote) => &emote.name,
+            Self::User(emote) => &emote.name,
+        }
+    }
+
+    /// ID of the user that owns the emote, `None` for global emotes
+    pub fn owner_id(&self) -> Option<&types::UserIdRef> {
+        match self {
+            Self::Global(_) => None,
+            Self::Channel { owner_id, .. } => Some(owner_id),
+            Self::Set(emote) => Some(&emote.owner_id),
+            Self::User(emote) => Some(&emote.owner_id),
+        }
+    }
+
+    /// ID of the emote set the emote belongs to, not known for global emotes
+    pub fn emote_set_id(&self) -> Option<&types::EmoteSetIdRef> {
+        match self {
+            Self::Global(_) => None,
+            Self::Channel { emote, .. } => Some(&emote.emote_set_id),
+            Self::Set(emote) => Some(&emote.emote_set_id),
+            Self::User(emote) => Some(&emote.emote_set_id),
+        }
+    }
+
+    /// The type of the emote, like `subscriptions`, `follower` or `bitstier`. Always `globals` for global emotes
+    pub fn emote_type(&self) -> &str {
+        match self {
+            Self::Global(_) => "globals",
+            Self::Channel { emote, .. } => &emote.emote_type,
+            Self::Set(emote) => &emote.emote_type,
+            Self::User(emote) => &emote.emote_type,
+        }
+    }
+
+    /// What a user needs to use the emote
+    pub fn requirement(&self) -> Requirement {
+        match self.emote_type() {
+            "globals" | "smilies" => Requirement::None,
+            "follower" => Requirement::Follower,
+            "subscriptions" => Requirement::Subscriber(match self {
+                Self::Channel { emote, .. } => emote.tier.clone(),
+                _ => None,
+            }),
+            "bitstier" => Requirement::Bits,
+            _ => Requirement::Unknown,
+        }
+    }
+
+    /// Create a [`EmoteUrlBuilder`](types::EmoteUrlBuilder) for this emote
+    pub fn url(&self) -> types::EmoteUrlBuilder<'_> { types::EmoteUrlBuilder::new(self.id()) }
+
+    /// How much is known about the emote, only global and channel emotes know their tier
+    fn detail(&self) -> u8 {
+        match self {
+            Self::Global(_) | Self::Channel { .. } => 2,
+            Self::Set(_) => 1,
+            Self::User(_) => 0,
+        }
+    }
+}
+
+/// What a user needs to use an emote, see [`KnownEmote::requirement`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Requirement {
+    /// Anyone can use the emote
+    None,
+    /// The user has to follow the channel
+    Follower,
+    /// The user has to subscribe to the channel, at the tier if known
+    Subscriber(Option<types::SubscriptionTier>),
+    /// The user has to have cheered enough bits in the channel
+    Bits,
+    /// Unknown emote type
+    Unknown,
+}
+
+/// What is known about a user in a channel, to check [`Requirement`]s
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Eligibility {
+    /// Whether the user follows the channel
+    pub following: bool,
+    /// The tier the user subscribes at, if subscribed
+    pub subscription_tier: Option<types::SubscriptionTier>,
+}
+
+impl Eligibility {
+    /// Set whether the user follows the channel
+    pub fn following(mut self, following: bool) -> Self {
+        self.following = following;
+        self
+    }
+
+    /// Set the tier the user subscribes at
+    pub fn subscription_tier(mut self, tier: impl Into<Option<types::SubscriptionTier>>) -> Self {
+        self.subscription_tier = tier.into();
+        self
+    }
+
+    /// Whether the requirement is met
+    ///
+    /// [`Requirement::Bits`] and [`Requirement::Unknown`] are never met, since this can't be known without [Get User Emotes](super::get_user_emotes).
+    pub fn allows(&self, requirement: &Requirement) -> bool {
+        match requirement {
+            Requirement::None => true,
+            Requirement::Follower => self.following,
+            Requirement::Subscriber(tier) => match &self.subscription_tier {
+                Some(subscribed) => tier_rank(subscribed) >= tier.as_ref().map_or(1, tier_rank),
+                None => false,
+            },
+            Requirement::Bits | Requirement::Unknown => false,
+        }
+    }
+}
+
+/// Prime subscriptions unlock tier 1 emotes
+fn tier_rank(tier: &types::SubscriptionTier) -> u8 {
+    match tier {
+        types::SubscriptionTier::Tier1 | types::SubscriptionTier::Prime => 1,
+        types::SubscriptionTier::Tier2 => 2,
+        types::SubscriptionTier::Tier3 => 3,
+        _ => 0,
+    }
+}
+
+/// Caches and indexes emotes, see the [module documentation](self)
+#[derive(Clone, Debug, Default)]
+pub struct EmoteRegistry {
+    emotes: HashMap<types::EmoteId, KnownEmote>,
+    names: HashMap<String, Vec<types::EmoteId>>,
+    sets: HashMap<types::EmoteSetId, Vec<types::EmoteId>>,
+    channels: HashMap<types::UserId, Vec<types::EmoteId>>,
+    globals: Option<Vec<types::EmoteId>>,
+    /// Emotes of a user, in all channels or in a channel
+    users: HashMap<(types::UserId, Option<types::UserId>), HashSet<types::EmoteId>>,
+}
+
+impl EmoteRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self { Self::default() }
+
+    /// Add an emote, a less detailed emote doesn't replace a more detailed one, see [`KnownEmote::detail`]
+    fn insert(&mut self, emote: KnownEmote) -> types::EmoteId {
+        let id = emote.id().to_owned();
+        let names = self.names.entry(emote.name().to_owned()).or_default();
+        if !names.contains(&id) {
+            names.push(id.clone());
+        }
+        if let Some(set_id) = emote.emote_set_id() {
+            let set = self.sets.entry(set_id.to_owned()).or_default();
+            if !set.contains(&id) {
+                set.push(id.clone());
+            }
+        }
+        match self.emotes.get(&id) {
+            Some(existing) if existing.detail() > emote.detail() => (),
+            _ => {
+                self.emotes.insert(id.clone(), emote);
+            }
+        }
+        id
+    }
+
+    /// Add the response of [Get Global Emotes](super::get_global_emotes)
+    pub fn insert_global_emotes(&mut self, emotes: impl IntoIterator<Item = GlobalEmote>) {
+        let ids = emotes
+            .into_iter()
+            .map(|emote| self.insert(KnownEmote::Global(emote)))
+            .collect();
+        self.globals = Some(ids);
+    }
+
+    /// Add the response of [Get Channel Emotes](super::get_channel_emotes)
+    pub fn insert_channel_emotes(
+        &mut self,
+        broadcaster_id: impl Into<types::UserId>,
+        emotes: impl IntoIterator<Item = ChannelEmote>,
+    ) {
+        let owner_id = broadcaster_id.into();
+        let ids = emotes
+            .into_iter()
+            .map(|emote| {
+                self.insert(KnownEmote::Channel {
+                    owner_id: owner_id.clone(),
+                    emote,
+                })
+            })
+            .collect();
+        self.channels.insert(owner_id, ids);
+    }
+
+    /// Add the response of [Get Emote Sets](super::get_emote_sets)
+    ///
+    /// `emote_set_ids` are the requested sets, so sets without emotes aren't requested again.
+    pub fn insert_emote_sets(
+        &mut self,
+        emote_set_ids: &[&types::EmoteSetIdRef],
+        emotes: impl IntoIterator<Item = get_emote_sets::Emote>,
+    ) {
+        for &set_id in emote_set_ids {
+            self.sets.entry(set_id.to_owned()).or_default();
+        }
+        for emote in emotes {
+            self.insert(KnownEmote::Set(emote));
+        }
+    }
+
+    /// Add the response of [Get User Emotes](super::get_user_emotes), requested with `broadcaster_id` set to `channel_id`
+    pub fn insert_user_emotes(
+        &mut self,
+        user_id: impl Into<types::UserId>,
+        channel_id: Option<types::UserId>,
+        emotes: impl IntoIterator<Item = UserEmote>,
+    ) {
+        let ids = emotes
+            .into_iter()
+            .map(|emote| self.insert(KnownEmote::User(emote)))
+            .collect();
+        self.users.insert((user_id.into(), channel_id), ids);
+    }
+
+    /// Get an emote by ID
+    pub fn get(&self, id: &types::EmoteIdRef) -> Option<&KnownEmote> { self.emotes.get(id) }
+
+    /// All emotes with a name. Names are case-sensitive
+    pub fn find(&self, name: &str) -> impl Iterator<Item = &KnownEmote> + '_ {
+        self.names
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter_map(move |id| self.emotes.get(id))
+    }
+
+    fn resolve<'a>(
+        &'a self,
+        ids: impl IntoIterator<Item = &'a types::EmoteId> + 'a,
+    ) -> impl Iterator<Item = &'a KnownEmote> + 'a {
+        ids.into_iter().filter_map(move |id| self.emotes.get(id))
+    }
+
+    /// The global emotes, `None` if not cached
+    pub fn global_emotes(&self) -> Option<impl Iterator<Item = &KnownEmote> + '_> {
+        self.globals.as_ref().map(|ids| self.resolve(ids))
+    }
+
+    /// The emotes of a channel, `None` if not cached
+    pub fn channel_emotes(
+        &self,
+        broadcaster_id: &types::UserIdRef,
+    ) -> Option<impl Iterator<Item = &KnownEmote> + '_> {
+        self.channels
+            .get(broadcaster_id)
+            .map(|ids| self.resolve(ids))
+    }
+
+    /// The emotes in an emote set, `None` if not cached
+    pub fn emote_set(
+        &self,
+        emote_set_id: &types::EmoteSetIdRef,
+    ) -> Option<impl Iterator<Item = &KnownEmote> + '_> {
+        self.sets.get(emote_set_id).map(|ids| self.resolve(ids))
+    }
+
+    /// The emotes a user can use, in all channels or in a channel, `None` if not cached
+    pub fn user_emotes(
+        &self,
+        user_id: &types::UserIdRef,
+        channel_id: Option<&types::UserIdRef>,
+    ) -> Option<impl Iterator<Item = &KnownEmote> + '_> {
+        self.users
+            .get(&(user_id.to_owned(), channel_id.map(ToOwned::to_owned)))
+            .map(|ids| self.resolve(ids))
+    }
+
+    /// The cached global and channel emotes that a user with the [`Eligibility`] can use in a channel
+    pub fn usable_in_channel<'a>(
+        &'a self,
+        broadcaster_id: &types::UserIdRef,
+        eligibility: &'a Eligibility,
+    ) -> impl Iterator<Item = &'a KnownEmote> + 'a {
+        self.globals
+            .iter()
+            .flatten()
+            .chain(self.channels.get(broadcaster_id).into_iter().flatten())
+            .filter_map(move |id| self.emotes.get(id))
+            .filter(move |emote| eligibility.allows(&emote.requirement()))
+    }
+
+    /// Find an emote by name that the user can use, from the cached [user emotes](Self::user_emotes)
+    pub fn find_usable(
+        &self,
+        name: &str,
+        user_id: &types::UserIdRef,
+        channel_id: Option<&types::UserIdRef>,
+    ) -> Option<&KnownEmote> {
+        let usable = self
+            .users
+            .get(&(user_id.to_owned(), channel_id.map(ToOwned::to_owned)))?;
+        self.names
+            .get(name)?
+            .iter()
+            .find(|id| usable.contains(*id))
+            .and_then(|id| self.emotes.get(id))
+    }
+
+    /// Find an emote by name that a user with the [`Eligibility`] can use in a channel, preferring the channel's emotes over global ones
+    pub fn find_in_channel(
+        &self,
+        name: &str,
+        broadcaster_id: &types::UserIdRef,
+        eligibility: &Eligibility,
+    ) -> Option<&KnownEmote> {
+        let channel = self.channels.get(broadcaster_id);
+        let global = self.globals.as_ref();
+        let usable = |ids: Option<&Vec<types::EmoteId>>| {
+            self.names
+                .get(name)
+                .into_iter()
+                .flatten()
+                .filter(move |id| ids.is_some_and(|ids| ids.contains(*id)))
+                .filter_map(|id| self.emotes.get(id))
+                .find(|emote| eligibility.allows(&emote.requirement()))
+        };
+        usable(channel).or_else(|| usable(global))
+    }
+
+    /// Forget the emotes of a channel, including which of them users can use
+    pub fn invalidate_channel(&mut self, broadcaster_id: &types::UserIdRef) {
+        self.channels.remove(broadcaster_id);
+        self.users
+            .retain(|(_, channel_id), _| channel_id.as_deref() != Some(broadcaster_id));
+    }
+
+    /// Forget which emotes a user can use
+    pub fn invalidate_user(&mut self, user_id: &types::UserIdRef) {
+        self.users.retain(|(id, _), _| &**id != user_id);
+    }
+
+    /// Forget everything
+    pub fn clear(&mut self) { *self = Self::default(); }
+}
+
+#[cfg(feature = "client")]
+#[cfg_attr(nightly, doc(cfg(feature = "client")))]
+impl EmoteRegistry {
+    /// Fetch the global emotes if not cached
+    pub async fn load_global_emotes<'c, C, T>(
+        &mut self,
+        client: &'c crate::helix::HelixClient<'c, C>,
+        token: &T,
+    ) -> Result<(), crate::helix::ClientRequestError<<C as crate::HttpClient>::Error>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+    {
+        if self.globals.is_none() {
+            let emotes = client.get_global_emotes(token).await?;
+            self.insert_global_emotes(emotes);
+        }
+        Ok(())
+    }
+
+    /// Fetch the emotes of a channel if not cached
+    pub async fn load_channel_emotes<'c, C, T>(
+        &mut self,
+        client: &'c crate::helix::HelixClient<'c, C>,
+        token: &T,
+        broadcaster_id: &types::UserIdRef,
+    ) -> Result<(), crate::helix::ClientRequestError<<C as crate::HttpClient>::Error>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+    {
+        if !self.channels.contains_key(broadcaster_id) {
+            let emotes = client
+                .get_channel_emotes_from_id(broadcaster_id, token)
+                .await?;
+            self.insert_channel_emotes(broadcaster_id.to_owned(), emotes);
+        }
+        Ok(())
+    }
+
+    /// Fetch the emote sets that aren't cached, 25 at a time
+    pub async fn load_emote_sets<'c, C, T>(
+        &mut self,
+        client: &'c crate::helix::HelixClient<'c, C>,
+        token: &T,
+        emote_set_ids: &[&types::EmoteSetIdRef],
+    ) -> Result<(), crate::helix::ClientRequestError<<C as crate::HttpClient>::Error>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+    {
+        let mut seen = HashSet::new();
+        let missing: Vec<&types::EmoteSetIdRef> = emote_set_ids
+            .iter()
+            .copied()
+            .filter(|id| !self.sets.contains_key(*id) && seen.insert(*id))
+            .collect();
+        for chunk in missing.chunks(25) {
+            let emotes = client
+                .req_get(
+                    get_emote_sets::GetEmoteSetsRequest::emote_set_ids(chunk),
+                    token,
+                )
+                .await?
+                .data;
+            self.insert_emote_sets(chunk, emotes);
+        }
+        Ok(())
+    }
+
+    /// Fetch the emotes a user can use, in all channels or in a channel, if not cached
+    ///
+    /// The token has to be a user token of the user with the `user:read:emotes` scope.
+    pub async fn load_user_emotes<'c, C, T>(
+        &mut self,
+        client: &'c crate::helix::HelixClient<'c, C>,
+        token: &'c T,
+        user_id: &'c types::UserIdRef,
+        channel_id: Option<&'c types::UserIdRef>,
+    ) -> Result<(), crate::helix::ClientRequestError<<C as crate::HttpClient>::Error>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+    {
+        use futures::TryStreamExt;
+
+        let key = (user_id.to_owned(), channel_id.map(ToOwned::to_owned));
+        if self.users.contains_key(&key) {
+            return Ok(());
+        }
+        let emotes: Vec<UserEmote> = match channel_id {
+            Some(channel_id) => {
+                client
+                    .get_user_emotes_in_channel(user_id, channel_id, token)
+                    .try_collect()
+                    .await?
+            }
+            None => client.get_user_emotes(user_id, token).try_collect().await?,
+        };
+        self.insert_user_emotes(key.0, key.1, emotes);
+        Ok(())
+    }
+}
+
+/// An emote found in a message by [`find_emotes`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EmoteMatch<'a> {
+    /// Byte range of the emote name in the message
+    pub range: Range<usize>,
+    /// The emote
+    pub emote: &'a KnownEmote,
+}
+
+/// Find the emotes in a message
+///
+/// Every whitespace separated word is looked up with `lookup`, usually one of
+/// [`EmoteRegistry::find_in_channel`] or [`EmoteRegistry::find_usable`].
+pub fn find_emotes<'a, 's>(
+    message: &'s str,
+    mut lookup: impl FnMut(&'s str) -> Option<&'a KnownEmote>,
+) -> Vec<EmoteMatch<'a>> {
+    let mut found = vec![];
+    let mut start = None;
+    for (i, c) in message
+        .char_indices()
+        .chain(std::iter::once((message.len(), ' ')))
+    {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                if let Some(emote) = lookup(&message[s..i]) {
+                    found.push(EmoteMatch { range: s..i, emote });
+                }
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => (),
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn global(id: &str, name: &str) -> GlobalEmote {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "images": {
+                "url_1x": "https://static-cdn.jtvnw.net/emoticons/v2/25/static/light/1.0",
+                "url_2x": "https://static-cdn.jtvnw.net/emoticons/v2/25/static/light/2.0",
+                "url_4x": "https://static-cdn.jtvnw.net/emoticons/v2/25/static/light/3.0"
+            },
+            "format": ["static"],
+            "scale": ["1.0", "2.0", "3.0"],
+            "theme_mode": ["light", "dark"]
+        }))
+        .unwrap()
+    }
+
+    fn channel(id: &str, name: &str, emote_type: &str, tier: &str) -> ChannelEmote {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "images": {
+                "url_1x": "https://static-cdn.jtvnw.net/emoticons/v2/1/static/light/1.0",
+                "url_2x": "https://static-cdn.jtvnw.net/emoticons/v2/1/static/light/2.0",
+                "url_4x": "https://static-cdn.jtvnw.net/emoticons/v2/1/static/light/3.0"
+            },
+            "tier": tier,
+            "emote_type": emote_type,
+            "emote_set_id": format!("set{id}"),
+            "format": ["static"],
+            "scale": ["1.0", "2.0", "3.0"],
+            "theme_mode": ["light", "dark"]
+        }))
+        .unwrap()
+    }
+
+    fn user(id: &str, name: &str, owner_id: &str) -> UserEmote {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "emote_type": "subscriptions",
+            "emote_set_id": format!("set{id}"),
+            "owner_id": owner_id,
+            "format": ["static"],
+            "scale": ["1.0", "2.0", "3.0"],
+            "theme_mode": ["light", "dark"]
+        }))
+        .unwrap()
+    }
+
+    fn set(id: &str, name: &str, owner_id: &str) -> get_emote_sets::Emote {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "images": {
+                "url_1x": "https://static-cdn.jtvnw.net/emoticons/v2/1/static/light/1.0",
+                "url_2x": "https://static-cdn.jtvnw.net/emoticons/v2/1/static/light/2.0",
+                "url_4x": "https://static-cdn.jtvnw.net/emoticons/v2/1/static/light/3.0"
+            },
+            "emote_type": "subscriptions",
+            "emote_set_id": format!("set{id}"),
+            "owner_id": owner_id,
+            "format": ["static"],
+            "scale": ["1.0", "2.0", "3.0"],
+            "theme_mode": ["light", "dark"]
+        }))
+        .unwrap()
+    }
+
+    fn registry() -> EmoteRegistry {
+        let mut registry = EmoteRegistry::new();
+        registry.insert_global_emotes(vec![global("25", "Kappa"), global("88", "PogChamp")]);
+        registry.insert_channel_emotes(
+            "1234",
+            vec![
+                channel("1", "cool1", "follower", ""),
+                channel("2", "cool2", "subscriptions", "1000"),
+                channel("3", "cool3", "subscriptions", "3000"),
+                channel("4", "cool4", "bitstier", ""),
+            ],
+        );
+        registry
+    }
+
+    #[test]
+    fn eligibility() {
+        let registry = registry();
+        assert_eq!(registry.global_emotes().unwrap().count(), 2);
+        assert!(registry.channel_emotes("5678".into()).is_none());
+        assert_eq!(
+            registry.get("2".into()).unwrap().owner_id(),
+            Some("1234".into())
+        );
+        assert_eq!(
+            registry.get("3".into()).unwrap().requirement(),
+            Requirement::Subscriber(Some(types::SubscriptionTier::Tier3))
+        );
+        assert_eq!(
+            registry
+                .emote_set("set2".into())
+                .unwrap()
+                .next()
+                .unwrap()
+                .name(),
+            "cool2"
+        );
+
+        let usable = |eligibility: &Eligibility| {
+            let mut names: Vec<&str> = registry
+                .usable_in_channel("1234".into(), eligibility)
+                .map(|emote| emote.name())
+                .collect();
+            names.sort_unstable();
+            names
+        };
+        assert_eq!(usable(&Eligibility::default()), ["Kappa", "PogChamp"]);
+        assert_eq!(
+            usable(&Eligibility::default().following(true)),
+            ["Kappa", "PogChamp", "cool1"]
+        );
+        assert_eq!(
+            usable(&Eligibility::default().subscription_tier(types::SubscriptionTier::Prime)),
+            ["Kappa", "PogChamp", "cool2"]
+        );
+        assert_eq!(
+            usable(
+                &Eligibility::default()
+                    .following(true)
+                    .subscription_tier(types::SubscriptionTier::Tier3)
+            ),
+            ["Kappa", "PogChamp", "cool1", "cool2", "cool3"]
+        );
+    }
+
+    #[test]
+    fn lookup() {
+        let mut registry = registry();
+        let eligibility = Eligibility::default().following(true);
+        let message = "Kappa  cool1 cool2\tPogChamp kappa";
+        let found = find_emotes(message, |word| {
+            registry.find_in_channel(word, "1234".into(), &eligibility)
+        });
+        let found: Vec<(&str, &str)> = found
+            .iter()
+            .map(|m| (&message[m.range.clone()], m.emote.id().as_str()))
+            .collect();
+        assert_eq!(found, [("Kappa", "25"), ("cool1", "1"), ("PogChamp", "88")]);
+
+        // user emotes don't replace the channel emotes, but are tracked per user
+        registry.insert_user_emotes(
+            "42",
+            Some("1234".into()),
+            vec![user("2", "cool2", "1234"), user("77", "other", "5678")],
+        );
+        assert!(matches!(
+            registry.get("2".into()),
+            Some(KnownEmote::Channel { .. })
+        ));
+        assert!(matches!(
+            registry.get("77".into()),
+            Some(KnownEmote::User(_))
+        ));
+        assert_eq!(
+            registry
+                .find_usable("cool2", "42".into(), Some("1234".into()))
+                .map(|e| e.id().as_str()),
+            Some("2")
+        );
+        assert!(registry
+            .find_usable("cool1", "42".into(), Some("1234".into()))
+            .is_none());
+        assert!(registry.find_usable("cool2", "42".into(), None).is_none());
+
+        registry.invalidate_channel("1234".into());
+        assert!(registry.channel_emotes("1234".into()).is_none());
+        assert!(registry
+            .user_emotes("42".into(), Some("1234".into()))
+            .is_none());
+        assert_eq!(registry.find("cool2").count(), 1);
+    }
+
+    #[test]
+    fn sets_keep_channel_tier() {
+        let mut registry = registry();
+        let set_ids: [&types::EmoteSetIdRef; 2] = ["set3".into(), "set9".into()];
+        registry.insert_emote_sets(
+            &set_ids,
+            vec![set("3", "cool3", "1234"), set("9", "cool9", "5678")],
+        );
+        let tier3 = Requirement::Subscriber(Some(types::SubscriptionTier::Tier3));
+        assert_eq!(registry.get("3".into()).unwrap().requirement(), tier3);
+        assert!(matches!(registry.get("9".into()), Some(KnownEmote::Set(_))));
+        assert_eq!(registry.emote_set("set9".into()).unwrap().count(), 1);
+
+        let prime = Eligibility::default().subscription_tier(types::SubscriptionTier::Prime);
+        assert!(registry
+            .find_in_channel("cool3", "1234".into(), &prime)
+            .is_none());
+        assert!(!registry
+            .usable_in_channel("1234".into(), &prime)
+            .any(|emote| emote.name() == "cool3"));
+    }
+
+    #[test]
+    fn global_fallback() {
+        let mut registry = registry();
+        registry.insert_global_emotes(vec![global("25", "Kappa"), global("66", "cool2")]);
+        let found = |eligibility: &Eligibility| {
+            registry
+                .find_in_channel("cool2", "1234".into(), eligibility)
+                .map(|emote| emote.id().as_str())
+        };
+        assert_eq!(
+            found(&Eligibility::default().subscription_tier(types::SubscriptionTier::Tier1)),
+            Some("2")
+        );
+        assert_eq!(found(&Eligibility::default()), Some("66"));
+    }
+}
@@ -35,6 +35,7 @@ use crate::{
 use serde_derive::{Deserialize, Serialize};
 use std::borrow::Cow;
 
+pub mod emotes;
 pub mod get_channel_chat_badges;
 pub mod get_channel_emotes;
 pub mod get_chat_settings;
@@ -45,6 +46,7 @@ pub mod get_global_emotes;
 pub mod get_shared_chat_session;
 pub mod get_user_chat_color;
 pub mod get_user_emotes;
+pub mod queue;
 pub mod send_a_shoutout;
 pub mod send_chat_announcement;
 pub mod send_chat_message;
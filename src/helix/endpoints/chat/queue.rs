@@ -0,0 +1,812 @@
+//! Outgoing chat message queue for [Send Chat Message](super::send_chat_message)
+//!
+//! Twitch drops messages that are too long or sent too fast, and answers with [`is_sent: false`](super::SendChatMessageResponse::is_sent)
+//! and a [`ChatMessageDropCode`] instead of failing the request. [`ChatQueue`] queues messages for one channel and takes care of this:
+//!
+//! * messages over [`QueueConfig::max_length`] characters are [split](split_message) on word boundaries,
+//! * messages are sent one at a time, no faster than [`QueueConfig::min_interval`] or the channel's [slow mode](ChatRestrictions::slow_mode),
+//! * a message identical to the previous one is [altered or delayed](Duplicates), since Twitch drops duplicates sent within 30 seconds,
+//! * messages dropped for slow mode, rate limits or duplicates are retried, other drops are reported as a typed [`DropReason`].
+//!
+//! The queue does no IO itself. Send the [`Outgoing`] messages from [`ChatQueue::poll_send`] and pass the response to [`ChatQueue::handle_response`],
+//! or use [`ChatQueue::send_next`] with a [`HelixClient`](crate::helix::HelixClient).
+//! Keep the [restrictions](ChatQueue::set_restrictions) up to date with [`GetChatSettingsRequest`](super::GetChatSettingsRequest)
+//! and the `channel.chat_settings.update` EventSub subscription.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use std::time::Instant;
+//! use twitch_api::helix::{
+//!     self,
+//!     chat::queue::{ChatQueue, Outcome, QueueConfig},
+//! };
+//! # let client: helix::HelixClient<'static, twitch_api::client::DummyHttpClient> = helix::HelixClient::default();
+//! # let token = twitch_oauth2::AccessToken::new("validtoken".to_string());
+//! # let token = twitch_oauth2::UserToken::from_existing(&client, token, None, None).await?;
+//!
+//! let mut queue = ChatQueue::new("1234", "5678", QueueConfig::default());
+//! let settings = client.get_chat_settings("1234", None, &token).await?;
+//! queue.set_restrictions(&settings);
+//! queue.push("a very long message ...");
+//!
+//! while !queue.is_empty() {
+//!     if let Some(at) = queue.poll_timeout() {
+//!         tokio::time::sleep_until(at.into()).await;
+//!     }
+//!     match queue.send_next(&client, &token, Instant::now()).await {
+//!         Ok(Some(Outcome::Dropped { reason, .. })) => println!("message was dropped: {reason}"),
+//!         Err(e) => {
+//!             println!("request failed: {}", e.error);
+//!             if let Some(Outcome::Dropped { .. }) = e.outcome {
+//!                 println!("message was dropped after too many retries");
+//!             }
+//!         }
+//!         _ => (),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::{
+    ChatMessageDropCode, ChatMessageDropReason, ChatSettings, SendChatMessageBody,
+    SendChatMessageResponse,
+};
+use crate::types;
+
+/// What to do with a message that is identical to the previous one
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Duplicates {
+    /// Append a suffix, alternating with the unaltered message
+    Alter(String),
+    /// Wait until the [duplicate window](QueueConfig::duplicate_window) has passed
+    Wait,
+}
+
+impl Default for Duplicates {
+    /// Append a space and U+E0000, which chat clients don't show
+    fn default() -> Self { Self::Alter(" \u{E0000}".to_owned()) }
+}
+
+/// Configuration for a [`ChatQueue`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct QueueConfig {
+    /// Maximum length of a message in characters, including the suffix of [`Duplicates::Alter`]. Defaults to `500`
+    pub max_length: usize,
+    /// Minimum time between two messages. Defaults to 1.5 seconds, i.e. 20 messages in 30 seconds
+    pub min_interval: Duration,
+    /// Whether the sender is the broadcaster or a moderator, which aren't subject to slow mode. Defaults to `false`
+    pub exempt: bool,
+    /// Time in which Twitch drops identical messages. Defaults to 30 seconds
+    pub duplicate_window: Duration,
+    /// What to do with identical messages
+    pub duplicates: Duplicates,
+    /// How often to retry a message that was dropped for slow mode, rate limits or being a duplicate. Defaults to `3`
+    pub max_retries: u32,
+    /// Time to wait before retrying a message that was rate limited or failed to send. Defaults to 2 seconds
+    pub retry_delay: Duration,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            max_length: 500,
+            min_interval: Duration::from_millis(1500),
+            exempt: false,
+            duplicate_window: Duration::from_secs(30),
+            duplicates: Duplicates::default(),
+            max_retries: 3,
+            retry_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl QueueConfig {
+    /// Set the maximum length of a message
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length.max(1);
+        self
+    }
+
+    /// Set the minimum time between two messages
+    pub fn min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// Set whether the sender is exempt from slow mode
+    pub fn exempt(mut self, exempt: bool) -> Self {
+        self.exempt = exempt;
+        self
+    }
+
+    /// Set what to do with identical messages
+    pub fn duplicates(mut self, duplicates: Duplicates) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    /// Set how often to retry a message
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the time to wait before retrying a message
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+}
+
+/// Chat settings of a channel that affect sending messages
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ChatRestrictions {
+    /// Time between messages of a user, if slow mode is on
+    pub slow_mode: Option<Duration>,
+    /// How long users have to follow to chat, if followers-only mode is on
+    pub followers_only: Option<Duration>,
+    /// Whether only subscribers can chat
+    pub subscribers_only: bool,
+    /// Whether messages can only contain emotes
+    pub emote_only: bool,
+    /// Whether messages have to be unique
+    pub unique_chat: bool,
+}
+
+impl From<&ChatSettings> for ChatRestrictions {
+    fn from(settings: &ChatSettings) -> Self {
+        Self {
+            slow_mode: settings
+                .slow_mode
+                .then(|| Duration::from_secs(settings.slow_mode_wait_time.unwrap_or_default())),
+            followers_only: settings.follower_mode.then(|| {
+                Duration::from_secs(60 * settings.follower_mode_duration.unwrap_or_default())
+            }),
+            subscribers_only: settings.subscriber_mode,
+            emote_only: settings.emote_mode,
+            unique_chat: settings.unique_chat_mode,
+        }
+    }
+}
+
+#[cfg(feature = "eventsub")]
+#[cfg_attr(nightly, doc(cfg(feature = "eventsub")))]
+impl From<&crate::eventsub::channel::ChannelChatSettingsUpdateV1Payload> for ChatRestrictions {
+    fn from(settings: &crate::eventsub::channel::ChannelChatSettingsUpdateV1Payload) -> Self {
+        Self {
+            slow_mode: settings.slow_mode.then(|| {
+                Duration::from_secs(settings.slow_mode_wait_time_seconds.unwrap_or_default() as u64)
+            }),
+            followers_only: settings.follower_mode.then(|| {
+                Duration::from_secs(
+                    60 * settings.follower_mode_duration_minutes.unwrap_or_default() as u64,
+                )
+            }),
+            subscribers_only: settings.subscriber_mode,
+            emote_only: settings.emote_mode,
+            unique_chat: settings.unique_chat_mode,
+        }
+    }
+}
+
+/// Why a message was dropped, see [`ChatMessageDropCode`] for the codes from Twitch
+#[derive(Clone, Debug, PartialEq, Eq, displaydoc::Display)]
+#[non_exhaustive]
+pub enum DropReason {
+    /// the message is identical to the previous one
+    Duplicate,
+    /// the channel is in slow mode
+    SlowMode,
+    /// messages are sent too fast
+    RateLimited,
+    /// the channel is in followers-only mode
+    FollowersOnly,
+    /// the channel is in subscribers-only mode
+    SubscribersOnly,
+    /// the channel is in emote-only mode
+    EmoteOnly,
+    /// the channel is in unique chat mode and the message is not unique
+    NotUnique,
+    /// the sender is banned in the channel
+    Banned,
+    /// the sender is timed out in the channel
+    TimedOut,
+    /// the message was held or rejected by AutoMod or the channel's moderation settings
+    Rejected,
+    /// the sender needs a verified email or phone number
+    VerificationRequired,
+    /// the message could not be sent, {0}
+    Other(ChatMessageDropCode),
+    /// the request failed
+    RequestFailed,
+}
+
+impl DropReason {
+    /// Classify a drop code from Twitch
+    pub fn from_code(code: &ChatMessageDropCode) -> Self {
+        use ChatMessageDropCode as Code;
+        match code {
+            Code::MsgDuplicate => Self::Duplicate,
+            Code::MsgSlowmode => Self::SlowMode,
+            Code::MsgRatelimit => Self::RateLimited,
+            Code::MsgFollowersonly
+            | Code::MsgFollowersonlyFollowed
+            | Code::MsgFollowersonlyZero => Self::FollowersOnly,
+            Code::MsgSubsonly => Self::SubscribersOnly,
+            Code::MsgEmoteonly => Self::EmoteOnly,
+            Code::MsgR9k => Self::NotUnique,
+            Code::MsgBanned => Self::Banned,
+            Code::MsgTimedout => Self::TimedOut,
+            Code::MsgRejected | Code::MsgRejectedMandatory => Self::Rejected,
+            Code::MsgRequiresVerifiedPhoneNumber | Code::MsgVerifiedEmail => {
+                Self::VerificationRequired
+            }
+            code => Self::Other(code.clone()),
+        }
+    }
+
+    /// Whether sending the message again can succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Duplicate
+                | Self::SlowMode
+                | Self::RateLimited
+                | Self::NotUnique
+                | Self::RequestFailed
+        )
+    }
+}
+
+/// Identifies a message pushed to a [`ChatQueue`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct QueuedId(u64);
+
+/// A message to send, returned by [`ChatQueue::poll_send`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Outgoing {
+    /// The queued message this is a part of
+    pub id: QueuedId,
+    /// The ID of the broadcaster whose chat room the message will be sent to.
+    pub broadcaster_id: types::UserId,
+    /// The ID of the user sending the message.
+    pub sender_id: types::UserId,
+    /// The text to send
+    pub message: String,
+    /// The ID of the chat message being replied to.
+    pub reply_parent_message_id: Option<types::MsgId>,
+}
+
+impl Outgoing {
+    /// The body for a [`SendChatMessageRequest`](super::SendChatMessageRequest)
+    pub fn body(&self) -> SendChatMessageBody<'_> {
+        let body = SendChatMessageBody::new(
+            &*self.broadcaster_id,
+            &*self.sender_id,
+            self.message.as_str(),
+        );
+        match &self.reply_parent_message_id {
+            Some(id) => body.reply_parent_message_id(&**id),
+            None => body,
+        }
+    }
+}
+
+/// Result of sending a message, returned by [`ChatQueue::handle_response`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Outcome {
+    /// A part of the message was sent
+    Sent {
+        /// The queued message
+        id: QueuedId,
+        /// The ID of the sent chat message
+        message_id: Option<types::MsgId>,
+        /// How many parts of the message are left to send
+        remaining_parts: usize,
+    },
+    /// The message was dropped and will be sent again
+    Retrying {
+        /// The queued message
+        id: QueuedId,
+        /// Why the message was dropped
+        reason: DropReason,
+        /// When the message is sent again
+        at: Instant,
+    },
+    /// The message was dropped, any parts that weren't sent yet are discarded
+    Dropped {
+        /// The queued message
+        id: QueuedId,
+        /// Why the message was dropped
+        reason: DropReason,
+        /// The explanation from Twitch
+        message: Option<String>,
+    },
+}
+
+#[derive(Debug)]
+struct Queued {
+    id: QueuedId,
+    parts: VecDeque<String>,
+    reply_to: Option<types::MsgId>,
+    attempts: u32,
+    /// Append the duplicate suffix to the next attempt
+    alter: bool,
+}
+
+/// A queue of outgoing chat messages for one channel
+///
+/// See the [module documentation](self) for more.
+#[derive(Debug)]
+pub struct ChatQueue {
+    config: QueueConfig,
+    broadcaster_id: types::UserId,
+    sender_id: types::UserId,
+    restrictions: ChatRestrictions,
+    queue: VecDeque<Queued>,
+    in_flight: bool,
+    next_send: Option<Instant>,
+    /// The last message sent, as sent, and when
+    last_sent: Option<(String, Instant)>,
+    next_id: u64,
+}
+
+impl ChatQueue {
+    /// Create a queue for messages from `sender_id` in the chat of `broadcaster_id`
+    pub fn new(
+        broadcaster_id: impl Into<types::UserId>,
+        sender_id: impl Into<types::UserId>,
+        config: QueueConfig,
+    ) -> Self {
+        Self {
+            config,
+            broadcaster_id: broadcaster_id.into(),
+            sender_id: sender_id.into(),
+            restrictions: ChatRestrictions::default(),
+            queue: VecDeque::new(),
+            in_flight: false,
+            next_send: None,
+            last_sent: None,
+            next_id: 0,
+        }
+    }
+
+    /// Update the chat settings of the channel
+    pub fn set_restrictions(&mut self, restrictions: impl Into<ChatRestrictions>) {
+        self.restrictions = restrictions.into();
+    }
+
+    /// The current chat settings of the channel
+    pub fn restrictions(&self) -> &ChatRestrictions { &self.restrictions }
+
+    /// Queue a message, splitting it if it's too long
+    ///
+    /// Messages without any text are ignored.
+    pub fn push(&mut self, message: &str) -> QueuedId { self.enqueue(message, None) }
+
+    /// Queue a reply to a chat message. Only the first part of a split message is sent as a reply
+    pub fn push_reply(&mut self, message: &str, reply_to: impl Into<types::MsgId>) -> QueuedId {
+        self.enqueue(message, Some(reply_to.into()))
+    }
+
+    fn enqueue(&mut self, message: &str, reply_to: Option<types::MsgId>) -> QueuedId {
+        let id = QueuedId(self.next_id);
+        self.next_id += 1;
+        // leave room for the suffix of altered duplicates
+        let reserved = match &self.config.duplicates {
+            Duplicates::Alter(suffix) => suffix.chars().count(),
+            Duplicates::Wait => 0,
+        };
+        let parts: VecDeque<_> =
+            split_message(message, self.config.max_length.saturating_sub(reserved)).into();
+        if !parts.is_empty() {
+            self.queue.push_back(Queued {
+                id,
+                parts,
+                reply_to,
+                attempts: 0,
+                alter: false,
+            });
+        }
+        id
+    }
+
+    /// Whether there are no messages left to send
+    pub fn is_empty(&self) -> bool { self.queue.is_empty() }
+
+    /// Number of queued messages, including the one being sent
+    pub fn len(&self) -> usize { self.queue.len() }
+
+    /// When the next message can be sent, if there is one and it can't be sent right away
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        if self.in_flight || self.queue.is_empty() {
+            return None;
+        }
+        let mut at = self.next_send;
+        if self.config.duplicates == Duplicates::Wait {
+            if let (Some((last, sent_at)), Some(part)) = (&self.last_sent, self.next_part()) {
+                if *last == part {
+                    at = at.max(Some(*sent_at + self.config.duplicate_window));
+                }
+            }
+        }
+        at
+    }
+
+    /// The next message to send, if one can be sent at `now`
+    ///
+    /// Pass the response to [`ChatQueue::handle_response`], or call [`ChatQueue::handle_error`] if the request failed.
+    /// No other message is returned until then.
+    pub fn poll_send(&mut self, now: Instant) -> Option<Outgoing> {
+        if self.poll_timeout().map_or(false, |at| at > now) {
+            return None;
+        }
+        if self.in_flight {
+            return None;
+        }
+        let message = self.next_part()?;
+        let queued = self.queue.front()?;
+        // cleared once the first part is sent
+        let reply_parent_message_id = queued.reply_to.clone();
+        self.in_flight = true;
+        Some(Outgoing {
+            id: queued.id,
+            broadcaster_id: self.broadcaster_id.clone(),
+            sender_id: self.sender_id.clone(),
+            message,
+            reply_parent_message_id,
+        })
+    }
+
+    /// The text of the next part to send, altered if it would be a duplicate
+    fn next_part(&self) -> Option<String> {
+        let queued = self.queue.front()?;
+        let part = queued.parts.front()?;
+        let duplicate = self
+            .last_sent
+            .as_ref()
+            .map_or(false, |(last, _)| last == part);
+        match &self.config.duplicates {
+            Duplicates::Alter(suffix) if queued.alter || duplicate => {
+                Some(format!("{part}{suffix}"))
+            }
+            _ => Some(part.clone()),
+        }
+    }
+
+    /// Handle the response to the last [`Outgoing`] message
+    pub fn handle_response(
+        &mut self,
+        response: &SendChatMessageResponse,
+        now: Instant,
+    ) -> Option<Outcome> {
+        if !std::mem::replace(&mut self.in_flight, false) {
+            return None;
+        }
+        if response.is_sent {
+            let sent = self.next_part()?;
+            let queued = self.queue.front_mut()?;
+            queued.parts.pop_front();
+            queued.attempts = 0;
+            queued.alter = false;
+            queued.reply_to = None;
+            let id = queued.id;
+            let remaining_parts = queued.parts.len();
+            if remaining_parts == 0 {
+                self.queue.pop_front();
+            }
+            self.last_sent = Some((sent, now));
+            self.next_send = Some(now + self.interval());
+            return Some(Outcome::Sent {
+                id,
+                message_id: response.message_id.clone(),
+                remaining_parts,
+            });
+        }
+        let (reason, message) = match &response.drop_reason {
+            Some(ChatMessageDropReason { code, message, .. }) => {
+                (DropReason::from_code(code), Some(message.clone()))
+            }
+            None => (
+                DropReason::Other(ChatMessageDropCode::Unknown(String::new())),
+                None,
+            ),
+        };
+        self.retry_or_drop(reason, message, now)
+    }
+
+    /// Handle a failed request for the last [`Outgoing`] message
+    ///
+    /// The message is retried after [`QueueConfig::retry_delay`], up to [`QueueConfig::max_retries`] times.
+    pub fn handle_error(&mut self, now: Instant) -> Option<Outcome> {
+        if !std::mem::replace(&mut self.in_flight, false) {
+            return None;
+        }
+        self.retry_or_drop(DropReason::RequestFailed, None, now)
+    }
+
+    fn retry_or_drop(
+        &mut self,
+        reason: DropReason,
+        message: Option<String>,
+        now: Instant,
+    ) -> Option<Outcome> {
+        let delay = match reason {
+            DropReason::SlowMode => self.interval().max(self.config.retry_delay),
+            DropReason::Duplicate | DropReason::NotUnique => match self.config.duplicates {
+                Duplicates::Alter(_) => self.config.min_interval,
+                Duplicates::Wait => self.config.duplicate_window,
+            },
+            _ => self.config.retry_delay,
+        };
+        let max_retries = self.config.max_retries;
+        let queued = self.queue.front_mut()?;
+        let id = queued.id;
+        if reason.is_retryable() && queued.attempts < max_retries {
+            queued.attempts += 1;
+            if matches!(reason, DropReason::Duplicate | DropReason::NotUnique) {
+                queued.alter = !queued.alter;
+            }
+            let at = now + delay;
+            self.next_send = Some(at);
+            Some(Outcome::Retrying { id, reason, at })
+        } else {
+            self.queue.pop_front();
+            Some(Outcome::Dropped {
+                id,
+                reason,
+                message,
+            })
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        match self.restrictions.slow_mode {
+            Some(slow_mode) if !self.config.exempt => slow_mode.max(self.config.min_interval),
+            _ => self.config.min_interval,
+        }
+    }
+
+    /// Send the next message with a [`HelixClient`](crate::helix::HelixClient), if one can be sent at `now`
+    ///
+    /// Returns `Ok(None)` if no message can be sent yet, wait until [`ChatQueue::poll_timeout`] before calling this again.
+    /// A failed request is handled like with [`ChatQueue::handle_error`], the [`SendError`] carries the outcome,
+    /// which is [`Outcome::Dropped`] when the message ran out of retries.
+    #[cfg(feature = "client")]
+    #[cfg_attr(nightly, doc(cfg(feature = "client")))]
+    pub async fn send_next<'c, C, T>(
+        &mut self,
+        client: &'c crate::helix::HelixClient<'c, C>,
+        token: &T,
+        now: Instant,
+    ) -> Result<Option<Outcome>, SendError<<C as crate::HttpClient>::Error>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+    {
+        let Some(outgoing) = self.poll_send(now) else {
+            return Ok(None);
+        };
+        match client
+            .req_post(super::SendChatMessageRequest::new(), outgoing.body(), token)
+            .await
+        {
+            Ok(response) => Ok(self.handle_response(&response.data, now)),
+            Err(error) => Err(SendError {
+                outcome: self.handle_error(now),
+                error,
+            }),
+        }
+    }
+}
+
+/// A failed request in [`ChatQueue::send_next`]
+#[cfg(feature = "client")]
+#[cfg_attr(nightly, doc(cfg(feature = "client")))]
+#[derive(Debug, thiserror::Error)]
+#[error("could not send chat message")]
+#[non_exhaustive]
+pub struct SendError<RE: std::error::Error + Send + Sync + 'static> {
+    /// The request error
+    #[source]
+    pub error: crate::helix::ClientRequestError<RE>,
+    /// What happened to the message, see [`ChatQueue::handle_error`]
+    pub outcome: Option<Outcome>,
+}
+
+/// Split a message into parts of at most `max_chars` characters
+///
+/// Messages are split on whitespace where possible. Words longer than `max_chars` are split between characters,
+/// but not before combining marks, variation selectors, emoji modifiers and tags, or after a zero width joiner.
+/// Whitespace around the parts is trimmed, and empty messages result in no parts.
+pub fn split_message(message: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut parts = vec![];
+    let mut rest = message.trim();
+    while !rest.is_empty() {
+        // byte index after `max_chars` characters
+        let Some((limit, c)) = rest.char_indices().nth(max_chars) else {
+            parts.push(rest.to_owned());
+            break;
+        };
+        // the first `max_chars` characters and the one after
+        let window = &rest[..limit + c.len_utf8()];
+        let cut = window
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map(|(i, _)| i)
+            .filter(|i| *i > 0)
+            .unwrap_or_else(|| {
+                window
+                    .char_indices()
+                    .map(|(i, _)| i)
+                    .rev()
+                    .find(|i| *i > 0 && is_boundary(rest, *i))
+                    .unwrap_or(limit)
+            });
+        parts.push(rest[..cut].trim_end().to_owned());
+        rest = rest[cut..].trim_start();
+    }
+    parts
+}
+
+/// Whether `s` can be split at byte `i` without breaking up a character sequence
+fn is_boundary(s: &str, i: usize) -> bool {
+    let before = s[..i].chars().next_back();
+    let after = s[i..].chars().next();
+    !matches!(after, Some(c) if is_extending(c)) && before != Some('\u{200D}')
+}
+
+fn is_extending(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{200D}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0020}'..='\u{E007F}'
+        | '\u{E0100}'..='\u{E01EF}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(json: &str) -> SendChatMessageResponse { serde_json::from_str(json).unwrap() }
+
+    fn sent() -> SendChatMessageResponse {
+        response(r#"{"message_id":"abc-123","is_sent":true,"drop_reason":null}"#)
+    }
+
+    fn dropped(code: &str) -> SendChatMessageResponse {
+        response(&format!(
+            r#"{{"message_id":"","is_sent":false,"drop_reason":{{"code":"{code}","message":"dropped"}}}}"#
+        ))
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(split_message("  ", 10), Vec::<String>::new());
+        assert_eq!(split_message("hello world", 20), ["hello world"]);
+        assert_eq!(split_message("hello big world", 9), ["hello big", "world"]);
+        assert_eq!(split_message("abcdefgh ij", 4), ["abcd", "efgh", "ij"]);
+        // never split a combining mark or a zwj sequence
+        assert_eq!(split_message("abce\u{301}f", 4), ["abc", "e\u{301}f"]);
+        assert_eq!(split_message("ab👩\u{200D}💻", 3), ["ab", "👩\u{200D}💻"]);
+        assert_eq!(split_message("ab👍🏽", 3), ["ab", "👍🏽"]);
+        let parts = split_message(&"word ".repeat(300), 500);
+        assert!(parts.iter().all(|p| p.chars().count() <= 500));
+        assert_eq!(parts.join(" "), "word ".repeat(300).trim());
+    }
+
+    #[test]
+    fn send_parts_and_alter_duplicates() {
+        let mut queue = ChatQueue::new("1", "2", QueueConfig::default().max_length(7));
+        let now = Instant::now();
+        let id = queue.push_reply("hello hello", "parent");
+
+        let first = queue.poll_send(now).unwrap();
+        assert_eq!(first.message, "hello");
+        assert_eq!(
+            first
+                .reply_parent_message_id
+                .as_deref()
+                .map(|id| id.as_str()),
+            Some("parent")
+        );
+        assert!(queue.poll_send(now).is_none());
+        assert_eq!(
+            queue.handle_response(&sent(), now),
+            Some(Outcome::Sent {
+                id,
+                message_id: Some("abc-123".into()),
+                remaining_parts: 1
+            })
+        );
+
+        let later = now + Duration::from_millis(1500);
+        assert_eq!(queue.poll_timeout(), Some(later));
+        assert!(queue.poll_send(now).is_none());
+        let second = queue.poll_send(later).unwrap();
+        assert_eq!(second.message, "hello \u{E0000}");
+        assert_eq!(second.reply_parent_message_id, None);
+        assert!(matches!(
+            queue.handle_response(&sent(), later),
+            Some(Outcome::Sent {
+                remaining_parts: 0,
+                ..
+            })
+        ));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn altered_parts_fit() {
+        let mut queue = ChatQueue::new("1", "2", QueueConfig::default());
+        let now = Instant::now();
+        queue.push(&"a".repeat(500));
+
+        let first = queue.poll_send(now).unwrap();
+        assert_eq!(first.message.chars().count(), 498);
+        assert!(matches!(
+            queue.handle_response(&dropped("msg_duplicate"), now),
+            Some(Outcome::Retrying {
+                reason: DropReason::Duplicate,
+                ..
+            })
+        ));
+        let retry = queue.poll_send(now + Duration::from_secs(2)).unwrap();
+        assert!(retry.message.ends_with('\u{E0000}'));
+        assert_eq!(retry.message.chars().count(), 500);
+    }
+
+    #[test]
+    fn slow_mode_and_drops() {
+        let mut queue = ChatQueue::new("1", "2", QueueConfig::default());
+        queue.set_restrictions(ChatRestrictions {
+            slow_mode: Some(Duration::from_secs(10)),
+            ..ChatRestrictions::default()
+        });
+        let now = Instant::now();
+        let first = queue.push("first");
+        let second = queue.push("second");
+
+        queue.poll_send(now).unwrap();
+        assert!(matches!(
+            queue.handle_response(&dropped("msg_slowmode"), now),
+            Some(Outcome::Retrying { id, reason: DropReason::SlowMode, at }) if id == first && at == now + Duration::from_secs(10)
+        ));
+        let now = now + Duration::from_secs(10);
+        assert_eq!(queue.poll_send(now).unwrap().message, "first");
+        queue.handle_response(&sent(), now);
+        assert_eq!(queue.poll_timeout(), Some(now + Duration::from_secs(10)));
+
+        let now = now + Duration::from_secs(10);
+        queue.poll_send(now).unwrap();
+        assert_eq!(
+            queue.handle_response(&dropped("msg_banned"), now),
+            Some(Outcome::Dropped {
+                id: second,
+                reason: DropReason::Banned,
+                message: Some("dropped".to_owned()),
+            })
+        );
+        assert!(queue.is_empty());
+    }
+}
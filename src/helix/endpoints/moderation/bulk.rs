@@ -0,0 +1,751 @@
+//! Ban, time out and unban many users at once with [Ban User](super::ban_user) and [Unban User](super::unban_user)
+//!
+//! During a hate raid, hundreds of accounts may have to be banned quickly, but every ban is a separate request.
+//! [`BulkModerator`] takes a list of [targets](Target) and:
+//!
+//! * resolves logins to user IDs with [Get Users](crate::helix::users::get_users), 100 at a time,
+//! * sends the requests with at most [`BulkConfig::concurrency`] in flight,
+//!   and no faster than [`BulkConfig::requests_per_minute`],
+//! * retries requests that were rate limited or failed on Twitch's side, backing off for everyone,
+//! * reports the [outcome](Outcome) for every user in a [`BatchReport`], which can be [undone](BulkModerator::undo).
+//!
+//! With [`BulkConfig::dry_run`], logins are resolved but no user is banned or unbanned.
+//!
+//! The crate doesn't depend on an async runtime, so the functions take a `sleep` function used to wait for the rate limit,
+//! e.g. [`tokio::time::sleep`](https://docs.rs/tokio/latest/tokio/time/fn.sleep.html).
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use twitch_api::helix::{
+//!     self,
+//!     moderation::bulk::{Action, BulkConfig, BulkModerator, Target},
+//! };
+//! # let client: helix::HelixClient<'static, twitch_api::client::DummyHttpClient> = helix::HelixClient::default();
+//! # let token = twitch_oauth2::AccessToken::new("validtoken".to_string());
+//! # let token = twitch_oauth2::UserToken::from_existing(&client, token, None, None).await?;
+//!
+//! let moderator = BulkModerator::new("1234", "5678", BulkConfig::default());
+//! let targets = vec![Target::login("hateraider1"), Target::login("hateraider2"), Target::id("9876")];
+//! let report = moderator
+//!     .run(&client, &token, &Action::ban("hate raid"), targets, tokio::time::sleep)
+//!     .await?;
+//! for result in report.failed() {
+//!     println!("could not ban {}: {:?}", result.target, result.outcome);
+//! }
+//!
+//! // it was a mistake
+//! moderator.undo(&client, &token, &report, tokio::time::sleep).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::*;
+
+/// Longest timeout Twitch allows, two weeks
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(1_209_600);
+
+/// A user to moderate
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Target {
+    /// A user ID
+    Id(types::UserId),
+    /// A login, resolved to a user ID before moderating
+    Login(types::UserName),
+}
+
+impl Target {
+    /// A user by ID
+    pub fn id(id: impl Into<types::UserId>) -> Self { Self::Id(id.into()) }
+
+    /// A user by login
+    pub fn login(login: impl Into<types::UserName>) -> Self { Self::Login(login.into()) }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id(id) => write!(f, "user {id}"),
+            Self::Login(login) => f.write_str(login.as_str()),
+        }
+    }
+}
+
+impl From<types::UserId> for Target {
+    fn from(id: types::UserId) -> Self { Self::Id(id) }
+}
+
+impl From<types::UserName> for Target {
+    fn from(login: types::UserName) -> Self { Self::Login(login) }
+}
+
+/// What to do with the users of a batch
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Action {
+    /// Ban the users, or time them out if there is a duration
+    Ban {
+        /// The reason, at most 500 characters
+        reason: String,
+        /// Duration of the timeout in seconds, or `None` for a ban
+        duration: Option<u32>,
+    },
+    /// Remove bans and timeouts
+    Unban,
+}
+
+impl Action {
+    /// Ban the users indefinitely
+    pub fn ban(reason: impl Into<String>) -> Self {
+        Self::Ban {
+            reason: reason.into(),
+            duration: None,
+        }
+    }
+
+    /// Time out the users for one second, which removes their messages from chat
+    pub fn purge(reason: impl Into<String>) -> Self {
+        Self::Ban {
+            reason: reason.into(),
+            duration: Some(1),
+        }
+    }
+
+    /// Time out the users. The duration is rounded to seconds and clamped to between 1 second and [`MAX_TIMEOUT`]
+    pub fn timeout(duration: Duration, reason: impl Into<String>) -> Self {
+        Self::Ban {
+            reason: reason.into(),
+            duration: Some(
+                duration
+                    .clamp(Duration::from_secs(1), MAX_TIMEOUT)
+                    .as_secs() as u32,
+            ),
+        }
+    }
+}
+
+/// Configuration for a [`BulkModerator`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct BulkConfig {
+    /// Maximum number of requests in flight. Defaults to `4`
+    pub concurrency: usize,
+    /// Maximum number of requests to start per minute. Defaults to `600`, leaving room in the default
+    /// Helix bucket of 800 points per minute for other requests
+    pub requests_per_minute: u32,
+    /// How often to retry a request that was rate limited or failed with a server error. Defaults to `3`
+    pub max_retries: u32,
+    /// Time to wait before the first retry, doubled for every following retry. Defaults to 5 seconds
+    pub retry_delay: Duration,
+    /// Only resolve the users, without banning or unbanning anyone. Defaults to `false`
+    pub dry_run: bool,
+}
+
+impl Default for BulkConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            requests_per_minute: 600,
+            max_retries: 3,
+            retry_delay: Duration::from_secs(5),
+            dry_run: false,
+        }
+    }
+}
+
+impl BulkConfig {
+    /// Set the maximum number of requests in flight
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Set the maximum number of requests per minute
+    pub fn requests_per_minute(mut self, requests_per_minute: u32) -> Self {
+        self.requests_per_minute = requests_per_minute.max(1);
+        self
+    }
+
+    /// Set how often to retry a request
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the time to wait before the first retry
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Set whether to only resolve the users
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// Spaces out requests to stay below a number of requests per minute
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter allowing `requests_per_minute` requests per minute
+    pub fn new(requests_per_minute: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(60) / requests_per_minute.max(1),
+            next: None,
+        }
+    }
+
+    /// Reserve a slot for a request, returning when the request may be sent
+    pub fn reserve(&mut self, now: Instant) -> Instant {
+        let at = self.next.map_or(now, |next| next.max(now));
+        self.next = Some(at + self.interval);
+        at
+    }
+
+    /// Don't allow any request before `until`, e.g. after being rate limited
+    pub fn back_off(&mut self, until: Instant) {
+        self.next = Some(self.next.map_or(until, |next| next.max(until)));
+    }
+}
+
+/// What happened to a user in a batch
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Outcome {
+    /// The user would have been moderated, but this was a [dry run](BulkConfig::dry_run)
+    DryRun,
+    /// The user was banned or timed out
+    Banned(BanUser),
+    /// The ban or timeout of the user was removed
+    Unbanned,
+    /// Nothing changed, the user was already banned, or wasn't banned when unbanning
+    Unchanged,
+    /// No user with the login exists
+    NotFound,
+    /// The user appeared more than once in the batch, and was only moderated once
+    Duplicate,
+    /// The request failed
+    Failed {
+        /// Status code of the response, if there was one
+        status: Option<http::StatusCode>,
+        /// The error message
+        message: String,
+    },
+}
+
+impl Outcome {
+    /// Whether the user is in the state the batch wanted
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self,
+            Self::DryRun | Self::Banned(_) | Self::Unbanned | Self::Unchanged | Self::Duplicate
+        )
+    }
+}
+
+/// The result for one [target](Target) of a batch
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UserResult {
+    /// The target as given
+    pub target: Target,
+    /// The ID of the user, if the target could be resolved
+    pub user_id: Option<types::UserId>,
+    /// What happened to the user
+    pub outcome: Outcome,
+}
+
+/// The results of a batch, in the order the targets were given
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BatchReport {
+    /// The channel
+    pub broadcaster_id: types::UserId,
+    /// The moderator the requests were made as
+    pub moderator_id: types::UserId,
+    /// What was done
+    pub action: Action,
+    /// Whether this was a [dry run](BulkConfig::dry_run)
+    pub dry_run: bool,
+    /// The result for every target
+    pub results: Vec<UserResult>,
+}
+
+impl BatchReport {
+    /// Results of users that are in the state the batch wanted
+    pub fn succeeded(&self) -> impl Iterator<Item = &UserResult> + '_ {
+        self.results.iter().filter(|r| r.outcome.is_success())
+    }
+
+    /// Results of users that could not be found or moderated
+    pub fn failed(&self) -> impl Iterator<Item = &UserResult> + '_ {
+        self.results.iter().filter(|r| !r.outcome.is_success())
+    }
+
+    /// Users that were banned or timed out by this batch, and would be unbanned to undo it
+    ///
+    /// Users that were already banned are left out, as are all users of an [unban](Action::Unban) batch,
+    /// since the reasons and durations of their bans aren't known.
+    pub fn undo_targets(&self) -> Vec<Target> {
+        self.results
+            .iter()
+            .filter(|r| matches!(r.outcome, Outcome::Banned(_)))
+            .filter_map(|r| r.user_id.clone().map(Target::Id))
+            .collect()
+    }
+}
+
+/// How to handle a failed request
+#[cfg(feature = "client")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Failure {
+    Retry,
+    Unchanged,
+    Fatal,
+}
+
+/// Classify an error from Twitch
+#[cfg(feature = "client")]
+fn classify(status: Option<http::StatusCode>, message: &str) -> Failure {
+    let Some(status) = status else {
+        return Failure::Fatal;
+    };
+    let message = message.to_ascii_lowercase();
+    if status == http::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        Failure::Retry
+    } else if status == http::StatusCode::BAD_REQUEST
+        && (message.contains("already banned") || message.contains("not banned"))
+    {
+        Failure::Unchanged
+    } else {
+        Failure::Fatal
+    }
+}
+
+/// Ban, time out or unban many users in a channel
+///
+/// See the [module documentation](self) for more.
+#[derive(Debug)]
+pub struct BulkModerator {
+    broadcaster_id: types::UserId,
+    moderator_id: types::UserId,
+    config: BulkConfig,
+    limiter: std::sync::Mutex<RateLimiter>,
+}
+
+impl BulkModerator {
+    /// Create a moderator for the chat of `broadcaster_id`, making requests as `moderator_id`
+    pub fn new(
+        broadcaster_id: impl Into<types::UserId>,
+        moderator_id: impl Into<types::UserId>,
+        config: BulkConfig,
+    ) -> Self {
+        Self {
+            broadcaster_id: broadcaster_id.into(),
+            moderator_id: moderator_id.into(),
+            limiter: std::sync::Mutex::new(RateLimiter::new(config.requests_per_minute)),
+            config,
+        }
+    }
+
+    /// The broadcaster whose chat is moderated
+    pub fn broadcaster_id(&self) -> &types::UserIdRef { &self.broadcaster_id }
+
+    /// The moderator making the requests
+    pub fn moderator_id(&self) -> &types::UserIdRef { &self.moderator_id }
+
+    /// The configuration
+    pub fn config(&self) -> &BulkConfig { &self.config }
+}
+
+#[cfg(feature = "client")]
+#[cfg_attr(nightly, doc(cfg(feature = "client")))]
+impl BulkModerator {
+    /// Time until the next request may be sent
+    fn reserve(&self) -> Duration {
+        let now = Instant::now();
+        self.limiter
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .reserve(now)
+            .saturating_duration_since(now)
+    }
+
+    fn back_off(&self, delay: Duration) {
+        let until = Instant::now().checked_add(delay);
+        let mut limiter = self.limiter.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(until) = until {
+            limiter.back_off(until);
+        }
+    }
+
+    /// Time to wait before a retry, after `retries` retries
+    fn retry_delay(&self, retries: u32) -> Duration {
+        self.config
+            .retry_delay
+            .saturating_mul(2u32.saturating_pow(retries))
+    }
+
+    /// Build the report of a batch from resolved targets, with users that weren't found or appear more than once settled
+    /// and the rest marked as [dry run](Outcome::DryRun)
+    fn report(
+        &self,
+        action: &Action,
+        resolved: Vec<(Target, Option<types::UserId>)>,
+    ) -> BatchReport {
+        let mut seen = std::collections::HashSet::new();
+        let results = resolved
+            .into_iter()
+            .map(|(target, user_id)| {
+                let outcome = match &user_id {
+                    None => Outcome::NotFound,
+                    Some(id) if !seen.insert(id.clone()) => Outcome::Duplicate,
+                    Some(_) => Outcome::DryRun,
+                };
+                UserResult {
+                    target,
+                    user_id,
+                    outcome,
+                }
+            })
+            .collect();
+        BatchReport {
+            broadcaster_id: self.broadcaster_id.clone(),
+            moderator_id: self.moderator_id.clone(),
+            action: action.clone(),
+            dry_run: self.config.dry_run,
+            results,
+        }
+    }
+
+    /// Resolve the logins of the targets to user IDs, 100 at a time
+    ///
+    /// Logins of users that don't exist resolve to `None`.
+    /// Requests that were rate limited or failed on Twitch's side are retried like the moderation requests.
+    pub async fn resolve<'c, C, T, S, F>(
+        &self,
+        client: &'c crate::helix::HelixClient<'c, C>,
+        token: &T,
+        targets: impl IntoIterator<Item = Target>,
+        sleep: S,
+    ) -> Result<
+        Vec<(Target, Option<types::UserId>)>,
+        crate::helix::ClientRequestError<<C as crate::HttpClient>::Error>,
+    >
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+        S: Fn(Duration) -> F,
+        F: std::future::Future<Output = ()>,
+    {
+        let targets: Vec<Target> = targets.into_iter().collect();
+        let logins: Vec<&types::UserNameRef> = targets
+            .iter()
+            .filter_map(|target| match target {
+                Target::Login(login) => Some(&**login),
+                Target::Id(_) => None,
+            })
+            .collect();
+        use crate::helix::{ClientRequestError, HelixRequestGetError};
+
+        let mut ids = std::collections::HashMap::new();
+        for chunk in logins.chunks(100) {
+            let mut retries = 0;
+            let users = loop {
+                let wait = self.reserve();
+                if !wait.is_zero() {
+                    sleep(wait).await;
+                }
+                let error = match client
+                    .req_get(crate::helix::users::GetUsersRequest::logins(chunk), token)
+                    .await
+                {
+                    Ok(response) => break response.data,
+                    Err(error) => error,
+                };
+                let (status, message) = match &error {
+                    ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+                        status,
+                        message,
+                        ..
+                    }) => (Some(*status), message.as_str()),
+                    _ => (None, ""),
+                };
+                match classify(status, message) {
+                    Failure::Retry if retries < self.config.max_retries => {
+                        self.back_off(self.retry_delay(retries));
+                        retries += 1;
+                    }
+                    _ => return Err(error),
+                }
+            };
+            for user in users {
+                ids.insert(user.login.as_str().to_ascii_lowercase(), user.id);
+            }
+        }
+        Ok(targets
+            .into_iter()
+            .map(|target| {
+                let id = match &target {
+                    Target::Id(id) => Some(id.clone()),
+                    Target::Login(login) => ids.get(&login.as_str().to_ascii_lowercase()).cloned(),
+                };
+                (target, id)
+            })
+            .collect())
+    }
+
+    /// Resolve the targets and apply the action to all of them
+    ///
+    /// Only failing to resolve the logins is an error, failures for single users are reported in the [`BatchReport`].
+    pub async fn run<'c, C, T, S, F>(
+        &self,
+        client: &'c crate::helix::HelixClient<'c, C>,
+        token: &T,
+        action: &Action,
+        targets: impl IntoIterator<Item = Target>,
+        sleep: S,
+    ) -> Result<BatchReport, crate::helix::ClientRequestError<<C as crate::HttpClient>::Error>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+        S: Fn(Duration) -> F,
+        F: std::future::Future<Output = ()>,
+    {
+        use futures::StreamExt;
+
+        let resolved = self.resolve(client, token, targets, &sleep).await?;
+        let mut report = self.report(action, resolved);
+        if self.config.dry_run {
+            return Ok(report);
+        }
+        let sleep = &sleep;
+        report.results = futures::stream::iter(std::mem::take(&mut report.results))
+            .map(|mut result| async move {
+                let pending = matches!(result.outcome, Outcome::DryRun);
+                if let (true, Some(user_id)) = (pending, &result.user_id) {
+                    result.outcome = self.moderate(client, token, action, user_id, sleep).await;
+                }
+                result
+            })
+            .buffered(self.config.concurrency.max(1))
+            .collect()
+            .await;
+        Ok(report)
+    }
+
+    /// Unban the users that were banned or timed out by a batch, see [`BatchReport::undo_targets`]
+    pub async fn undo<'c, C, T, S, F>(
+        &self,
+        client: &'c crate::helix::HelixClient<'c, C>,
+        token: &T,
+        report: &BatchReport,
+        sleep: S,
+    ) -> Result<BatchReport, crate::helix::ClientRequestError<<C as crate::HttpClient>::Error>>
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+        S: Fn(Duration) -> F,
+        F: std::future::Future<Output = ()>,
+    {
+        self.run(client, token, &Action::Unban, report.undo_targets(), sleep)
+            .await
+    }
+
+    /// Apply the action to one user, retrying if rate limited
+    async fn moderate<'c, C, T, S, F>(
+        &self,
+        client: &'c crate::helix::HelixClient<'c, C>,
+        token: &T,
+        action: &Action,
+        user_id: &types::UserIdRef,
+        sleep: &S,
+    ) -> Outcome
+    where
+        C: crate::HttpClient + Send + Sync + 'c,
+        T: twitch_oauth2::TwitchToken + Send + Sync + ?Sized,
+        S: Fn(Duration) -> F,
+        F: std::future::Future<Output = ()>,
+    {
+        use crate::helix::{ClientRequestError, HelixRequestDeleteError, HelixRequestPostError};
+
+        let mut retries = 0;
+        loop {
+            let wait = self.reserve();
+            if !wait.is_zero() {
+                sleep(wait).await;
+            }
+            let result = match action {
+                Action::Ban { reason, duration } => client
+                    .req_post(
+                        BanUserRequest::new(&*self.broadcaster_id, &*self.moderator_id),
+                        BanUserBody::new(user_id, reason.as_str(), *duration),
+                        token,
+                    )
+                    .await
+                    .map(|response| Outcome::Banned(response.data)),
+                Action::Unban => client
+                    .req_delete(
+                        UnbanUserRequest::new(&*self.broadcaster_id, &*self.moderator_id, user_id),
+                        token,
+                    )
+                    .await
+                    .map(|_| Outcome::Unbanned),
+            };
+            let error = match result {
+                Ok(outcome) => return outcome,
+                Err(error) => error,
+            };
+            let (status, message) = match &error {
+                ClientRequestError::HelixRequestPostError(HelixRequestPostError::Error {
+                    status,
+                    message,
+                    ..
+                })
+                | ClientRequestError::HelixRequestDeleteError(HelixRequestDeleteError::Error {
+                    status,
+                    message,
+                    ..
+                }) => (Some(*status), message.clone()),
+                error => (None, error.to_string()),
+            };
+            match classify(status, &message) {
+                Failure::Retry if retries < self.config.max_retries => {
+                    self.back_off(self.retry_delay(retries));
+                    retries += 1;
+                }
+                Failure::Unchanged => return Outcome::Unchanged,
+                Failure::Retry | Failure::Fatal => return Outcome::Failed { status, message },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limiter() {
+        let mut limiter = RateLimiter::new(120);
+        let now = Instant::now();
+        assert_eq!(limiter.reserve(now), now);
+        assert_eq!(limiter.reserve(now), now + Duration::from_millis(500));
+        assert_eq!(limiter.reserve(now), now + Duration::from_secs(1));
+
+        limiter.back_off(now + Duration::from_secs(10));
+        assert_eq!(limiter.reserve(now), now + Duration::from_secs(10));
+        // backing off never brings the next slot forward
+        limiter.back_off(now);
+        assert_eq!(
+            limiter.reserve(now + Duration::from_secs(20)),
+            now + Duration::from_secs(20)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn classify_errors() {
+        use http::StatusCode;
+
+        assert_eq!(
+            classify(Some(StatusCode::TOO_MANY_REQUESTS), ""),
+            Failure::Retry
+        );
+        assert_eq!(
+            classify(Some(StatusCode::SERVICE_UNAVAILABLE), ""),
+            Failure::Retry
+        );
+        assert_eq!(
+            classify(
+                Some(StatusCode::BAD_REQUEST),
+                "The user specified in the user_id field is already banned."
+            ),
+            Failure::Unchanged
+        );
+        assert_eq!(
+            classify(
+                Some(StatusCode::BAD_REQUEST),
+                "The user specified in the user_id field is not banned."
+            ),
+            Failure::Unchanged
+        );
+        assert_eq!(
+            classify(
+                Some(StatusCode::BAD_REQUEST),
+                "The user specified in the user_id field may not be banned."
+            ),
+            Failure::Fatal
+        );
+        assert_eq!(classify(None, "connection reset"), Failure::Fatal);
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn report() {
+        let moderator = BulkModerator::new("1234", "5678", BulkConfig::default().dry_run(true));
+        let resolved = vec![
+            (Target::login("spammer"), Some("1".into())),
+            (Target::login("nobody"), None),
+            (Target::id("1"), Some("1".into())),
+            (Target::id("2"), Some("2".into())),
+        ];
+        let report = moderator.report(&Action::ban("spam"), resolved);
+        let outcomes: Vec<_> = report.results.iter().map(|r| &r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            [
+                &Outcome::DryRun,
+                &Outcome::NotFound,
+                &Outcome::Duplicate,
+                &Outcome::DryRun
+            ]
+        );
+        assert_eq!(report.failed().count(), 1);
+        // nothing was banned in a dry run
+        assert!(report.undo_targets().is_empty());
+
+        let ban = BanUser {
+            broadcaster_id: "1234".into(),
+            created_at: types::Timestamp::try_from("2021-07-15T20:00:00Z").unwrap(),
+            end_time: None,
+            moderator_id: "5678".into(),
+            user_id: "2".into(),
+        };
+        let mut report = report;
+        report.results[3].outcome = Outcome::Banned(ban);
+        report.results[0].outcome = Outcome::Unchanged;
+        assert_eq!(report.undo_targets(), [Target::id("2")]);
+
+        assert_eq!(
+            Action::timeout(Duration::from_secs(60 * 60 * 24 * 30), "spam"),
+            Action::Ban {
+                reason: "spam".to_owned(),
+                duration: Some(1_209_600)
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn retry_delay() {
+        let moderator = BulkModerator::new("1234", "5678", BulkConfig::default().max_retries(100));
+        assert_eq!(moderator.retry_delay(0), Duration::from_secs(5));
+        assert_eq!(moderator.retry_delay(2), Duration::from_secs(20));
+        // doesn't overflow with many retries
+        assert_eq!(moderator.retry_delay(40), Duration::from_secs(5) * u32::MAX);
+        moderator.back_off(Duration::MAX);
+    }
+}
@@ -47,6 +47,7 @@ use std::borrow::Cow;
 pub mod add_blocked_term;
 pub mod add_channel_moderator;
 pub mod ban_user;
+pub mod bulk;
 pub mod check_automod_status;
 pub mod delete_chat_messages;
 pub mod get_automod_settings;
@@ -76,9 +76,9 @@ impl<'a> StartARaidRequest<'a> {
 #[non_exhaustive]
 pub struct StartARaidResponse {
     /// The UTC date and time, in RFC3339 format, when the raid request was created.
-    created_at: types::Timestamp,
+    pub created_at: types::Timestamp,
     /// A Boolean value that indicates whether the channel being raided contains mature content.
-    is_mature: bool,
+    pub is_mature: bool,
 }
 impl Request for StartARaidRequest<'_> {
     type Response = StartARaidResponse;
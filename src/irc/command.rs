@@ -0,0 +1,125 @@
+//! Outgoing IRC commands
+
+use std::fmt;
+
+use crate::types;
+
+/// Capabilities to request with [`Command::CapReq`] to receive tags, `CLEARCHAT`, `USERNOTICE` and other Twitch messages
+pub const TWITCH_CAPABILITIES: &[&str] = &[
+    "twitch.tv/tags",
+    "twitch.tv/commands",
+    "twitch.tv/membership",
+];
+
+/// A command to send to Twitch IRC
+///
+/// [`Display`](fmt::Display) serializes the command without the trailing `\r\n`.
+/// Line breaks in messages are replaced with spaces, since they would end the command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Command<'a> {
+    /// Request capabilities, `CAP REQ`
+    CapReq(&'a [&'a str]),
+    /// Authenticate with an OAuth token, `PASS oauth:<token>`
+    Pass(&'a str),
+    /// Set the login of the connecting user, `NICK`
+    Nick(&'a types::UserNameRef),
+    /// Join channels, `JOIN`
+    Join(&'a [&'a types::UserNameRef]),
+    /// Leave channels, `PART`
+    Part(&'a [&'a types::UserNameRef]),
+    /// Send a chat message, `PRIVMSG`
+    Privmsg {
+        /// Login of the channel
+        channel_login: &'a types::UserNameRef,
+        /// The message
+        message: &'a str,
+        /// The message to reply to
+        reply_parent_message_id: Option<&'a types::MsgIdRef>,
+    },
+    /// Answer a `PING`, `PONG`
+    Pong(&'a str),
+}
+
+impl<'a> Command<'a> {
+    /// Send a chat message
+    pub fn privmsg(channel_login: &'a types::UserNameRef, message: &'a str) -> Self {
+        Self::Privmsg {
+            channel_login,
+            message,
+            reply_parent_message_id: None,
+        }
+    }
+
+    /// Reply to a chat message
+    pub fn reply(
+        channel_login: &'a types::UserNameRef,
+        reply_parent_message_id: &'a types::MsgIdRef,
+        message: &'a str,
+    ) -> Self {
+        Self::Privmsg {
+            channel_login,
+            message,
+            reply_parent_message_id: Some(reply_parent_message_id),
+        }
+    }
+}
+
+impl fmt::Display for Command<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CapReq(caps) => write!(f, "CAP REQ :{}", caps.join(" ")),
+            Self::Pass(token) => {
+                let token = token.strip_prefix("oauth:").unwrap_or(token);
+                write!(f, "PASS oauth:{token}")
+            }
+            Self::Nick(login) => write!(f, "NICK {login}"),
+            Self::Join(channels) => write!(f, "JOIN {}", Channels(channels)),
+            Self::Part(channels) => write!(f, "PART {}", Channels(channels)),
+            Self::Privmsg {
+                channel_login,
+                message,
+                reply_parent_message_id,
+            } => {
+                if let Some(id) = reply_parent_message_id {
+                    write!(
+                        f,
+                        "@reply-parent-msg-id={} ",
+                        super::raw::escape(id.as_str())
+                    )?;
+                }
+                write!(f, "PRIVMSG #{channel_login} :")?;
+                single_line(f, message)
+            }
+            Self::Pong(argument) => {
+                f.write_str("PONG :")?;
+                single_line(f, argument)
+            }
+        }
+    }
+}
+
+struct Channels<'a>(&'a [&'a types::UserNameRef]);
+
+impl fmt::Display for Channels<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, channel) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "#{channel}")?;
+        }
+        Ok(())
+    }
+}
+
+fn single_line(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            f.write_str(" ")?;
+        }
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        f.write_str(&line.replace('\r', " "))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,465 @@
+//! Convert IRC chat messages to EventSub [`channel.chat.message`](crate::eventsub::channel::ChannelChatMessageV1) payloads
+//!
+//! A [`Privmsg`] has the same information as a [`ChannelChatMessageV1Payload`], except for some details
+//! that IRC doesn't include:
+//!
+//! * mentions only have the login of the user, so `@login` only becomes a [mention fragment](Fragment::Mention)
+//!   for users added with [`Converter::insert_user`],
+//! * emotes only have their ID, so [emote fragments](Fragment::Emote) have an empty set ID, owner ID and format
+//!   unless the emote was added with [`Converter::insert_emote`],
+//! * cheermotes aren't marked at all. Words like `Cheer100` in messages with bits become
+//!   [cheermote fragments](Fragment::Cheermote) if their cheermote was added with `Converter::insert_cheermotes`,
+//!   which needs the `helix` feature.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use twitch_api::{
+//!     eventsub::channel::{chat::Fragment, ChannelChatMessageV1Payload},
+//!     irc::{convert::Converter, Message},
+//! };
+//!
+//! let line = "@badges=;bits=100;color=;display-name=viewer32;emotes=25:0-4;id=cc106a89-1814-919d-454c-f4f2f970aae7;room-id=1971641;user-id=4145994 :viewer32!viewer32@viewer32.tmi.twitch.tv PRIVMSG #streamer :Kappa cheer100 @streamer";
+//! let Message::Privmsg(privmsg) = Message::parse(line)? else {
+//!     panic!("not a PRIVMSG");
+//! };
+//!
+//! let mut converter = Converter::new();
+//! converter.insert_user("1971641", "streamer", "Streamer");
+//! let payload: ChannelChatMessageV1Payload = converter.convert(&privmsg);
+//! assert!(matches!(
+//!     payload.message.fragments.as_slice(),
+//!     [
+//!         Fragment::Emote { .. },
+//!         Fragment::Text { .. },
+//!         Fragment::Mention { .. },
+//!     ]
+//! ));
+//! # Ok::<(), twitch_api::irc::ParseError>(())
+//! ```
+
+use std::collections::HashMap;
+
+use super::{messages::parse_badges, Badge, Privmsg};
+use crate::eventsub::channel::chat::{self, message, Fragment};
+use crate::eventsub::channel::ChannelChatMessageV1Payload;
+use crate::types;
+
+/// Converts [`Privmsg`]s to [`ChannelChatMessageV1Payload`]s
+///
+/// See the [module documentation](self) for more.
+#[derive(Clone, Debug, Default)]
+pub struct Converter {
+    users: HashMap<types::UserName, (types::UserId, types::DisplayName)>,
+    emotes: HashMap<types::EmoteId, chat::Emote>,
+    #[cfg(feature = "helix")]
+    cheermotes: Vec<crate::helix::bits::Cheermote>,
+}
+
+impl Converter {
+    /// Create a converter that doesn't know any users, emotes or cheermotes
+    pub fn new() -> Self { Self::default() }
+
+    /// Add a user that can be mentioned
+    pub fn insert_user(
+        &mut self,
+        id: impl Into<types::UserId>,
+        login: impl Into<types::UserName>,
+        display_name: impl Into<types::DisplayName>,
+    ) {
+        self.users
+            .insert(login.into(), (id.into(), display_name.into()));
+    }
+
+    /// Add the details of an emote
+    pub fn insert_emote(
+        &mut self,
+        id: impl Into<types::EmoteId>,
+        emote_set_id: impl Into<types::EmoteSetId>,
+        owner_id: impl Into<types::UserId>,
+        format: Vec<types::EmoteAnimationSetting>,
+    ) {
+        let id = id.into();
+        self.emotes.insert(
+            id.clone(),
+            chat::Emote {
+                id,
+                emote_set_id: emote_set_id.into(),
+                owner_id: owner_id.into(),
+                format,
+            },
+        );
+    }
+
+    /// Add the cheermotes of a channel, as returned by [`GetCheermotesRequest`](crate::helix::bits::GetCheermotesRequest)
+    #[cfg(feature = "helix")]
+    #[cfg_attr(nightly, doc(cfg(feature = "helix")))]
+    pub fn insert_cheermotes(&mut self, cheermotes: &[crate::helix::bits::Cheermote]) {
+        self.cheermotes.retain(|known| {
+            !cheermotes
+                .iter()
+                .any(|cheermote| cheermote.prefix.eq_ignore_ascii_case(&known.prefix))
+        });
+        self.cheermotes.extend_from_slice(cheermotes);
+    }
+
+    /// Convert a chat message
+    pub fn convert(&self, privmsg: &Privmsg) -> ChannelChatMessageV1Payload {
+        let tag = |key| privmsg.tags.get(key).filter(|v| !v.is_empty());
+        let reply = privmsg.reply_parent.as_ref().map(|parent| {
+            let thread_user_login = parent
+                .thread_user_login
+                .clone()
+                .unwrap_or_else(|| parent.user_login.clone());
+            message::Reply {
+                parent_message_id: parent.message_id.clone(),
+                parent_message_body: parent.message_body.clone(),
+                parent_user_id: parent.user_id.clone(),
+                parent_user_name: parent.display_name.clone(),
+                parent_user_login: parent.user_login.clone(),
+                thread_message_id: parent
+                    .thread_message_id
+                    .clone()
+                    .unwrap_or_else(|| parent.message_id.clone()),
+                thread_user_id: tag("reply-thread-parent-user-id")
+                    .map(types::UserId::from)
+                    .unwrap_or_else(|| parent.user_id.clone()),
+                thread_user_name: tag("reply-thread-parent-display-name")
+                    .map(types::DisplayName::from)
+                    .unwrap_or_else(|| thread_user_login.as_str().into()),
+                thread_user_login,
+            }
+        });
+        let message_type = match tag("msg-id") {
+            Some("highlighted-message") => message::MessageType::ChannelPointsHighlighted,
+            Some("skip-subs-mode-message") => message::MessageType::ChannelPointsSubOnly,
+            Some("user-intro") => message::MessageType::UserIntro,
+            Some("gigantified-emote-message") => message::MessageType::PowerUpsGigantifiedEmote,
+            Some("animated-message") => message::MessageType::PowerUpsMessageEffect,
+            _ => message::MessageType::Text,
+        };
+        let source_badges = match (tag("source-badges"), tag("source-badge-info")) {
+            (Some(badges), info) => parse_badges(badges, info.unwrap_or_default())
+                .iter()
+                .map(convert_badge)
+                .collect(),
+            (None, _) => vec![],
+        };
+        ChannelChatMessageV1Payload {
+            broadcaster_user_id: privmsg.room_id.clone(),
+            broadcaster_user_name: privmsg.channel_login.as_str().into(),
+            broadcaster_user_login: privmsg.channel_login.clone(),
+            chatter_user_id: privmsg.user_id.clone(),
+            chatter_user_name: privmsg.display_name.clone(),
+            chatter_user_login: privmsg.user_login.clone(),
+            message_id: privmsg.message_id.clone(),
+            message: chat::Message {
+                text: privmsg.message.clone(),
+                fragments: self.fragments(privmsg),
+            },
+            message_type,
+            badges: privmsg.badges.iter().map(convert_badge).collect(),
+            cheer: privmsg.bits.map(|bits| message::Cheer {
+                bits: bits as usize,
+            }),
+            color: privmsg.color.clone().unwrap_or_else(|| "".into()),
+            reply,
+            channel_points_custom_reward_id: privmsg.custom_reward_id.clone(),
+            channel_points_animation_id: tag("animation-id").map(str::to_owned),
+            source_broadcaster_user_id: privmsg.source_room_id.clone(),
+            source_broadcaster_user_name: None,
+            source_broadcaster_user_login: None,
+            source_message_id: privmsg.source_message_id.clone(),
+            source_badges,
+        }
+    }
+
+    /// Split the message into fragments
+    fn fragments(&self, privmsg: &Privmsg) -> Vec<Fragment> {
+        let text = privmsg.message.as_str();
+        // byte offset of every character, and of the end
+        let offsets: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        let mut fragments = vec![];
+        let mut position = 0;
+        for emote in &privmsg.emotes {
+            let (Some(&start), Some(&end)) = (offsets.get(emote.start), offsets.get(emote.end))
+            else {
+                continue;
+            };
+            // skip overlapping emotes
+            if start < position {
+                continue;
+            }
+            self.text_fragments(
+                &text[position..start],
+                privmsg.bits.is_some(),
+                &mut fragments,
+            );
+            fragments.push(Fragment::Emote {
+                text: text[start..end].to_owned(),
+                emote: self
+                    .emotes
+                    .get(&emote.id)
+                    .cloned()
+                    .unwrap_or_else(|| chat::Emote {
+                        id: emote.id.clone(),
+                        emote_set_id: "".into(),
+                        owner_id: "".into(),
+                        format: vec![],
+                    }),
+            });
+            position = end;
+        }
+        self.text_fragments(&text[position..], privmsg.bits.is_some(), &mut fragments);
+        fragments
+    }
+
+    /// Split text between emotes into text, cheermote and mention fragments
+    fn text_fragments(&self, text: &str, has_bits: bool, fragments: &mut Vec<Fragment>) {
+        let mut word_start = None;
+        for (i, c) in text
+            .char_indices()
+            .chain(std::iter::once((text.len(), ' ')))
+        {
+            match (c.is_whitespace(), word_start) {
+                (false, None) => word_start = Some(i),
+                (true, Some(start)) => {
+                    word_start = None;
+                    let word = &text[start..i];
+                    if let Some(cheermote) = self.cheermote(word, has_bits) {
+                        fragments.push(Fragment::Cheermote {
+                            text: word.to_owned(),
+                            cheermote,
+                        });
+                    } else if let Some((mention, len)) = self.mention(word) {
+                        fragments.push(Fragment::Mention {
+                            text: word[..len].to_owned(),
+                            mention,
+                        });
+                        push_text(fragments, &word[len..]);
+                    } else {
+                        push_text(fragments, word);
+                    }
+                }
+                _ => {}
+            }
+            if c.is_whitespace() && i < text.len() {
+                push_text(fragments, &text[i..i + c.len_utf8()]);
+            }
+        }
+    }
+
+    #[cfg(feature = "helix")]
+    fn cheermote(&self, word: &str, has_bits: bool) -> Option<chat::Cheermote> {
+        use std::convert::TryFrom;
+
+        if !has_bits {
+            return None;
+        }
+        let cheer = crate::helix::bits::cheer::parse_cheer(word, &self.cheermotes)?;
+        Some(chat::Cheermote {
+            prefix: cheer.prefix.to_ascii_lowercase(),
+            bits: i32::try_from(cheer.bits).ok()?,
+            tier: i32::try_from(cheer.tier.min_bits).ok()?,
+        })
+    }
+
+    #[cfg(not(feature = "helix"))]
+    fn cheermote(&self, _word: &str, _has_bits: bool) -> Option<chat::Cheermote> { None }
+
+    /// A mention of a known user at the start of the word, and the length of the mention
+    fn mention(&self, word: &str) -> Option<(chat::Mention, usize)> {
+        let login = word.strip_prefix('@')?;
+        let len = login
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(login.len());
+        let login = types::UserName::from(login[..len].to_ascii_lowercase());
+        let (user_id, user_name) = self.users.get(&login)?;
+        Some((
+            chat::Mention {
+                user_id: user_id.clone(),
+                user_name: user_name.clone(),
+                user_login: login,
+            },
+            len + 1,
+        ))
+    }
+}
+
+impl From<&Privmsg> for ChannelChatMessageV1Payload {
+    /// Convert with a [`Converter`] that doesn't know any users, emotes or cheermotes
+    fn from(privmsg: &Privmsg) -> Self { Converter::new().convert(privmsg) }
+}
+
+/// Append text, merging it with a previous text fragment
+fn push_text(fragments: &mut Vec<Fragment>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match fragments.last_mut() {
+        Some(Fragment::Text { text: previous }) => previous.push_str(text),
+        _ => fragments.push(Fragment::Text {
+            text: text.to_owned(),
+        }),
+    }
+}
+
+fn convert_badge(badge: &Badge) -> message::Badge {
+    message::Badge {
+        set_id: badge.set_id.clone(),
+        id: badge.id.clone(),
+        info: badge.info.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irc::Message;
+
+    /// A cheermote with tiers for the given minimum bits
+    #[cfg(feature = "helix")]
+    fn cheermote(prefix: &str, tiers: &[i64]) -> crate::helix::bits::Cheermote {
+        let urls = serde_json::json!({"1": "", "1.5": "", "2": "", "3": "", "4": ""});
+        let images = serde_json::json!({"animated": urls, "static": urls});
+        let tiers: Vec<_> = tiers
+            .iter()
+            .map(|min_bits| {
+                serde_json::json!({
+                    "min_bits": min_bits,
+                    "id": min_bits.to_string(),
+                    "color": "#979797",
+                    "images": {"dark": images, "light": images},
+                    "can_cheer": true,
+                    "show_in_bits_card": true,
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "prefix": prefix,
+            "tiers": tiers,
+            "type": "global_first_party",
+            "order": 1,
+            "last_updated": "2018-05-22T00:06:04Z",
+            "is_charitable": false,
+        }))
+        .unwrap()
+    }
+
+    fn privmsg(line: &str) -> Privmsg {
+        match Message::parse(line).unwrap() {
+            Message::Privmsg(privmsg) => privmsg,
+            message => panic!("expected PRIVMSG, got {message:?}"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "helix")]
+    fn convert() {
+        let privmsg = privmsg("@badge-info=subscriber/16;badges=subscriber/12,bits/1000;bits=100;color=#00FF7F;display-name=viewer32;emotes=25:8-12;id=cc106a89-1814-919d-454c-f4f2f970aae7;reply-parent-display-name=streamer;reply-parent-msg-body=hello\\severyone;reply-parent-msg-id=c8f3b22b-ee0e-4d0a-9ad2-f64ba6f3d7c5;reply-parent-user-id=1971641;reply-parent-user-login=streamer;reply-thread-parent-msg-id=c8f3b22b-ee0e-4d0a-9ad2-f64ba6f3d7c5;reply-thread-parent-user-login=streamer;room-id=1971641;tmi-sent-ts=1699294307492;user-id=4145994 :viewer32!viewer32@viewer32.tmi.twitch.tv PRIVMSG #streamer :Hi chat Kappa @Streamer, cheer100 🎉 @nobody Cheer5");
+        let mut converter = Converter::new();
+        converter.insert_user("1971641", "streamer", "Streamer");
+        converter.insert_cheermotes(&[cheermote("Cheer", &[1, 100, 1000])]);
+        let payload = converter.convert(&privmsg);
+
+        // the same message from EventSub
+        let expected: ChannelChatMessageV1Payload = serde_json::from_value(serde_json::json!({
+            "broadcaster_user_id": "1971641",
+            "broadcaster_user_login": "streamer",
+            "broadcaster_user_name": "streamer",
+            "chatter_user_id": "4145994",
+            "chatter_user_login": "viewer32",
+            "chatter_user_name": "viewer32",
+            "message_id": "cc106a89-1814-919d-454c-f4f2f970aae7",
+            "message": {
+                "text": "Hi chat Kappa @Streamer, cheer100 🎉 @nobody Cheer5",
+                "fragments": [
+                    {"type": "text", "text": "Hi chat "},
+                    {"type": "emote", "text": "Kappa", "emote": {"id": "25", "emote_set_id": "", "owner_id": "", "format": []}},
+                    {"type": "text", "text": " "},
+                    {"type": "mention", "text": "@Streamer", "mention": {"user_id": "1971641", "user_name": "Streamer", "user_login": "streamer"}},
+                    {"type": "text", "text": ", "},
+                    {"type": "cheermote", "text": "cheer100", "cheermote": {"prefix": "cheer", "bits": 100, "tier": 100}},
+                    {"type": "text", "text": " 🎉 @nobody "},
+                    {"type": "cheermote", "text": "Cheer5", "cheermote": {"prefix": "cheer", "bits": 5, "tier": 1}},
+                ]
+            },
+            "color": "#00FF7F",
+            "badges": [
+                {"set_id": "subscriber", "id": "12", "info": "16"},
+                {"set_id": "bits", "id": "1000", "info": ""}
+            ],
+            "message_type": "text",
+            "cheer": {"bits": 100},
+            "reply": {
+                "parent_message_id": "c8f3b22b-ee0e-4d0a-9ad2-f64ba6f3d7c5",
+                "parent_message_body": "hello everyone",
+                "parent_user_id": "1971641",
+                "parent_user_name": "streamer",
+                "parent_user_login": "streamer",
+                "thread_message_id": "c8f3b22b-ee0e-4d0a-9ad2-f64ba6f3d7c5",
+                "thread_user_id": "1971641",
+                "thread_user_name": "streamer",
+                "thread_user_login": "streamer"
+            },
+            "channel_points_custom_reward_id": null,
+            "channel_points_animation_id": null,
+            "source_broadcaster_user_id": null,
+            "source_broadcaster_user_name": null,
+            "source_broadcaster_user_login": null,
+            "source_message_id": null,
+            "source_badges": null
+        }))
+        .unwrap();
+        assert_eq!(payload, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "helix")]
+    fn cheermotes() {
+        let privmsg = privmsg("@bits=150;display-name=viewer32;emotes=;id=1;room-id=2;user-id=3 :viewer32!viewer32@viewer32.tmi.twitch.tv PRIVMSG #streamer :Kappa100 bits50 abc1x2 cheer0 cheer50");
+        let mut converter = Converter::new();
+        converter
+            .insert_cheermotes(&[cheermote("Cheer", &[1, 100]), cheermote("Kappa", &[1, 100])]);
+        let payload = converter.convert(&privmsg);
+        let bits: Vec<_> = payload
+            .message
+            .fragments
+            .iter()
+            .filter_map(|f| match f {
+                Fragment::Cheermote { cheermote, .. } => {
+                    Some((cheermote.prefix.as_str(), cheermote.bits, cheermote.tier))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(bits, [("kappa", 100, 100), ("cheer", 50, 1)]);
+        assert_eq!(payload.color.as_str(), "");
+
+        // no bits, no cheermotes
+        let privmsg = privmsg("@display-name=viewer32;id=1;room-id=2;user-id=3 :viewer32!viewer32@viewer32.tmi.twitch.tv PRIVMSG #streamer :Kappa100");
+        let payload = converter.convert(&privmsg);
+        assert_eq!(
+            payload.message.fragments,
+            [Fragment::Text {
+                text: "Kappa100".to_owned()
+            }]
+        );
+    }
+
+    #[test]
+    fn unknown_cheermotes() {
+        let privmsg = privmsg("@bits=100;display-name=viewer32;id=1;room-id=2;user-id=3 :viewer32!viewer32@viewer32.tmi.twitch.tv PRIVMSG #streamer :Cheer100 hi");
+        let payload = ChannelChatMessageV1Payload::from(&privmsg);
+        assert_eq!(
+            payload.message.fragments,
+            [Fragment::Text {
+                text: "Cheer100 hi".to_owned()
+            }]
+        );
+    }
+}
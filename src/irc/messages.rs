@@ -0,0 +1,592 @@
+//! Typed Twitch IRC messages
+
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use super::{raw::Tags, IrcMessage, ParseError};
+use crate::types;
+
+/// A chat badge, from the `badges` and `badge-info` tags
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Badge {
+    /// An ID that identifies this set of chat badges. For example, Bits or Subscriber.
+    pub set_id: types::BadgeSetId,
+    /// An ID that identifies this version of the badge. For example, for Bits, the ID is the Bits tier level.
+    pub id: types::ChatBadgeId,
+    /// Metadata from the `badge-info` tag, e.g. the number of months for subscriber badges. Empty if there is none.
+    pub info: String,
+}
+
+/// Position of an emote in a message, from the `emotes` tag
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EmotePosition {
+    /// ID of the emote
+    pub id: types::EmoteId,
+    /// Index of the first character of the emote, in characters (not bytes)
+    pub start: usize,
+    /// Index after the last character of the emote, in characters (not bytes)
+    pub end: usize,
+}
+
+impl EmotePosition {
+    /// The text of the emote in the message it was parsed from
+    pub fn text<'a>(&self, message: &'a str) -> Option<&'a str> {
+        let mut indices = message
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(message.len()));
+        let start = indices.nth(self.start)?;
+        let end = indices.nth(self.end.checked_sub(self.start + 1)?)?;
+        Some(&message[start..end])
+    }
+}
+
+/// The message a [`Privmsg`] replies to, from the `reply-parent-*` tags
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ReplyParent {
+    /// ID of the parent message
+    pub message_id: types::MsgId,
+    /// ID of the sender of the parent message
+    pub user_id: types::UserId,
+    /// Login of the sender of the parent message
+    pub user_login: types::UserName,
+    /// Display name of the sender of the parent message
+    pub display_name: types::DisplayName,
+    /// Text of the parent message
+    pub message_body: String,
+    /// ID of the top-level message of the thread
+    pub thread_message_id: Option<types::MsgId>,
+    /// Login of the sender of the top-level message of the thread
+    pub thread_user_login: Option<types::UserName>,
+}
+
+/// A chat message, `PRIVMSG`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Privmsg {
+    /// Login of the channel
+    pub channel_login: types::UserName,
+    /// ID of the channel
+    pub room_id: types::UserId,
+    /// ID of the message
+    pub message_id: types::MsgId,
+    /// ID of the sender
+    pub user_id: types::UserId,
+    /// Login of the sender
+    pub user_login: types::UserName,
+    /// Display name of the sender
+    pub display_name: types::DisplayName,
+    /// Chat color of the sender, if set
+    pub color: Option<types::HexColor>,
+    /// Badges of the sender
+    pub badges: Vec<Badge>,
+    /// The message, without the `ACTION` wrapping of `/me` messages
+    pub message: String,
+    /// Whether the message was sent with `/me`
+    pub is_action: bool,
+    /// Emotes in the message, ordered by position
+    pub emotes: Vec<EmotePosition>,
+    /// Bits cheered in the message
+    pub bits: Option<u64>,
+    /// Whether this is the first message of the sender in the channel
+    pub first_message: bool,
+    /// The message this message replies to
+    pub reply_parent: Option<ReplyParent>,
+    /// ID of the channel points reward redeemed with this message
+    pub custom_reward_id: Option<types::RewardId>,
+    /// ID of the channel the message was sent in, if it was sent in another channel in a shared chat session
+    pub source_room_id: Option<types::UserId>,
+    /// ID of the message in the channel it was sent in, if it was sent in another channel in a shared chat session
+    pub source_message_id: Option<types::MsgId>,
+    /// When the message was sent, in milliseconds since the Unix epoch
+    pub sent_ts: Option<u64>,
+    /// All tags of the message
+    pub tags: Tags,
+}
+
+impl Privmsg {
+    /// Parse a `PRIVMSG`
+    pub fn from_irc(msg: &IrcMessage<'_>) -> Result<Self, ParseError> {
+        expect_command(msg, "PRIVMSG")?;
+        let text = param(msg, 1)?;
+        let (message, is_action) = match text
+            .strip_prefix("\u{1}ACTION ")
+            .map(|action| action.strip_suffix('\u{1}').unwrap_or(action))
+        {
+            Some(action) => (action, true),
+            None => (text, false),
+        };
+        let reply_parent = match msg.tag_value("reply-parent-msg-id") {
+            Some(message_id) => Some(ReplyParent {
+                message_id: owned(message_id),
+                user_id: owned(required(msg, "reply-parent-user-id")?),
+                user_login: owned(required(msg, "reply-parent-user-login")?),
+                display_name: owned(required(msg, "reply-parent-display-name")?),
+                message_body: msg
+                    .tag("reply-parent-msg-body")
+                    .unwrap_or_default()
+                    .into_owned(),
+                thread_message_id: msg.tag_value("reply-thread-parent-msg-id").map(owned),
+                thread_user_login: msg.tag_value("reply-thread-parent-user-login").map(owned),
+            }),
+            None => None,
+        };
+        Ok(Self {
+            channel_login: channel(msg)?,
+            room_id: owned(required(msg, "room-id")?),
+            message_id: owned(required(msg, "id")?),
+            user_id: owned(required(msg, "user-id")?),
+            user_login: owned(Cow::Borrowed(nick(msg)?)),
+            display_name: display_name(msg)?,
+            color: msg.tag_value("color").map(owned),
+            badges: badges(msg),
+            message: message.to_owned(),
+            is_action,
+            emotes: emotes(msg)?,
+            bits: parse_tag(msg, "bits")?,
+            first_message: flag(msg, "first-msg"),
+            reply_parent,
+            custom_reward_id: msg.tag_value("custom-reward-id").map(owned),
+            source_room_id: msg.tag_value("source-room-id").map(owned),
+            source_message_id: msg.tag_value("source-id").map(owned),
+            sent_ts: parse_tag(msg, "tmi-sent-ts")?,
+            tags: msg.tags(),
+        })
+    }
+}
+
+/// A notice about a user in a channel, like a subscription or raid, `USERNOTICE`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UserNotice {
+    /// Login of the channel
+    pub channel_login: types::UserName,
+    /// ID of the channel
+    pub room_id: types::UserId,
+    /// The kind of notice from the `msg-id` tag, e.g. `sub`, `resub`, `subgift` or `raid`
+    pub kind: String,
+    /// ID of the notice
+    pub message_id: types::MsgId,
+    /// ID of the user
+    pub user_id: types::UserId,
+    /// Login of the user
+    pub user_login: types::UserName,
+    /// Display name of the user
+    pub display_name: types::DisplayName,
+    /// Chat color of the user, if set
+    pub color: Option<types::HexColor>,
+    /// Badges of the user
+    pub badges: Vec<Badge>,
+    /// The message Twitch shows for the notice
+    pub system_message: String,
+    /// The message the user added, if any
+    pub message: Option<String>,
+    /// Emotes in [`message`](Self::message), ordered by position
+    pub emotes: Vec<EmotePosition>,
+    /// The `msg-param-*` tags, without the `msg-param-` prefix
+    pub params: Vec<(String, String)>,
+    /// When the notice was sent, in milliseconds since the Unix epoch
+    pub sent_ts: Option<u64>,
+    /// All tags of the notice
+    pub tags: Tags,
+}
+
+impl UserNotice {
+    /// Parse a `USERNOTICE`
+    pub fn from_irc(msg: &IrcMessage<'_>) -> Result<Self, ParseError> {
+        expect_command(msg, "USERNOTICE")?;
+        Ok(Self {
+            channel_login: channel(msg)?,
+            room_id: owned(required(msg, "room-id")?),
+            kind: required(msg, "msg-id")?.into_owned(),
+            message_id: owned(required(msg, "id")?),
+            user_id: owned(required(msg, "user-id")?),
+            user_login: owned(required(msg, "login")?),
+            display_name: display_name(msg)?,
+            color: msg.tag_value("color").map(owned),
+            badges: badges(msg),
+            system_message: msg.tag("system-msg").unwrap_or_default().into_owned(),
+            message: msg.param(1).map(str::to_owned),
+            emotes: emotes(msg)?,
+            params: msg
+                .tags
+                .iter()
+                .filter_map(|(key, value)| {
+                    Some((
+                        key.strip_prefix("msg-param-")?.to_owned(),
+                        super::raw::unescape(value).into_owned(),
+                    ))
+                })
+                .collect(),
+            sent_ts: parse_tag(msg, "tmi-sent-ts")?,
+            tags: msg.tags(),
+        })
+    }
+
+    /// The value of a `msg-param-*` tag, e.g. `param("cumulative-months")`
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Messages of a user or the whole chat were removed, `CLEARCHAT`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ClearChat {
+    /// Login of the channel
+    pub channel_login: types::UserName,
+    /// ID of the channel
+    pub room_id: Option<types::UserId>,
+    /// ID of the user that was banned or timed out. `None` if the whole chat was cleared
+    pub target_user_id: Option<types::UserId>,
+    /// Login of the user that was banned or timed out. `None` if the whole chat was cleared
+    pub target_user_login: Option<types::UserName>,
+    /// Length of the timeout in seconds. `None` for bans and chat clears
+    pub ban_duration: Option<u64>,
+    /// When the messages were removed, in milliseconds since the Unix epoch
+    pub sent_ts: Option<u64>,
+    /// All tags of the message
+    pub tags: Tags,
+}
+
+impl ClearChat {
+    /// Parse a `CLEARCHAT`
+    pub fn from_irc(msg: &IrcMessage<'_>) -> Result<Self, ParseError> {
+        expect_command(msg, "CLEARCHAT")?;
+        Ok(Self {
+            channel_login: channel(msg)?,
+            room_id: msg.tag_value("room-id").map(owned),
+            target_user_id: msg.tag_value("target-user-id").map(owned),
+            target_user_login: msg.param(1).map(|login| login.into()),
+            ban_duration: parse_tag(msg, "ban-duration")?,
+            sent_ts: parse_tag(msg, "tmi-sent-ts")?,
+            tags: msg.tags(),
+        })
+    }
+}
+
+/// A single message was removed, `CLEARMSG`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ClearMsg {
+    /// Login of the channel
+    pub channel_login: types::UserName,
+    /// Login of the sender of the removed message
+    pub user_login: types::UserName,
+    /// ID of the removed message
+    pub target_message_id: types::MsgId,
+    /// Text of the removed message
+    pub message: String,
+    /// When the message was removed, in milliseconds since the Unix epoch
+    pub sent_ts: Option<u64>,
+    /// All tags of the message
+    pub tags: Tags,
+}
+
+impl ClearMsg {
+    /// Parse a `CLEARMSG`
+    pub fn from_irc(msg: &IrcMessage<'_>) -> Result<Self, ParseError> {
+        expect_command(msg, "CLEARMSG")?;
+        Ok(Self {
+            channel_login: channel(msg)?,
+            user_login: owned(required(msg, "login")?),
+            target_message_id: owned(required(msg, "target-msg-id")?),
+            message: param(msg, 1)?.to_owned(),
+            sent_ts: parse_tag(msg, "tmi-sent-ts")?,
+            tags: msg.tags(),
+        })
+    }
+}
+
+/// Chat settings of a channel, `ROOMSTATE`
+///
+/// When joining a channel, all settings are sent. After that, only changed settings are sent and the others are `None`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RoomState {
+    /// Login of the channel
+    pub channel_login: types::UserName,
+    /// ID of the channel
+    pub room_id: Option<types::UserId>,
+    /// Whether only emotes are allowed
+    pub emote_only: Option<bool>,
+    /// How many minutes users have to follow to chat, `-1` if followers-only mode is off
+    pub followers_only: Option<i64>,
+    /// Whether messages have to be unique
+    pub unique_chat: Option<bool>,
+    /// How many seconds users have to wait between messages, `0` if slow mode is off
+    pub slow: Option<u64>,
+    /// Whether only subscribers can chat
+    pub subscribers_only: Option<bool>,
+    /// All tags of the message
+    pub tags: Tags,
+}
+
+impl RoomState {
+    /// Parse a `ROOMSTATE`
+    pub fn from_irc(msg: &IrcMessage<'_>) -> Result<Self, ParseError> {
+        expect_command(msg, "ROOMSTATE")?;
+        Ok(Self {
+            channel_login: channel(msg)?,
+            room_id: msg.tag_value("room-id").map(owned),
+            emote_only: parse_tag::<u8>(msg, "emote-only")?.map(|v| v != 0),
+            followers_only: parse_tag(msg, "followers-only")?,
+            unique_chat: parse_tag::<u8>(msg, "r9k")?.map(|v| v != 0),
+            slow: parse_tag(msg, "slow")?,
+            subscribers_only: parse_tag::<u8>(msg, "subs-only")?.map(|v| v != 0),
+            tags: msg.tags(),
+        })
+    }
+}
+
+/// State of the connected user in a channel, `USERSTATE`
+///
+/// Sent when joining a channel and after sending a message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UserState {
+    /// Login of the channel
+    pub channel_login: types::UserName,
+    /// Display name of the user
+    pub display_name: Option<types::DisplayName>,
+    /// Chat color of the user, if set
+    pub color: Option<types::HexColor>,
+    /// Badges of the user
+    pub badges: Vec<Badge>,
+    /// Emote sets the user can use
+    pub emote_sets: Vec<types::EmoteSetId>,
+    /// Whether the user is a moderator in the channel
+    pub moderator: bool,
+    /// ID of the message that was sent, if this was sent after sending a message
+    pub message_id: Option<types::MsgId>,
+    /// All tags of the message
+    pub tags: Tags,
+}
+
+impl UserState {
+    /// Parse a `USERSTATE`
+    pub fn from_irc(msg: &IrcMessage<'_>) -> Result<Self, ParseError> {
+        expect_command(msg, "USERSTATE")?;
+        Ok(Self {
+            channel_login: channel(msg)?,
+            display_name: msg.tag_value("display-name").map(owned),
+            color: msg.tag_value("color").map(owned),
+            badges: badges(msg),
+            emote_sets: msg
+                .tag("emote-sets")
+                .unwrap_or_default()
+                .split(',')
+                .filter(|set| !set.is_empty())
+                .map(|set| set.to_owned().into())
+                .collect(),
+            moderator: flag(msg, "mod"),
+            message_id: msg.tag_value("id").map(owned),
+            tags: msg.tags(),
+        })
+    }
+}
+
+/// A whisper to the connected user, `WHISPER`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Whisper {
+    /// Login of the sender
+    pub from_login: types::UserName,
+    /// Login of the recipient
+    pub to_login: types::UserName,
+    /// ID of the sender
+    pub user_id: types::UserId,
+    /// Display name of the sender
+    pub display_name: types::DisplayName,
+    /// Chat color of the sender, if set
+    pub color: Option<types::HexColor>,
+    /// Badges of the sender
+    pub badges: Vec<Badge>,
+    /// The whisper
+    pub message: String,
+    /// Emotes in the whisper, ordered by position
+    pub emotes: Vec<EmotePosition>,
+    /// ID of the whisper
+    pub message_id: String,
+    /// ID of the whisper thread
+    pub thread_id: String,
+    /// All tags of the message
+    pub tags: Tags,
+}
+
+impl Whisper {
+    /// Parse a `WHISPER`
+    pub fn from_irc(msg: &IrcMessage<'_>) -> Result<Self, ParseError> {
+        expect_command(msg, "WHISPER")?;
+        Ok(Self {
+            from_login: owned(Cow::Borrowed(nick(msg)?)),
+            to_login: param(msg, 0)?.into(),
+            user_id: owned(required(msg, "user-id")?),
+            display_name: display_name(msg)?,
+            color: msg.tag_value("color").map(owned),
+            badges: badges(msg),
+            message: param(msg, 1)?.to_owned(),
+            emotes: emotes(msg)?,
+            message_id: msg.tag("message-id").unwrap_or_default().into_owned(),
+            thread_id: msg.tag("thread-id").unwrap_or_default().into_owned(),
+            tags: msg.tags(),
+        })
+    }
+}
+
+/// A notice from the server, e.g. when a command failed, `NOTICE`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Notice {
+    /// Login of the channel, `None` for notices not related to a channel
+    pub channel_login: Option<types::UserName>,
+    /// ID of the notice, from the `msg-id` tag, see <https://dev.twitch.tv/docs/irc/msg-id/>
+    pub kind: Option<String>,
+    /// The notice
+    pub message: String,
+    /// All tags of the message
+    pub tags: Tags,
+}
+
+impl Notice {
+    /// Parse a `NOTICE`
+    pub fn from_irc(msg: &IrcMessage<'_>) -> Result<Self, ParseError> {
+        expect_command(msg, "NOTICE")?;
+        Ok(Self {
+            channel_login: param(msg, 0)?.strip_prefix('#').map(|login| login.into()),
+            kind: msg.tag_value("msg-id").map(Cow::into_owned),
+            message: param(msg, 1)?.to_owned(),
+            tags: msg.tags(),
+        })
+    }
+}
+
+fn expect_command(msg: &IrcMessage<'_>, command: &'static str) -> Result<(), ParseError> {
+    if msg.command == command {
+        Ok(())
+    } else {
+        Err(ParseError::UnexpectedCommand {
+            expected: command,
+            found: msg.command.to_owned(),
+        })
+    }
+}
+
+fn owned<T: From<String>>(value: Cow<'_, str>) -> T { T::from(value.into_owned()) }
+
+fn param<'a>(msg: &IrcMessage<'a>, index: usize) -> Result<&'a str, ParseError> {
+    msg.param(index).ok_or_else(|| ParseError::MissingParam {
+        command: msg.command.to_owned(),
+        index,
+    })
+}
+
+fn channel(msg: &IrcMessage<'_>) -> Result<types::UserName, ParseError> {
+    Ok(param(msg, 0)?.trim_start_matches('#').into())
+}
+
+fn nick<'a>(msg: &IrcMessage<'a>) -> Result<&'a str, ParseError> {
+    msg.nick().ok_or_else(|| ParseError::MissingPrefix {
+        command: msg.command.to_owned(),
+    })
+}
+
+fn required<'a>(msg: &IrcMessage<'a>, tag: &'static str) -> Result<Cow<'a, str>, ParseError> {
+    msg.tag_value(tag).ok_or_else(|| ParseError::MissingTag {
+        command: msg.command.to_owned(),
+        tag,
+    })
+}
+
+/// The display name, which can be empty for some users, falling back to the login
+fn display_name(msg: &IrcMessage<'_>) -> Result<types::DisplayName, ParseError> {
+    match msg.tag_value("display-name") {
+        Some(name) => Ok(owned(name)),
+        None => Ok(msg
+            .tag_value("login")
+            .map(Cow::into_owned)
+            .or_else(|| msg.nick().map(str::to_owned))
+            .ok_or_else(|| ParseError::MissingTag {
+                command: msg.command.to_owned(),
+                tag: "display-name",
+            })?
+            .into()),
+    }
+}
+
+fn parse_tag<T: FromStr>(msg: &IrcMessage<'_>, tag: &'static str) -> Result<Option<T>, ParseError> {
+    msg.tag_value(tag)
+        .map(|value| {
+            value.parse().map_err(|_| ParseError::InvalidTag {
+                tag,
+                value: value.into_owned(),
+            })
+        })
+        .transpose()
+}
+
+fn flag(msg: &IrcMessage<'_>, tag: &str) -> bool { msg.tag(tag).map_or(false, |v| v == "1") }
+
+fn badges(msg: &IrcMessage<'_>) -> Vec<Badge> {
+    parse_badges(
+        &msg.tag("badges").unwrap_or_default(),
+        &msg.tag("badge-info").unwrap_or_default(),
+    )
+}
+
+/// Parse the value of a `badges` tag, with the matching `badge-info` tag
+pub(crate) fn parse_badges(badges: &str, info: &str) -> Vec<Badge> {
+    let info: Vec<_> = info
+        .split(',')
+        .filter_map(|badge| badge.split_once('/'))
+        .collect();
+    badges
+        .split(',')
+        .filter_map(|badge| badge.split_once('/'))
+        .map(|(set_id, id)| Badge {
+            set_id: set_id.to_owned().into(),
+            id: id.to_owned().into(),
+            info: info
+                .iter()
+                .find(|(info_set, _)| *info_set == set_id)
+                .map(|(_, info)| (*info).to_owned())
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+fn emotes(msg: &IrcMessage<'_>) -> Result<Vec<EmotePosition>, ParseError> {
+    let Some(tag) = msg.tag_value("emotes") else {
+        return Ok(vec![]);
+    };
+    let invalid = || ParseError::InvalidTag {
+        tag: "emotes",
+        value: tag.clone().into_owned(),
+    };
+    let mut emotes = vec![];
+    for emote in tag.split('/').filter(|e| !e.is_empty()) {
+        let (id, ranges) = emote.split_once(':').ok_or_else(invalid)?;
+        for range in ranges.split(',') {
+            let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+            let start: usize = start.parse().map_err(|_| invalid())?;
+            let end: usize = end.parse().map_err(|_| invalid())?;
+            if end < start {
+                return Err(invalid());
+            }
+            emotes.push(EmotePosition {
+                id: id.to_owned().into(),
+                start,
+                // the tag has inclusive ranges
+                end: end + 1,
+            });
+        }
+    }
+    emotes.sort_by_key(|emote| emote.start);
+    Ok(emotes)
+}
@@ -0,0 +1,279 @@
+#![doc(alias = "tmi")]
+//! Parse and serialize messages from Twitch IRC
+//!
+//! Twitch chat is still available over [IRC](https://dev.twitch.tv/docs/irc/), and many chat tools and log archives use it.
+//! This module parses IRCv3 messages with Twitch tags into typed structs using the same [`types`](crate::types) as
+//! Helix and EventSub, and serializes outgoing [commands](Command).
+//! It does not connect to Twitch itself.
+//!
+//! [`Message::parse`] parses a single line, [`parse_lines`] parses every line in a websocket frame or log file.
+//! Messages that aren't one of the typed messages, like `JOIN` or numeric replies, are returned as [`Message::Other`],
+//! and [`IrcMessage`] gives access to the raw tags, prefix and parameters of any message.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use twitch_api::irc::{Command, Message};
+//!
+//! let line = "@badge-info=subscriber/16;badges=subscriber/12;color=#00FF7F;display-name=viewer32;emotes=25:6-10;id=cc106a89-1814-919d-454c-f4f2f970aae7;room-id=1971641;tmi-sent-ts=1699294307492;user-id=4145994 :viewer32!viewer32@viewer32.tmi.twitch.tv PRIVMSG #streamer :Hello Kappa";
+//! let Message::Privmsg(privmsg) = Message::parse(line)? else {
+//!     panic!("not a PRIVMSG");
+//! };
+//! assert_eq!(privmsg.user_login.as_str(), "viewer32");
+//! assert_eq!(privmsg.emotes[0].text(&privmsg.message), Some("Kappa"));
+//! assert_eq!(privmsg.badges[0].info, "16");
+//!
+//! let reply = Command::reply(&privmsg.channel_login, &privmsg.message_id, "hi!");
+//! assert_eq!(
+//!     reply.to_string(),
+//!     "@reply-parent-msg-id=cc106a89-1814-919d-454c-f4f2f970aae7 PRIVMSG #streamer :hi!"
+//! );
+//! # Ok::<(), twitch_api::irc::ParseError>(())
+//! ```
+
+pub mod command;
+#[cfg(feature = "eventsub")]
+#[cfg_attr(nightly, doc(cfg(feature = "eventsub")))]
+pub mod convert;
+pub mod messages;
+pub mod raw;
+
+#[doc(inline)]
+pub use command::{Command, TWITCH_CAPABILITIES};
+#[doc(inline)]
+pub use messages::{
+    Badge, ClearChat, ClearMsg, EmotePosition, Notice, Privmsg, ReplyParent, RoomState, UserNotice,
+    UserState, Whisper,
+};
+#[doc(inline)]
+pub use raw::{IrcMessage, Tags};
+
+/// Location of Twitch IRC over WebSocket
+pub const TWITCH_IRC_WEBSOCKET_URL: &str = "wss://irc-ws.chat.twitch.tv:443";
+
+/// Errors from parsing an IRC message
+#[derive(Debug, thiserror::Error, displaydoc::Display, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseError {
+    /// message is empty
+    Empty,
+    /// message has no command
+    MissingCommand,
+    /// expected a `{expected}` message, got `{found}`
+    UnexpectedCommand {
+        /// The command that was expected
+        expected: &'static str,
+        /// The command of the message
+        found: String,
+    },
+    /// `{command}` message has no prefix
+    MissingPrefix {
+        /// The command of the message
+        command: String,
+    },
+    /// `{command}` message is missing parameter {index}
+    MissingParam {
+        /// The command of the message
+        command: String,
+        /// The index of the parameter
+        index: usize,
+    },
+    /// `{command}` message is missing tag `{tag}`
+    MissingTag {
+        /// The command of the message
+        command: String,
+        /// The missing tag
+        tag: &'static str,
+    },
+    /// tag `{tag}` has an invalid value `{value}`
+    InvalidTag {
+        /// The tag
+        tag: &'static str,
+        /// The value of the tag
+        value: String,
+    },
+}
+
+/// A message from Twitch IRC
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Message {
+    /// A chat message
+    Privmsg(Privmsg),
+    /// A notice about a user, like a subscription or raid
+    UserNotice(UserNotice),
+    /// Messages of a user or the whole chat were removed
+    ClearChat(ClearChat),
+    /// A single message was removed
+    ClearMsg(ClearMsg),
+    /// Chat settings of a channel
+    RoomState(RoomState),
+    /// State of the connected user in a channel
+    UserState(UserState),
+    /// A whisper to the connected user
+    Whisper(Whisper),
+    /// A notice from the server
+    Notice(Notice),
+    /// A keepalive, answer it with [`Command::Pong`] and the same argument
+    Ping(String),
+    /// Any other message, serialized back to a line
+    Other(String),
+}
+
+impl Message {
+    /// Parse a single line, with or without a trailing `\r\n`
+    pub fn parse(line: &str) -> Result<Self, ParseError> {
+        Self::from_irc(&IrcMessage::parse(line)?)
+    }
+
+    /// Convert a parsed [`IrcMessage`]
+    pub fn from_irc(msg: &IrcMessage<'_>) -> Result<Self, ParseError> {
+        Ok(match msg.command {
+            "PRIVMSG" => Self::Privmsg(Privmsg::from_irc(msg)?),
+            "USERNOTICE" => Self::UserNotice(UserNotice::from_irc(msg)?),
+            "CLEARCHAT" => Self::ClearChat(ClearChat::from_irc(msg)?),
+            "CLEARMSG" => Self::ClearMsg(ClearMsg::from_irc(msg)?),
+            "ROOMSTATE" => Self::RoomState(RoomState::from_irc(msg)?),
+            "USERSTATE" => Self::UserState(UserState::from_irc(msg)?),
+            "WHISPER" => Self::Whisper(Whisper::from_irc(msg)?),
+            "NOTICE" => Self::Notice(Notice::from_irc(msg)?),
+            "PING" => Self::Ping(msg.param(0).unwrap_or_default().to_owned()),
+            _ => Self::Other(msg.to_string()),
+        })
+    }
+}
+
+/// Parse every line in a text, skipping empty lines
+///
+/// Twitch can send multiple messages in one websocket frame, separated by `\r\n`.
+pub fn parse_lines(text: &str) -> impl Iterator<Item = Result<Message, ParseError>> + '_ {
+    text.split('\n')
+        .filter(|line| !line.trim().is_empty())
+        .map(Message::parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_roundtrip() {
+        let line = r"@badges=;display-name=Name\swith\sspace;msg-param-x=a\:b\\c :tmi.twitch.tv USERNOTICE #channel :hello there";
+        let msg = IrcMessage::parse(line).unwrap();
+        assert_eq!(msg.prefix, Some("tmi.twitch.tv"));
+        assert_eq!(msg.nick(), None);
+        assert_eq!(msg.params, ["#channel", "hello there"]);
+        assert_eq!(msg.tag("display-name").as_deref(), Some("Name with space"));
+        assert_eq!(msg.tag("msg-param-x").as_deref(), Some(r"a;b\c"));
+        assert_eq!(msg.tag_value("badges"), None);
+        assert_eq!(msg.to_string(), line.replace("badges=;", "badges;"));
+        assert_eq!(raw::escape("a;b c\\"), r"a\:b\sc\\");
+
+        let msg = IrcMessage::parse("PING :tmi.twitch.tv\r\n").unwrap();
+        assert_eq!(msg.command, "PING");
+        assert_eq!(msg.params, ["tmi.twitch.tv"]);
+        assert_eq!(IrcMessage::parse("  \r\n"), Err(ParseError::Empty));
+    }
+
+    #[test]
+    fn privmsg() {
+        let line = "@badge-info=subscriber/16;badges=subscriber/12,bits/1000;bits=100;color=#00FF7F;display-name=viewer32;emotes=25:0-4,22-26/1902:6-10;first-msg=1;id=cc106a89-1814-919d-454c-f4f2f970aae7;reply-parent-display-name=streamer;reply-parent-msg-body=hello\\severyone;reply-parent-msg-id=c8f3b22b-ee0e-4d0a-9ad2-f64ba6f3d7c5;reply-parent-user-id=1971641;reply-parent-user-login=streamer;room-id=1971641;tmi-sent-ts=1699294307492;user-id=4145994 :viewer32!viewer32@viewer32.tmi.twitch.tv PRIVMSG #streamer :\u{1}ACTION Kappa Keepo 🎉cheer100 Kappa\u{1}";
+        let Message::Privmsg(msg) = Message::parse(line).unwrap() else {
+            panic!("expected PRIVMSG")
+        };
+        assert_eq!(msg.channel_login.as_str(), "streamer");
+        assert_eq!(msg.user_login.as_str(), "viewer32");
+        assert!(msg.is_action);
+        assert!(msg.first_message);
+        assert_eq!(msg.message, "Kappa Keepo 🎉cheer100 Kappa");
+        assert_eq!(msg.bits, Some(100));
+        assert_eq!(msg.sent_ts, Some(1699294307492));
+        assert_eq!(msg.badges.len(), 2);
+        assert_eq!(msg.badges[0].info, "16");
+        assert_eq!(msg.badges[1].info, "");
+        let emotes: Vec<_> = msg
+            .emotes
+            .iter()
+            .map(|e| (e.id.as_str(), e.text(&msg.message).unwrap()))
+            .collect();
+        assert_eq!(
+            emotes,
+            [("25", "Kappa"), ("1902", "Keepo"), ("25", "Kappa")]
+        );
+        let reply = msg.reply_parent.unwrap();
+        assert_eq!(reply.message_body, "hello everyone");
+        assert_eq!(reply.thread_message_id, None);
+
+        let err = Message::parse(":viewer32!viewer32@viewer32.tmi.twitch.tv PRIVMSG #streamer :hi");
+        assert!(matches!(
+            err,
+            Err(ParseError::MissingTag { tag: "room-id", .. })
+        ));
+    }
+
+    #[test]
+    fn other_messages() {
+        let frame = "@login=ronni;room-id=1337;target-msg-id=abc-123-def;tmi-sent-ts=1642720582342 :tmi.twitch.tv CLEARMSG #dallas :HeyGuys\r\n\
+            @ban-duration=350;room-id=1337;target-user-id=1234;tmi-sent-ts=1642719320727 :tmi.twitch.tv CLEARCHAT #dallas :ronni\r\n\
+            @emote-only=0;followers-only=-1;r9k=0;room-id=12345678;slow=10;subs-only=0 :tmi.twitch.tv ROOMSTATE #bar\r\n\
+            @badge-info=;badges=moderator/1;color=;display-name=bot;emote-sets=0,300374282;mod=1 :tmi.twitch.tv USERSTATE #dallas\r\n\
+            @msg-id=slow_off :tmi.twitch.tv NOTICE #dallas :This room is no longer in slow mode.\r\n\
+            @badges=;color=;display-name=Sender;emotes=;message-id=306;thread-id=12345_67890;user-id=12345 :sender!sender@sender.tmi.twitch.tv WHISPER receiver :hello\r\n\
+            @badge-info=;badges=staff/1;color=#008000;display-name=ronni;emotes=;id=db25007f-7a18-43eb-9379-80131e44d633;login=ronni;msg-id=resub;msg-param-cumulative-months=6;msg-param-sub-plan=Prime;room-id=12345678;system-msg=ronni\\shas\\ssubscribed\\sfor\\s6\\smonths!;tmi-sent-ts=1507246572675;user-id=87654321 :tmi.twitch.tv USERNOTICE #dallas :Great stream -- keep it up!\r\n\
+            PING :tmi.twitch.tv\r\n\
+            :foo!foo@foo.tmi.twitch.tv JOIN #bar\r\n";
+        let messages: Vec<_> = parse_lines(frame).collect::<Result<_, _>>().unwrap();
+        assert_eq!(messages.len(), 9);
+        assert!(
+            matches!(&messages[0], Message::ClearMsg(m) if m.target_message_id.as_str() == "abc-123-def" && m.message == "HeyGuys")
+        );
+        assert!(
+            matches!(&messages[1], Message::ClearChat(m) if m.ban_duration == Some(350) && m.target_user_login.as_ref().map(|l| l.as_str()) == Some("ronni"))
+        );
+        assert!(
+            matches!(&messages[2], Message::RoomState(m) if m.slow == Some(10) && m.followers_only == Some(-1) && m.emote_only == Some(false))
+        );
+        assert!(
+            matches!(&messages[3], Message::UserState(m) if m.moderator && m.emote_sets.len() == 2 && m.color.is_none())
+        );
+        assert!(
+            matches!(&messages[4], Message::Notice(m) if m.kind.as_deref() == Some("slow_off"))
+        );
+        assert!(
+            matches!(&messages[5], Message::Whisper(m) if m.from_login.as_str() == "sender" && m.to_login.as_str() == "receiver")
+        );
+        let Message::UserNotice(notice) = &messages[6] else {
+            panic!("expected USERNOTICE")
+        };
+        assert_eq!(notice.kind, "resub");
+        assert_eq!(notice.param("cumulative-months"), Some("6"));
+        assert_eq!(notice.system_message, "ronni has subscribed for 6 months!");
+        assert_eq!(
+            notice.message.as_deref(),
+            Some("Great stream -- keep it up!")
+        );
+        assert_eq!(messages[7], Message::Ping("tmi.twitch.tv".to_owned()));
+        assert_eq!(
+            messages[8],
+            Message::Other(":foo!foo@foo.tmi.twitch.tv JOIN #bar".to_owned())
+        );
+    }
+
+    #[test]
+    fn commands() {
+        let channels = [types_login("foo"), types_login("bar")];
+        assert_eq!(Command::Join(&channels).to_string(), "JOIN #foo,#bar");
+        assert_eq!(
+            Command::CapReq(TWITCH_CAPABILITIES).to_string(),
+            "CAP REQ :twitch.tv/tags twitch.tv/commands twitch.tv/membership"
+        );
+        assert_eq!(Command::Pass("oauth:abc").to_string(), "PASS oauth:abc");
+        assert_eq!(Command::Pass("abc").to_string(), "PASS oauth:abc");
+        assert_eq!(
+            Command::privmsg(types_login("foo"), "line\r\nbreak").to_string(),
+            "PRIVMSG #foo :line break"
+        );
+    }
+
+    fn types_login(login: &str) -> &crate::types::UserNameRef { login.into() }
+}
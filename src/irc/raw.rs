@@ -0,0 +1,206 @@
+//! Untyped IRC messages and IRCv3 tags
+
+use std::borrow::Cow;
+use std::fmt;
+
+use super::ParseError;
+
+/// An IRC message, borrowing from the line it was parsed from
+///
+/// Tag values are kept escaped, use [`IrcMessage::tag`] to get an unescaped value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct IrcMessage<'a> {
+    /// IRCv3 tags, with escaped values
+    pub tags: Vec<(&'a str, &'a str)>,
+    /// The prefix, without the leading `:`, e.g. `nick!nick@nick.tmi.twitch.tv`
+    pub prefix: Option<&'a str>,
+    /// The command, e.g. `PRIVMSG` or `001`
+    pub command: &'a str,
+    /// The parameters, including the trailing parameter without its leading `:`
+    pub params: Vec<&'a str>,
+}
+
+impl<'a> IrcMessage<'a> {
+    /// Parse a single line, with or without a trailing `\r\n`
+    pub fn parse(line: &'a str) -> Result<Self, ParseError> {
+        let mut rest = line.trim_end_matches(['\r', '\n']).trim_start_matches(' ');
+        if rest.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        let mut tags = vec![];
+        if let Some(stripped) = rest.strip_prefix('@') {
+            let (raw_tags, after) = stripped.split_once(' ').unwrap_or((stripped, ""));
+            tags = raw_tags
+                .split(';')
+                .filter(|tag| !tag.is_empty())
+                .map(|tag| tag.split_once('=').unwrap_or((tag, "")))
+                .collect();
+            rest = after.trim_start_matches(' ');
+        }
+        let mut prefix = None;
+        if let Some(stripped) = rest.strip_prefix(':') {
+            let (p, after) = stripped.split_once(' ').unwrap_or((stripped, ""));
+            prefix = Some(p);
+            rest = after.trim_start_matches(' ');
+        }
+        let (command, mut rest) = rest.split_once(' ').unwrap_or((rest, ""));
+        if command.is_empty() {
+            return Err(ParseError::MissingCommand);
+        }
+        let mut params = vec![];
+        loop {
+            rest = rest.trim_start_matches(' ');
+            if rest.is_empty() {
+                break;
+            }
+            if let Some(trailing) = rest.strip_prefix(':') {
+                params.push(trailing);
+                break;
+            }
+            let (param, after) = rest.split_once(' ').unwrap_or((rest, ""));
+            params.push(param);
+            rest = after;
+        }
+        Ok(Self {
+            tags,
+            prefix,
+            command,
+            params,
+        })
+    }
+
+    /// The unescaped value of a tag. Tags without a value are returned as empty strings
+    pub fn tag(&self, key: &str) -> Option<Cow<'a, str>> {
+        self.tags
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| unescape(value))
+    }
+
+    /// The unescaped value of a tag, or `None` if the tag is missing or empty
+    pub fn tag_value(&self, key: &str) -> Option<Cow<'a, str>> {
+        self.tag(key).filter(|value| !value.is_empty())
+    }
+
+    /// A parameter
+    pub fn param(&self, index: usize) -> Option<&'a str> { self.params.get(index).copied() }
+
+    /// The nick in the prefix, e.g. `nick` for `nick!nick@nick.tmi.twitch.tv`
+    pub fn nick(&self) -> Option<&'a str> {
+        let prefix = self.prefix?;
+        match prefix.split_once('!') {
+            Some((nick, _)) => Some(nick),
+            // a server prefix like `tmi.twitch.tv` is not a nick
+            None if prefix.contains('.') => None,
+            None => Some(prefix),
+        }
+    }
+
+    /// All tags, unescaped
+    pub fn tags(&self) -> Tags {
+        Tags(
+            self.tags
+                .iter()
+                .map(|(k, v)| ((*k).to_owned(), unescape(v).into_owned()))
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for IrcMessage<'_> {
+    /// Serialize the message, without a trailing `\r\n`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.tags.is_empty() {
+            f.write_str("@")?;
+            for (i, (key, value)) in self.tags.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(";")?;
+                }
+                f.write_str(key)?;
+                if !value.is_empty() {
+                    write!(f, "={value}")?;
+                }
+            }
+            f.write_str(" ")?;
+        }
+        if let Some(prefix) = self.prefix {
+            write!(f, ":{prefix} ")?;
+        }
+        f.write_str(self.command)?;
+        if let Some((last, params)) = self.params.split_last() {
+            for param in params {
+                write!(f, " {param}")?;
+            }
+            if last.is_empty() || last.starts_with(':') || last.contains(' ') {
+                write!(f, " :{last}")?;
+            } else {
+                write!(f, " {last}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Unescaped IRCv3 tags of a message, in the order they were sent
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tags(pub Vec<(String, String)>);
+
+impl Tags {
+    /// The value of a tag
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Iterate over the tags
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// Unescape an IRCv3 tag value
+pub fn unescape(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            // a trailing backslash is dropped
+            None => {}
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+/// Escape an IRCv3 tag value
+pub fn escape(value: &str) -> Cow<'_, str> {
+    if !value.contains([';', ' ', '\\', '\r', '\n']) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        match c {
+            ';' => escaped.push_str("\\:"),
+            ' ' => escaped.push_str("\\s"),
+            '\\' => escaped.push_str("\\\\"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
@@ -101,6 +101,7 @@
 //! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>client</code></span> | Gives a [client abstraction](HttpClient) for endpoints. See [`HelixClient`] |
 //! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>helix</code></span> | Enables [Helix](helix) endpoints |
 //! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>eventsub</code></span> | Enables deserializable structs for [EventSub](eventsub) |
+//! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>irc</code></span> | Enables parsing and serializing [Twitch IRC](irc) messages |
 //! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>hmac</code></span> | Enable [message authentication](eventsub::Event::verify_payload) using HMAC on [EventSub](eventsub) |
 //! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>time</code></span> | Enable time utilities on [Timestamp](types::Timestamp) |
 //! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>all</code></span> | Enables all above features. Do not use this in production, it's better if you specify exactly what you need |
@@ -108,6 +109,7 @@
 //! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>surf</code></span> | Enables surf for [`HttpClient`]. Note that this does not enable any default client backend, if you get a compile error, specify `surf` in your `Cargo.toml`. By default, `surf` uses feature `curl-client` |
 //! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>reqwest</code></span> | Enables reqwest for [`HttpClient`]. Note that this does not enable any default TLS backend, if you get `invalid URL, scheme is not http`, specify `reqwest` in your Cargo.toml. By default, `reqwest` uses feature `default-tls` |
 //! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>tower</code></span> | Enables using [tower services](client::TowerService) for [`HttpClient`]. |
+//! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>mock_server</code></span> | Enables in-process mock servers for [EventSub](eventsub::mock_server) and, with feature `mock`, [PubSub](pubsub::mock_server) for testing, uses [tokio](https://crates.io/crates/tokio). |
 //! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>beta</code></span> | Enables beta endpoints, topics or features. Breakage may occur, semver compatibility not guaranteed. |
 //! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>unsupported</code></span> | Enables undocumented or experimental endpoints, including beta endpoints, topics or features. Breakage may occur, semver compatibility not guaranteed. |
 //! | <span class="module-item stab portability" style="display: inline; border-radius: 3px; padding: 2px; font-size: 80%; line-height: 1.2;"><code>trace_unknown_fields</code></span> | Logs ignored fields as `WARN` log messages where  applicable. Please consider using this and filing an issue or PR when a new field has been added to the endpoint but not added to this library. |
@@ -134,6 +136,9 @@ pub mod pubsub;
 #[cfg(feature = "eventsub")]
 pub mod eventsub;
 
+#[cfg(feature = "irc")]
+pub mod irc;
+
 #[cfg(all(feature = "helix", feature = "client"))]
 #[doc(inline)]
 pub use crate::helix::HelixClient;
@@ -400,6 +405,143 @@ where
     deserializer.deserialize_any(Inner(std::marker::PhantomData))
 }
 
+#[cfg(any(
+    feature = "eventsub",
+    all(
+        feature = "pubsub",
+        feature = "helix",
+        any(feature = "unsupported", feature = "tpm")
+    )
+))]
+/// Parse a `YYYY-MM-DDTHH:MM:SS[.fraction]Z` timestamp into milliseconds since the Unix epoch
+fn timestamp_to_unix_millis(s: &str) -> Option<i64> {
+    let bytes = s.as_bytes();
+    if bytes.len() < 20
+        || !s.is_char_boundary(19)
+        || !s.ends_with('Z')
+        || [4, 7].iter().any(|&i| bytes[i] != b'-')
+        || bytes[10] != b'T'
+        || [13, 16].iter().any(|&i| bytes[i] != b':')
+    {
+        return None;
+    }
+    let num = |range: std::ops::Range<usize>| s.get(range)?.parse::<i64>().ok();
+    let (year, month, day) = (num(0..4)?, num(5..7)?, num(8..10)?);
+    let (hour, minute, second) = (num(11..13)?, num(14..16)?, num(17..19)?);
+    let fraction = &s[19..s.len() - 1];
+    let millis = if fraction.is_empty() {
+        0
+    } else {
+        let digits = fraction
+            .strip_prefix('.')
+            .filter(|d| !d.is_empty() && d.bytes().all(|b| b.is_ascii_digit()))?;
+        digits
+            .bytes()
+            .chain(std::iter::repeat(b'0'))
+            .take(3)
+            .fold(0, |acc, b| acc * 10 + i64::from(b - b'0'))
+    };
+
+    // Days from civil, see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    Some((days * 86_400 + hour * 3_600 + minute * 60 + second) * 1_000 + millis)
+}
+
+#[cfg(any(feature = "eventsub", feature = "pubsub"))]
+/// The current time in milliseconds since the Unix epoch, `0` if the clock is before it
+pub(crate) fn now_unix_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+#[cfg(feature = "eventsub")]
+/// Format milliseconds since the Unix epoch as a `YYYY-MM-DDTHH:MM:SS.sssZ` timestamp
+fn unix_millis_to_timestamp(millis: i64) -> types::Timestamp {
+    use std::convert::TryFrom;
+
+    let secs = millis.div_euclid(1_000);
+
+    // Civil from days, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = secs.div_euclid(86_400) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let time = secs.rem_euclid(86_400);
+
+    let timestamp = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60,
+        millis.rem_euclid(1_000)
+    );
+    types::Timestamp::try_from(timestamp).expect("formatted timestamp should be valid")
+}
+
+#[cfg(any(
+    feature = "pubsub",
+    all(
+        feature = "eventsub",
+        any(feature = "mock", feature = "mock_server", test)
+    )
+))]
+/// SplitMix64, a small seeded generator for mocked data and jitter
+#[derive(Clone, Debug)]
+struct SplitMix64 {
+    state: u64,
+}
+
+#[cfg(any(
+    feature = "pubsub",
+    all(
+        feature = "eventsub",
+        any(feature = "mock", feature = "mock_server", test)
+    )
+))]
+impl SplitMix64 {
+    const fn new(seed: u64) -> Self { Self { state: seed } }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A random UUID (v4)
+    #[cfg(all(
+        feature = "eventsub",
+        any(feature = "mock", feature = "mock_server", test)
+    ))]
+    fn uuid(&mut self) -> String {
+        let (a, b) = (self.next_u64(), self.next_u64());
+        format!(
+            "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+            a >> 32,
+            (a >> 16) & 0xffff,
+            a & 0x0fff,
+            ((b >> 48) & 0x3fff) | 0x8000,
+            b & 0xffff_ffff_ffff
+        )
+    }
+}
+
 /// Helper functions for tests
 #[cfg(test)]
 pub mod tests {
@@ -415,4 +557,47 @@ pub mod tests {
         )
         .expect("could not convert back from cbor");
     }
+
+    #[cfg(feature = "eventsub")]
+    #[test]
+    fn unix_millis() {
+        use crate::{timestamp_to_unix_millis, unix_millis_to_timestamp};
+
+        assert_eq!(timestamp_to_unix_millis("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            timestamp_to_unix_millis("2020-10-19T17:24:16.5Z"),
+            Some(1_603_128_256_500)
+        );
+        // digits after milliseconds are truncated
+        assert_eq!(
+            timestamp_to_unix_millis("2020-07-15T17:16:03.17106713Z"),
+            Some(1_594_833_363_171)
+        );
+        assert_eq!(
+            timestamp_to_unix_millis("2000-02-29T23:59:59.999Z"),
+            Some(951_868_799_999)
+        );
+        assert_eq!(
+            timestamp_to_unix_millis("1969-12-31T23:59:59Z"),
+            Some(-1_000)
+        );
+        assert_eq!(timestamp_to_unix_millis("2020-10-19T17:24:16"), None);
+        assert_eq!(timestamp_to_unix_millis("2020-10-19T17:24:16.Z"), None);
+        assert_eq!(timestamp_to_unix_millis("2020-10-19 17:24:16Z"), None);
+
+        assert_eq!(
+            unix_millis_to_timestamp(1_603_128_256_500).as_str(),
+            "2020-10-19T17:24:16.500Z"
+        );
+        assert_eq!(
+            unix_millis_to_timestamp(-1).as_str(),
+            "1969-12-31T23:59:59.999Z"
+        );
+        for millis in [0, 951_868_799_999, 951_868_800_000, 4_102_444_800_000] {
+            assert_eq!(
+                timestamp_to_unix_millis(unix_millis_to_timestamp(millis).as_str()),
+                Some(millis)
+            );
+        }
+    }
 }
@@ -0,0 +1,828 @@
+#![doc(alias = "eventsub")]
+//! Helpers for moving PubSub consumers over to EventSub
+//!
+//! [`equivalents`] maps a PubSub [`Topics`] to the [EventSub subscriptions](crate::eventsub::EventSubscription) that deliver the same information,
+//! and [`to_topic_data`] converts an EventSub [`Event`] back into the closest [`TopicData`],
+//! so that code written against PubSub replies can be switched to EventSub one topic at a time.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use twitch_api::pubsub::{self, migrate};
+//!
+//! let topic = pubsub::Topics::ChannelPointsChannelV1(
+//!     pubsub::channel_points::ChannelPointsChannelV1 { channel_id: 1234 },
+//! );
+//! for equivalent in migrate::equivalents(&topic)? {
+//!     println!(
+//!         "{} v{} ({:?}): {}",
+//!         equivalent.event_type,
+//!         equivalent.version,
+//!         equivalent.coverage,
+//!         equivalent.condition
+//!     );
+//! }
+//! # Ok::<(), serde_json::Error>(())
+//! ```
+//!
+//! Not every topic has a counterpart. Topics that only exist for the authenticated user,
+//! like `predictions-user-v1`, map to no subscriptions at all.
+use super::{TopicData, Topics};
+use crate::eventsub::{self, Event, EventSubscription, EventType, Message, Payload};
+use crate::types;
+
+/// How closely an EventSub subscription covers a PubSub topic
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Coverage {
+    /// The subscription delivers the same events as the topic
+    Full,
+    /// The subscription delivers only some of the events of the topic, or with less information
+    Partial,
+}
+
+/// An EventSub subscription equivalent to (part of) a PubSub topic
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Equivalent {
+    /// Event type of the subscription
+    pub event_type: EventType,
+    /// Version of the subscription
+    pub version: &'static str,
+    /// Condition of the subscription
+    pub condition: serde_json::Value,
+    /// How much of the topic this subscription covers
+    pub coverage: Coverage,
+}
+
+impl Equivalent {
+    /// Create an equivalent from a subscription
+    pub fn new<E: EventSubscription>(
+        subscription: &E,
+        coverage: Coverage,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            event_type: E::EVENT_TYPE,
+            version: E::VERSION,
+            condition: subscription.condition()?,
+            coverage,
+        })
+    }
+
+    /// Turn this equivalent into a [`DesiredSubscription`](crate::eventsub::reconcile::DesiredSubscription) for the reconciler
+    #[cfg(all(feature = "helix", feature = "client"))]
+    #[cfg_attr(nightly, doc(cfg(all(feature = "helix", feature = "client"))))]
+    pub fn to_desired(
+        &self,
+        transport: eventsub::Transport,
+    ) -> eventsub::reconcile::DesiredSubscription {
+        eventsub::reconcile::DesiredSubscription::from_parts(
+            self.event_type,
+            self.version,
+            self.condition.clone(),
+            transport,
+        )
+    }
+}
+
+/// Get the EventSub subscriptions that deliver the events of a PubSub topic
+///
+/// Returns an empty list if EventSub has no counterpart for the topic.
+pub fn equivalents(topic: &Topics) -> Result<Vec<Equivalent>, serde_json::Error> {
+    use eventsub::{automod, channel};
+    use Coverage::*;
+
+    let id = |id: u32| types::UserId::new(id.to_string());
+    Ok(match topic {
+        #[cfg(feature = "pubsub")]
+        Topics::AutoModQueue(t) => vec![
+            Equivalent::new(
+                &automod::AutomodMessageHoldV2::new(id(t.channel_id), id(t.moderator_id)),
+                Full,
+            )?,
+            Equivalent::new(
+                &automod::AutomodMessageUpdateV2::new(id(t.channel_id), id(t.moderator_id)),
+                Full,
+            )?,
+        ],
+        #[cfg(feature = "unsupported")]
+        Topics::CommunityPointsChannelV1(t) => vec![
+            Equivalent::new(
+                &channel::ChannelPointsCustomRewardRedemptionAddV1::broadcaster_user_id(id(
+                    t.channel_id
+                )),
+                Partial,
+            )?,
+            Equivalent::new(
+                &channel::ChannelPointsCustomRewardUpdateV1::broadcaster_user_id(id(t.channel_id)),
+                Partial,
+            )?,
+        ],
+        #[cfg(feature = "pubsub")]
+        Topics::ChannelBitsEventsV2(t) => vec![Equivalent::new(
+            &channel::ChannelBitsUseV1::broadcaster_user_id(id(t.channel_id)),
+            Full,
+        )?],
+        #[cfg(feature = "pubsub")]
+        Topics::ChannelBitsBadgeUnlocks(t) => vec![Equivalent::new(
+            &channel::ChannelChatNotificationV1::new(id(t.channel_id), id(t.channel_id)),
+            Partial,
+        )?],
+        #[cfg(feature = "unsupported")]
+        Topics::ChannelCheerEventsPublicV1(t) => vec![Equivalent::new(
+            &channel::ChannelCheerV1::broadcaster_user_id(id(t.channel_id)),
+            Partial,
+        )?],
+        #[cfg(feature = "unsupported")]
+        Topics::ChannelSubGiftsV1(t) => vec![Equivalent::new(
+            &channel::ChannelSubscriptionGiftV1::broadcaster_user_id(id(t.channel_id)),
+            Full,
+        )?],
+        #[cfg(feature = "pubsub")]
+        Topics::ChatModeratorActions(t) => vec![Equivalent::new(
+            &channel::ChannelModerateV2::new(id(t.channel_id), id(t.user_id)),
+            Full,
+        )?],
+        #[cfg(feature = "pubsub")]
+        Topics::ChannelPointsChannelV1(t) => vec![
+            Equivalent::new(
+                &channel::ChannelPointsCustomRewardRedemptionAddV1::broadcaster_user_id(id(
+                    t.channel_id
+                )),
+                Full,
+            )?,
+            Equivalent::new(
+                &channel::ChannelPointsCustomRewardRedemptionUpdateV1::broadcaster_user_id(id(
+                    t.channel_id
+                )),
+                Full,
+            )?,
+            Equivalent::new(
+                &channel::ChannelPointsCustomRewardUpdateV1::broadcaster_user_id(id(t.channel_id)),
+                Full,
+            )?,
+        ],
+        #[cfg(feature = "pubsub")]
+        Topics::ChannelSubscribeEventsV1(t) => vec![
+            Equivalent::new(
+                &channel::ChannelSubscribeV1::broadcaster_user_id(id(t.channel_id)),
+                Full,
+            )?,
+            Equivalent::new(
+                &channel::ChannelSubscriptionMessageV1::broadcaster_user_id(id(t.channel_id)),
+                Full,
+            )?,
+            Equivalent::new(
+                &channel::ChannelSubscriptionGiftV1::broadcaster_user_id(id(t.channel_id)),
+                Partial,
+            )?,
+        ],
+        // EventSub only knows channels by id
+        #[cfg(feature = "unsupported")]
+        Topics::VideoPlayback(_) => vec![],
+        #[cfg(any(feature = "unsupported", feature = "tpm"))]
+        Topics::VideoPlaybackById(t) => vec![
+            Equivalent::new(
+                &eventsub::stream::StreamOnlineV1::broadcaster_user_id(id(t.channel_id)),
+                Partial,
+            )?,
+            Equivalent::new(
+                &eventsub::stream::StreamOfflineV1::broadcaster_user_id(id(t.channel_id)),
+                Partial,
+            )?,
+            Equivalent::new(
+                &channel::ChannelAdBreakBeginV1::broadcaster_user_id(id(t.channel_id)),
+                Partial,
+            )?,
+        ],
+        #[cfg(feature = "unsupported")]
+        Topics::HypeTrainEventsV1(t) => vec![
+            Equivalent::new(
+                &channel::ChannelHypeTrainBeginV1::broadcaster_user_id(id(t.channel_id)),
+                Partial,
+            )?,
+            Equivalent::new(
+                &channel::ChannelHypeTrainProgressV1::broadcaster_user_id(id(t.channel_id)),
+                Partial,
+            )?,
+            Equivalent::new(
+                &channel::ChannelHypeTrainEndV1::broadcaster_user_id(id(t.channel_id)),
+                Partial,
+            )?,
+        ],
+        #[cfg(feature = "unsupported")]
+        Topics::HypeTrainEventsV1Rewards(_) => vec![],
+        // PubSub did not require moderator authorization, so the broadcaster is used as the moderator
+        #[cfg(feature = "unsupported")]
+        Topics::Following(t) => vec![Equivalent::new(
+            &channel::ChannelFollowV2::new(id(t.channel_id), id(t.channel_id)),
+            Full,
+        )?],
+        #[cfg(any(feature = "unsupported", feature = "tpm"))]
+        Topics::Raid(t) => vec![Equivalent::new(
+            &channel::ChannelRaidV1::from_broadcaster_user_id(id(t.channel_id)),
+            Partial,
+        )?],
+        #[cfg(feature = "pubsub")]
+        Topics::UserModerationNotifications(t) => vec![
+            Equivalent::new(
+                &channel::ChannelChatUserMessageHoldV1::new(
+                    id(t.channel_id),
+                    id(t.current_user_id),
+                ),
+                Full,
+            )?,
+            Equivalent::new(
+                &channel::ChannelChatUserMessageUpdateV1::new(
+                    id(t.channel_id),
+                    id(t.current_user_id),
+                ),
+                Full,
+            )?,
+        ],
+        #[cfg(any(feature = "unsupported", feature = "tpm"))]
+        Topics::PredictionsChannelV1(t) => vec![
+            Equivalent::new(
+                &channel::ChannelPredictionBeginV1::broadcaster_user_id(id(t.channel_id)),
+                Full,
+            )?,
+            Equivalent::new(
+                &channel::ChannelPredictionProgressV1::broadcaster_user_id(id(t.channel_id)),
+                Full,
+            )?,
+            Equivalent::new(
+                &channel::ChannelPredictionLockV1::broadcaster_user_id(id(t.channel_id)),
+                Full,
+            )?,
+            Equivalent::new(
+                &channel::ChannelPredictionEndV1::broadcaster_user_id(id(t.channel_id)),
+                Full,
+            )?,
+        ],
+        #[cfg(any(feature = "unsupported", feature = "tpm"))]
+        Topics::PredictionsUserV1(_) => vec![],
+        #[cfg(any(feature = "unsupported", feature = "tpm"))]
+        Topics::CommunityPointsUserV1(_) => vec![],
+    })
+}
+
+/// Convert an EventSub notification into the closest PubSub [`TopicData`]
+///
+/// `received_at` is used where PubSub includes a timestamp that EventSub does not send,
+/// for example the websocket `message_timestamp`.
+///
+/// Returns [`None`] if the event is not a notification, or if PubSub has no reply of a similar shape.
+/// The following are converted:
+///
+/// * [`channel.channel_points_custom_reward_redemption.add`](eventsub::channel::ChannelPointsCustomRewardRedemptionAddV1) and
+///   [`.update`](eventsub::channel::ChannelPointsCustomRewardRedemptionUpdateV1) into [`TopicData::ChannelPointsChannelV1`].
+///   Reward settings that EventSub does not send are filled in as disabled.
+/// * [`channel.bits.use`](eventsub::channel::ChannelBitsUseV1) cheers into [`TopicData::ChannelBitsEventsV2`].
+///   `total_bits_used` is the amount of bits in the cheer.
+/// * [`channel.subscribe`](eventsub::channel::ChannelSubscribeV1) and [`channel.subscription.message`](eventsub::channel::ChannelSubscriptionMessageV1)
+///   into [`TopicData::ChannelSubscribeEventsV1`]. Gifted subscriptions are skipped, as EventSub does not say who gifted them.
+/// * [`channel.moderate`](eventsub::channel::ChannelModerateV2) into [`TopicData::ChatModeratorActions`],
+///   except for shared chat actions, warnings and term changes.
+/// * [`channel.follow`](eventsub::channel::ChannelFollowV2) into `TopicData::Following` (requires the `unsupported` feature)
+pub fn to_topic_data(event: &Event, received_at: &types::TimestampRef) -> Option<TopicData> {
+    match event {
+        #[cfg(feature = "pubsub")]
+        Event::ChannelPointsCustomRewardRedemptionAddV1(Payload {
+            message: Message::Notification(p),
+            ..
+        }) => redemption(&p.broadcaster_user_id, serde_json::to_value(p).ok()?, true),
+        #[cfg(feature = "pubsub")]
+        Event::ChannelPointsCustomRewardRedemptionUpdateV1(Payload {
+            message: Message::Notification(p),
+            ..
+        }) => redemption(&p.broadcaster_user_id, serde_json::to_value(p).ok()?, false),
+        #[cfg(feature = "pubsub")]
+        Event::ChannelBitsUseV1(Payload {
+            message: Message::Notification(p),
+            ..
+        }) => {
+            use super::channel_bits::*;
+
+            if !matches!(p.type_, eventsub::channel::bits::BitsType::Cheer) {
+                return None;
+            }
+            Some(TopicData::ChannelBitsEventsV2 {
+                topic: ChannelBitsEventsV2 {
+                    channel_id: pubsub_id(&p.broadcaster_user_id)?,
+                },
+                reply: Box::new(ChannelBitsEventsV2Reply::BitsEvent {
+                    data: BitsEventData {
+                        badge_entitlement: None,
+                        bits_used: p.bits as i64,
+                        channel_id: p.broadcaster_user_id.clone(),
+                        channel_name: p.broadcaster_user_login.clone(),
+                        chat_message: p
+                            .message
+                            .as_ref()
+                            .map(|m| m.text.clone())
+                            .unwrap_or_default(),
+                        context: BitsContext::Cheer,
+                        is_anonymous: false,
+                        time: received_at.to_owned(),
+                        total_bits_used: p.bits as i64,
+                        user_id: p.user_id.clone(),
+                        user_name: p.user_login.clone(),
+                    },
+                    message_id: String::new(),
+                    version: "1.0".to_owned(),
+                    is_anonymous: false,
+                }),
+            })
+        }
+        #[cfg(feature = "pubsub")]
+        Event::ChannelSubscribeV1(Payload {
+            message: Message::Notification(p),
+            ..
+        }) => {
+            use super::channel_subscriptions::*;
+
+            if p.is_gift {
+                return None;
+            }
+            Some(TopicData::ChannelSubscribeEventsV1 {
+                topic: ChannelSubscribeEventsV1 {
+                    channel_id: pubsub_id(&p.broadcaster_user_id)?,
+                },
+                reply: Box::new(ChannelSubscribeEventsV1Reply::Sub(Sub {
+                    benefit_end_month: 0,
+                    channel_id: p.broadcaster_user_id.clone(),
+                    channel_name: p.broadcaster_user_login.clone(),
+                    cumulative_months: 1,
+                    is_gift: false,
+                    months: 0,
+                    multi_month_duration: 1,
+                    sub_message: SubMessage {
+                        emotes: vec![],
+                        message: String::new(),
+                    },
+                    sub_plan: p.tier.clone(),
+                    sub_plan_name: String::new(),
+                    time: received_at.to_owned(),
+                    user_id: p.user_id.clone(),
+                    user_name: p.user_login.clone(),
+                    display_name: p.user_name.clone(),
+                })),
+            })
+        }
+        #[cfg(feature = "pubsub")]
+        Event::ChannelSubscriptionMessageV1(Payload {
+            message: Message::Notification(p),
+            ..
+        }) => {
+            use super::channel_subscriptions::*;
+
+            Some(TopicData::ChannelSubscribeEventsV1 {
+                topic: ChannelSubscribeEventsV1 {
+                    channel_id: pubsub_id(&p.broadcaster_user_id)?,
+                },
+                reply: Box::new(ChannelSubscribeEventsV1Reply::ReSub(ReSub {
+                    benefit_end_month: None,
+                    channel_id: p.broadcaster_user_id.clone(),
+                    channel_name: p.broadcaster_user_login.clone(),
+                    cumulative_months: p.cumulative_months,
+                    is_gift: false,
+                    streak_months: p.streak_months,
+                    months: 0,
+                    multi_month_duration: Some(p.duration_months),
+                    // emote positions are not carried over
+                    sub_message: SubMessage {
+                        emotes: vec![],
+                        message: p.message.text.clone(),
+                    },
+                    sub_plan: p.tier.clone(),
+                    sub_plan_name: String::new(),
+                    time: received_at.to_owned(),
+                    user_id: p.user_id.clone(),
+                    user_name: p.user_login.clone(),
+                    display_name: p.user_name.clone(),
+                })),
+            })
+        }
+        #[cfg(feature = "pubsub")]
+        Event::ChannelModerateV2(Payload {
+            subscription,
+            message: Message::Notification(p),
+        }) => Some(TopicData::ChatModeratorActions {
+            topic: super::moderation::ChatModeratorActions {
+                user_id: pubsub_id(&subscription.condition.moderator_user_id)?,
+                channel_id: pubsub_id(&p.broadcaster_user_id)?,
+            },
+            reply: Box::new(moderation(p, received_at)?),
+        }),
+        #[cfg(feature = "unsupported")]
+        Event::ChannelFollowV2(Payload {
+            message: Message::Notification(p),
+            ..
+        }) => Some(TopicData::Following {
+            topic: super::following::Following {
+                channel_id: pubsub_id(&p.broadcaster_user_id)?,
+            },
+            reply: Box::new(super::following::FollowingReply {
+                display_name: p.user_name.clone(),
+                user_id: p.user_id.clone(),
+                username: p.user_login.clone(),
+            }),
+        }),
+        _ => None,
+    }
+}
+
+/// PubSub topics use numeric ids
+fn pubsub_id(id: &types::UserIdRef) -> Option<u32> { id.as_str().parse().ok() }
+
+/// Build a channel points reply from a serialized redemption add or update payload, they share their shape.
+fn redemption(
+    broadcaster_user_id: &types::UserIdRef,
+    p: serde_json::Value,
+    added: bool,
+) -> Option<TopicData> {
+    use super::channel_points::*;
+
+    let status = match p["status"].as_str()? {
+        "unfulfilled" => "UNFULFILLED",
+        "fulfilled" => "FULFILLED",
+        "canceled" => "ACTION_TAKEN",
+        _ => return None,
+    };
+    let user_input = p["user_input"].as_str().filter(|s| !s.is_empty());
+    let disabled = |key: &str| serde_json::json!({ "is_enabled": false, key: 0 });
+    let redemption: Redemption = serde_json::from_value(serde_json::json!({
+        "channel_id": p["broadcaster_user_id"],
+        "id": p["id"],
+        "redeemed_at": p["redeemed_at"],
+        "reward": {
+            "background_color": "",
+            "channel_id": p["broadcaster_user_id"],
+            "cooldown_expires_at": null,
+            "cost": p["reward"]["cost"],
+            "default_image": null,
+            "global_cooldown": disabled("global_cooldown_seconds"),
+            "id": p["reward"]["id"],
+            "image": null,
+            "is_enabled": true,
+            "is_in_stock": true,
+            "is_paused": false,
+            "is_sub_only": false,
+            "is_user_input_required": user_input.is_some(),
+            "max_per_stream": disabled("max_per_stream"),
+            "max_per_user_per_stream": disabled("max_per_user_per_stream"),
+            "prompt": p["reward"]["prompt"],
+            "redemptions_redeemed_current_stream": null,
+            "should_redemptions_skip_request_queue": false,
+            "template_id": null,
+            "title": p["reward"]["title"],
+            "updated_for_indicator_at": null,
+        },
+        "status": status,
+        "user": {
+            "id": p["user_id"],
+            "login": p["user_login"],
+            "display_name": p["user_name"],
+        },
+        "user_input": user_input,
+        "cursor": null,
+    }))
+    .ok()?;
+    let timestamp = redemption.redeemed_at.as_str().to_owned();
+    Some(TopicData::ChannelPointsChannelV1 {
+        topic: ChannelPointsChannelV1 {
+            channel_id: pubsub_id(broadcaster_user_id)?,
+        },
+        reply: Box::new(if added {
+            ChannelPointsChannelV1Reply::RewardRedeemed {
+                timestamp,
+                redemption,
+            }
+        } else {
+            ChannelPointsChannelV1Reply::RedemptionStatusUpdate {
+                timestamp,
+                redemption,
+            }
+        }),
+    })
+}
+
+fn moderation(
+    p: &eventsub::channel::ChannelModerateV2Payload,
+    received_at: &types::TimestampRef,
+) -> Option<super::moderation::ChatModeratorActionsReply> {
+    use super::moderation::*;
+    use eventsub::channel::moderate::ActionV2;
+
+    let action = |command: ModerationActionCommand,
+                  type_: ModerationType,
+                  target: Option<&types::UserIdRef>,
+                  args: Vec<String>| {
+        ChatModeratorActionsReply::ModerationAction(ModerationAction {
+            args,
+            created_by: Some(p.moderator_user_login.clone()),
+            created_by_user_id: Some(p.moderator_user_id.clone()),
+            from_automod: false,
+            moderation_action: command,
+            msg_id: None,
+            target_user_id: target
+                .map(ToOwned::to_owned)
+                .unwrap_or_else(|| types::UserId::from_static("")),
+            type_,
+            target_user_login: None,
+            created_at: None,
+        })
+    };
+    let user = |command, target: &types::UserIdRef, args| {
+        action(
+            command,
+            ModerationType::ChatLoginModeration,
+            Some(target),
+            args,
+        )
+    };
+    let channel =
+        |command, args| action(command, ModerationType::ChatChannelModeration, None, args);
+    let unban_request = |command, r: &eventsub::channel::moderate::UnbanRequest| UnbanRequest {
+        created_by_id: p.moderator_user_id.clone(),
+        created_by_login: p.moderator_user_login.clone(),
+        moderation_action: command,
+        moderator_message: r.moderator_message.clone(),
+        target_user_id: r.user_id.clone(),
+        target_user_login: r.user_login.clone(),
+    };
+
+    Some(match &p.action {
+        ActionV2::Ban(a) => user(
+            ModerationActionCommand::Ban,
+            &a.user_id,
+            vec![
+                a.user_login.to_string(),
+                a.reason.clone().unwrap_or_default(),
+            ],
+        ),
+        ActionV2::Unban(a) => user(
+            ModerationActionCommand::Unban,
+            &a.user_id,
+            vec![a.user_login.to_string()],
+        ),
+        ActionV2::Timeout(a) => {
+            let seconds = (crate::timestamp_to_unix_millis(a.expires_at.as_str())?
+                - crate::timestamp_to_unix_millis(received_at.as_str())?)
+                / 1000;
+            user(
+                ModerationActionCommand::Timeout,
+                &a.user_id,
+                vec![
+                    a.user_login.to_string(),
+                    seconds.max(1).to_string(),
+                    a.reason.clone().unwrap_or_default(),
+                ],
+            )
+        }
+        ActionV2::Untimeout(a) => user(
+            ModerationActionCommand::Untimeout,
+            &a.user_id,
+            vec![a.user_login.to_string()],
+        ),
+        ActionV2::Delete(a) => user(
+            ModerationActionCommand::Delete,
+            &a.user_id,
+            vec![
+                a.user_login.to_string(),
+                a.message_body.clone(),
+                a.message_id.to_string(),
+            ],
+        ),
+        ActionV2::Raid(a) => user(
+            ModerationActionCommand::Raid,
+            &a.user_id,
+            vec![a.user_login.to_string()],
+        ),
+        ActionV2::Unraid(a) => user(
+            ModerationActionCommand::Unraid,
+            &a.user_id,
+            vec![a.user_login.to_string()],
+        ),
+        ActionV2::Unvip(a) => user(
+            ModerationActionCommand::Unvip,
+            &a.user_id,
+            vec![a.user_login.to_string()],
+        ),
+        ActionV2::Mod(a) => ChatModeratorActionsReply::ModeratorAdded(ModeratorAdded {
+            channel_id: p.broadcaster_user_id.clone(),
+            target_user_id: a.user_id.clone(),
+            moderation_action: ModerationActionCommand::Mod,
+            target_user_login: a.user_login.clone(),
+            created_by_user_id: p.moderator_user_id.clone(),
+            created_by: p.moderator_user_login.clone(),
+        }),
+        ActionV2::Unmod(a) => ChatModeratorActionsReply::ModeratorRemoved(ModeratorRemoved {
+            channel_id: p.broadcaster_user_id.clone(),
+            target_user_id: a.user_id.clone(),
+            moderation_action: ModerationActionCommand::Unmod,
+            target_user_login: a.user_login.clone(),
+            created_by_user_id: p.moderator_user_id.clone(),
+            created_by: p.moderator_user_login.clone(),
+        }),
+        ActionV2::Vip(a) => ChatModeratorActionsReply::VipAdded(VipAdded {
+            channel_id: p.broadcaster_user_id.clone(),
+            created_by: p.moderator_user_login.clone(),
+            created_by_user_id: p.moderator_user_id.clone(),
+            target_user_id: a.user_id.clone(),
+            target_user_login: a.user_login.clone(),
+        }),
+        ActionV2::ApproveUnbanRequest(r) => ChatModeratorActionsReply::ApproveUnbanRequest(
+            unban_request(ModerationActionCommand::ApproveUnbanRequest, r),
+        ),
+        ActionV2::DenyUnbanRequest(r) => ChatModeratorActionsReply::DenyUnbanRequest(
+            unban_request(ModerationActionCommand::DenyUnbanRequest, r),
+        ),
+        ActionV2::Followers(a) => channel(
+            ModerationActionCommand::Followers,
+            vec![a.follow_duration_minutes.to_string()],
+        ),
+        ActionV2::FollowersOff => channel(ModerationActionCommand::FollowersOff, vec![]),
+        ActionV2::Slow(a) => channel(
+            ModerationActionCommand::Slow,
+            vec![a.wait_time_seconds.to_string()],
+        ),
+        ActionV2::SlowOff => channel(ModerationActionCommand::SlowOff, vec![]),
+        ActionV2::EmoteOnly => channel(ModerationActionCommand::EmoteOnly, vec![]),
+        ActionV2::EmoteOnlyOff => channel(ModerationActionCommand::EmoteOnlyOff, vec![]),
+        ActionV2::Subscribers => channel(ModerationActionCommand::Subscribers, vec![]),
+        ActionV2::SubscribersOff => channel(ModerationActionCommand::SubscribersOff, vec![]),
+        ActionV2::Uniquechat => channel(ModerationActionCommand::R9KBeta, vec![]),
+        ActionV2::UniquechatOff => channel(ModerationActionCommand::R9KBetaOff, vec![]),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn mapping() {
+        let topic = Topics::ChatModeratorActions(super::super::moderation::ChatModeratorActions {
+            user_id: 1,
+            channel_id: 2,
+        });
+        let equivalents = equivalents(&topic).unwrap();
+        assert_eq!(equivalents.len(), 1);
+        assert_eq!(equivalents[0].event_type, EventType::ChannelModerate);
+        assert_eq!(equivalents[0].version, "2");
+        assert_eq!(
+            equivalents[0].condition,
+            serde_json::json!({ "broadcaster_user_id": "2", "moderator_user_id": "1" })
+        );
+
+        let topic = Topics::ChannelBitsEventsV2(super::super::channel_bits::ChannelBitsEventsV2 {
+            channel_id: 1234,
+        });
+        let equivalents = equivalents(&topic).unwrap();
+        assert_eq!(equivalents[0].event_type, EventType::ChannelBitsUse);
+        assert_eq!(equivalents[0].coverage, Coverage::Full);
+    }
+
+    #[test]
+    fn redemption() {
+        use super::super::channel_points::{ChannelPointsChannelV1Reply, RedemptionStatus};
+
+        let payload = r##"
+    {
+        "subscription": {
+            "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+            "type": "channel.channel_points_custom_reward_redemption.add",
+            "version": "1",
+            "status": "enabled",
+            "cost": 0,
+            "condition": {
+                "broadcaster_user_id": "1337"
+            },
+             "transport": {
+                "method": "webhook",
+                "callback": "https://example.com/webhooks/callback"
+            },
+            "created_at": "2019-11-16T10:11:12.123Z"
+        },
+        "event": {
+            "id": "1234",
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cool_user",
+            "broadcaster_user_name": "Cool_User",
+            "user_id": "9001",
+            "user_login": "cooler_user",
+            "user_name": "Cooler_User",
+            "user_input": "pogchamp",
+            "status": "unfulfilled",
+            "reward": {
+                "id": "9001",
+                "title": "title",
+                "cost": 100,
+                "prompt": "reward prompt"
+            },
+            "redeemed_at": "2020-07-15T17:16:03.17106713Z"
+        }
+    }
+    "##;
+        let event = Event::parse(payload).unwrap();
+        let received_at = types::Timestamp::try_from("2020-07-15T17:16:04Z".to_owned()).unwrap();
+        let Some(TopicData::ChannelPointsChannelV1 { topic, reply }) =
+            to_topic_data(&event, &received_at)
+        else {
+            panic!("expected channel points data");
+        };
+        assert_eq!(topic.channel_id, 1337);
+        let ChannelPointsChannelV1Reply::RewardRedeemed { redemption, .. } = *reply else {
+            panic!("expected a redemption");
+        };
+        assert_eq!(redemption.user.login.as_str(), "cooler_user");
+        assert_eq!(redemption.user_input.as_deref(), Some("pogchamp"));
+        assert_eq!(redemption.reward.cost, 100);
+        assert_eq!(redemption.reward.title, "title");
+        assert_eq!(redemption.status, RedemptionStatus::Unfulfilled);
+    }
+
+    #[test]
+    fn moderation() {
+        use super::super::moderation::{ChatModeratorActionsReply, ModerationActionCommand};
+
+        let payload = r##"
+    {
+        "subscription": {
+            "id": "5a893cd8-c10e-4198-a620-e9f35fd6ccd6",
+            "status": "enabled",
+            "type": "channel.moderate",
+            "version": "2",
+            "condition": {
+                "broadcaster_user_id": "129546453",
+                "moderator_user_id": "1234"
+            },
+            "transport": {
+                "method": "websocket",
+                "session_id": "AgoQMmDnfVEhRI6iLiBniEWHkxIGY2VsbC1j"
+            },
+            "created_at": "2024-11-27T20:01:50.171283653Z",
+            "cost": 0
+        },
+        "event": {
+            "broadcaster_user_id": "129546453",
+            "broadcaster_user_login": "nerixyz",
+            "broadcaster_user_name": "nerixyz",
+            "source_broadcaster_user_id": null,
+            "source_broadcaster_user_login": null,
+            "source_broadcaster_user_name": null,
+            "moderator_user_id": "129546453",
+            "moderator_user_login": "nerixyz",
+            "moderator_user_name": "nerixyz",
+            "action": "timeout",
+            "followers": null,
+            "slow": null,
+            "vip": null,
+            "unvip": null,
+            "mod": null,
+            "unmod": null,
+            "ban": null,
+            "unban": null,
+            "timeout": {
+                "user_id": "141981764",
+                "user_login": "twitchdev",
+                "user_name": "TwitchDev",
+                "reason": "test Kappa",
+                "expires_at": "2024-11-27T20:01:55.358149527Z"
+            },
+            "untimeout": null,
+            "raid": null,
+            "unraid": null,
+            "delete": null,
+            "automod_terms": null,
+            "unban_request": null,
+            "warn": null,
+            "shared_chat_ban": null,
+            "shared_chat_unban": null,
+            "shared_chat_timeout": null,
+            "shared_chat_untimeout": null,
+            "shared_chat_delete": null
+        }
+    }
+    "##;
+        let event = Event::parse(payload).unwrap();
+        let received_at =
+            types::Timestamp::try_from("2024-11-27T20:01:50.358Z".to_owned()).unwrap();
+        let Some(TopicData::ChatModeratorActions { topic, reply }) =
+            to_topic_data(&event, &received_at)
+        else {
+            panic!("expected moderator actions");
+        };
+        assert_eq!(topic.user_id, 1234);
+        assert_eq!(topic.channel_id, 129546453);
+        let ChatModeratorActionsReply::ModerationAction(action) = *reply else {
+            panic!("expected a moderation action");
+        };
+        assert_eq!(action.moderation_action, ModerationActionCommand::Timeout);
+        assert_eq!(action.target_user_id.as_str(), "141981764");
+        assert_eq!(action.args, ["twitchdev", "5", "test Kappa"]);
+    }
+}
@@ -0,0 +1,647 @@
+//! In-process mock PubSub server
+//!
+//! Start a [`MockServer`] in your tests and point your websocket client at [`MockServer::url`],
+//! either directly or through the `TWITCH_PUBSUB_URL` environment variable with the `mock_api` feature enabled.
+//!
+//! [`TWITCH_PUBSUB_URL`](crate::TWITCH_PUBSUB_URL) reads the variable only once, the first time it's used.
+//! To go through it, pick the server address up front with [`MockServerConfig::addr`]
+//! and set the variable before anything connects, see the second example below.
+//! This also makes a [`Pool`](super::pool::Pool) connect to the server.
+//!
+//! The server behaves like the Twitch PubSub server:
+//!
+//! * client messages are parsed as a [`Request`],
+//! * `PING`s are answered with a `PONG`, see [`MockServer::pause_pongs`] to test your ping timeouts,
+//! * `LISTEN` and `UNLISTEN` are answered with a [`RESPONSE`](super::TwitchResponse) carrying the request nonce,
+//!   and an `ERR_BADAUTH` or `ERR_BADTOPIC` error for [rejected tokens](MockServer::reject_auth_token) and [rejected topics](MockServer::reject_topic),
+//! * `LISTEN` and `UNLISTEN` with topics that don't exist are answered with `ERR_BADTOPIC`,
+//!   other messages that are not a valid request with `ERR_BADMESSAGE`,
+//! * connections that don't send a `PING` within [`MockServerConfig::ping_timeout`] are closed,
+//! * topic messages are sent to every connection listening to the topic with [`MockServer::send`],
+//! * [`MockServer::reconnect`] sends a `RECONNECT` message.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use twitch_api::pubsub::{self, mock_server::MockServer};
+//!
+//! let server = MockServer::start().await?;
+//!
+//! // connect your websocket client to `server.url()`, and wait for it
+//! let connection = server.next_connection().await.expect("server is running");
+//!
+//! let message = pubsub::Response::parse(
+//!     r#"{
+//!         "type": "MESSAGE",
+//!         "data": {
+//!             "topic": "channel-bits-events-v2.1234",
+//!             "message": "{\"data\":{\"user_name\":\"justintv\",\"channel_name\":\"tmi\",\"user_id\":\"12345\",\"channel_id\":\"1234\",\"time\":\"2020-10-19T17:50:24.807841596Z\",\"chat_message\":\"Corgo1\",\"bits_used\":5,\"total_bits_used\":29,\"is_anonymous\":false,\"context\":\"cheer\",\"badge_entitlement\":null},\"version\":\"1.0\",\"message_type\":\"bits_event\",\"message_id\":\"d1831817-95f2-5dfa-8864-f36f16eeb5d8\"}"
+//!         }
+//!     }"#,
+//! )?;
+//! if let pubsub::Response::Message { data } = message {
+//!     // sent to every connection that listened to `channel-bits-events-v2.1234`
+//!     server.send(data)?;
+//! }
+//! server.reconnect(connection)?;
+//! # Ok(()) }
+//! ```
+//!
+//! Connecting through `TWITCH_PUBSUB_URL`, with the `mock_api` feature enabled
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use twitch_api::pubsub::mock_server::MockServer;
+//!
+//! // must happen before `twitch_api::TWITCH_PUBSUB_URL` is first used
+//! std::env::set_var("TWITCH_PUBSUB_URL", "ws://127.0.0.1:18421");
+//! let server = MockServer::config()
+//!     .addr(([127, 0, 0, 1], 18421))
+//!     .start()
+//!     .await?;
+//! assert_eq!(server.url(), &*twitch_api::TWITCH_PUBSUB_URL);
+//!
+//! let (_socket, _) = tokio_tungstenite::connect_async(twitch_api::TWITCH_PUBSUB_URL.as_str()).await?;
+//! let _connection = server.next_connection().await.expect("server is running");
+//! # Ok(()) }
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+use super::{ListenData, Request, Response, TopicData, Topics, TwitchResponse};
+
+/// Errors from the [`MockServer`]
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+#[non_exhaustive]
+pub enum MockServerError {
+    /// connection `{0}` is not connected
+    NoConnection(u64),
+    /// no connection listens to `{0}`
+    NoListener(Topics),
+    /// could not serialize message
+    SerializeError(#[from] serde_json::Error),
+}
+
+/// Configuration for a [`MockServer`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct MockServerConfig {
+    /// The address to listen on. Defaults to `127.0.0.1:0`, a random local port
+    pub addr: SocketAddr,
+    /// Close connections that don't send a `PING` for this long, `None` keeps them open. Defaults to 5 minutes
+    pub ping_timeout: Option<Duration>,
+}
+
+impl Default for MockServerConfig {
+    fn default() -> Self {
+        Self {
+            addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+            ping_timeout: Some(Duration::from_secs(5 * 60)),
+        }
+    }
+}
+
+impl MockServerConfig {
+    /// Set the address to listen on, use port `0` for a random port
+    pub fn addr(mut self, addr: impl Into<SocketAddr>) -> Self {
+        self.addr = addr.into();
+        self
+    }
+
+    /// Set how long to wait for a `PING` before closing a connection, `None` keeps connections open
+    pub fn ping_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.ping_timeout = timeout.into();
+        self
+    }
+
+    /// Start a server with this configuration
+    pub async fn start(self) -> std::io::Result<MockServer> { MockServer::start_with(self).await }
+}
+
+enum Command {
+    Frame(String),
+    Close,
+}
+
+struct Connection {
+    tx: mpsc::UnboundedSender<Command>,
+    topics: HashSet<Topics>,
+}
+
+#[derive(Default)]
+struct State {
+    connections: HashMap<u64, Connection>,
+    rejected_tokens: HashSet<String>,
+    rejected_topics: HashSet<Topics>,
+}
+
+struct Shared {
+    config: MockServerConfig,
+    url: url::Url,
+    next_id: AtomicU64,
+    pause_pongs: AtomicBool,
+    state: Mutex<State>,
+    new_connections: mpsc::UnboundedSender<u64>,
+}
+
+/// An in-process mock of the PubSub server.
+///
+/// The server is stopped when dropped. See the [module documentation](self) for an example.
+pub struct MockServer {
+    shared: Arc<Shared>,
+    new_connections: tokio::sync::Mutex<mpsc::UnboundedReceiver<u64>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl std::fmt::Debug for MockServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockServer")
+            .field("url", &self.shared.url)
+            .field("config", &self.shared.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        let state = self.shared.state.lock().expect("poisoned");
+        for connection in state.connections.values() {
+            let _ = connection.tx.send(Command::Close);
+        }
+    }
+}
+
+impl MockServer {
+    /// Start a server with the default [configuration](MockServerConfig) on a random local port
+    pub async fn start() -> std::io::Result<Self> { Self::start_with(Default::default()).await }
+
+    /// Create a configuration to start a server with
+    pub fn config() -> MockServerConfig { MockServerConfig::default() }
+
+    /// Start a server with the given configuration, listening on [`MockServerConfig::addr`]
+    pub async fn start_with(config: MockServerConfig) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(config.addr).await?;
+        let addr = listener.local_addr()?;
+        let url = url::Url::parse(&format!("ws://{addr}")).expect("url should be valid");
+        let (new_connections_tx, new_connections) = mpsc::unbounded_channel();
+        let shared = Arc::new(Shared {
+            config,
+            url,
+            next_id: AtomicU64::new(0),
+            pause_pongs: AtomicBool::new(false),
+            state: Mutex::new(State::default()),
+            new_connections: new_connections_tx,
+        });
+
+        let task = tokio::spawn({
+            let shared = shared.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(handle_connection(shared.clone(), stream));
+                }
+            }
+        });
+
+        Ok(Self {
+            shared,
+            new_connections: tokio::sync::Mutex::new(new_connections),
+            task,
+        })
+    }
+
+    /// The websocket url to connect to
+    pub fn url(&self) -> &url::Url { &self.shared.url }
+
+    /// Wait for the next connection and return its id
+    pub async fn next_connection(&self) -> Option<u64> {
+        self.new_connections.lock().await.recv().await
+    }
+
+    /// Ids of all open connections
+    pub fn connections(&self) -> Vec<u64> {
+        let mut connections = self
+            .shared
+            .state
+            .lock()
+            .expect("poisoned")
+            .connections
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        connections.sort_unstable();
+        connections
+    }
+
+    /// Topics a connection listens to
+    pub fn topics(&self, connection: u64) -> Result<Vec<Topics>, MockServerError> {
+        self.shared
+            .state
+            .lock()
+            .expect("poisoned")
+            .connections
+            .get(&connection)
+            .map(|c| c.topics.iter().cloned().collect())
+            .ok_or(MockServerError::NoConnection(connection))
+    }
+
+    /// Answer `LISTEN`s with this auth token with `ERR_BADAUTH`, connections already listening with it are not affected
+    pub fn reject_auth_token(&self, token: impl Into<String>) {
+        self.shared
+            .state
+            .lock()
+            .expect("poisoned")
+            .rejected_tokens
+            .insert(token.into());
+    }
+
+    /// Answer `LISTEN`s containing this topic with `ERR_BADTOPIC`
+    pub fn reject_topic(&self, topic: Topics) {
+        self.shared
+            .state
+            .lock()
+            .expect("poisoned")
+            .rejected_topics
+            .insert(topic);
+    }
+
+    /// Stop answering `PING`s, or resume answering them
+    pub fn pause_pongs(&self, paused: bool) {
+        self.shared.pause_pongs.store(paused, Ordering::Relaxed)
+    }
+
+    /// Send a topic message to every connection listening to its topic.
+    ///
+    /// Returns the number of connections the message was sent to.
+    pub fn send(&self, data: TopicData) -> Result<usize, MockServerError> {
+        let topic = topic_of(&data)?;
+        let frame = serde_json::to_string(&Response::Message { data })?;
+        let state = self.shared.state.lock().expect("poisoned");
+        let sent = state
+            .connections
+            .values()
+            .filter(|c| c.topics.contains(&topic))
+            .filter(|c| c.tx.send(Command::Frame(frame.clone())).is_ok())
+            .count();
+        if sent == 0 {
+            return Err(MockServerError::NoListener(topic));
+        }
+        Ok(sent)
+    }
+
+    /// Send a raw text frame to a connection
+    pub fn send_raw(
+        &self,
+        connection: u64,
+        frame: impl Into<String>,
+    ) -> Result<(), MockServerError> {
+        self.command(connection, Command::Frame(frame.into()))
+    }
+
+    /// Send a `RECONNECT` message to a connection
+    pub fn reconnect(&self, connection: u64) -> Result<(), MockServerError> {
+        let frame = serde_json::to_string(&Response::Reconnect)?;
+        self.command(connection, Command::Frame(frame))
+    }
+
+    /// Send a `RECONNECT` message to all connections
+    pub fn reconnect_all(&self) -> Result<(), MockServerError> {
+        for connection in self.connections() {
+            self.reconnect(connection)?;
+        }
+        Ok(())
+    }
+
+    /// Close a connection
+    pub fn close(&self, connection: u64) -> Result<(), MockServerError> {
+        self.command(connection, Command::Close)
+    }
+
+    fn command(&self, connection: u64, command: Command) -> Result<(), MockServerError> {
+        let state = self.shared.state.lock().expect("poisoned");
+        state
+            .connections
+            .get(&connection)
+            .and_then(|c| c.tx.send(command).ok())
+            .ok_or(MockServerError::NoConnection(connection))
+    }
+}
+
+impl Shared {
+    /// Handle a client message, returning the reply
+    fn handle(&self, connection: u64, message: &str) -> Option<Response> {
+        let request = match Request::parse(message) {
+            Ok(request) => request,
+            Err(_) => return Some(reject(message)),
+        };
+        let mut state = self.state.lock().expect("poisoned");
+        match request {
+            Request::Ping => {
+                if self.pause_pongs.load(Ordering::Relaxed) {
+                    None
+                } else {
+                    Some(Response::Pong)
+                }
+            }
+            Request::Listen {
+                data: ListenData { topics, auth_token },
+                nonce,
+            } => {
+                let error = if auth_token
+                    .as_ref()
+                    .map_or(true, |t| t.is_empty() || state.rejected_tokens.contains(t))
+                {
+                    "ERR_BADAUTH"
+                } else if topics.is_empty()
+                    || topics.iter().any(|t| state.rejected_topics.contains(t))
+                {
+                    "ERR_BADTOPIC"
+                } else {
+                    if let Some(c) = state.connections.get_mut(&connection) {
+                        c.topics.extend(topics);
+                    }
+                    ""
+                };
+                Some(response(nonce, error))
+            }
+            Request::UnListen {
+                data: ListenData { topics, .. },
+                nonce,
+            } => {
+                if let Some(c) = state.connections.get_mut(&connection) {
+                    for topic in &topics {
+                        c.topics.remove(topic);
+                    }
+                }
+                Some(response(nonce, ""))
+            }
+        }
+    }
+}
+
+async fn handle_connection(shared: Arc<Shared>, stream: TcpStream) {
+    let Ok(ws) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    let (mut sink, mut stream) = ws.split();
+
+    let id = shared.next_id.fetch_add(1, Ordering::Relaxed);
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    shared.state.lock().expect("poisoned").connections.insert(
+        id,
+        Connection {
+            tx,
+            topics: HashSet::new(),
+        },
+    );
+    let _ = shared.new_connections.send(id);
+
+    let ping_timeout = shared.config.ping_timeout;
+    let timeout = tokio::time::sleep(ping_timeout.unwrap_or(Duration::MAX));
+    tokio::pin!(timeout);
+
+    loop {
+        tokio::select! {
+            command = rx.recv() => match command {
+                Some(Command::Frame(frame)) => {
+                    if sink.send(WsMessage::Text(frame.into())).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Command::Close) | None => {
+                    let _ = sink.send(WsMessage::Close(None)).await;
+                    break;
+                }
+            },
+            _ = &mut timeout, if ping_timeout.is_some() => {
+                let _ = sink.send(WsMessage::Close(None)).await;
+                break;
+            },
+            message = stream.next() => match message {
+                Some(Ok(WsMessage::Text(text))) => {
+                    if let (Ok(Request::Ping), Some(timeout_after)) = (Request::parse(&text), ping_timeout) {
+                        timeout.as_mut().reset(tokio::time::Instant::now() + timeout_after);
+                    }
+                    if let Some(reply) = shared.handle(id, &text) {
+                        let frame = serde_json::to_string(&reply).expect("response should serialize");
+                        if sink.send(WsMessage::Text(frame.into())).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                Some(Ok(WsMessage::Close(_))) | None | Some(Err(_)) => break,
+                Some(Ok(_)) => {
+                    // tungstenite queues the pong, make sure it's sent
+                    let _ = sink.flush().await;
+                }
+            },
+        }
+    }
+    shared
+        .state
+        .lock()
+        .expect("poisoned")
+        .connections
+        .remove(&id);
+}
+
+fn response(nonce: Option<String>, error: &str) -> Response {
+    Response::Response(TwitchResponse {
+        nonce,
+        error: Some(error.to_owned()),
+    })
+}
+
+/// Answer a message that isn't a valid request
+///
+/// `LISTEN` and `UNLISTEN` with topics that don't exist get `ERR_BADTOPIC`, anything else `ERR_BADMESSAGE`.
+fn reject(message: &str) -> Response {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(message) else {
+        return response(None, "ERR_BADMESSAGE");
+    };
+    let nonce = value
+        .get("nonce")
+        .and_then(|nonce| nonce.as_str())
+        .map(str::to_owned);
+    // the request is fine without its topics
+    let bad_topic = value["data"]["topics"]
+        .as_array()
+        .map_or(false, |topics| topics.iter().all(|t| t.is_string()))
+        && {
+            let mut without_topics = value.clone();
+            without_topics["data"]["topics"] = serde_json::json!([]);
+            Request::parse(&without_topics.to_string()).is_ok()
+        };
+    response(
+        nonce,
+        if bad_topic {
+            "ERR_BADTOPIC"
+        } else {
+            "ERR_BADMESSAGE"
+        },
+    )
+}
+
+fn topic_of(data: &TopicData) -> Result<Topics, serde_json::Error> {
+    let mut value = serde_json::to_value(data)?;
+    serde_json::from_value(value["topic"].take())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::{self, channel_bits::ChannelBitsEventsV2, Topic as _};
+
+    type Client = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    async fn roundtrip(client: &mut Client, message: String) -> Response {
+        client.send(WsMessage::Text(message.into())).await.unwrap();
+        next(client).await
+    }
+
+    async fn next(client: &mut Client) -> Response {
+        loop {
+            match client.next().await.unwrap().unwrap() {
+                WsMessage::Text(text) => return Response::parse(&text).unwrap(),
+                WsMessage::Ping(_) | WsMessage::Pong(_) => continue,
+                other => panic!("unexpected message: {other:?}"),
+            }
+        }
+    }
+
+    fn bits() -> TopicData {
+        let source = r#"{"type":"MESSAGE","data":{"topic":"channel-bits-events-v2.1234","message":"{\"data\":{\"user_name\":\"justintv\",\"channel_name\":\"tmi\",\"user_id\":\"12345\",\"channel_id\":\"1234\",\"time\":\"2020-10-19T17:50:24.807841596Z\",\"chat_message\":\"Corgo1\",\"bits_used\":5,\"total_bits_used\":29,\"is_anonymous\":false,\"context\":\"cheer\",\"badge_entitlement\":null},\"version\":\"1.0\",\"message_type\":\"bits_event\",\"message_id\":\"d1831817-95f2-5dfa-8864-f36f16eeb5d8\"}"}}"#;
+        match Response::parse(source).unwrap() {
+            Response::Message { data } => data,
+            other => panic!("unexpected response: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn listen_message_reconnect() {
+        let server = MockServer::start().await.unwrap();
+        let (mut client, _) = tokio_tungstenite::connect_async(server.url().as_str())
+            .await
+            .unwrap();
+        let connection = server.next_connection().await.unwrap();
+
+        let topic = ChannelBitsEventsV2 { channel_id: 1234 }.into_topic();
+        assert!(matches!(
+            server.send(bits()),
+            Err(MockServerError::NoListener(_))
+        ));
+
+        let listen = pubsub::listen_command(&[topic.clone()], "token", "a").unwrap();
+        assert_eq!(
+            roundtrip(&mut client, listen).await,
+            response(Some("a".to_owned()), "")
+        );
+        assert_eq!(server.topics(connection).unwrap(), vec![topic.clone()]);
+
+        assert_eq!(server.send(bits()).unwrap(), 1);
+        assert_eq!(next(&mut client).await, Response::Message { data: bits() });
+
+        assert_eq!(
+            roundtrip(&mut client, r#"{"type":"PING"}"#.to_owned()).await,
+            Response::Pong
+        );
+
+        server.reconnect(connection).unwrap();
+        assert_eq!(next(&mut client).await, Response::Reconnect);
+
+        let unlisten = pubsub::unlisten_command(&[topic], "b").unwrap();
+        assert_eq!(
+            roundtrip(&mut client, unlisten).await,
+            response(Some("b".to_owned()), "")
+        );
+        assert!(server.topics(connection).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let server = MockServer::start().await.unwrap();
+        let (mut client, _) = tokio_tungstenite::connect_async(server.url().as_str())
+            .await
+            .unwrap();
+        let connection = server.next_connection().await.unwrap();
+
+        let topic = ChannelBitsEventsV2 { channel_id: 1 }.into_topic();
+        let listen = pubsub::listen_command(&[topic.clone()], None::<&str>, "a").unwrap();
+        assert_eq!(
+            roundtrip(&mut client, listen).await,
+            response(Some("a".to_owned()), "ERR_BADAUTH")
+        );
+
+        server.reject_auth_token("revoked");
+        let listen = pubsub::listen_command(&[topic.clone()], "revoked", "b").unwrap();
+        assert_eq!(
+            roundtrip(&mut client, listen).await,
+            response(Some("b".to_owned()), "ERR_BADAUTH")
+        );
+
+        server.reject_topic(topic.clone());
+        let listen = pubsub::listen_command(&[topic], "token", "c").unwrap();
+        assert_eq!(
+            roundtrip(&mut client, listen).await,
+            response(Some("c".to_owned()), "ERR_BADTOPIC")
+        );
+
+        let bad = r#"{"type":"LISTEN","nonce":"d","data":{"topics":["nope.1"]}}"#;
+        assert_eq!(
+            roundtrip(&mut client, bad.to_owned()).await,
+            response(Some("d".to_owned()), "ERR_BADTOPIC")
+        );
+        let bad = r#"{"type":"LISTEN","nonce":"e","data":{"topics":"nope.1"}}"#;
+        assert_eq!(
+            roundtrip(&mut client, bad.to_owned()).await,
+            response(Some("e".to_owned()), "ERR_BADMESSAGE")
+        );
+        assert!(server.topics(connection).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn ping_timeout() {
+        let server = MockServer::config()
+            .ping_timeout(Duration::from_millis(100))
+            .start()
+            .await
+            .unwrap();
+        let (mut client, _) = tokio_tungstenite::connect_async(server.url().as_str())
+            .await
+            .unwrap();
+
+        // no pong, and closed after the ping timeout
+        server.pause_pongs(true);
+        client
+            .send(WsMessage::Text(r#"{"type":"PING"}"#.into()))
+            .await
+            .unwrap();
+        loop {
+            match client.next().await {
+                Some(Ok(WsMessage::Close(_))) | None => break,
+                Some(Ok(WsMessage::Ping(_) | WsMessage::Pong(_))) => continue,
+                other => panic!("unexpected message: {other:?}"),
+            }
+        }
+    }
+}
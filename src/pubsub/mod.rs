@@ -103,15 +103,27 @@ pub mod community_points;
 pub mod following;
 #[cfg(feature = "unsupported")]
 pub mod hypetrain;
+#[cfg(feature = "eventsub")]
+#[cfg_attr(nightly, doc(cfg(feature = "eventsub")))]
+pub mod migrate;
+#[cfg(all(feature = "mock", feature = "mock_server"))]
+#[cfg_attr(nightly, doc(cfg(all(feature = "mock", feature = "mock_server"))))]
+pub mod mock_server;
 #[cfg(feature = "pubsub")]
 pub mod moderation;
 #[cfg(any(feature = "unsupported", feature = "tpm"))]
+pub mod points_ledger;
+#[cfg(feature = "pubsub")]
+pub mod pool;
+#[cfg(any(feature = "unsupported", feature = "tpm"))]
 pub mod raid;
 #[cfg(feature = "pubsub")]
 pub mod user_moderation_notifications;
 #[cfg(any(feature = "unsupported", feature = "tpm"))]
 pub mod video_playback;
 #[cfg(any(feature = "unsupported", feature = "tpm"))]
+pub mod viewer_series;
+#[cfg(any(feature = "unsupported", feature = "tpm"))]
 pub mod predictions;
 
 use crate::parse_json;
@@ -365,6 +377,67 @@ pub struct TwitchResponse {
 impl TwitchResponse {
     /// Whether response indicates success or not
     pub fn is_successful(&self) -> bool { self.error.as_ref().map_or(true, |s| s.is_empty()) }
+
+    /// The error of the response, if any
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_api::pubsub::{PubSubError, Response};
+    ///
+    /// let response = Response::parse(r#"{"type":"RESPONSE","nonce":"44h1k13746815ab1r2","error":"ERR_BADAUTH"}"#)?;
+    /// let Response::Response(response) = response else {
+    ///     panic!("expected a response")
+    /// };
+    /// assert_eq!(response.result(), Err(PubSubError::BadAuth));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn result(&self) -> Result<(), PubSubError> {
+        match self.error.as_deref() {
+            None | Some("") => Ok(()),
+            Some(code) => Err(PubSubError::from_code(code)),
+        }
+    }
+}
+
+/// Error returned by Twitch PubSub in a [`TwitchResponse`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, thiserror::Error, displaydoc::Display)]
+#[non_exhaustive]
+pub enum PubSubError {
+    /// the message was malformed (`ERR_BADMESSAGE`)
+    BadMessage,
+    /// the auth token is invalid or lacks a required scope (`ERR_BADAUTH`)
+    BadAuth,
+    /// the server had an internal error (`ERR_SERVER`)
+    Server,
+    /// the topic is invalid (`ERR_BADTOPIC`)
+    BadTopic,
+    /// unknown error `{0}`
+    Unknown(String),
+}
+
+impl PubSubError {
+    /// Parse an error code, e.g. `ERR_BADAUTH`
+    pub fn from_code(code: &str) -> Self {
+        match code {
+            "ERR_BADMESSAGE" => Self::BadMessage,
+            "ERR_BADAUTH" => Self::BadAuth,
+            "ERR_SERVER" => Self::Server,
+            "ERR_BADTOPIC" => Self::BadTopic,
+            code => Self::Unknown(code.to_owned()),
+        }
+    }
+
+    /// The error code, e.g. `ERR_BADAUTH`
+    pub fn code(&self) -> &str {
+        match self {
+            Self::BadMessage => "ERR_BADMESSAGE",
+            Self::BadAuth => "ERR_BADAUTH",
+            Self::Server => "ERR_SERVER",
+            Self::BadTopic => "ERR_BADTOPIC",
+            Self::Unknown(code) => code,
+        }
+    }
 }
 
 // FIXME: Add example
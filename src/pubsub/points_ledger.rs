@@ -0,0 +1,584 @@
+//! Channel points balances from [`CommunityPointsUserV1`](super::community_points::CommunityPointsUserV1)
+//! and [`PredictionsUserV1`](super::predictions::PredictionsUserV1)
+//!
+//! [`PointsLedger`] folds the replies of these topics into per-channel state:
+//!
+//! * the current balance, as last reported by Twitch,
+//! * points earned and spent, with earnings broken down by `reason_code`,
+//! * points wagered, won and refunded in predictions,
+//! * a history of every change, with the timestamp Twitch sent.
+//!
+//! The balance and totals are only taken from `points-earned` and `points-spent` messages,
+//! which Twitch also sends for claimed bonuses and prediction bets.
+//! `points-spent` messages only have the new balance, so spending is only counted once a previous balance is known.
+//! Claims and predictions are recorded in the history without changing the balance.
+//!
+//! The ledger can be serialized to persist it, and [`PointsLedger::snapshot`] gives a smaller summary without history,
+//! suitable to sample periodically for showing point accrual over time.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use twitch_api::pubsub::{points_ledger::PointsLedger, Response};
+//!
+//! let mut ledger = PointsLedger::new();
+//! let message = r#"{"type":"points-earned","data":{"timestamp":"2021-01-01T00:00:00Z","channel_id":"1234","point_gain":{"user_id":"5678","channel_id":"1234","total_points":50,"baseline_points":10,"reason_code":"WATCH"},"balance":{"user_id":"5678","channel_id":"1234","balance":1050}}}"#;
+//! let source = format!(
+//!     r#"{{"type":"MESSAGE","data":{{"topic":"community-points-user-v1.5678","message":{message:?}}}}}"#
+//! );
+//! if let Response::Message { data } = Response::parse(&source)? {
+//!     ledger.apply(&data);
+//! }
+//! assert_eq!(ledger.balance("1234"), Some(1050));
+//! assert_eq!(ledger.channel("1234").unwrap().earned_by_reason["WATCH"], 50);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    pubsub::{
+        community_points::{Balance, CommunityPointsUserV1Reply},
+        predictions::PredictionsUserV1Reply,
+        TopicData,
+    },
+    types,
+};
+
+/// Default amount of history entries kept per channel
+pub const DEFAULT_HISTORY_LIMIT: usize = 1000;
+
+/// Channel points state for all channels, see the [module documentation](self)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PointsLedger {
+    channels: BTreeMap<types::UserId, ChannelLedger>,
+    history_limit: usize,
+}
+
+impl Default for PointsLedger {
+    fn default() -> Self { Self::new() }
+}
+
+/// Channel points state for one channel
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ChannelLedger {
+    /// The channel
+    pub channel_id: types::UserId,
+    /// The last known balance
+    pub balance: Option<i64>,
+    /// Total points earned
+    pub earned: i64,
+    /// Total points spent, including prediction bets
+    ///
+    /// Spends while the balance is unknown, like the first message for a channel, aren't counted,
+    /// since `points-spent` messages don't have the amount.
+    pub spent: i64,
+    /// Points earned by `reason_code`
+    pub earned_by_reason: BTreeMap<String, i64>,
+    /// Total points wagered in predictions
+    pub wagered: i64,
+    /// Total points won in predictions
+    pub won: i64,
+    /// Total points refunded from canceled predictions
+    pub refunded: i64,
+    /// Predictions by id
+    pub predictions: BTreeMap<String, Bet>,
+    /// Time of the last change
+    pub updated_at: Option<types::Timestamp>,
+    /// Changes, oldest first
+    pub history: VecDeque<LedgerEntry>,
+}
+
+/// A bet in a prediction
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Bet {
+    /// The prediction event
+    pub event_id: String,
+    /// The predicted outcome
+    pub outcome_id: String,
+    /// Points wagered
+    pub points: i64,
+    /// The result, `WIN`, `LOSE` or `REFUND`
+    pub result: Option<String>,
+    /// Points won
+    pub points_won: Option<i64>,
+}
+
+/// A change in a [`ChannelLedger`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct LedgerEntry {
+    /// When the change happened, as sent by Twitch
+    pub timestamp: types::Timestamp,
+    /// Change to the balance, `None` if the previous balance was unknown or the entry doesn't change the balance
+    pub change: Option<i64>,
+    /// The balance after the change
+    pub balance: Option<i64>,
+    /// What happened
+    pub kind: LedgerEntryKind,
+}
+
+/// What happened in a [`LedgerEntry`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum LedgerEntryKind {
+    /// Points were earned
+    Earned {
+        /// The reason, e.g. `WATCH` or `RAID`
+        reason_code: String,
+        /// Points earned
+        total_points: i64,
+        /// Points earned without multipliers
+        baseline_points: i64,
+    },
+    /// A bonus was claimed
+    Claimed {
+        /// The claim
+        claim_id: String,
+        /// Points earned
+        total_points: i64,
+    },
+    /// Points were spent
+    Spent,
+    /// A prediction bet was made or updated
+    Predicted {
+        /// The prediction
+        prediction_id: String,
+        /// Points wagered
+        points: i64,
+    },
+    /// A prediction was resolved
+    PredictionResult {
+        /// The prediction
+        prediction_id: String,
+        /// `WIN`, `LOSE` or `REFUND`
+        result: String,
+        /// Points won
+        points_won: Option<i64>,
+    },
+}
+
+/// Summary of a [`ChannelLedger`] without history, see [`PointsLedger::snapshot`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ChannelSnapshot {
+    /// The channel
+    pub channel_id: types::UserId,
+    /// The last known balance
+    pub balance: Option<i64>,
+    /// Total points earned
+    pub earned: i64,
+    /// Total points spent
+    pub spent: i64,
+    /// Points earned by `reason_code`
+    pub earned_by_reason: BTreeMap<String, i64>,
+    /// Total points wagered in predictions
+    pub wagered: i64,
+    /// Total points won in predictions
+    pub won: i64,
+    /// Total points refunded from canceled predictions
+    pub refunded: i64,
+    /// Time of the last change
+    pub updated_at: Option<types::Timestamp>,
+}
+
+impl PointsLedger {
+    /// Create an empty ledger, keeping [`DEFAULT_HISTORY_LIMIT`] history entries per channel
+    pub fn new() -> Self {
+        Self {
+            channels: BTreeMap::new(),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+        }
+    }
+
+    /// Set how many history entries to keep per channel
+    pub fn with_history_limit(mut self, limit: usize) -> Self {
+        self.history_limit = limit;
+        for channel in self.channels.values_mut() {
+            channel.truncate(limit);
+        }
+        self
+    }
+
+    /// Apply a topic message. Messages from other topics are ignored.
+    pub fn apply(&mut self, data: &TopicData) -> Option<&LedgerEntry> {
+        match data {
+            TopicData::CommunityPointsUserV1 { reply, .. } => self.apply_points(reply),
+            TopicData::PredictionsUserV1 { reply, .. } => self.apply_prediction(reply),
+            _ => None,
+        }
+    }
+
+    /// Apply a [`CommunityPointsUserV1Reply`]
+    pub fn apply_points(&mut self, reply: &CommunityPointsUserV1Reply) -> Option<&LedgerEntry> {
+        let limit = self.history_limit;
+        let (channel, entry) = match reply {
+            CommunityPointsUserV1Reply::PointsEarned {
+                timestamp,
+                channel_id,
+                point_gain,
+                balance,
+            } => {
+                let channel = self.channel_mut(channel_id);
+                channel.earned += point_gain.total_points;
+                *channel
+                    .earned_by_reason
+                    .entry(point_gain.reason_code.clone())
+                    .or_default() += point_gain.total_points;
+                let entry = channel.set_balance(
+                    timestamp,
+                    balance,
+                    LedgerEntryKind::Earned {
+                        reason_code: point_gain.reason_code.clone(),
+                        total_points: point_gain.total_points,
+                        baseline_points: point_gain.baseline_points,
+                    },
+                );
+                (channel, entry)
+            }
+            CommunityPointsUserV1Reply::PointsSpent { timestamp, balance } => {
+                let channel = self.channel_mut(&balance.channel_id);
+                if let Some(previous) = channel.balance {
+                    channel.spent += (previous - balance.balance).max(0);
+                }
+                let entry = channel.set_balance(timestamp, balance, LedgerEntryKind::Spent);
+                (channel, entry)
+            }
+            CommunityPointsUserV1Reply::ClaimClaimed { timestamp, claim } => {
+                let channel = self.channel_mut(&claim.channel_id);
+                // the points are counted from the `points-earned` message for the claim
+                let entry = LedgerEntry {
+                    timestamp: timestamp.clone(),
+                    change: None,
+                    balance: channel.balance,
+                    kind: LedgerEntryKind::Claimed {
+                        claim_id: claim.id.clone(),
+                        total_points: claim.point_gain.total_points,
+                    },
+                };
+                (channel, entry)
+            }
+        };
+        channel.push(entry, limit)
+    }
+
+    /// Apply a [`PredictionsUserV1Reply`]
+    pub fn apply_prediction(&mut self, reply: &PredictionsUserV1Reply) -> Option<&LedgerEntry> {
+        let limit = self.history_limit;
+        let prediction = &reply.data.prediction;
+        let channel = self.channel_mut(&types::UserId::new(prediction.channel_id.clone()));
+        let bet = channel
+            .predictions
+            .entry(prediction.id.clone())
+            .or_insert_with(|| Bet {
+                event_id: prediction.event_id.clone(),
+                outcome_id: prediction.outcome_id.clone(),
+                points: 0,
+                result: None,
+                points_won: None,
+            });
+        channel.wagered += prediction.points - bet.points;
+        bet.points = prediction.points;
+        bet.outcome_id = prediction.outcome_id.clone();
+
+        let kind = match &prediction.result {
+            Some(result) if bet.result.is_none() => {
+                bet.result = Some(result.type_field.clone());
+                bet.points_won = result.points_won;
+                if result.type_field == "REFUND" {
+                    // the wager is returned
+                    channel.refunded += result.points_won.unwrap_or(bet.points);
+                } else {
+                    channel.won += result.points_won.unwrap_or_default();
+                }
+                LedgerEntryKind::PredictionResult {
+                    prediction_id: prediction.id.clone(),
+                    result: result.type_field.clone(),
+                    points_won: result.points_won,
+                }
+            }
+            // already resolved
+            Some(_) => return None,
+            None => LedgerEntryKind::Predicted {
+                prediction_id: prediction.id.clone(),
+                points: prediction.points,
+            },
+        };
+        let entry = LedgerEntry {
+            timestamp: reply.data.timestamp.clone(),
+            change: None,
+            balance: channel.balance,
+            kind,
+        };
+        channel.push(entry, limit)
+    }
+
+    /// The ledger for a channel
+    pub fn channel<'a>(
+        &self,
+        channel_id: impl Into<&'a types::UserIdRef>,
+    ) -> Option<&ChannelLedger> {
+        self.channels.get(channel_id.into())
+    }
+
+    /// All channels in the ledger
+    pub fn channels(&self) -> impl Iterator<Item = &ChannelLedger> + '_ { self.channels.values() }
+
+    /// The last known balance in a channel
+    pub fn balance<'a>(&self, channel_id: impl Into<&'a types::UserIdRef>) -> Option<i64> {
+        self.channel(channel_id)?.balance
+    }
+
+    /// Summaries of all channels, without history
+    pub fn snapshot(&self) -> Vec<ChannelSnapshot> {
+        self.channels
+            .values()
+            .map(|channel| ChannelSnapshot {
+                channel_id: channel.channel_id.clone(),
+                balance: channel.balance,
+                earned: channel.earned,
+                spent: channel.spent,
+                earned_by_reason: channel.earned_by_reason.clone(),
+                wagered: channel.wagered,
+                won: channel.won,
+                refunded: channel.refunded,
+                updated_at: channel.updated_at.clone(),
+            })
+            .collect()
+    }
+
+    fn channel_mut(&mut self, channel_id: &types::UserIdRef) -> &mut ChannelLedger {
+        self.channels
+            .entry(channel_id.to_owned())
+            .or_insert_with(|| ChannelLedger {
+                channel_id: channel_id.to_owned(),
+                balance: None,
+                earned: 0,
+                spent: 0,
+                earned_by_reason: BTreeMap::new(),
+                wagered: 0,
+                won: 0,
+                refunded: 0,
+                predictions: BTreeMap::new(),
+                updated_at: None,
+                history: VecDeque::new(),
+            })
+    }
+}
+
+impl ChannelLedger {
+    fn set_balance(
+        &mut self,
+        timestamp: &types::Timestamp,
+        balance: &Balance,
+        kind: LedgerEntryKind,
+    ) -> LedgerEntry {
+        let change = self.balance.map(|previous| balance.balance - previous);
+        self.balance = Some(balance.balance);
+        LedgerEntry {
+            timestamp: timestamp.clone(),
+            change,
+            balance: self.balance,
+            kind,
+        }
+    }
+
+    fn push(&mut self, entry: LedgerEntry, limit: usize) -> Option<&LedgerEntry> {
+        self.updated_at = Some(entry.timestamp.clone());
+        self.history.push_back(entry);
+        self.truncate(limit);
+        self.history.back()
+    }
+
+    fn truncate(&mut self, limit: usize) {
+        while self.history.len() > limit {
+            self.history.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(reply: serde_json::Value) -> CommunityPointsUserV1Reply {
+        serde_json::from_value(reply).unwrap()
+    }
+
+    fn earned(
+        timestamp: &str,
+        total_points: i64,
+        reason_code: &str,
+        balance: i64,
+    ) -> serde_json::Value {
+        serde_json::json!({
+            "type": "points-earned",
+            "data": {
+                "timestamp": timestamp,
+                "channel_id": "1234",
+                "point_gain": {
+                    "user_id": "5678",
+                    "channel_id": "1234",
+                    "total_points": total_points,
+                    "baseline_points": total_points,
+                    "reason_code": reason_code
+                },
+                "balance": {"user_id": "5678", "channel_id": "1234", "balance": balance}
+            }
+        })
+    }
+
+    fn prediction(
+        timestamp: &str,
+        points: i64,
+        result: serde_json::Value,
+    ) -> PredictionsUserV1Reply {
+        serde_json::from_value(serde_json::json!({
+            "type": if result.is_null() { "prediction-made" } else { "prediction-result" },
+            "data": {
+                "timestamp": timestamp,
+                "prediction": {
+                    "id": "p1",
+                    "event_id": "e1",
+                    "outcome_id": "o1",
+                    "channel_id": "1234",
+                    "points": points,
+                    "predicted_at": "2021-01-01T00:03:00Z",
+                    "updated_at": timestamp,
+                    "user_id": "5678",
+                    "result": result,
+                    "user_display_name": null
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn ledger() {
+        let mut ledger = PointsLedger::new().with_history_limit(6);
+        ledger.apply_points(&points(earned("2021-01-01T00:00:00Z", 10, "WATCH", 1000)));
+        ledger.apply_points(&points(earned("2021-01-01T00:01:00Z", 250, "RAID", 1250)));
+
+        let entry = ledger
+            .apply_points(&points(serde_json::json!({
+                "type": "claim-claimed",
+                "data": {
+                    "timestamp": "2021-01-01T00:02:00Z",
+                    "claim": {
+                        "id": "c1",
+                        "user_id": "5678",
+                        "channel_id": "1234",
+                        "point_gain": {
+                            "user_id": "5678",
+                            "channel_id": "1234",
+                            "total_points": 50,
+                            "baseline_points": 50,
+                            "reason_code": "CLAIM"
+                        },
+                        "created_at": "2021-01-01T00:02:00Z"
+                    }
+                }
+            })))
+            .unwrap();
+        assert_eq!(entry.change, None);
+        assert_eq!(entry.balance, Some(1250));
+        ledger.apply_points(&points(earned("2021-01-01T00:02:00Z", 50, "CLAIM", 1300)));
+
+        // bet 300 points, twitch also sends points-spent
+        ledger.apply_prediction(&prediction(
+            "2021-01-01T00:03:00Z",
+            300,
+            serde_json::Value::Null,
+        ));
+        let entry = ledger
+            .apply_points(&points(serde_json::json!({
+                "type": "points-spent",
+                "data": {
+                    "timestamp": "2021-01-01T00:03:00Z",
+                    "balance": {"user_id": "5678", "channel_id": "1234", "balance": 1000}
+                }
+            })))
+            .unwrap();
+        assert_eq!(entry.change, Some(-300));
+
+        let result =
+            serde_json::json!({"type": "WIN", "points_won": 600, "is_acknowledged": false});
+        assert!(ledger
+            .apply_prediction(&prediction("2021-01-01T00:10:00Z", 300, result.clone()))
+            .is_some());
+        // repeated results are ignored
+        assert!(ledger
+            .apply_prediction(&prediction("2021-01-01T00:11:00Z", 300, result))
+            .is_none());
+
+        let channel = ledger.channel("1234").unwrap();
+        assert_eq!(channel.balance, Some(1000));
+        assert_eq!(channel.earned, 310);
+        assert_eq!(channel.spent, 300);
+        assert_eq!(channel.earned_by_reason["RAID"], 250);
+        assert_eq!(channel.earned_by_reason["CLAIM"], 50);
+        assert_eq!(channel.wagered, 300);
+        assert_eq!(channel.won, 600);
+        assert_eq!(channel.predictions["p1"].result.as_deref(), Some("WIN"));
+        assert_eq!(channel.history.len(), 6);
+        assert_eq!(
+            channel.updated_at.as_ref().map(|t| t.as_str()),
+            Some("2021-01-01T00:10:00Z")
+        );
+
+        let snapshot = ledger.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].balance, Some(1000));
+
+        let json = serde_json::to_string(&ledger).unwrap();
+        assert_eq!(serde_json::from_str::<PointsLedger>(&json).unwrap(), ledger);
+    }
+
+    #[test]
+    fn refund() {
+        let mut ledger = PointsLedger::new();
+        ledger.apply_prediction(&prediction(
+            "2021-01-01T00:03:00Z",
+            200,
+            serde_json::Value::Null,
+        ));
+        let result =
+            serde_json::json!({"type": "REFUND", "points_won": null, "is_acknowledged": false});
+        ledger.apply_prediction(&prediction("2021-01-01T00:10:00Z", 200, result));
+
+        let channel = ledger.channel("1234").unwrap();
+        assert_eq!(channel.wagered, 200);
+        assert_eq!(channel.won, 0);
+        assert_eq!(channel.refunded, 200);
+        assert_eq!(ledger.snapshot()[0].refunded, 200);
+    }
+
+    #[test]
+    fn unknown_balance() {
+        let mut ledger = PointsLedger::new();
+        let spent = |balance: i64| {
+            points(serde_json::json!({
+                "type": "points-spent",
+                "data": {
+                    "timestamp": "2021-01-01T00:03:00Z",
+                    "balance": {"user_id": "5678", "channel_id": "1234", "balance": balance}
+                }
+            }))
+        };
+        // the amount of the first spend isn't known
+        let entry = ledger.apply_points(&spent(1000)).unwrap();
+        assert_eq!(entry.change, None);
+        assert_eq!(ledger.channel("1234").unwrap().spent, 0);
+
+        ledger.apply_points(&spent(900));
+        assert_eq!(ledger.channel("1234").unwrap().spent, 100);
+    }
+}
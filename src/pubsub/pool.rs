@@ -0,0 +1,1078 @@
+//! Managed pool of PubSub connections
+//!
+//! PubSub allows at most 50 topics per connection, requires a `PING` at least every five minutes
+//! and can ask clients to reconnect at any time. [`Pool`] takes care of this for many topics at once:
+//!
+//! * topics are sharded over as many connections as needed, see [`PoolConfig::max_topics_per_connection`],
+//! * every connection sends a `PING` every [`PoolConfig::ping_interval`], minus a random jitter,
+//! * a connection that doesn't get a `PONG` back within [`PoolConfig::pong_timeout`] is considered dead,
+//! * dead connections and connections that receive a [`RECONNECT`](super::Response::Reconnect) are reconnected with an exponential backoff,
+//!   and all their topics are `LISTEN`ed again,
+//! * every `LISTEN` and `UNLISTEN` gets a nonce, and the [response](super::TwitchResponse) is matched back to its topics,
+//!   whose [state](TopicState) is tracked per topic,
+//! * when a `LISTEN` fails with [`ERR_BADAUTH`](super::PubSubError::BadAuth), a [`TokenProvider`] is asked for a fresh token
+//!   and only the topics of that `LISTEN` are listened to again.
+//!
+//! The pool does no IO itself. Instead, you
+//!
+//! * run the [actions](Action) returned by [`Pool::poll_action`], i.e. open, write to and close websockets,
+//! * tell the pool about opened and closed connections with [`Pool::connected`] and [`Pool::disconnected`],
+//! * feed it received messages with [`Pool::receive`],
+//! * and call [`Pool::handle_timeout`] when [`Pool::poll_timeout`] has passed.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use std::time::Instant;
+//! use twitch_api::pubsub::{
+//!     self,
+//!     pool::{Action, Pool, PoolConfig, PoolEvent},
+//!     Topic as _,
+//! };
+//!
+//! let mut pool = Pool::new(PoolConfig::default());
+//! pool.listen(
+//!     (1..=200).map(|channel_id| {
+//!         pubsub::channel_bits::ChannelBitsEventsV2 { channel_id }.into_topic()
+//!     }),
+//!     Some("authtoken"),
+//! )?;
+//!
+//! let mut opened = vec![];
+//! loop {
+//!     while let Some(action) = pool.poll_action() {
+//!         match action {
+//!             Action::Connect { connection, url } => {
+//!                 /* open a websocket to `url` */
+//!                 opened.push(connection);
+//!             }
+//!             Action::Send {
+//!                 connection,
+//!                 message,
+//!             } => { /* send `message` as a text frame */ }
+//!             Action::Close { connection } => { /* close the websocket */ }
+//!             _ => {}
+//!         }
+//!     }
+//!     // wait until `pool.poll_timeout()` or for a websocket to open, close or receive a message
+//!     # let (connection, message) = (opened[0], r#"{"type":"PONG"}"#);
+//!     // then, for a received message
+//!     match pool.receive(connection, message, Instant::now())? {
+//!         Some(PoolEvent::Message(data)) => println!("{data:?}"),
+//!         Some(PoolEvent::Failed { topics, error, .. }) => {
+//!             eprintln!("could not listen to {topics:?}: {error}")
+//!         }
+//!         _ => {}
+//!     }
+//!     for event in pool.handle_timeout(Instant::now()) {
+//!         println!("{event:?}");
+//!     }
+//! }
+//! # }
+//! ```
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use super::{PubSubError, Response, TopicData, Topics, TwitchResponse};
+
+/// Configuration for a [`Pool`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct PoolConfig {
+    /// Maximum amount of topics on one connection. Defaults to `50`
+    pub max_topics_per_connection: usize,
+    /// Time between `PING`s on a connection. Defaults to 4 minutes
+    pub ping_interval: Duration,
+    /// Maximum random time subtracted from [`ping_interval`](Self::ping_interval) for every `PING`. Defaults to 30 seconds
+    pub ping_jitter: Duration,
+    /// How long to wait for a `PONG` before reconnecting. Defaults to 10 seconds
+    pub pong_timeout: Duration,
+    /// Backoff for the first reconnect, doubled on every failed attempt. Defaults to 1 second
+    pub backoff_initial: Duration,
+    /// Maximum backoff between reconnects. Defaults to 2 minutes
+    pub backoff_max: Duration,
+    /// Seed for jitter and nonces
+    pub seed: u64,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_topics_per_connection: 50,
+            ping_interval: Duration::from_secs(4 * 60),
+            ping_jitter: Duration::from_secs(30),
+            pong_timeout: Duration::from_secs(10),
+            backoff_initial: Duration::from_secs(1),
+            backoff_max: Duration::from_secs(120),
+            seed: crate::now_unix_millis() as u64,
+        }
+    }
+}
+
+impl PoolConfig {
+    /// Set the maximum amount of topics on one connection
+    pub fn max_topics_per_connection(mut self, max: usize) -> Self {
+        self.max_topics_per_connection = max.max(1);
+        self
+    }
+
+    /// Set the time between `PING`s
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval;
+        self
+    }
+
+    /// Set the maximum jitter subtracted from the ping interval
+    pub fn ping_jitter(mut self, jitter: Duration) -> Self {
+        self.ping_jitter = jitter;
+        self
+    }
+
+    /// Set how long to wait for a `PONG`
+    pub fn pong_timeout(mut self, timeout: Duration) -> Self {
+        self.pong_timeout = timeout;
+        self
+    }
+
+    /// Set the initial and maximum backoff between reconnects
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff_initial = initial;
+        self.backoff_max = max;
+        self
+    }
+
+    /// Set the seed for jitter and nonces
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// Identifies a connection in a [`Pool`]
+///
+/// A connection keeps its id across reconnects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConnectionId(u64);
+
+impl std::fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { self.0.fmt(f) }
+}
+
+/// Something the [`Pool`] needs you to do
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Action {
+    /// Open a websocket, and call [`Pool::connected`] once it's open or [`Pool::disconnected`] if it fails
+    Connect {
+        /// The connection to open
+        connection: ConnectionId,
+        /// Where to connect to, [`TWITCH_PUBSUB_URL`](crate::TWITCH_PUBSUB_URL)
+        url: url::Url,
+    },
+    /// Send a text message on a websocket
+    Send {
+        /// The connection to send on
+        connection: ConnectionId,
+        /// The message
+        message: String,
+    },
+    /// Close a websocket. Don't call [`Pool::disconnected`] for this connection
+    Close {
+        /// The connection to close
+        connection: ConnectionId,
+    },
+}
+
+/// A command sent by the [`Pool`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Command {
+    /// `LISTEN`
+    Listen,
+    /// `UNLISTEN`
+    Unlisten,
+}
+
+/// Something that happened in the [`Pool`]
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum PoolEvent {
+    /// A message on a topic
+    Message(TopicData),
+    /// Twitch confirmed a `LISTEN`
+    Listened(Vec<Topics>),
+    /// Twitch confirmed an `UNLISTEN`
+    Unlistened(Vec<Topics>),
+    /// Twitch returned an error for a command. Topics that failed to `LISTEN` are removed from the pool
+    Failed {
+        /// The command that failed
+        command: Command,
+        /// The topics in the command
+        topics: Vec<Topics>,
+        /// The error from Twitch
+        error: PubSubError,
+    },
+    /// A `LISTEN` failed with [`ERR_BADAUTH`](PubSubError::BadAuth) and the [`TokenProvider`] returned a fresh token.
+    /// The topics are listened to again with the new token
+    Reauthenticating {
+        /// The topics that are listened to again
+        topics: Vec<Topics>,
+    },
+    /// A response with a nonce that wasn't sent by the pool
+    UnknownResponse(TwitchResponse),
+    /// A connection is going to reconnect
+    Reconnecting {
+        /// The connection
+        connection: ConnectionId,
+        /// The connection is reconnected at this time
+        at: Instant,
+    },
+}
+
+/// State of a topic in a [`Pool`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TopicState {
+    /// Waiting for the connection to open or for Twitch to confirm the `LISTEN`
+    Pending,
+    /// Twitch confirmed the `LISTEN`
+    Listening,
+}
+
+/// Provides fresh auth tokens to a [`Pool`] when Twitch rejects a token with [`ERR_BADAUTH`](PubSubError::BadAuth)
+///
+/// The provider is called from [`Pool::receive`] and can not wait for a token to be refreshed over the network,
+/// keep a refreshed token at hand instead. Closures taking the same arguments implement this trait.
+pub trait TokenProvider: Send {
+    /// Return a token to replace `stale` for `topics`, or [`None`] to give up on the topics
+    fn fresh_token(&mut self, stale: Option<&str>, topics: &[Topics]) -> Option<String>;
+}
+
+impl<F> TokenProvider for F
+where F: FnMut(Option<&str>, &[Topics]) -> Option<String> + Send
+{
+    fn fresh_token(&mut self, stale: Option<&str>, topics: &[Topics]) -> Option<String> {
+        self(stale, topics)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum State {
+    /// A [`Action::Connect`] was sent
+    Connecting,
+    Open {
+        next_ping: Instant,
+        pong_deadline: Option<Instant>,
+    },
+    Backoff {
+        until: Instant,
+    },
+}
+
+#[derive(Debug)]
+struct Entry {
+    topic: Topics,
+    token: Option<String>,
+    state: TopicState,
+    /// The token was replaced after `ERR_BADAUTH` and the `LISTEN` with it hasn't been confirmed yet
+    refreshed: bool,
+}
+
+#[derive(Debug)]
+struct Shard {
+    topics: Vec<Entry>,
+    state: State,
+    /// Failed connection attempts in a row
+    attempt: u32,
+}
+
+#[derive(Debug)]
+struct Pending {
+    connection: ConnectionId,
+    command: Command,
+    topics: Vec<Topics>,
+}
+
+/// A pool of PubSub connections
+///
+/// See the [module documentation](self) for more.
+pub struct Pool {
+    config: PoolConfig,
+    shards: HashMap<ConnectionId, Shard>,
+    pending: HashMap<String, Pending>,
+    actions: VecDeque<Action>,
+    token_provider: Option<Box<dyn TokenProvider>>,
+    next_id: u64,
+    rng: crate::SplitMix64,
+}
+
+impl std::fmt::Debug for Pool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pool")
+            .field("config", &self.config)
+            .field("shards", &self.shards)
+            .field("pending", &self.pending)
+            .field("actions", &self.actions)
+            .field("token_provider", &self.token_provider.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Pool {
+    /// Create a new, empty, pool
+    pub fn new(config: PoolConfig) -> Self {
+        Self {
+            rng: crate::SplitMix64::new(config.seed),
+            config,
+            shards: HashMap::new(),
+            pending: HashMap::new(),
+            actions: VecDeque::new(),
+            token_provider: None,
+            next_id: 0,
+        }
+    }
+
+    /// Ask `provider` for a fresh token when a `LISTEN` fails with [`ERR_BADAUTH`](PubSubError::BadAuth)
+    ///
+    /// Without a provider, or if the fresh token is rejected as well, the topics are removed from the pool.
+    pub fn with_token_provider(mut self, provider: impl TokenProvider + 'static) -> Self {
+        self.token_provider = Some(Box::new(provider));
+        self
+    }
+
+    /// The configuration of this pool
+    pub const fn config(&self) -> &PoolConfig { &self.config }
+
+    /// All topics in the pool
+    pub fn topics(&self) -> impl Iterator<Item = &Topics> + '_ {
+        self.shards
+            .values()
+            .flat_map(|shard| shard.topics.iter().map(|entry| &entry.topic))
+    }
+
+    /// The state of a topic, or [`None`] if it isn't in the pool
+    pub fn topic_state(&self, topic: &Topics) -> Option<TopicState> {
+        self.shards
+            .values()
+            .flat_map(|shard| &shard.topics)
+            .find(|entry| entry.topic == *topic)
+            .map(|entry| entry.state)
+    }
+
+    /// The topics on a connection
+    pub fn connection_topics(&self, connection: ConnectionId) -> Vec<&Topics> {
+        self.shards
+            .get(&connection)
+            .map(|shard| shard.topics.iter().map(|entry| &entry.topic).collect())
+            .unwrap_or_default()
+    }
+
+    /// All connections in the pool
+    pub fn connections(&self) -> Vec<ConnectionId> {
+        let mut connections = self.shards.keys().copied().collect::<Vec<_>>();
+        connections.sort();
+        connections
+    }
+
+    /// Whether the connection is open, i.e. [`Pool::connected`] was called and it hasn't been closed since
+    pub fn is_open(&self, connection: ConnectionId) -> bool {
+        matches!(
+            self.shards.get(&connection),
+            Some(Shard {
+                state: State::Open { .. },
+                ..
+            })
+        )
+    }
+
+    /// Listen to topics, opening new connections when needed. Topics that are already in the pool are ignored.
+    pub fn listen(
+        &mut self,
+        topics: impl IntoIterator<Item = Topics>,
+        auth_token: Option<&str>,
+    ) -> Result<(), serde_json::Error> {
+        let existing = self.topics().cloned().collect::<HashSet<_>>();
+        let mut added: HashMap<ConnectionId, Vec<Topics>> = HashMap::new();
+        let mut seen = HashSet::new();
+        for topic in topics {
+            if existing.contains(&topic) || !seen.insert(topic.clone()) {
+                continue;
+            }
+            let connection = self.shard_with_room();
+            let shard = self.shards.get_mut(&connection).expect("shard exists");
+            shard.topics.push(Entry {
+                topic: topic.clone(),
+                token: auth_token.map(str::to_owned),
+                state: TopicState::Pending,
+                refreshed: false,
+            });
+            added.entry(connection).or_default().push(topic);
+        }
+
+        let mut added = added.into_iter().collect::<Vec<_>>();
+        added.sort_by_key(|(connection, _)| *connection);
+        for (connection, topics) in added {
+            // topics on connections that aren't open are sent in `connected`
+            if self.is_open(connection) {
+                self.send_command(connection, Command::Listen, topics, auth_token)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop listening to topics. Connections without topics are closed.
+    pub fn unlisten(&mut self, topics: &[Topics]) -> Result<(), serde_json::Error> {
+        let mut connections = self.connections();
+        connections.retain(|connection| {
+            self.shards[connection]
+                .topics
+                .iter()
+                .any(|entry| topics.contains(&entry.topic))
+        });
+        for connection in connections {
+            let shard = self.shards.get_mut(&connection).expect("shard exists");
+            let mut removed = vec![];
+            shard.topics.retain(|entry| {
+                if topics.contains(&entry.topic) {
+                    removed.push(entry.topic.clone());
+                    false
+                } else {
+                    true
+                }
+            });
+            let empty = shard.topics.is_empty();
+            if self.is_open(connection) {
+                self.send_command(connection, Command::Unlisten, removed, None)?;
+            }
+            if empty {
+                self.remove(connection);
+            }
+        }
+        Ok(())
+    }
+
+    /// The next [`Action`] to perform
+    pub fn poll_action(&mut self) -> Option<Action> { self.actions.pop_front() }
+
+    /// When [`Pool::handle_timeout`] should be called next
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.shards
+            .values()
+            .filter_map(|shard| match shard.state {
+                State::Connecting => None,
+                State::Open {
+                    next_ping,
+                    pong_deadline,
+                } => Some(pong_deadline.unwrap_or(next_ping)),
+                State::Backoff { until } => Some(until),
+            })
+            .min()
+    }
+
+    /// Send `PING`s, reconnect connections that didn't get a `PONG` back in time, and open connections after their backoff.
+    ///
+    /// Returns a [`PoolEvent::Reconnecting`] for every connection that timed out.
+    pub fn handle_timeout(&mut self, now: Instant) -> Vec<PoolEvent> {
+        let mut events = vec![];
+        for connection in self.connections() {
+            let shard = self.shards.get_mut(&connection).expect("shard exists");
+            match shard.state {
+                State::Connecting => {}
+                State::Open {
+                    pong_deadline: Some(deadline),
+                    ..
+                } if deadline <= now => {
+                    self.actions.push_back(Action::Close { connection });
+                    events.push(self.schedule_reconnect(connection, now));
+                }
+                State::Open {
+                    next_ping,
+                    pong_deadline: None,
+                } if next_ping <= now => {
+                    shard.state = State::Open {
+                        next_ping,
+                        pong_deadline: Some(now + self.config.pong_timeout),
+                    };
+                    self.actions.push_back(Action::Send {
+                        connection,
+                        message: r#"{"type":"PING"}"#.to_owned(),
+                    });
+                }
+                State::Open { .. } => {}
+                State::Backoff { until } if until <= now => {
+                    shard.state = State::Connecting;
+                    self.actions.push_back(Action::Connect {
+                        connection,
+                        url: crate::TWITCH_PUBSUB_URL.clone(),
+                    });
+                }
+                State::Backoff { .. } => {}
+            }
+        }
+        events
+    }
+
+    /// A connection was opened. Sends `LISTEN` for all its topics.
+    pub fn connected(
+        &mut self,
+        connection: ConnectionId,
+        now: Instant,
+    ) -> Result<(), serde_json::Error> {
+        let next_ping = self.next_ping(now);
+        let Some(shard) = self.shards.get_mut(&connection) else {
+            // the connection was removed while connecting
+            self.actions.push_back(Action::Close { connection });
+            return Ok(());
+        };
+        shard.state = State::Open {
+            next_ping,
+            pong_deadline: None,
+        };
+        let mut by_token: Vec<(Option<String>, Vec<Topics>)> = vec![];
+        for entry in &mut shard.topics {
+            entry.state = TopicState::Pending;
+            match by_token.iter_mut().find(|(t, _)| *t == entry.token) {
+                Some((_, topics)) => topics.push(entry.topic.clone()),
+                None => by_token.push((entry.token.clone(), vec![entry.topic.clone()])),
+            }
+        }
+        for (token, topics) in by_token {
+            self.send_command(connection, Command::Listen, topics, token.as_deref())?;
+        }
+        Ok(())
+    }
+
+    /// A connection was closed or failed to open. It is reconnected after a backoff.
+    pub fn disconnected(&mut self, connection: ConnectionId, now: Instant) -> Option<PoolEvent> {
+        if self.shards.contains_key(&connection) {
+            Some(self.schedule_reconnect(connection, now))
+        } else {
+            None
+        }
+    }
+
+    /// Handle a message received on a connection
+    pub fn receive(
+        &mut self,
+        connection: ConnectionId,
+        message: &str,
+        now: Instant,
+    ) -> Result<Option<PoolEvent>, crate::DeserError> {
+        let response = Response::parse(message)?;
+        if !self.shards.contains_key(&connection) {
+            return Ok(None);
+        }
+        Ok(match response {
+            Response::Message { data } => Some(PoolEvent::Message(data)),
+            Response::Pong => {
+                let next_ping = self.next_ping(now);
+                let shard = self.shards.get_mut(&connection).expect("shard exists");
+                shard.attempt = 0;
+                if let State::Open { .. } = shard.state {
+                    shard.state = State::Open {
+                        next_ping,
+                        pong_deadline: None,
+                    };
+                }
+                None
+            }
+            Response::Reconnect => {
+                self.actions.push_back(Action::Close { connection });
+                Some(self.schedule_reconnect(connection, now))
+            }
+            Response::Response(response) => {
+                let Some(pending) = response
+                    .nonce
+                    .as_ref()
+                    .and_then(|nonce| self.pending.remove(nonce))
+                else {
+                    return Ok(Some(PoolEvent::UnknownResponse(response)));
+                };
+                match response.result() {
+                    Ok(()) => {
+                        if let Some(shard) = self.shards.get_mut(&pending.connection) {
+                            shard.attempt = 0;
+                            for entry in &mut shard.topics {
+                                if pending.command == Command::Listen
+                                    && pending.topics.contains(&entry.topic)
+                                {
+                                    entry.state = TopicState::Listening;
+                                    entry.refreshed = false;
+                                }
+                            }
+                        }
+                        Some(match pending.command {
+                            Command::Listen => PoolEvent::Listened(pending.topics),
+                            Command::Unlisten => PoolEvent::Unlistened(pending.topics),
+                        })
+                    }
+                    Err(PubSubError::BadAuth) if pending.command == Command::Listen => {
+                        Some(self.reauthenticate(pending))
+                    }
+                    Err(error) => {
+                        if pending.command == Command::Listen {
+                            self.forget(&pending.topics);
+                        }
+                        Some(PoolEvent::Failed {
+                            command: pending.command,
+                            topics: pending.topics,
+                            error,
+                        })
+                    }
+                }
+            }
+        })
+    }
+
+    /// Find or open a connection with room for another topic
+    fn shard_with_room(&mut self) -> ConnectionId {
+        let max = self.config.max_topics_per_connection;
+        if let Some(connection) = self
+            .connections()
+            .into_iter()
+            .find(|connection| self.shards[connection].topics.len() < max)
+        {
+            return connection;
+        }
+        let connection = ConnectionId(self.next_id);
+        self.next_id += 1;
+        self.shards.insert(
+            connection,
+            Shard {
+                topics: vec![],
+                state: State::Connecting,
+                attempt: 0,
+            },
+        );
+        self.actions.push_back(Action::Connect {
+            connection,
+            url: crate::TWITCH_PUBSUB_URL.clone(),
+        });
+        connection
+    }
+
+    fn send_command(
+        &mut self,
+        connection: ConnectionId,
+        command: Command,
+        topics: Vec<Topics>,
+        auth_token: Option<&str>,
+    ) -> Result<(), serde_json::Error> {
+        if topics.is_empty() {
+            return Ok(());
+        }
+        let nonce = format!("{:016x}", self.rng.next_u64());
+        let message = match command {
+            Command::Listen => super::listen_command(&topics, auth_token, nonce.as_str())?,
+            Command::Unlisten => super::unlisten_command(&topics, nonce.as_str())?,
+        };
+        self.pending.insert(
+            nonce,
+            Pending {
+                connection,
+                command,
+                topics,
+            },
+        );
+        self.actions.push_back(Action::Send {
+            connection,
+            message,
+        });
+        Ok(())
+    }
+
+    /// Get a fresh token for topics that failed with `ERR_BADAUTH` and listen to them again, or forget them
+    fn reauthenticate(&mut self, pending: Pending) -> PoolEvent {
+        let entries = || {
+            self.shards
+                .values()
+                .flat_map(|shard| &shard.topics)
+                .filter(|entry| pending.topics.contains(&entry.topic))
+        };
+        // the tokens the failed topics were listened to with, a `LISTEN` has one token for all its topics
+        let mut stale: Vec<String> = vec![];
+        for token in entries().filter_map(|entry| entry.token.as_ref()) {
+            if !stale.contains(token) {
+                stale.push(token.clone());
+            }
+        }
+        let refreshed = entries().any(|entry| entry.refreshed);
+        let token = match &mut self.token_provider {
+            Some(provider) if !refreshed => {
+                provider.fresh_token(stale.first().map(String::as_str), &pending.topics)
+            }
+            _ => None,
+        };
+        let Some(token) = token else {
+            self.forget(&pending.topics);
+            return PoolEvent::Failed {
+                command: pending.command,
+                topics: pending.topics,
+                error: PubSubError::BadAuth,
+            };
+        };
+
+        for entry in self.shards.values_mut().flat_map(|shard| &mut shard.topics) {
+            let failed = pending.topics.contains(&entry.topic);
+            // other topics with a stale token use the fresh one from their next `LISTEN` on,
+            // topics without a token keep listening without one
+            let has_stale_token = entry
+                .token
+                .as_ref()
+                .is_some_and(|token| stale.contains(token));
+            if failed || has_stale_token {
+                entry.token = Some(token.clone());
+            }
+            if failed {
+                entry.state = TopicState::Pending;
+                entry.refreshed = true;
+            }
+        }
+        // topics on connections that aren't open are sent in `connected`
+        if self.is_open(pending.connection) {
+            self.send_command(
+                pending.connection,
+                Command::Listen,
+                pending.topics.clone(),
+                Some(&token),
+            )
+            .expect("a LISTEN command can always be serialized");
+        }
+        PoolEvent::Reauthenticating {
+            topics: pending.topics,
+        }
+    }
+
+    fn schedule_reconnect(&mut self, connection: ConnectionId, now: Instant) -> PoolEvent {
+        self.pending
+            .retain(|_, pending| pending.connection != connection);
+        let shard = self.shards.get_mut(&connection).expect("shard exists");
+        for entry in &mut shard.topics {
+            entry.state = TopicState::Pending;
+        }
+        let backoff = self
+            .config
+            .backoff_initial
+            .saturating_mul(2u32.saturating_pow(shard.attempt))
+            .min(self.config.backoff_max);
+        shard.attempt = shard.attempt.saturating_add(1);
+        let at = now + backoff;
+        shard.state = State::Backoff { until: at };
+        PoolEvent::Reconnecting { connection, at }
+    }
+
+    /// Remove topics that failed to listen
+    fn forget(&mut self, topics: &[Topics]) {
+        let mut empty = vec![];
+        for (connection, shard) in &mut self.shards {
+            shard.topics.retain(|entry| !topics.contains(&entry.topic));
+            if shard.topics.is_empty() {
+                empty.push(*connection);
+            }
+        }
+        for connection in empty {
+            self.remove(connection);
+        }
+    }
+
+    fn remove(&mut self, connection: ConnectionId) {
+        if let Some(shard) = self.shards.remove(&connection) {
+            if !matches!(shard.state, State::Backoff { .. }) {
+                self.actions.push_back(Action::Close { connection });
+            }
+        }
+    }
+
+    fn next_ping(&mut self, now: Instant) -> Instant {
+        let jitter = self.config.ping_jitter.as_millis() as u64;
+        let jitter = if jitter == 0 {
+            0
+        } else {
+            self.rng.next_u64() % jitter
+        };
+        now + self
+            .config
+            .ping_interval
+            .saturating_sub(Duration::from_millis(jitter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::{channel_bits::ChannelBitsEventsV2, Topic as _};
+
+    fn topic(channel_id: u32) -> Topics { ChannelBitsEventsV2 { channel_id }.into_topic() }
+
+    fn actions(pool: &mut Pool) -> Vec<Action> {
+        std::iter::from_fn(|| pool.poll_action()).collect()
+    }
+
+    fn nonce(message: &str) -> String {
+        let value: serde_json::Value = serde_json::from_str(message).unwrap();
+        value["nonce"].as_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn shards_and_listens() {
+        let now = Instant::now();
+        let mut pool = Pool::new(PoolConfig::default().seed(1));
+        pool.listen((0..120).map(topic), Some("token")).unwrap();
+        let connections = pool.connections();
+        assert_eq!(connections.len(), 3);
+        assert_eq!(pool.connection_topics(connections[0]).len(), 50);
+        assert_eq!(pool.connection_topics(connections[2]).len(), 20);
+        assert!(actions(&mut pool)
+            .iter()
+            .all(|action| matches!(action, Action::Connect { .. })));
+
+        // duplicates are ignored
+        pool.listen([topic(0)], Some("token")).unwrap();
+        assert_eq!(pool.topics().count(), 120);
+
+        pool.connected(connections[2], now).unwrap();
+        let actions = actions(&mut pool);
+        let [Action::Send { message, .. }] = actions.as_slice() else {
+            panic!("expected one LISTEN, got {actions:?}")
+        };
+        assert!(message.contains(r#""type":"LISTEN""#));
+        assert!(message.contains(r#""auth_token":"token""#));
+
+        let response = format!(
+            r#"{{"type":"RESPONSE","nonce":"{}","error":""}}"#,
+            nonce(message)
+        );
+        let Some(PoolEvent::Listened(topics)) =
+            pool.receive(connections[2], &response, now).unwrap()
+        else {
+            panic!("expected listened")
+        };
+        assert_eq!(topics.len(), 20);
+
+        // a new topic goes on a connection with room, and is listened to right away
+        pool.listen([topic(1000)], None).unwrap();
+        assert_eq!(pool.connection_topics(connections[2]).len(), 21);
+        let actions = actions(&mut pool);
+        let [Action::Send { message, .. }] = actions.as_slice() else {
+            panic!("expected one LISTEN, got {actions:?}")
+        };
+        let response = format!(
+            r#"{{"type":"RESPONSE","nonce":"{}","error":"ERR_BADAUTH"}}"#,
+            nonce(message)
+        );
+        assert_eq!(
+            pool.receive(connections[2], &response, now).unwrap(),
+            Some(PoolEvent::Failed {
+                command: Command::Listen,
+                topics: vec![topic(1000)],
+                error: PubSubError::BadAuth,
+            })
+        );
+        assert_eq!(pool.connection_topics(connections[2]).len(), 20);
+
+        pool.unlisten(&(100..120).map(topic).collect::<Vec<_>>())
+            .unwrap();
+        let actions = actions(&mut pool);
+        assert!(
+            matches!(actions[0], Action::Send { ref message, .. } if message.contains("UNLISTEN"))
+        );
+        assert_eq!(
+            actions[1],
+            Action::Close {
+                connection: connections[2]
+            }
+        );
+        assert_eq!(pool.connections().len(), 2);
+    }
+
+    #[test]
+    fn refresh_token_on_bad_auth() {
+        let now = Instant::now();
+        let mut pool = Pool::new(PoolConfig::default().seed(3)).with_token_provider(
+            |stale: Option<&str>, topics: &[Topics]| {
+                assert_eq!(stale, Some("old"));
+                assert_eq!(topics, [topic(2)]);
+                Some("new".to_owned())
+            },
+        );
+        pool.listen([topic(1)], Some("other")).unwrap();
+        pool.listen([topic(2)], Some("old")).unwrap();
+        let connection = pool.connections()[0];
+        assert_eq!(pool.topic_state(&topic(2)), Some(TopicState::Pending));
+        pool.connected(connection, now).unwrap();
+        let sent = actions(&mut pool);
+        let Some(Action::Send { message, .. }) = sent.iter().find(
+            |action| matches!(action, Action::Send { message, .. } if message.contains("old")),
+        ) else {
+            panic!("expected a LISTEN with the old token, got {sent:?}")
+        };
+        let response = format!(
+            r#"{{"type":"RESPONSE","nonce":"{}","error":"ERR_BADAUTH"}}"#,
+            nonce(message)
+        );
+        assert_eq!(
+            pool.receive(connection, &response, now).unwrap(),
+            Some(PoolEvent::Reauthenticating {
+                topics: vec![topic(2)]
+            })
+        );
+
+        // only the failed topic is listened to again, with the fresh token
+        let resent = actions(&mut pool);
+        let [Action::Send { message, .. }] = resent.as_slice() else {
+            panic!("expected one LISTEN, got {resent:?}")
+        };
+        assert!(message.contains(r#""auth_token":"new""#));
+        assert!(!message.contains(r#""channel-bits-events-v2.1""#));
+        assert_eq!(pool.topic_state(&topic(2)), Some(TopicState::Pending));
+
+        // the fresh token is rejected as well, give up
+        let response = format!(
+            r#"{{"type":"RESPONSE","nonce":"{}","error":"ERR_BADAUTH"}}"#,
+            nonce(message)
+        );
+        assert_eq!(
+            pool.receive(connection, &response, now).unwrap(),
+            Some(PoolEvent::Failed {
+                command: Command::Listen,
+                topics: vec![topic(2)],
+                error: PubSubError::BadAuth,
+            })
+        );
+        assert_eq!(pool.topic_state(&topic(2)), None);
+        assert_eq!(pool.topic_state(&topic(1)), Some(TopicState::Pending));
+    }
+
+    #[test]
+    fn refresh_only_stale_tokens() {
+        let now = Instant::now();
+        let mut pool = Pool::new(PoolConfig::default().seed(4)).with_token_provider(
+            |stale: Option<&str>, topics: &[Topics]| {
+                assert_eq!(stale, Some("old"));
+                assert_eq!(topics, [topic(2)]);
+                Some("new".to_owned())
+            },
+        );
+        pool.listen([topic(1)], None).unwrap();
+        pool.listen([topic(2)], Some("old")).unwrap();
+        pool.listen([topic(4)], Some("other")).unwrap();
+        let connection = pool.connections()[0];
+        pool.connected(connection, now).unwrap();
+        pool.listen([topic(3)], Some("old")).unwrap();
+        let sent = actions(&mut pool);
+        let Some(Action::Send { message, .. }) = sent.iter().find(|action| match action {
+            Action::Send { message, .. } => message.contains(r#""channel-bits-events-v2.2""#),
+            _ => false,
+        }) else {
+            panic!("expected a LISTEN for topic 2, got {sent:?}")
+        };
+        let response = format!(
+            r#"{{"type":"RESPONSE","nonce":"{}","error":"ERR_BADAUTH"}}"#,
+            nonce(message)
+        );
+        assert_eq!(
+            pool.receive(connection, &response, now).unwrap(),
+            Some(PoolEvent::Reauthenticating {
+                topics: vec![topic(2)]
+            })
+        );
+        actions(&mut pool);
+
+        // after a reconnect, only the topics with the stale token use the fresh one
+        pool.disconnected(connection, now);
+        actions(&mut pool);
+        pool.connected(connection, now).unwrap();
+        let sent = actions(&mut pool);
+        let listen_for = |channel_id: u32| {
+            let topic = format!(r#""channel-bits-events-v2.{channel_id}""#);
+            sent.iter()
+                .find_map(|action| match action {
+                    Action::Send { message, .. } if message.contains(&topic) => {
+                        Some(message.as_str())
+                    }
+                    _ => None,
+                })
+                .unwrap_or_else(|| panic!("expected a LISTEN for topic {channel_id}, got {sent:?}"))
+        };
+        assert!(listen_for(2).contains(r#""auth_token":"new""#));
+        assert!(listen_for(3).contains(r#""auth_token":"new""#));
+        assert!(listen_for(4).contains(r#""auth_token":"other""#));
+        assert!(!listen_for(1).contains(r#""auth_token":""#));
+    }
+
+    #[test]
+    fn ping_pong_and_reconnect() {
+        let now = Instant::now();
+        let config = PoolConfig::default().seed(2);
+        let mut pool = Pool::new(config.clone());
+        pool.listen([topic(1)], Some("token")).unwrap();
+        let connection = pool.connections()[0];
+        pool.connected(connection, now).unwrap();
+        actions(&mut pool);
+
+        let ping_at = pool.poll_timeout().unwrap();
+        assert!(ping_at <= now + config.ping_interval);
+        assert!(ping_at >= now + config.ping_interval - config.ping_jitter);
+        pool.handle_timeout(ping_at);
+        assert_eq!(
+            actions(&mut pool),
+            vec![Action::Send {
+                connection,
+                message: r#"{"type":"PING"}"#.to_owned()
+            }]
+        );
+        assert_eq!(pool.poll_timeout(), Some(ping_at + config.pong_timeout));
+
+        // pong in time
+        pool.receive(connection, r#"{"type":"PONG"}"#, ping_at)
+            .unwrap();
+        let ping_at = pool.poll_timeout().unwrap();
+        pool.handle_timeout(ping_at);
+        actions(&mut pool);
+
+        // no pong, reconnect after the initial backoff
+        let reconnect_at = ping_at + config.pong_timeout + config.backoff_initial;
+        assert_eq!(
+            pool.handle_timeout(ping_at + config.pong_timeout),
+            vec![PoolEvent::Reconnecting {
+                connection,
+                at: reconnect_at
+            }]
+        );
+        assert_eq!(actions(&mut pool), vec![Action::Close { connection }]);
+        assert!(!pool.is_open(connection));
+        assert_eq!(pool.poll_timeout(), Some(reconnect_at));
+        pool.handle_timeout(reconnect_at);
+        assert!(matches!(
+            actions(&mut pool).as_slice(),
+            [Action::Connect { connection: c, .. }] if *c == connection
+        ));
+
+        // failed to connect, backoff doubles
+        assert_eq!(
+            pool.disconnected(connection, reconnect_at),
+            Some(PoolEvent::Reconnecting {
+                connection,
+                at: reconnect_at + config.backoff_initial * 2
+            })
+        );
+        pool.handle_timeout(reconnect_at + config.backoff_initial * 2);
+        actions(&mut pool);
+
+        // reconnected, topics are listened again
+        pool.connected(connection, reconnect_at).unwrap();
+        assert!(matches!(
+            actions(&mut pool).as_slice(),
+            [Action::Send { message, .. }] if message.contains("LISTEN")
+        ));
+
+        // twitch asks to reconnect
+        assert!(matches!(
+            pool.receive(connection, r#"{"type":"RECONNECT"}"#, now)
+                .unwrap(),
+            Some(PoolEvent::Reconnecting { .. })
+        ));
+        assert_eq!(actions(&mut pool), vec![Action::Close { connection }]);
+    }
+}
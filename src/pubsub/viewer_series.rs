@@ -0,0 +1,657 @@
+//! Viewer count time series from [`VideoPlaybackById`](super::video_playback::VideoPlaybackById)
+//!
+//! [`ViewerSeries`] folds [`VideoPlaybackReply`] messages into per-channel series:
+//!
+//! * view count samples,
+//! * uptime segments, from `stream-up` to `stream-down`,
+//! * ad breaks and TOS strikes,
+//! * gaps, where samples are missing while the stream was live, e.g. during a reconnect.
+//!
+//! All times are the server time sent by Twitch, in seconds since the Unix epoch.
+//!
+//! If collection starts while a stream is live, the segment starts at the first sample.
+//! With feature `helix`, use `ViewerSeries::backfill_stream` with a stream from
+//! [Get Streams](https://dev.twitch.tv/docs/api/reference#get-streams) to set the real start.
+//!
+//! Series can be [downsampled](ChannelSeries::downsample) and exported as [CSV](ViewerSeries::write_csv) or [JSON lines](ViewerSeries::write_json_lines).
+//!
+//! # Examples
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+//! use twitch_api::pubsub::{viewer_series::ViewerSeries, Response};
+//!
+//! let mut series = ViewerSeries::new();
+//! for message in [
+//!     r#"{\"type\":\"stream-up\",\"server_time\":1603291436,\"play_delay\":0}"#,
+//!     r#"{\"type\":\"viewcount\",\"server_time\":1603291466.5,\"viewers\":2}"#,
+//!     r#"{\"type\":\"viewcount\",\"server_time\":1603291496.5,\"viewers\":5}"#,
+//! ] {
+//!     let source = format!(
+//!         r#"{{"type":"MESSAGE","data":{{"topic":"video-playback-by-id.1234","message":"{message}"}}}}"#
+//!     );
+//!     if let Response::Message { data } = Response::parse(&source)? {
+//!         series.apply(&data);
+//!     }
+//! }
+//! let channel = series.channel("1234").unwrap();
+//! assert_eq!(channel.samples.len(), 2);
+//! assert!(channel.is_live());
+//!
+//! let mut csv = vec![];
+//! series.write_csv(&mut csv)?;
+//! assert!(String::from_utf8(csv)?.starts_with("channel_id,server_time,kind,value\n1234,1603291436,stream_up,0\n"));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{collections::BTreeMap, io, time::SystemTime};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    pubsub::{video_playback::VideoPlaybackReply, TopicData},
+    types,
+};
+
+/// Default interval without samples after which a live stream is considered to have a gap, in seconds
+///
+/// Twitch sends view counts about every 30 seconds.
+pub const DEFAULT_GAP_THRESHOLD: f64 = 90.0;
+
+/// Viewer count time series for all channels, see the [module documentation](self)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ViewerSeries {
+    channels: BTreeMap<types::UserId, ChannelSeries>,
+    gap_threshold: f64,
+}
+
+impl Default for ViewerSeries {
+    fn default() -> Self { Self::new() }
+}
+
+/// Time series for one channel
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ChannelSeries {
+    /// The channel
+    pub channel_id: types::UserId,
+    /// View count samples, oldest first
+    pub samples: Vec<Sample>,
+    /// Uptime segments, oldest first
+    pub segments: Vec<Segment>,
+    /// Ad breaks, oldest first
+    pub ad_breaks: Vec<AdBreak>,
+    /// Times of TOS strikes
+    pub tos_strikes: Vec<f64>,
+    /// Periods without samples while live, oldest first
+    pub gaps: Vec<Gap>,
+}
+
+/// A view count sample
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Sample {
+    /// Server time
+    pub server_time: f64,
+    /// Current viewers
+    pub viewers: i64,
+}
+
+/// A period where the stream was live
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Segment {
+    /// Server time the stream went live
+    pub start: f64,
+    /// Server time the stream went offline, `None` if still live
+    pub end: Option<f64>,
+    /// Delay as set in broadcaster settings, only known from `stream-up`
+    pub play_delay: Option<i64>,
+    /// Whether the start is known, `false` if the segment was started from the first sample after collection began
+    pub start_known: bool,
+}
+
+impl Segment {
+    /// Length of the segment in seconds, up to `now` if still live
+    pub fn duration(&self, now: f64) -> f64 { (self.end.unwrap_or(now) - self.start).max(0.0) }
+}
+
+/// An ad break
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct AdBreak {
+    /// Server time the commercial started
+    pub server_time: f64,
+    /// Length of the commercial in seconds
+    pub length: i64,
+    /// Whether the commercial was scheduled, if known
+    pub scheduled: Option<bool>,
+}
+
+/// A period without samples while the stream was live
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Gap {
+    /// Time of the last sample or disconnect before the gap
+    pub start: f64,
+    /// Time of the first sample or reconnect after the gap
+    pub end: f64,
+}
+
+/// A downsampled bucket, see [`ChannelSeries::downsample`]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Bucket {
+    /// Start of the bucket
+    pub start: f64,
+    /// Number of samples in the bucket
+    pub count: usize,
+    /// Fewest viewers
+    pub min: i64,
+    /// Most viewers
+    pub max: i64,
+    /// Average viewers
+    pub mean: f64,
+    /// Viewers at the last sample
+    pub last: i64,
+}
+
+/// A single entry in an export, see [`ViewerSeries::entries`]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Entry {
+    /// The channel
+    pub channel_id: types::UserId,
+    /// Server time
+    pub server_time: f64,
+    /// What happened
+    #[serde(flatten)]
+    pub kind: EntryKind,
+}
+
+/// What happened in an [`Entry`]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum EntryKind {
+    /// A view count sample
+    ViewCount {
+        /// Current viewers
+        viewers: i64,
+    },
+    /// The stream went live
+    StreamUp {
+        /// Delay as set in broadcaster settings
+        play_delay: Option<i64>,
+    },
+    /// The stream went offline
+    StreamDown,
+    /// A commercial started
+    Commercial {
+        /// Length of the commercial in seconds
+        length: i64,
+    },
+    /// The channel was hit by a TOS strike
+    TosStrike,
+    /// A gap started
+    GapStart,
+    /// A gap ended
+    GapEnd,
+}
+
+impl EntryKind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::ViewCount { .. } => "view_count",
+            Self::StreamUp { .. } => "stream_up",
+            Self::StreamDown => "stream_down",
+            Self::Commercial { .. } => "commercial",
+            Self::TosStrike => "tos_strike",
+            Self::GapStart => "gap_start",
+            Self::GapEnd => "gap_end",
+        }
+    }
+
+    fn value(&self) -> Option<i64> {
+        match self {
+            Self::ViewCount { viewers } => Some(*viewers),
+            Self::StreamUp { play_delay } => *play_delay,
+            Self::Commercial { length } => Some(*length),
+            _ => None,
+        }
+    }
+}
+
+impl ViewerSeries {
+    /// Create an empty collector with a gap threshold of [`DEFAULT_GAP_THRESHOLD`]
+    pub fn new() -> Self {
+        Self {
+            channels: BTreeMap::new(),
+            gap_threshold: DEFAULT_GAP_THRESHOLD,
+        }
+    }
+
+    /// Set the interval in seconds without samples after which a live stream is considered to have a gap
+    pub fn with_gap_threshold(mut self, seconds: f64) -> Self {
+        self.gap_threshold = seconds;
+        self
+    }
+
+    /// Apply a topic message. Messages from other topics are ignored.
+    pub fn apply(&mut self, data: &TopicData) -> bool {
+        match data {
+            TopicData::VideoPlaybackById { topic, reply } => {
+                self.apply_reply(&types::UserId::new(topic.channel_id.to_string()), reply)
+            }
+            _ => false,
+        }
+    }
+
+    /// Apply a [`VideoPlaybackReply`] for a channel. Returns `false` if the reply isn't part of the series.
+    pub fn apply_reply(
+        &mut self,
+        channel_id: &types::UserIdRef,
+        reply: &VideoPlaybackReply,
+    ) -> bool {
+        let gap_threshold = self.gap_threshold;
+        let channel = self.channel_mut(channel_id);
+        match *reply {
+            VideoPlaybackReply::ViewCount {
+                server_time,
+                viewers,
+            } => {
+                if channel.is_live() {
+                    if let Some(last) = channel.samples.last() {
+                        let covered = channel
+                            .gaps
+                            .last()
+                            .map_or(false, |g| g.end >= last.server_time);
+                        if server_time - last.server_time > gap_threshold && !covered {
+                            channel.gaps.push(Gap {
+                                start: last.server_time,
+                                end: server_time,
+                            });
+                        }
+                    }
+                } else {
+                    // view counts are only sent while live, so the stream went live before collection started
+                    channel.segments.push(Segment {
+                        start: server_time,
+                        end: None,
+                        play_delay: None,
+                        start_known: false,
+                    });
+                }
+                channel.samples.push(Sample {
+                    server_time,
+                    viewers,
+                });
+            }
+            VideoPlaybackReply::StreamUp {
+                server_time,
+                play_delay,
+            } => {
+                // a missed stream-down, end the segment at the last thing we saw
+                let last = channel.samples.last().map(|s| s.server_time);
+                if let Some(segment) = channel.open_segment() {
+                    segment.end = Some(last.unwrap_or(segment.start).max(segment.start));
+                }
+                channel.segments.push(Segment {
+                    start: server_time,
+                    end: None,
+                    play_delay: Some(play_delay),
+                    start_known: true,
+                });
+            }
+            VideoPlaybackReply::StreamDown { server_time } => match channel.open_segment() {
+                Some(segment) => segment.end = Some(server_time),
+                None => return false,
+            },
+            VideoPlaybackReply::Commercial {
+                server_time,
+                length,
+                scheduled,
+            } => channel.ad_breaks.push(AdBreak {
+                server_time,
+                length,
+                scheduled,
+            }),
+            VideoPlaybackReply::TosStrike { server_time } => channel.tos_strikes.push(server_time),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Record that the connection was lost between `disconnected` and `reconnected`
+    ///
+    /// A gap is added to every channel that was live, as messages sent in between were missed.
+    pub fn reconnected(&mut self, disconnected: SystemTime, reconnected: SystemTime) {
+        let epoch = |time: SystemTime| {
+            time.duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0.0, |d| d.as_secs_f64())
+        };
+        let (start, end) = (epoch(disconnected), epoch(reconnected));
+        for channel in self.channels.values_mut() {
+            if channel.open_segment().is_some() {
+                channel.gaps.push(Gap { start, end });
+            }
+        }
+    }
+
+    /// Set the start of the current segment from [Get Streams](crate::helix::streams::get_streams)
+    ///
+    /// If there's no live segment, one is started. Returns `false` if `started_at` couldn't be parsed.
+    #[cfg(feature = "helix")]
+    #[cfg_attr(nightly, doc(cfg(feature = "helix")))]
+    pub fn backfill_stream(&mut self, stream: &crate::helix::streams::Stream) -> bool {
+        let Some(start) = crate::timestamp_to_unix_millis(stream.started_at.as_str()) else {
+            return false;
+        };
+        let start = start as f64 / 1_000.0;
+        let channel = self.channel_mut(&stream.user_id);
+        if let Some(segment) = channel.open_segment() {
+            if !segment.start_known {
+                segment.start = segment.start.min(start);
+                segment.start_known = true;
+            }
+        } else {
+            channel.segments.push(Segment {
+                start,
+                end: None,
+                play_delay: None,
+                start_known: true,
+            });
+        }
+        true
+    }
+
+    /// The series for a channel
+    pub fn channel<'a>(
+        &self,
+        channel_id: impl Into<&'a types::UserIdRef>,
+    ) -> Option<&ChannelSeries> {
+        self.channels.get(channel_id.into())
+    }
+
+    /// All channels
+    pub fn channels(&self) -> impl Iterator<Item = &ChannelSeries> + '_ { self.channels.values() }
+
+    /// All entries of all channels, ordered by channel and then time
+    pub fn entries(&self) -> impl Iterator<Item = Entry> + '_ {
+        self.channels.values().flat_map(ChannelSeries::entries)
+    }
+
+    /// Write all entries as CSV, with columns `channel_id,server_time,kind,value`
+    ///
+    /// `value` is the viewers for `view_count`, the play delay for `stream_up` and the length for `commercial`.
+    pub fn write_csv(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writeln!(writer, "channel_id,server_time,kind,value")?;
+        for entry in self.entries() {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                entry.channel_id,
+                entry.server_time,
+                entry.kind.name(),
+                entry
+                    .kind
+                    .value()
+                    .map(|v| v.to_string())
+                    .unwrap_or_default()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write all entries as JSON lines
+    pub fn write_json_lines(&self, mut writer: impl io::Write) -> io::Result<()> {
+        for entry in self.entries() {
+            serde_json::to_writer(&mut writer, &entry)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn channel_mut(&mut self, channel_id: &types::UserIdRef) -> &mut ChannelSeries {
+        self.channels
+            .entry(channel_id.to_owned())
+            .or_insert_with(|| ChannelSeries {
+                channel_id: channel_id.to_owned(),
+                samples: vec![],
+                segments: vec![],
+                ad_breaks: vec![],
+                tos_strikes: vec![],
+                gaps: vec![],
+            })
+    }
+}
+
+impl ChannelSeries {
+    /// Whether the channel is live
+    pub fn is_live(&self) -> bool { self.segments.last().map_or(false, |s| s.end.is_none()) }
+
+    /// The current segment, if live
+    pub fn current_segment(&self) -> Option<&Segment> {
+        self.segments.last().filter(|s| s.end.is_none())
+    }
+
+    /// Samples within `start..end`
+    pub fn samples_between(&self, start: f64, end: f64) -> &[Sample] {
+        let from = self.samples.partition_point(|s| s.server_time < start);
+        let to = self.samples.partition_point(|s| s.server_time < end);
+        &self.samples[from..to.max(from)]
+    }
+
+    /// Downsample into buckets of `interval` seconds, aligned to the Unix epoch
+    ///
+    /// Buckets without samples are left out, an `interval` that isn't a positive finite number gives no buckets.
+    pub fn downsample(&self, interval: f64) -> Vec<Bucket> {
+        if !(interval > 0.0 && interval.is_finite()) {
+            return vec![];
+        }
+        let mut buckets: Vec<(Bucket, i64)> = vec![];
+        for sample in &self.samples {
+            let start = (sample.server_time / interval).floor() * interval;
+            match buckets.last_mut() {
+                Some((bucket, sum)) if bucket.start == start => {
+                    bucket.count += 1;
+                    bucket.min = bucket.min.min(sample.viewers);
+                    bucket.max = bucket.max.max(sample.viewers);
+                    bucket.last = sample.viewers;
+                    *sum += sample.viewers;
+                }
+                _ => buckets.push((
+                    Bucket {
+                        start,
+                        count: 1,
+                        min: sample.viewers,
+                        max: sample.viewers,
+                        mean: 0.0,
+                        last: sample.viewers,
+                    },
+                    sample.viewers,
+                )),
+            }
+        }
+        buckets
+            .into_iter()
+            .map(|(mut bucket, sum)| {
+                bucket.mean = sum as f64 / bucket.count as f64;
+                bucket
+            })
+            .collect()
+    }
+
+    /// All entries, ordered by time
+    pub fn entries(&self) -> Vec<Entry> {
+        let entry = |server_time, kind| Entry {
+            channel_id: self.channel_id.clone(),
+            server_time,
+            kind,
+        };
+        let mut entries = vec![];
+        for segment in &self.segments {
+            if segment.start_known {
+                entries.push(entry(
+                    segment.start,
+                    EntryKind::StreamUp {
+                        play_delay: segment.play_delay,
+                    },
+                ));
+            }
+            if let Some(end) = segment.end {
+                entries.push(entry(end, EntryKind::StreamDown));
+            }
+        }
+        entries.extend(
+            self.samples
+                .iter()
+                .map(|s| entry(s.server_time, EntryKind::ViewCount { viewers: s.viewers })),
+        );
+        entries.extend(
+            self.ad_breaks
+                .iter()
+                .map(|a| entry(a.server_time, EntryKind::Commercial { length: a.length })),
+        );
+        entries.extend(
+            self.tos_strikes
+                .iter()
+                .map(|&t| entry(t, EntryKind::TosStrike)),
+        );
+        for gap in &self.gaps {
+            entries.push(entry(gap.start, EntryKind::GapStart));
+            entries.push(entry(gap.end, EntryKind::GapEnd));
+        }
+        // stable, so a segment ending and the next starting at the same time keep their order
+        entries.sort_by(|a, b| a.server_time.total_cmp(&b.server_time));
+        entries
+    }
+
+    fn open_segment(&mut self) -> Option<&mut Segment> {
+        self.segments.last_mut().filter(|s| s.end.is_none())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(reply: serde_json::Value) -> VideoPlaybackReply {
+        serde_json::from_value(reply).unwrap()
+    }
+
+    fn viewcount(server_time: f64, viewers: i64) -> VideoPlaybackReply {
+        reply(
+            serde_json::json!({"type": "viewcount", "server_time": server_time, "viewers": viewers}),
+        )
+    }
+
+    #[test]
+    fn series() {
+        let mut series = ViewerSeries::new();
+        let id = types::UserIdRef::from_static("1234");
+
+        // collection starts mid-stream
+        series.apply_reply(id, &viewcount(1000.0, 10));
+        series.apply_reply(id, &viewcount(1030.0, 20));
+        // missed samples
+        series.apply_reply(id, &viewcount(1200.0, 30));
+        series.apply_reply(
+            id,
+            &reply(serde_json::json!({"type": "commercial", "server_time": 1210.0, "length": 90})),
+        );
+        series.apply_reply(id, &viewcount(1230.0, 40));
+        series.apply_reply(
+            id,
+            &reply(serde_json::json!({"type": "stream-down", "server_time": 1250.0})),
+        );
+        series.apply_reply(
+            id,
+            &reply(
+                serde_json::json!({"type": "stream-up", "server_time": 2000.0, "play_delay": 0}),
+            ),
+        );
+
+        let channel = series.channel(id).unwrap();
+        assert_eq!(channel.segments.len(), 2);
+        assert!(!channel.segments[0].start_known);
+        assert_eq!(channel.segments[0].duration(0.0), 250.0);
+        assert!(channel.is_live());
+        assert_eq!(
+            channel.gaps,
+            [Gap {
+                start: 1030.0,
+                end: 1200.0
+            }]
+        );
+        assert_eq!(channel.samples_between(1030.0, 1230.0).len(), 2);
+
+        let buckets = channel.downsample(60.0);
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].start, 960.0);
+        assert_eq!(buckets[0].mean, 15.0);
+        assert_eq!(
+            (buckets[1].min, buckets[1].max, buckets[1].count),
+            (30, 40, 2)
+        );
+        assert!(channel.downsample(0.0).is_empty());
+        assert!(channel.downsample(f64::NAN).is_empty());
+
+        let mut csv = vec![];
+        series.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[1], "1234,1000,view_count,10");
+        assert_eq!(lines[3], "1234,1030,gap_start,");
+        assert_eq!(lines[lines.len() - 1], "1234,2000,stream_up,0");
+
+        let mut json = vec![];
+        series.write_json_lines(&mut json).unwrap();
+        let first: serde_json::Value =
+            serde_json::from_slice(json.split(|&b| b == b'\n').next().unwrap()).unwrap();
+        assert_eq!(
+            first,
+            serde_json::json!({"channel_id": "1234", "server_time": 1000.0, "kind": "view_count", "viewers": 10})
+        );
+    }
+
+    #[cfg(feature = "helix")]
+    #[test]
+    fn backfill() {
+        let mut series = ViewerSeries::new();
+        let id = types::UserIdRef::from_static("98765");
+        series.apply_reply(id, &viewcount(1615388761.0, 10));
+
+        let stream = |user_id: &str, started_at: &str| -> crate::helix::streams::Stream {
+            serde_json::from_value(serde_json::json!({
+                "id": "40952121085",
+                "user_id": user_id,
+                "user_login": "sandysanderman",
+                "user_name": "sandysanderman",
+                "game_id": "494131",
+                "game_name": "Little Nightmares",
+                "type": "live",
+                "title": "hablamos y le damos a Little Nightmares 1",
+                "tags": ["Español"],
+                "viewer_count": 78365,
+                "started_at": started_at,
+                "language": "es",
+                "thumbnail_url": "https://static-cdn.jtvnw.net/previews-ttv/live_user_auronplay-{width}x{height}.jpg",
+                "tag_ids": [],
+                "is_mature": false
+            }))
+            .unwrap()
+        };
+        assert!(series.backfill_stream(&stream("98765", "2021-03-10T15:04:21Z")));
+
+        let segment = series.channel(id).unwrap().current_segment().unwrap();
+        assert!(segment.start_known);
+        assert_eq!(segment.start, 1615388661.0);
+
+        // fractional seconds are kept, a channel without segments gets a new one
+        assert!(series.backfill_stream(&stream("1234", "2021-03-10T15:04:21.5Z")));
+        let segment = series.channel("1234").unwrap().current_segment().unwrap();
+        assert_eq!(segment.start, 1615388661.5);
+    }
+}